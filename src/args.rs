@@ -0,0 +1,913 @@
+use std::env;
+use std::ffi::OsString;
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+use anyhow::{Context as _, Result, bail};
+use clap::error::ErrorKind;
+use clap::parser::ValueSource;
+use clap::{CommandFactory as _, FromArgMatches as _, Parser, Subcommand, ValueEnum};
+use log::LevelFilter;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use retrieve_missing_steam_game_icons::art::ArtKind;
+use retrieve_missing_steam_game_icons::filter::{Exclusions, GameIdFilter};
+use retrieve_missing_steam_game_icons::hook::PostRunCommand;
+use retrieve_missing_steam_game_icons::report::ReportFormat;
+use retrieve_missing_steam_game_icons::{
+    DEFAULT_CONCURRENCY,
+    DEFAULT_CONNECT_TIMEOUT_SECS,
+    DEFAULT_DELAY_MS,
+    DEFAULT_MAX_RATE_LIMIT_PAUSES,
+    DEFAULT_REQUEST_TIMEOUT_SECS,
+    DEFAULT_RETRIES,
+    budget,
+    download,
+    max_age,
+};
+
+/// Days since an unused icon was modified before it's removed, unless specified otherwise
+const DEFAULT_MIN_AGE_DAYS: u64 = 30;
+
+/// Retrieve the icons missing from Steam game shortcuts.
+///
+/// Without a command, the missing icons are downloaded (as with `download`).
+#[derive(Debug, Parser)]
+#[command(version)]
+pub struct Cli {
+    /// What to do (downloading the missing icons if not given)
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// Options for every command
+    #[command(flatten)]
+    pub global: GlobalArgs,
+
+    /// Options for downloading, when no command is given
+    #[command(flatten)]
+    pub download: DownloadArgs,
+}
+
+impl Cli {
+    /// Parse the command line, exiting with usage information if it's invalid.
+    pub fn from_command_line() -> Self {
+        Self::try_from_args(env::args_os()).unwrap_or_else(|error| error.exit())
+    }
+
+    /// Parse the provided arguments (including the program name).
+    pub fn try_from_args(args: impl IntoIterator<Item = OsString>) -> Result<Self, clap::Error> {
+        let mut command = Self::command();
+        let matches = command.try_get_matches_from_mut(args)?;
+
+        // Options for downloading are only for when no command is given,
+        // rather than being silently ignored before one
+        if let Some((name, _)) = matches.subcommand() {
+            let download_arg = command.get_arguments().find(|arg| {
+                !arg.is_global_set()
+                    && matches.value_source(arg.get_id().as_str()) == Some(ValueSource::CommandLine)
+            });
+            if let Some(arg) = download_arg {
+                let arg = match arg.get_long() {
+                    Some(long) => format!("--{long}"),
+                    None => arg.get_id().as_str().to_uppercase(),
+                };
+                return Err(command.error(
+                    ErrorKind::ArgumentConflict,
+                    format!("`{arg}` can't be used before `{name}`"),
+                ));
+            }
+        }
+
+        // Global options given before the command aren't checked against the command's options
+        let cli = Self::from_arg_matches(&matches)?;
+        let progress_events = match &cli.command {
+            None => cli.download.report.progress_events,
+            Some(command) => command
+                .report()
+                .is_some_and(|report| report.progress_events),
+        };
+        if cli.global.json && progress_events {
+            return Err(command.error(
+                ErrorKind::ArgumentConflict,
+                "`--json` can't be used with `--progress-events`",
+            ));
+        }
+        Ok(cli)
+    }
+
+    /// The command to run, downloading the missing icons if none was given.
+    pub fn into_command(self) -> (GlobalArgs, Command) {
+        let command = self
+            .command
+            .unwrap_or_else(|| Command::Download(Box::new(self.download)));
+        (self.global, command)
+    }
+}
+
+/// What the script was asked to do.
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Download the missing icons (the default)
+    Download(Box<DownloadArgs>),
+
+    /// Only report which icons are missing, without downloading or writing anything
+    Check(CheckArgs),
+
+    /// List every shortcut with its game, icon, and whether the icon is there,
+    /// without downloading anything
+    List(ListArgs),
+
+    /// Remove the icons downloaded before
+    Undo(UndoArgs),
+
+    /// Remove the icons no shortcut uses anymore
+    Clean(CleanArgs),
+
+    /// Check every icon in the icon directory, optionally downloading damaged ones again
+    Verify(VerifyArgs),
+
+    /// Repair every problem with the `.url` shortcuts that keeps their icons from showing
+    FixShortcuts(FixShortcutsArgs),
+
+    /// Export PNG copies of the icons already there (for launchers that want PNGs),
+    /// without downloading anything
+    ExportPng(ExportPngArgs),
+}
+
+impl Command {
+    /// How to report on the run, for the commands that process shortcuts.
+    pub fn report(&self) -> Option<&ReportArgs> {
+        match self {
+            Self::Download(args) => Some(&args.report),
+            Self::Check(args) => Some(&args.report),
+            Self::Verify(args) => Some(&args.report),
+            Self::ExportPng(args) => Some(&args.report),
+            Self::List(_) | Self::Undo(_) | Self::Clean(_) | Self::FixShortcuts(_) => None,
+        }
+    }
+}
+
+/// Options for every command.
+#[derive(Debug, clap::Args)]
+pub struct GlobalArgs {
+    /// Directory Steam loads game icons from (found automatically if not given)
+    #[arg(long, global = true, value_name = "DIR")]
+    pub icon_dir: Option<PathBuf>,
+
+    /// Most detailed logs to write to stderr (`RUST_LOG` is used if not given)
+    #[arg(long, global = true, value_name = "LEVEL")]
+    pub log_level: Option<LevelFilter>,
+
+    /// Print a JSON report to stdout (only logging problems to stderr, unless told otherwise)
+    #[arg(long, global = true)]
+    pub json: bool,
+}
+
+/// Options for finding the shortcuts to process.
+#[derive(Debug, clap::Args)]
+pub struct ShortcutArgs {
+    /// Directory containing the shortcuts to process (the current directory if not given)
+    #[arg(value_name = "SHORTCUT_DIR")]
+    pub shortcut_dir: Option<PathBuf>,
+
+    /// Also process shortcuts in subdirectories
+    #[arg(long)]
+    pub recursive: bool,
+
+    /// Follow symlinks (and junctions) to shortcuts elsewhere,
+    /// and to directories when combined with `--recursive`
+    #[arg(long)]
+    pub follow_links: bool,
+
+    /// Process the shortcuts on the desktops (unless a shortcut directory is given)
+    #[arg(long)]
+    pub auto: bool,
+
+    /// Process the shortcuts on every local user's desktop
+    /// (unless a shortcut directory is given)
+    #[arg(long)]
+    pub all_users: bool,
+
+    /// Also process Steam's Start Menu shortcuts
+    #[arg(long)]
+    pub include_start_menu: bool,
+
+    /// Only process these games (e.g. `620,440-450`; can be repeated)
+    #[arg(long = "game-id", value_name = "IDS")]
+    pub game_ids: Vec<GameIdFilter>,
+
+    /// Never process these games, even if they're also passed to `--game-id`
+    #[arg(long, value_name = "IDS")]
+    pub exclude: Vec<GameIdFilter>,
+}
+
+impl ShortcutArgs {
+    /// Games to process (all of them, if empty).
+    pub fn game_ids(&self) -> GameIdFilter {
+        merge_game_ids(&self.game_ids)
+    }
+
+    /// Games and shortcuts to never process.
+    pub fn exclusions(&self) -> Exclusions {
+        let mut exclusions = Exclusions::default();
+        exclusions.exclude_game_ids(merge_game_ids(&self.exclude));
+        exclusions
+    }
+}
+
+/// Options for reporting on the run as it goes and once it's over.
+#[derive(Debug, Default, clap::Args)]
+pub struct ReportArgs {
+    /// Write progress events to stdout as the run goes, one JSON object per line
+    /// (for programs wrapping the script)
+    #[arg(long, conflicts_with = "json")]
+    pub progress_events: bool,
+
+    /// Write a report of every shortcut processed to `--report-file` in this format
+    /// (`json` or `csv`)
+    #[arg(long = "report", value_name = "FORMAT", requires = "report_file")]
+    pub format: Option<ReportFormat>,
+
+    /// File to write the report to, once the run is over
+    #[arg(long, value_name = "FILE", requires = "format")]
+    pub report_file: Option<PathBuf>,
+}
+
+impl ReportArgs {
+    /// Where to write the report and in what format, if asked to.
+    pub fn report_file(&self) -> Option<(&Path, ReportFormat)> {
+        Some((self.report_file.as_deref()?, self.format?))
+    }
+}
+
+/// Options for looking up and downloading icons.
+#[derive(Debug, clap::Args)]
+pub struct FetchArgs {
+    /// Maximum number of icons to download at once
+    #[arg(long, value_name = "COUNT", default_value_t = DEFAULT_CONCURRENCY,
+          value_parser = parse_at_least_one::<usize>)]
+    pub concurrency: usize,
+
+    /// Number of times to retry a download after a transient failure
+    #[arg(long, value_name = "COUNT", default_value_t = DEFAULT_RETRIES)]
+    pub retries: u32,
+
+    /// Minimum time between requests to Steam, in milliseconds
+    #[arg(long = "delay", value_name = "MS", default_value_t = DEFAULT_DELAY_MS)]
+    pub delay_ms: u64,
+
+    /// Longest time to spend connecting to Steam, in seconds
+    #[arg(long = "connect-timeout", value_name = "SECONDS",
+          default_value_t = DEFAULT_CONNECT_TIMEOUT_SECS, value_parser = parse_at_least_one::<u64>)]
+    pub connect_timeout_secs: u64,
+
+    /// Longest time to spend on a whole request to Steam, in seconds
+    #[arg(long = "request-timeout", value_name = "SECONDS",
+          default_value_t = DEFAULT_REQUEST_TIMEOUT_SECS, value_parser = parse_at_least_one::<u64>)]
+    pub request_timeout_secs: u64,
+
+    /// Number of times in a row to pause for being rate limited before giving up
+    #[arg(long, value_name = "COUNT", default_value_t = DEFAULT_MAX_RATE_LIMIT_PAUSES)]
+    pub max_rate_limit_pauses: u32,
+
+    /// Most bytes per second to download (across all downloads at once)
+    #[arg(long, value_name = "BYTES_PER_SEC", value_parser = parse_at_least_one::<u64>)]
+    pub limit_rate: Option<u64>,
+
+    /// URL of the proxy to send requests through, instead of any in the environment
+    #[arg(long, value_name = "URL", conflicts_with = "no_proxy",
+          value_parser = download::parse_proxy_url)]
+    pub proxy: Option<String>,
+
+    /// Ignore any proxy in the environment
+    #[arg(long)]
+    pub no_proxy: bool,
+
+    /// User agent to send with every request
+    #[arg(long, value_name = "STRING",
+          default_value = download::DEFAULT_USER_AGENT, value_parser = parse_user_agent)]
+    pub user_agent: HeaderValue,
+
+    /// Extra header to send with every request, as `Name: value`
+    /// (can be repeated, with later ones replacing earlier ones of the same name)
+    #[arg(long = "header", value_name = "HEADER", value_parser = download::parse_header)]
+    pub headers: Vec<(HeaderName, HeaderValue)>,
+
+    /// PEM file with certificates to trust on top of the system's (can be repeated)
+    #[arg(long = "ca-cert", value_name = "PATH")]
+    pub ca_certs: Vec<PathBuf>,
+
+    /// Skip verifying certificates
+    #[arg(long)]
+    pub insecure: bool,
+
+    /// Host of the CDN to download icons from, instead of the default ones
+    /// (can be repeated, tried in order)
+    #[arg(long = "cdn-host", value_name = "HOST", value_parser = download::cdn_base_url)]
+    pub cdn_hosts: Vec<String>,
+
+    /// Base URL of a mirror of the CDN to download icons from, instead of the default ones
+    /// (can be repeated, tried in order after any `--cdn-host`)
+    #[arg(long = "cdn-base-url", value_name = "URL", value_parser = download::parse_cdn_base_url)]
+    pub cdn_base_urls: Vec<String>,
+
+    /// Skip checking downloaded icons against their hash
+    #[arg(long)]
+    pub no_verify: bool,
+
+    /// Save icons the CDN serves as PNGs as they are,
+    /// rather than converting them into the ICO files shortcuts expect
+    #[arg(long)]
+    pub no_convert: bool,
+
+    /// Ask the CDN for games it recently had no icon for, rather than skipping them
+    #[arg(long)]
+    pub no_cache: bool,
+
+    /// Forget every game remembered as having no icon on the CDN
+    #[arg(long, conflicts_with = "dry_run")]
+    pub clear_cache: bool,
+
+    /// Never ask the Steam store for the names of games
+    #[arg(long)]
+    pub no_names: bool,
+}
+
+impl Default for FetchArgs {
+    fn default() -> Self {
+        Self {
+            concurrency: DEFAULT_CONCURRENCY,
+            retries: DEFAULT_RETRIES,
+            delay_ms: DEFAULT_DELAY_MS,
+            connect_timeout_secs: DEFAULT_CONNECT_TIMEOUT_SECS,
+            request_timeout_secs: DEFAULT_REQUEST_TIMEOUT_SECS,
+            max_rate_limit_pauses: DEFAULT_MAX_RATE_LIMIT_PAUSES,
+            limit_rate: None,
+            proxy: None,
+            no_proxy: false,
+            user_agent: HeaderValue::from_static(download::DEFAULT_USER_AGENT),
+            headers: Vec::new(),
+            ca_certs: Vec::new(),
+            insecure: false,
+            cdn_hosts: Vec::new(),
+            cdn_base_urls: Vec::new(),
+            no_verify: false,
+            no_convert: false,
+            no_cache: false,
+            clear_cache: false,
+            no_names: false,
+        }
+    }
+}
+
+impl FetchArgs {
+    /// Minimum time between requests to Steam.
+    pub fn delay(&self) -> Duration {
+        Duration::from_millis(self.delay_ms)
+    }
+
+    /// Longest time to spend connecting to Steam.
+    pub fn connect_timeout(&self) -> Duration {
+        Duration::from_secs(self.connect_timeout_secs)
+    }
+
+    /// Longest time to spend on a whole request to Steam.
+    pub fn request_timeout(&self) -> Duration {
+        Duration::from_secs(self.request_timeout_secs)
+    }
+
+    /// Extra headers to send with every request
+    /// (clients only send one value per default header, so the last one wins).
+    pub fn headers(&self) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in &self.headers {
+            headers.insert(name.clone(), value.clone());
+        }
+        headers
+    }
+
+    /// Base URLs of the CDN to download icons from, replacing the default ones (if any are given).
+    pub fn cdn_base_urls(&self) -> Vec<String> {
+        self.cdn_hosts
+            .iter()
+            .chain(&self.cdn_base_urls)
+            .cloned()
+            .collect()
+    }
+}
+
+/// Options for `download`.
+#[derive(Debug, clap::Args)]
+pub struct DownloadArgs {
+    /// Where to find the shortcuts
+    #[command(flatten)]
+    pub shortcuts: ShortcutArgs,
+
+    /// How to download the icons
+    #[command(flatten)]
+    pub fetch: FetchArgs,
+
+    /// How to report on the run
+    #[command(flatten)]
+    pub report: ReportArgs,
+
+    /// Also process every game Steam has installed
+    #[arg(long)]
+    pub installed_games: bool,
+
+    /// Only report what would be downloaded
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Download icons even if they already exist, replacing them
+    #[arg(long)]
+    pub force: bool,
+
+    /// Download icons older than this again (e.g. `90d`, `12w`, or `1y 6M`), in case they changed
+    /// (e.g. since the game was rebranded), only replacing them if they did
+    #[arg(long, value_name = "AGE", value_parser = max_age::parse, conflicts_with = "offline")]
+    pub max_age: Option<Duration>,
+
+    /// Ask the CDN about icons that already exist (without downloading them),
+    /// downloading those again whose size or modification time shows they've changed
+    #[arg(long, conflicts_with = "offline")]
+    pub check_updates: bool,
+
+    /// Stop once this many downloads have started, leaving the rest for the next run
+    #[arg(long, value_name = "COUNT", value_parser = parse_at_least_one::<u64>,
+          conflicts_with = "offline")]
+    pub max_downloads: Option<u64>,
+
+    /// Stop once this much has been downloaded (e.g. `500K` or `5M`),
+    /// leaving the rest for the next run
+    #[arg(long, value_name = "SIZE", value_parser = budget::parse_size,
+          conflicts_with = "offline")]
+    pub max_bytes: Option<u64>,
+
+    /// Stop at the first shortcut (or piece of artwork) that fails, cancelling downloads in
+    /// progress, rather than carrying on with the rest
+    #[arg(long)]
+    pub fail_fast: bool,
+
+    /// Create shortcuts for installed games without one
+    #[arg(long)]
+    pub create_shortcuts: bool,
+
+    /// Point shortcuts with an icon in the wrong directory at the icon directory
+    #[arg(long)]
+    pub fix_shortcuts: bool,
+
+    /// Back up shortcuts before fixing them
+    #[arg(long, requires = "fix_shortcuts")]
+    pub backup_shortcuts: bool,
+
+    /// Save icons missing from the icon directory to a per-user directory instead
+    /// (`%LOCALAPPDATA%\SteamGameIcons`), pointing their shortcuts there (backing each up first),
+    /// for when the icon directory can't be written to without elevating
+    #[arg(long)]
+    pub user_icon_dir: bool,
+
+    /// Copy icons from Steam's library cache instead of downloading them, so nothing is requested
+    /// (games without a cached icon are reported as unavailable offline)
+    #[arg(long)]
+    pub offline: bool,
+
+    /// Steam's library cache, if it isn't `appcache\librarycache` in the Steam install
+    #[arg(long, value_name = "DIR", requires = "offline")]
+    pub library_cache_dir: Option<PathBuf>,
+
+    /// When the CDN has no icon for an installed game (e.g. since it was delisted),
+    /// extract the icon from the game's executable instead
+    #[arg(long, conflicts_with = "offline")]
+    pub extract_from_exe: bool,
+
+    /// When the CDN has no icon for a game (and none was extracted), generate a placeholder
+    /// (a colored square with the game's initials), replaced once the CDN has an icon after all
+    #[arg(long, conflicts_with = "offline")]
+    pub placeholder: bool,
+
+    /// Also save a PNG copy of each icon (its largest image) here as `<appid>.png`,
+    /// for launchers that want PNGs rather than ICO files (e.g. Playnite)
+    #[arg(long, value_name = "DIR")]
+    pub export_png: Option<PathBuf>,
+
+    /// Also download these kinds of artwork for every game (comma-separated, from `header`,
+    /// `logo`, `library_600x900`, `library_hero`, `capsule_231x87` and `capsule_616x353`),
+    /// saving them in `--art-dir` as `<appid>_<kind>.<ext>`
+    #[arg(
+        long,
+        value_name = "KINDS",
+        value_delimiter = ',',
+        requires = "art_dir",
+        conflicts_with = "offline"
+    )]
+    pub art: Vec<ArtKind>,
+
+    /// Directory to save the artwork asked for with `--art` in (created if needed)
+    #[arg(long, value_name = "DIR", requires = "art")]
+    pub art_dir: Option<PathBuf>,
+
+    /// Don't tell the Windows shell to show new icons right away
+    #[arg(long)]
+    pub no_shell_notify: bool,
+
+    /// Rebuild Explorer's icon cache once done, for when it keeps showing blank icons anyway
+    /// (which briefly disturbs the desktop)
+    #[arg(long, conflicts_with = "watch")]
+    pub rebuild_icon_cache: bool,
+
+    /// Show a notification summarizing the run once it's done (only on Windows),
+    /// unless nothing was missing (or even then, with `--notify=always`)
+    #[arg(long, value_name = "WHEN", value_enum, num_args = 0..=1, require_equals = true,
+          default_missing_value = "missing", conflicts_with = "watch")]
+    pub notify: Option<NotifyWhen>,
+
+    /// Command to run through the platform's shell once done, with `{downloaded}`, `{failed}`,
+    /// `{skipped}` and `{report_path}` replaced with the results (already quoted)
+    /// and set in the environment too (as `STEAM_ICONS_DOWNLOADED` and so on)
+    #[arg(long, value_name = "COMMAND", value_parser = PostRunCommand::parse)]
+    pub post_run: Option<PostRunCommand>,
+
+    /// Fail the run if the post-run command fails
+    #[arg(long, requires = "post_run")]
+    pub post_run_required: bool,
+
+    /// Run the post-run command even if the run was interrupted
+    #[arg(long, requires = "post_run")]
+    pub post_run_always: bool,
+
+    /// Keep processing new shortcuts after the initial pass
+    #[arg(long, conflicts_with = "dry_run")]
+    pub watch: bool,
+}
+
+/// Options for `check`.
+#[derive(Debug, clap::Args)]
+pub struct CheckArgs {
+    /// Where to find the shortcuts
+    #[command(flatten)]
+    pub shortcuts: ShortcutArgs,
+
+    /// Also check every game Steam has installed
+    #[arg(long)]
+    pub installed_games: bool,
+
+    /// How to report on the check
+    #[command(flatten)]
+    pub report: ReportArgs,
+}
+
+/// Options for `list`.
+#[derive(Debug, clap::Args)]
+pub struct ListArgs {
+    /// Where to find the shortcuts
+    #[command(flatten)]
+    pub shortcuts: ShortcutArgs,
+
+    /// What to sort the shortcuts by
+    #[arg(long, value_enum, default_value_t = SortKey::Name)]
+    pub sort: SortKey,
+}
+
+/// When to show a notification once a run is done.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum NotifyWhen {
+    /// Only if any icons were missing
+    Missing,
+
+    /// Even if nothing was missing
+    Always,
+}
+
+/// What to sort listed shortcuts by.
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum SortKey {
+    /// Name of the game (unnamed games last)
+    Name,
+
+    /// Steam game ID
+    #[value(name = "appid")]
+    GameId,
+
+    /// Whether the icon is there (shortcut problems first, then missing icons)
+    Status,
+}
+
+/// Options for `undo`.
+#[derive(Debug, clap::Args)]
+pub struct UndoArgs {
+    /// Only remove the icons for these games (e.g. `620,440-450`; can be repeated)
+    #[arg(long = "game-id", value_name = "IDS")]
+    pub game_ids: Vec<GameIdFilter>,
+
+    /// Only report what would be removed
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+impl UndoArgs {
+    /// Games to remove the icons for (all of them, if empty).
+    pub fn game_ids(&self) -> GameIdFilter {
+        merge_game_ids(&self.game_ids)
+    }
+}
+
+/// Options for `clean`.
+#[derive(Debug, clap::Args)]
+pub struct CleanArgs {
+    /// Where to find the shortcuts whose icons are kept
+    #[command(flatten)]
+    pub shortcuts: ShortcutArgs,
+
+    /// Also keep the icons of every game Steam has installed, even without a shortcut
+    #[arg(long)]
+    pub installed_games: bool,
+
+    /// Only report what would be removed
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Remove unused icons without asking first
+    #[arg(long)]
+    pub yes: bool,
+
+    /// Days since an unused icon was last modified before it's removed
+    #[arg(long = "min-age", value_name = "DAYS", default_value_t = DEFAULT_MIN_AGE_DAYS)]
+    pub min_age_days: u64,
+}
+
+impl CleanArgs {
+    /// How long ago an unused icon must have been modified to be removed.
+    pub fn min_age(&self) -> Duration {
+        Duration::from_secs(self.min_age_days.saturating_mul(24 * 60 * 60))
+    }
+}
+
+/// Options for `verify`.
+#[derive(Debug, clap::Args)]
+pub struct VerifyArgs {
+    /// Download damaged icons again, for the shortcuts that use them
+    #[arg(long)]
+    pub fix: bool,
+
+    /// Where to find the shortcuts, when fixing icons
+    #[command(flatten)]
+    pub shortcuts: ShortcutArgs,
+
+    /// How to download the icons, when fixing them
+    #[command(flatten)]
+    pub fetch: FetchArgs,
+
+    /// How to report on the run, when fixing icons
+    #[command(flatten)]
+    pub report: ReportArgs,
+
+    /// Also process every game Steam has installed, when fixing icons
+    #[arg(long)]
+    pub installed_games: bool,
+
+    /// Only report what would be downloaded, when fixing icons
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Point shortcuts with an icon in the wrong directory at the icon directory,
+    /// when fixing icons
+    #[arg(long)]
+    pub fix_shortcuts: bool,
+
+    /// Back up shortcuts before fixing them
+    #[arg(long, requires = "fix_shortcuts")]
+    pub backup_shortcuts: bool,
+}
+
+/// Options for `fix-shortcuts`.
+#[derive(Debug, clap::Args)]
+pub struct FixShortcutsArgs {
+    /// Where to find the shortcuts
+    #[command(flatten)]
+    pub shortcuts: ShortcutArgs,
+
+    /// Only report what would be fixed
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Back up shortcuts before fixing them
+    #[arg(long)]
+    pub backup_shortcuts: bool,
+}
+
+/// Options for `export-png`.
+#[derive(Debug, clap::Args)]
+pub struct ExportPngArgs {
+    /// Directory to save the PNGs in, as `<appid>.png` (created if needed)
+    #[arg(value_name = "EXPORT_DIR")]
+    pub export_dir: PathBuf,
+
+    /// Where to find the shortcuts
+    #[command(flatten)]
+    pub shortcuts: ShortcutArgs,
+
+    /// Replace PNGs exported before
+    #[arg(long)]
+    pub force: bool,
+
+    /// How to report on the export
+    #[command(flatten)]
+    pub report: ReportArgs,
+}
+
+/// Combine every `--game-id` (or `--exclude`) given into one filter.
+fn merge_game_ids(filters: &[GameIdFilter]) -> GameIdFilter {
+    let mut game_ids = GameIdFilter::default();
+    for filter in filters {
+        game_ids.merge(filter.clone());
+    }
+    game_ids
+}
+
+/// Parse a number that must be at least 1.
+fn parse_at_least_one<T>(value: &str) -> Result<T>
+where
+    T: FromStr + Default + PartialEq,
+    T::Err: Display,
+{
+    match value.parse::<T>() {
+        Ok(number) if number == T::default() => bail!("must be at least 1"),
+        Ok(number) => Ok(number),
+        Err(error) => bail!("{error}"),
+    }
+}
+
+/// Parse the user agent to send with every request.
+fn parse_user_agent(user_agent: &str) -> Result<HeaderValue> {
+    HeaderValue::from_str(user_agent).context("Invalid user agent")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Cli, clap::Error> {
+        let program = [env!("CARGO_PKG_NAME")];
+        Cli::try_from_args(program.iter().chain(args).map(OsString::from))
+    }
+
+    fn error_kind(args: &[&str]) -> ErrorKind {
+        match parse(args) {
+            Ok(cli) => panic!("{args:?} parsed as {cli:?}"),
+            Err(error) => error.kind(),
+        }
+    }
+
+    #[test]
+    fn downloads_without_command() {
+        let cli = parse(&["--force", "--game-id", "440", "Desktop"]).unwrap();
+        let (_, command) = cli.into_command();
+        let Command::Download(args) = command else {
+            panic!("Expected `download`, got {command:?}");
+        };
+        assert!(args.force);
+        assert!(args.shortcuts.game_ids().contains("440"));
+        assert_eq!(args.shortcuts.shortcut_dir, Some(PathBuf::from("Desktop")));
+    }
+
+    #[test]
+    fn parses_commands() {
+        let cli = parse(&["download", "--force"]).unwrap();
+        assert!(matches!(cli.command, Some(Command::Download(args)) if args.force));
+        let cli = parse(&["check", "Desktop"]).unwrap();
+        assert!(matches!(cli.command, Some(Command::Check(_))));
+        let cli = parse(&["fix-shortcuts", "--dry-run"]).unwrap();
+        assert!(matches!(cli.command, Some(Command::FixShortcuts(args)) if args.dry_run));
+        // Anything else is the shortcut directory to download icons for
+        let cli = parse(&["Desktop"]).unwrap();
+        assert!(cli.command.is_none());
+        assert_eq!(
+            cli.download.shortcuts.shortcut_dir,
+            Some(PathBuf::from("Desktop"))
+        );
+    }
+
+    #[test]
+    fn accepts_global_options_anywhere() {
+        for args in [&["--json", "check"], &["check", "--json"]] {
+            let cli = parse(args).unwrap();
+            assert!(cli.global.json, "{args:?}");
+        }
+        let cli = parse(&["--icon-dir", "icons", "verify"]).unwrap();
+        assert_eq!(cli.global.icon_dir, Some(PathBuf::from("icons")));
+    }
+
+    #[test]
+    fn rejects_download_options_before_command() {
+        for args in [
+            &["--force", "check"][..],
+            &["Desktop", "check"],
+            &["--retries", "1", "verify"],
+        ] {
+            assert_eq!(error_kind(args), ErrorKind::ArgumentConflict, "{args:?}");
+        }
+    }
+
+    #[test]
+    fn rejects_json_with_progress_events() {
+        for args in [
+            &["--json", "--progress-events"][..],
+            &["--progress-events", "--json"],
+            &["check", "--json", "--progress-events"],
+            &["--json", "check", "--progress-events"],
+            &["--json", "download", "--progress-events"],
+        ] {
+            assert_eq!(error_kind(args), ErrorKind::ArgumentConflict, "{args:?}");
+        }
+        assert!(parse(&["--progress-events"]).is_ok());
+        // Commands without progress events don't have the option to conflict with
+        assert!(parse(&["--json", "list"]).is_ok());
+    }
+
+    #[test]
+    fn requires_options_together() {
+        for args in [
+            &["--report", "json"][..],
+            &["--report-file", "report.json"],
+            &["--backup-shortcuts"],
+            &["--library-cache-dir", "cache"],
+            &["--art", "header"],
+            &["--art-dir", "art"],
+            &["--post-run-required"],
+            &["--post-run-always"],
+        ] {
+            assert_eq!(
+                error_kind(args),
+                ErrorKind::MissingRequiredArgument,
+                "{args:?}"
+            );
+        }
+        for args in [
+            &["--report", "json", "--report-file", "report.json"][..],
+            &["--fix-shortcuts", "--backup-shortcuts"],
+            &["--offline", "--library-cache-dir", "cache"],
+            &["--art", "header", "--art-dir", "art"],
+            &["--post-run", "echo {downloaded}", "--post-run-required"],
+        ] {
+            assert!(parse(args).is_ok(), "{args:?}");
+        }
+    }
+
+    #[test]
+    fn rejects_conflicting_options() {
+        for args in [
+            &["--offline", "--max-age", "30d"][..],
+            &["--offline", "--check-updates"],
+            &["--offline", "--max-downloads", "1"],
+            &["--offline", "--placeholder"],
+            &["--watch", "--dry-run"],
+            &["--watch", "--notify"],
+            &["--proxy", "http://proxy.local:3128", "--no-proxy"],
+            &["--clear-cache", "--dry-run"],
+        ] {
+            assert_eq!(error_kind(args), ErrorKind::ArgumentConflict, "{args:?}");
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_values() {
+        for args in [
+            &["--max-downloads", "0"][..],
+            &["--max-bytes", "lots"],
+            &["--max-age", "0d"],
+            &["--post-run", "echo {unknown}"],
+            &["--header", "no colon"],
+            &["--game-id", "abc"],
+            &["--report", "xml", "--report-file", "report.xml"],
+            &["--cdn-host", "ftp://cdn.example.com"],
+            &["--cdn-base-url", "cdn.example.com"],
+            &["--cdn-base-url", "https://cdn.example.com/?x=1"],
+            &["--proxy", "socks5://proxy.local"],
+        ] {
+            assert_eq!(error_kind(args), ErrorKind::ValueValidation, "{args:?}");
+        }
+    }
+
+    #[test]
+    fn cdn_hosts_come_before_base_urls() {
+        let cli = parse(&[
+            "--cdn-base-url",
+            "http://cache.local/steamstatic/",
+            "--cdn-host",
+            "cdn.example.com",
+            "--cdn-host",
+            "http://cdn.example.net",
+        ])
+        .unwrap();
+        assert_eq!(
+            cli.download.fetch.cdn_base_urls(),
+            [
+                "https://cdn.example.com",
+                "http://cdn.example.net",
+                "http://cache.local/steamstatic",
+            ]
+        );
+    }
+}
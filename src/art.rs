@@ -0,0 +1,127 @@
+//! Downloading games' other artwork from Steam's CDN (`--art`),
+//! like the header and library capsule images launchers show.
+
+use std::fmt::{self, Display};
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use anyhow::{Error, Result, bail};
+
+use crate::{jpeg, png};
+
+/// Kind of artwork the CDN has for a game, besides its icon.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ArtKind {
+    /// Banner shown at the top of the store page (460x215)
+    Header,
+
+    /// Game's logo, with a transparent background
+    Logo,
+
+    /// Portrait capsule shown in the library grid (600x900)
+    Library600x900,
+
+    /// Wide background shown behind the game in the library
+    LibraryHero,
+
+    /// Small capsule shown in store lists (231x87)
+    Capsule231x87,
+
+    /// Large capsule shown on the store's front page (616x353)
+    Capsule616x353,
+}
+
+impl ArtKind {
+    /// Every kind of artwork, in the order they're downloaded and reported.
+    pub const ALL: [Self; 6] = [
+        Self::Header,
+        Self::Logo,
+        Self::Library600x900,
+        Self::LibraryHero,
+        Self::Capsule231x87,
+        Self::Capsule616x353,
+    ];
+
+    /// Name of the artwork, as given to `--art` and used in its filenames.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Header => "header",
+            Self::Logo => "logo",
+            Self::Library600x900 => "library_600x900",
+            Self::LibraryHero => "library_hero",
+            Self::Capsule231x87 => "capsule_231x87",
+            Self::Capsule616x353 => "capsule_616x353",
+        }
+    }
+
+    /// Extension of the artwork's format (only logos need transparency, so they're PNGs).
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Logo => "png",
+            _ => "jpg",
+        }
+    }
+
+    /// URL of `game_id`'s artwork on the CDN at `base_url` (see [`crate::download::icon_url`]).
+    pub fn url(self, base_url: &str, game_id: &str) -> Result<String> {
+        if game_id.is_empty() || !game_id.bytes().all(|byte| byte.is_ascii_digit()) {
+            bail!("Invalid game ID `{game_id}`");
+        }
+        Ok(format!(
+            "{}/steam/apps/{game_id}/{}.{}",
+            base_url.trim_end_matches('/'),
+            self.name(),
+            self.extension()
+        ))
+    }
+
+    /// Filename `game_id`'s artwork is saved as: `<appid>_<kind>.<ext>`.
+    pub fn filename(self, game_id: &str) -> String {
+        format!("{game_id}_{}.{}", self.name(), self.extension())
+    }
+
+    /// Check that `head`, the start of a download, is artwork of this kind (a JPEG or a PNG).
+    pub fn validate_head(self, head: &[u8]) -> Result<()> {
+        match self.extension() {
+            "png" => png::dimensions(head).map(|_| ()),
+            _ if head.starts_with(&jpeg::SIGNATURE) => Ok(()),
+            _ => bail!("Missing JPEG header"),
+        }
+    }
+}
+
+impl Display for ArtKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for ArtKind {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match Self::ALL
+            .into_iter()
+            .find(|kind| kind.name().eq_ignore_ascii_case(value.trim()))
+        {
+            Some(kind) => Ok(kind),
+            None => {
+                let names: Vec<_> = Self::ALL.iter().map(|kind| kind.name()).collect();
+                bail!(
+                    "`{value}` is not a kind of artwork (expected one of {})",
+                    names.join(", ")
+                )
+            }
+        }
+    }
+}
+
+/// Which artwork to download, and where to.
+#[derive(Debug, Clone)]
+pub struct ArtDownload {
+    /// Kinds of artwork to download for each game
+    pub kinds: Vec<ArtKind>,
+
+    /// Directory the artwork is saved in (created if needed)
+    pub dir: PathBuf,
+}
@@ -0,0 +1,172 @@
+//! Reading Valve's binary KeyValues (VDF) files, such as `appinfo.vdf` and `shortcuts.vdf`.
+//!
+//! Each entry is a type byte, a null-terminated key (or an index into a key table),
+//! then a value whose length depends on its type.
+
+use std::collections::{HashMap, HashSet};
+
+use anyhow::{Context as _, Result, bail};
+
+use crate::vdf::Value;
+
+/// Type marking the end of an object
+const TYPE_END: u8 = 0x08;
+
+/// Parse a binary KeyValues file with inline keys (e.g. `shortcuts.vdf`)
+/// into an object of its root keys.
+pub fn parse(bytes: &[u8]) -> Result<Value> {
+    let mut reader = Reader { bytes, offset: 0 };
+    let entries = parse_entries(&mut reader, None)?;
+    Ok(Value::Object(entries))
+}
+
+/// Magic number of `appinfo.vdf` files that store keys inline
+const APPINFO_MAGIC_INLINE_KEYS: [u32; 2] = [0x07564427, 0x07564428];
+
+/// Magic number of `appinfo.vdf` files that store keys in a string table
+const APPINFO_MAGIC_KEY_TABLE: u32 = 0x07564429;
+
+/// Parse the apps in `wanted` out of Steam's `appinfo.vdf` cache,
+/// returning the (binary) KeyValues of each app found.
+pub fn parse_appinfo(bytes: &[u8], wanted: &HashSet<u32>) -> Result<HashMap<u32, Value>> {
+    let mut reader = Reader { bytes, offset: 0 };
+    let magic = reader.u32().context("Missing header")?;
+    reader.u32().context("Missing universe")?;
+
+    // Newer files keep every key in a table at the end, referencing them by index
+    let key_table = if magic == APPINFO_MAGIC_KEY_TABLE {
+        let table_offset = usize::try_from(reader.u64()?).context("Invalid key table offset")?;
+        let mut table = Reader {
+            bytes,
+            offset: table_offset,
+        };
+        let count = table.u32().context("Missing key table")?;
+        let keys = (0..count)
+            .map(|_| table.c_str())
+            .collect::<Result<Vec<_>>>()
+            .context("Failed to read key table")?;
+        Some(keys)
+    } else if APPINFO_MAGIC_INLINE_KEYS.contains(&magic) {
+        None
+    } else {
+        bail!("Unsupported `appinfo.vdf` version {magic:#010x}");
+    };
+
+    // App details start with the state, last update, PICS token, SHA-1, and change number,
+    // followed by the SHA-1 of the binary data in newer versions
+    let details_len = if magic == APPINFO_MAGIC_INLINE_KEYS[0] {
+        40
+    } else {
+        60
+    };
+
+    let mut apps = HashMap::new();
+    loop {
+        let app_id = reader.u32().context("Missing app ID")?;
+        if app_id == 0 {
+            return Ok(apps);
+        }
+        let len = reader.u32()? as usize;
+        let entry = reader
+            .take(len)
+            .with_context(|| format!("App #{app_id} is truncated"))?;
+        if !wanted.contains(&app_id) {
+            continue;
+        }
+
+        let Some(data) = entry.get(details_len..) else {
+            bail!("App #{app_id} is truncated");
+        };
+        let mut data = Reader {
+            bytes: data,
+            offset: 0,
+        };
+        let value = parse_entries(&mut data, key_table.as_deref())
+            .with_context(|| format!("Failed to parse app #{app_id}"))?;
+        apps.insert(app_id, Value::Object(value));
+    }
+}
+
+/// Parse binary keys and values until the end of the current object.
+fn parse_entries(
+    reader: &mut Reader,
+    key_table: Option<&[String]>,
+) -> Result<Vec<(String, Value)>> {
+    let mut entries = Vec::new();
+    loop {
+        let kind = reader.u8().context("Unexpected end of object")?;
+        if kind == TYPE_END {
+            return Ok(entries);
+        }
+
+        let key = match key_table {
+            Some(keys) => {
+                let index = reader.u32()? as usize;
+                keys.get(index)
+                    .with_context(|| format!("Key #{index} isn't in the key table"))?
+                    .clone()
+            }
+            None => reader.c_str()?,
+        };
+        let value = match kind {
+            0x00 => Value::Object(parse_entries(reader, key_table)?),
+            0x01 => Value::String(reader.c_str()?),
+            0x02 => Value::String(i32::from_le_bytes(reader.array()?).to_string()),
+            0x03 => Value::String(f32::from_le_bytes(reader.array()?).to_string()),
+            0x04 | 0x06 => Value::String(reader.u32()?.to_string()),
+            0x07 => Value::String(reader.u64()?.to_string()),
+            0x0A => Value::String(i64::from_le_bytes(reader.array()?).to_string()),
+            _ => bail!("Unsupported value type {kind:#04x} for `{key}`"),
+        };
+        entries.push((key, value));
+    }
+}
+
+/// Reads little-endian values from binary data.
+struct Reader<'a> {
+    /// Entire data
+    bytes: &'a [u8],
+
+    /// Position of the next value
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    /// Next `len` bytes.
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        let Some(bytes) = self.bytes.get(self.offset..self.offset.saturating_add(len)) else {
+            bail!("Unexpected end of data");
+        };
+        self.offset += len;
+        Ok(bytes)
+    }
+
+    /// Next `N` bytes, as an array.
+    fn array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        let bytes = self.take(N)?;
+        Ok(bytes.try_into().expect("exactly `N` bytes were taken"))
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        self.array().map(|[byte]| byte)
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        self.array().map(u32::from_le_bytes)
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        self.array().map(u64::from_le_bytes)
+    }
+
+    /// Next null-terminated UTF-8 string.
+    fn c_str(&mut self) -> Result<String> {
+        let rest = &self.bytes[self.offset.min(self.bytes.len())..];
+        let Some(len) = rest.iter().position(|&byte| byte == 0) else {
+            bail!("Unterminated string");
+        };
+        let text = String::from_utf8_lossy(&rest[..len]).into_owned();
+        self.offset += len + 1;
+        Ok(text)
+    }
+}
@@ -0,0 +1,259 @@
+//! Capping how much a run downloads (`--max-downloads` and `--max-bytes`),
+//! for metered connections.
+//!
+//! Downloads running at once share the budget: each one takes a download from it before it
+//! starts (so no more than the maximum ever start), and takes bytes from it as they arrive
+//! (so no more than the maximum are ever saved).
+//! Once anything is refused, the budget is used up and nothing else starts,
+//! while downloads already running carry on until they'd go over the byte budget themselves.
+//! Downloads stopped part way keep what they downloaded, so the next run can resume them.
+
+use std::error::Error;
+use std::fmt::{self, Display};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use anyhow::{Context as _, Result, bail};
+
+/// Suffixes sizes can be given with, and how many bytes each stands for
+const SIZE_SUFFIXES: [(&str, u64); 4] = [("", 1), ("K", 1 << 10), ("M", 1 << 20), ("G", 1 << 30)];
+
+/// Error for when a download was refused (or stopped part way) since the budget was used up.
+#[derive(Debug)]
+pub struct BudgetExhausted;
+
+impl Display for BudgetExhausted {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Download budget for the run is used up")
+    }
+}
+
+impl Error for BudgetExhausted {}
+
+/// How much a run may download.
+/// Clones share what's been used, so every download can be limited together.
+#[derive(Debug, Clone)]
+pub struct Budget {
+    /// Most downloads to start, if limited
+    max_downloads: Option<u64>,
+
+    /// Most bytes to download, if limited
+    max_bytes: Option<u64>,
+
+    /// What's been used so far
+    used: Arc<Mutex<Used>>,
+}
+
+/// What's been used of a [`Budget`].
+#[derive(Debug, Default)]
+struct Used {
+    /// Downloads started
+    downloads: u64,
+
+    /// Bytes downloaded
+    bytes: u64,
+
+    /// Whether anything has been refused, so nothing else should start
+    exhausted: bool,
+}
+
+impl Budget {
+    /// Budget allowing up to `max_downloads` downloads and `max_bytes` bytes (if limited).
+    pub fn new(max_downloads: Option<u64>, max_bytes: Option<u64>) -> Self {
+        Self {
+            max_downloads,
+            max_bytes,
+            used: Arc::default(),
+        }
+    }
+
+    /// Take a download from the budget before starting it,
+    /// failing if there are none left or all the bytes have been used.
+    pub fn start_download(&self) -> Result<(), BudgetExhausted> {
+        let mut used = self.lock();
+        let out_of_downloads = self.max_downloads.is_some_and(|max| used.downloads >= max);
+        let out_of_bytes = self.max_bytes.is_some_and(|max| used.bytes >= max);
+        if used.exhausted || out_of_downloads || out_of_bytes {
+            used.exhausted = true;
+            return Err(BudgetExhausted);
+        }
+        used.downloads += 1;
+        Ok(())
+    }
+
+    /// Make sure `bytes` more (e.g. the announced size of a response) would fit in the budget,
+    /// without taking them, so downloads that could never finish aren't started.
+    pub fn check_fits(&self, bytes: u64) -> Result<(), BudgetExhausted> {
+        let mut used = self.lock();
+        if self
+            .max_bytes
+            .is_some_and(|max| used.bytes.saturating_add(bytes) > max)
+        {
+            used.exhausted = true;
+            return Err(BudgetExhausted);
+        }
+        Ok(())
+    }
+
+    /// Take `bytes` that just arrived from the budget,
+    /// failing (without taking any) if that would go over it.
+    pub fn consume(&self, bytes: u64) -> Result<(), BudgetExhausted> {
+        let mut used = self.lock();
+        let total = used.bytes.saturating_add(bytes);
+        if self.max_bytes.is_some_and(|max| total > max) {
+            used.exhausted = true;
+            return Err(BudgetExhausted);
+        }
+        used.bytes = total;
+        Ok(())
+    }
+
+    /// Lock what's been used, even if another download panicked while holding it.
+    fn lock(&self) -> MutexGuard<'_, Used> {
+        self.used.lock().unwrap_or_else(|error| error.into_inner())
+    }
+}
+
+/// Parse a size in bytes, optionally with a binary suffix (e.g. `500K`, `5M`, or `1G`),
+/// which must be more than zero.
+pub fn parse_size(value: &str) -> Result<u64> {
+    let value = value.trim();
+    let digits = value.len() - value.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    let (number, suffix) = value.split_at(digits);
+    let Some(&(_, multiplier)) = SIZE_SUFFIXES
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(suffix.trim()))
+    else {
+        bail!("Unknown size `{value}` (expected bytes, optionally followed by K, M, or G)");
+    };
+    let bytes = number
+        .parse::<u64>()
+        .with_context(|| format!("`{value}` is not a size"))?
+        .checked_mul(multiplier)
+        .with_context(|| format!("`{value}` is too large"))?;
+    if bytes == 0 {
+        bail!("`{value}` must be more than zero");
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+
+    /// Run `task` with a clone of `budget` on each of `threads` threads at once,
+    /// returning how many times it succeeded.
+    fn run_concurrently(
+        budget: &Budget,
+        threads: usize,
+        task: impl Fn(&Budget) -> Result<(), BudgetExhausted> + Sync,
+    ) -> usize {
+        thread::scope(|scope| {
+            let handles: Vec<_> = (0..threads)
+                .map(|_| {
+                    let budget = budget.clone();
+                    let task = &task;
+                    scope.spawn(move || task(&budget).is_ok())
+                })
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap())
+                .filter(|&succeeded| succeeded)
+                .count()
+        })
+    }
+
+    #[test]
+    fn unlimited_budget_allows_everything() {
+        let budget = Budget::new(None, None);
+        assert_eq!(run_concurrently(&budget, 16, Budget::start_download), 16);
+        assert!(budget.check_fits(u64::MAX).is_ok());
+        assert!(budget.consume(u64::MAX).is_ok());
+    }
+
+    #[test]
+    fn concurrent_downloads_never_exceed_max_downloads() {
+        let budget = Budget::new(Some(5), None);
+        assert_eq!(run_concurrently(&budget, 32, Budget::start_download), 5);
+        assert_eq!(budget.lock().downloads, 5);
+        assert!(budget.start_download().is_err());
+    }
+
+    #[test]
+    fn concurrent_downloads_never_exceed_max_bytes() {
+        let budget = Budget::new(None, Some(1000));
+        let succeeded = run_concurrently(&budget, 32, |budget| {
+            budget.start_download()?;
+            for _ in 0..10 {
+                budget.consume(7)?;
+            }
+            Ok(())
+        });
+
+        let used = budget.lock();
+        assert!(used.bytes <= 1000, "{}", used.bytes);
+        // Downloads only stop once the next chunk wouldn't fit
+        assert!(used.bytes > 1000 - 7, "{}", used.bytes);
+        assert!(succeeded <= 1000 / 70);
+        assert!(used.exhausted);
+    }
+
+    #[test]
+    fn refusing_anything_stops_new_downloads() {
+        let budget = Budget::new(Some(10), Some(100));
+        budget.start_download().unwrap();
+        assert!(budget.check_fits(101).is_err());
+
+        // Downloads already running carry on while they fit, but nothing else starts
+        assert!(budget.clone().consume(50).is_ok());
+        assert!(budget.start_download().is_err());
+    }
+
+    #[test]
+    fn checks_fit_without_taking_bytes() {
+        let budget = Budget::new(None, Some(100));
+        budget.consume(60).unwrap();
+        assert!(budget.check_fits(40).is_ok());
+        assert!(budget.check_fits(40).is_ok());
+        assert_eq!(budget.lock().bytes, 60);
+        assert!(budget.check_fits(41).is_err());
+    }
+
+    #[test]
+    fn refused_bytes_arent_taken() {
+        let budget = Budget::new(None, Some(100));
+        budget.consume(90).unwrap();
+        assert!(budget.consume(11).is_err());
+        assert_eq!(budget.lock().bytes, 90);
+        assert!(budget.consume(10).is_ok());
+        assert!(budget.start_download().is_err());
+    }
+
+    #[test]
+    fn parses_sizes() {
+        for (value, bytes) in [
+            ("1", 1),
+            ("500K", 500 << 10),
+            ("500k", 500 << 10),
+            ("5M", 5 << 20),
+            ("1G", 1 << 30),
+            (" 2 M ", 2 << 20),
+        ] {
+            assert_eq!(parse_size(value).unwrap(), bytes, "{value}");
+        }
+        for value in [
+            "",
+            "0",
+            "0M",
+            "K",
+            "1T",
+            "1.5M",
+            "-1",
+            "99999999999999999999G",
+        ] {
+            assert!(parse_size(value).is_err(), "{value}");
+        }
+    }
+}
@@ -0,0 +1,165 @@
+//! Cancelling a run from outside, e.g. when the script is asked to exit.
+
+use std::future::Future;
+use std::sync::Arc;
+
+use anyhow::{Result, bail};
+use tokio::sync::watch;
+
+/// Shared flag for stopping a run early, along with why.
+/// Clones all refer to the same flag, so one can be kept to cancel the run it's given to.
+#[derive(Debug, Clone)]
+pub struct Cancellation {
+    /// Why the run was cancelled, once it is
+    sender: Arc<watch::Sender<Option<String>>>,
+}
+
+impl Default for Cancellation {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Cancellation {
+    /// Flag that hasn't been set yet.
+    pub fn new() -> Self {
+        Self {
+            sender: Arc::new(watch::channel(None).0),
+        }
+    }
+
+    /// Stop the run because of `reason` (e.g. the signal received).
+    /// Only the first reason is kept.
+    pub fn cancel(&self, reason: impl Into<String>) {
+        let reason = reason.into();
+        self.sender.send_if_modified(|current| {
+            if current.is_some() {
+                return false;
+            }
+            *current = Some(reason);
+            true
+        });
+    }
+
+    /// Why the run was cancelled, if it was.
+    pub fn reason(&self) -> Option<String> {
+        self.sender.borrow().clone()
+    }
+
+    /// Whether the run was cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.sender.borrow().is_some()
+    }
+
+    /// Fail if the run was cancelled (anywhere stopping is ideal).
+    pub fn check(&self) -> Result<()> {
+        match &*self.sender.borrow() {
+            Some(reason) => bail!("Stopping script due to {reason}"),
+            None => Ok(()),
+        }
+    }
+
+    /// Complete once the run is cancelled (for cutting short anything awaited).
+    pub async fn cancelled(&self) {
+        // The sender is kept alongside the receiver, so waiting can't fail
+        let mut receiver = self.sender.subscribe();
+        let _ = receiver.wait_for(Option::is_some).await;
+    }
+
+    /// Wait for `future`, unless the run is cancelled first,
+    /// in which case it's dropped (aborting whatever it was doing) and this fails.
+    pub async fn or_cancelled<T>(&self, future: impl Future<Output = T>) -> Result<T> {
+        self.check()?;
+        tokio::select! {
+            output = future => Ok(output),
+            () = self.cancelled() => {
+                bail!("Stopping script due to {}", self.reason().unwrap_or_default())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future;
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn check_fails_once_cancelled() {
+        let cancellation = Cancellation::new();
+        assert!(cancellation.check().is_ok());
+        assert!(!cancellation.is_cancelled());
+        assert_eq!(cancellation.reason(), None);
+
+        cancellation.cancel("SIGTERM");
+        let error = cancellation.check().unwrap_err();
+        assert_eq!(error.to_string(), "Stopping script due to SIGTERM");
+        assert!(cancellation.is_cancelled());
+    }
+
+    #[test]
+    fn keeps_first_reason() {
+        let cancellation = Cancellation::new();
+        cancellation.cancel("SIGTERM");
+        cancellation.cancel("SIGINT");
+        assert_eq!(cancellation.reason().as_deref(), Some("SIGTERM"));
+    }
+
+    #[test]
+    fn clones_share_the_flag() {
+        let cancellation = Cancellation::new();
+        let clone = cancellation.clone();
+        clone.cancel("SIGHUP");
+        assert!(cancellation.check().is_err());
+    }
+
+    #[tokio::test]
+    async fn or_cancelled_waits_for_future() {
+        let cancellation = Cancellation::new();
+        assert_eq!(cancellation.or_cancelled(async { 1 }).await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn or_cancelled_fails_once_cancelled() {
+        let cancellation = Cancellation::new();
+        cancellation.cancel("SIGTERM");
+        // Even a future that's already done isn't waited for
+        assert!(cancellation.or_cancelled(async { 1 }).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn or_cancelled_stops_waiting_when_cancelled() {
+        let cancellation = Cancellation::new();
+        let canceller = cancellation.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            canceller.cancel("SIGTERM");
+        });
+
+        let waited = cancellation.or_cancelled(future::pending::<()>());
+        let error = tokio::time::timeout(Duration::from_secs(5), waited)
+            .await
+            .expect("Cancelling should stop the wait")
+            .unwrap_err();
+        assert_eq!(error.to_string(), "Stopping script due to SIGTERM");
+    }
+
+    #[tokio::test]
+    async fn cancelled_completes_once_cancelled() {
+        let cancellation = Cancellation::new();
+        let waiting = tokio::spawn({
+            let cancellation = cancellation.clone();
+            async move { cancellation.cancelled().await }
+        });
+        cancellation.cancel("SIGTERM");
+        tokio::time::timeout(Duration::from_secs(5), waiting)
+            .await
+            .expect("Cancelling should complete the wait")
+            .unwrap();
+
+        // Including for anything waiting after the fact
+        cancellation.cancelled().await;
+    }
+}
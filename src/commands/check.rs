@@ -0,0 +1,63 @@
+//! `check`: listing the missing icons, without looking anything up or writing anything.
+
+use std::process::ExitCode;
+
+use anyhow::Result;
+use retrieve_missing_steam_game_icons::cancel::Cancellation;
+use retrieve_missing_steam_game_icons::report::Action;
+
+use super::{icon_dir, icons_missing, items_failed, options, process, shortcut_dirs, stopped};
+use crate::args::{CheckArgs, FetchArgs, GlobalArgs};
+
+/// List the icons missing from the shortcuts, failing if there are any.
+pub async fn run(
+    global: &GlobalArgs,
+    args: CheckArgs,
+    cancellation: &Cancellation,
+) -> Result<ExitCode> {
+    let check_sigint = &|| cancellation.check();
+    let dirs_with_shortcuts = shortcut_dirs(&args.shortcuts)?;
+    let local_icon_dir = icon_dir(global, "Checking for icons in")?;
+
+    // Checks make no requests and write nothing, so there's nothing to remember or record
+    let fetch = FetchArgs {
+        no_cache: true,
+        no_names: true,
+        ..FetchArgs::default()
+    };
+    let mut options = options(
+        global,
+        &args.shortcuts,
+        fetch,
+        dirs_with_shortcuts,
+        local_icon_dir,
+        cancellation,
+    )?;
+    options.check = true;
+    options.installed_games = args.installed_games;
+    options.manifest = None;
+
+    let (report, result) = process(options, global.json, &args.report).await;
+    if !global.json && !args.report.progress_events {
+        // List what's missing on stdout, so it's easy to alert on
+        for item in report
+            .items
+            .iter()
+            .filter(|item| item.action == Action::Missing)
+        {
+            println!(
+                "{}\t{}\t{}",
+                item.game_id.as_deref().unwrap_or_default(),
+                item.icon_filename.as_deref().unwrap_or_default(),
+                item.shortcut.display()
+            );
+        }
+    }
+    if let Err(error) = result {
+        return stopped(error, check_sigint);
+    }
+
+    Ok(items_failed(&report)
+        .or_else(|| icons_missing(&report))
+        .unwrap_or(ExitCode::SUCCESS))
+}
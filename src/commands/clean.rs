@@ -0,0 +1,181 @@
+//! `clean`: removing the icons no shortcut uses anymore.
+
+use std::io::{self, IsTerminal as _};
+use std::process::ExitCode;
+
+use anyhow::{Context as _, Result, bail};
+use log::*;
+use retrieve_missing_steam_game_icons::report::CleanReport;
+use retrieve_missing_steam_game_icons::{CleanOptions, find_orphaned_icons, remove_orphaned_icons};
+
+use super::{EXIT_ITEMS_FAILED, ensure_icon_dir_writable, icon_dir, shortcut_dirs, stopped};
+use crate::args::{CleanArgs, GlobalArgs};
+
+/// Remove the icons in the icon directory that none of the shortcuts use,
+/// after asking (unless told not to).
+pub fn run(
+    global: &GlobalArgs,
+    args: CleanArgs,
+    check_sigint: &impl Fn() -> Result<()>,
+) -> Result<ExitCode> {
+    let dirs_with_shortcuts = shortcut_dirs(&args.shortcuts)?;
+    let local_icon_dir = icon_dir(global, "Cleaning up icons in")?;
+    if !args.dry_run {
+        ensure_icon_dir_writable(&local_icon_dir, "`--icon-dir`")?;
+    }
+
+    let options = CleanOptions {
+        recursive: args.shortcuts.recursive,
+        follow_links: args.shortcuts.follow_links,
+        exclusions: args.shortcuts.exclusions(),
+        installed_games: args.installed_games,
+        min_age: args.min_age(),
+    };
+    let mut report = CleanReport {
+        dry_run: args.dry_run,
+        ..CleanReport::default()
+    };
+    let orphaned_icons = find_orphaned_icons(
+        &dirs_with_shortcuts,
+        &local_icon_dir,
+        &options,
+        &mut report,
+        check_sigint,
+    )?;
+    for icon in &orphaned_icons {
+        info!("No shortcut uses `{}`", icon.path.display());
+    }
+
+    // Only remove anything once it's been confirmed
+    let mut result = Ok(());
+    if args.dry_run {
+        for icon in &orphaned_icons {
+            report.record_removed(icon);
+        }
+    } else if orphaned_icons.is_empty() {
+        info!("No unused icons to remove");
+    } else if args.yes || confirm(&format!("Remove {} icon(s)?", orphaned_icons.len()))? {
+        result = remove_orphaned_icons(&orphaned_icons, &mut report, check_sigint);
+    } else {
+        info!("Not removing any icons");
+    }
+    report.log_summary();
+    if let Err(error) = result {
+        return stopped(error, check_sigint);
+    }
+
+    if !report.failed.is_empty() {
+        error!("Failed to remove {} icon(s)", report.failed.len());
+        return Ok(ExitCode::from(EXIT_ITEMS_FAILED));
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Ask the person running the script to confirm something on the terminal.
+/// Fails if there's nobody to ask.
+fn confirm(question: &str) -> Result<bool> {
+    if !io::stdin().is_terminal() {
+        bail!("Pass `--yes` to go ahead without being asked");
+    }
+    eprint!("{question} [y/N] ");
+    let mut answer = String::new();
+    io::stdin()
+        .read_line(&mut answer)
+        .context("Failed to read answer")?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes" | "Yes"))
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use std::ffi::OsString;
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::args::{Cli, Command};
+
+    /// Shortcut and icon directories with a shortcut to 440,
+    /// and icons for it and for 570 (which no shortcut uses).
+    fn layout() -> (TempDir, PathBuf, PathBuf) {
+        let dir = TempDir::new().unwrap();
+        let shortcuts = dir.path().join("Desktop");
+        let icons = dir.path().join("icons");
+        fs::create_dir(&shortcuts).unwrap();
+        fs::create_dir(&icons).unwrap();
+        fs::write(
+            shortcuts.join("440.desktop"),
+            "[Desktop Entry]\nName=Portal\nExec=steam steam://rungameid/440\nIcon=steam_icon_440\n",
+        )
+        .unwrap();
+        for game_id in ["440", "570"] {
+            fs::write(icons.join(format!("steam_icon_{game_id}.png")), "").unwrap();
+        }
+        (dir, shortcuts, icons)
+    }
+
+    /// Run `clean` over the shortcuts in `shortcuts` and icons in `icons`, with `args` as well.
+    fn clean(shortcuts: &Path, icons: &Path, args: &[&str]) -> ExitCode {
+        let args = ["clean", "--icon-dir"]
+            .iter()
+            .map(OsString::from)
+            .chain([icons.into(), shortcuts.into()])
+            .chain(args.iter().map(OsString::from));
+        let cli = Cli::try_from_args(
+            [OsString::from(env!("CARGO_PKG_NAME"))]
+                .into_iter()
+                .chain(args),
+        )
+        .unwrap();
+        let (global, command) = cli.into_command();
+        let Command::Clean(args) = command else {
+            panic!("Expected `clean`, got {command:?}");
+        };
+        run(&global, args, &|| Ok(())).unwrap()
+    }
+
+    /// Names of everything in `dir`, sorted.
+    fn dir_contents(dir: &Path) -> Vec<String> {
+        let mut contents: Vec<_> = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        contents.sort();
+        contents
+    }
+
+    #[test]
+    fn removes_icons_no_shortcut_uses() {
+        let (_dir, shortcuts, icons) = layout();
+        assert_eq!(
+            clean(&shortcuts, &icons, &["--yes", "--min-age", "0"]),
+            ExitCode::SUCCESS
+        );
+        assert_eq!(dir_contents(&icons), ["steam_icon_440.png"]);
+    }
+
+    #[test]
+    fn dry_run_removes_nothing() {
+        let (_dir, shortcuts, icons) = layout();
+        assert_eq!(
+            clean(&shortcuts, &icons, &["--dry-run", "--min-age", "0"]),
+            ExitCode::SUCCESS
+        );
+        assert_eq!(
+            dir_contents(&icons),
+            ["steam_icon_440.png", "steam_icon_570.png"]
+        );
+    }
+
+    #[test]
+    fn keeps_icons_modified_recently() {
+        let (_dir, shortcuts, icons) = layout();
+        let code = clean(&shortcuts, &icons, &["--yes", "--min-age", "1"]);
+        assert_eq!(code, ExitCode::SUCCESS);
+        assert_eq!(
+            dir_contents(&icons),
+            ["steam_icon_440.png", "steam_icon_570.png"]
+        );
+    }
+}
@@ -0,0 +1,177 @@
+//! `download`: downloading the missing icons (what the script does without a command).
+
+use std::fs;
+use std::process::ExitCode;
+
+use anyhow::{Context as _, Result, bail};
+use log::*;
+use retrieve_missing_steam_game_icons::art::{ArtDownload, ArtKind};
+use retrieve_missing_steam_game_icons::budget::Budget;
+use retrieve_missing_steam_game_icons::cancel::Cancellation;
+use retrieve_missing_steam_game_icons::export::PngExport;
+use retrieve_missing_steam_game_icons::{FailedFast, platform, shell};
+
+use super::{
+    budget_exhausted,
+    ensure_icon_dir_writable,
+    icon_dir,
+    items_failed,
+    options,
+    process,
+    run_post_run,
+    shortcut_dirs,
+    stopped,
+};
+use crate::args::{DownloadArgs, GlobalArgs, NotifyWhen};
+
+/// Download the icons missing from the shortcuts.
+pub async fn run(
+    global: &GlobalArgs,
+    args: DownloadArgs,
+    cancellation: &Cancellation,
+) -> Result<ExitCode> {
+    let check_sigint = &|| cancellation.check();
+    let dirs_with_shortcuts = shortcut_dirs(&args.shortcuts)?;
+    let local_icon_dir = icon_dir(global, "Saving icons to")?;
+
+    // Icons go to the per-user icon directory instead if asked, so only it needs to be writable
+    let user_icon_dir = if args.user_icon_dir {
+        let user_icon_dir = platform::user_icon_dir()?;
+        info!(
+            "Saving icons missing from the icon directory to {} instead",
+            user_icon_dir.display()
+        );
+        if !args.dry_run {
+            fs::create_dir_all(&user_icon_dir)
+                .with_context(|| format!("Failed to create `{}`", user_icon_dir.display()))?;
+            ensure_icon_dir_writable(&user_icon_dir, "`--icon-dir`")?;
+        }
+        Some(user_icon_dir)
+    } else {
+        if !args.dry_run {
+            ensure_icon_dir_writable(&local_icon_dir, "`--user-icon-dir` or `--icon-dir`")?;
+        }
+        None
+    };
+
+    // Find the icon cache up front, so it's clear straight away if there isn't one to rebuild
+    let icon_cache_dir = if args.rebuild_icon_cache {
+        Some(platform::icon_cache_dir()?)
+    } else {
+        None
+    };
+
+    // Find Steam's library cache up front when offline, since every icon has to come from it
+    let library_cache_dir = if args.offline {
+        let library_cache_dir = match &args.library_cache_dir {
+            Some(library_cache_dir) => library_cache_dir.clone(),
+            None => platform::steam_install_dir(&local_icon_dir)
+                .context(
+                    "Failed to find Steam's library cache (use `--library-cache-dir` to say where \
+                     it is)",
+                )?
+                .join("appcache")
+                .join("librarycache"),
+        };
+        if !library_cache_dir.is_dir() {
+            bail!(
+                "Steam's library cache `{}` isn't a directory",
+                library_cache_dir.display()
+            );
+        }
+        info!(
+            "Copying icons from Steam's library cache in {} instead of downloading them",
+            library_cache_dir.display()
+        );
+        Some(library_cache_dir)
+    } else {
+        None
+    };
+
+    let mut options = options(
+        global,
+        &args.shortcuts,
+        args.fetch,
+        dirs_with_shortcuts,
+        local_icon_dir,
+        cancellation,
+    )?;
+    options.dry_run = args.dry_run;
+    options.force = args.force;
+    options.max_age = args.max_age;
+    options.check_updates = args.check_updates;
+    if args.max_downloads.is_some() || args.max_bytes.is_some() {
+        options.budget = Some(Budget::new(args.max_downloads, args.max_bytes));
+    }
+    options.fail_fast = args.fail_fast;
+    options.installed_games = args.installed_games;
+    options.create_shortcuts = args.create_shortcuts;
+    options.fix_shortcuts = args.fix_shortcuts;
+    options.backup_shortcuts = args.backup_shortcuts;
+    options.user_icon_dir = user_icon_dir;
+    options.extract_from_exe = args.extract_from_exe;
+    options.placeholder = args.placeholder;
+    options.export_png = args.export_png.map(|dir| PngExport {
+        dir,
+        overwrite: args.force,
+    });
+    options.art = args.art_dir.map(|dir| ArtDownload {
+        kinds: ArtKind::ALL
+            .into_iter()
+            .filter(|kind| args.art.contains(kind))
+            .collect(),
+        dir,
+    });
+    // Nothing is requested at all offline, not even games' names
+    if args.offline {
+        options.library_cache_dir = library_cache_dir;
+        options.look_up_names = false;
+        options.negative_cache = None;
+    }
+    options.shell_notify = !args.no_shell_notify;
+    options.watch = args.watch;
+    // Dry runs download nothing, so they leave the manifest alone
+    if args.dry_run {
+        options.manifest = None;
+    }
+
+    let (report, mut result) = process(options, global.json, &args.report).await;
+
+    // Hand the results over to the post-run command, unless the run was interrupted
+    if let Some(post_run) = &args.post_run {
+        if check_sigint().is_err() && !args.post_run_always {
+            info!("Not running the post-run command, since the run was interrupted");
+        } else if let Err(error) =
+            run_post_run(post_run, &report, args.report.report_file.as_deref())
+        {
+            match result {
+                Ok(()) if args.post_run_required => result = Err(error),
+                _ => warn!("{error:#}"),
+            }
+        }
+    }
+
+    if let Err(error) = result {
+        // Stopping at the first failure exits as though it was the only one
+        if error.is::<FailedFast>() {
+            error!("{error:#}");
+            return Ok(items_failed(&report).unwrap_or(ExitCode::FAILURE));
+        }
+        return stopped(error, check_sigint);
+    }
+
+    if let Some(when) = args.notify {
+        match report.brief() {
+            Some(brief) => shell::notify(&brief),
+            None if when == NotifyWhen::Always => shell::notify("No icons were missing"),
+            None => debug!("Not showing a notification, since no icons were missing"),
+        }
+    }
+
+    if let Some(icon_cache_dir) = icon_cache_dir {
+        shell::rebuild_icon_cache(&icon_cache_dir, args.dry_run);
+    }
+    Ok(items_failed(&report)
+        .or_else(|| budget_exhausted(&report))
+        .unwrap_or(ExitCode::SUCCESS))
+}
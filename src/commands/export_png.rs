@@ -0,0 +1,55 @@
+//! `export-png`: exporting PNG copies of the icons already there, without downloading anything.
+
+use std::process::ExitCode;
+
+use anyhow::Result;
+use log::*;
+use retrieve_missing_steam_game_icons::cancel::Cancellation;
+use retrieve_missing_steam_game_icons::export::PngExport;
+
+use super::{icon_dir, items_failed, options, process, shortcut_dirs, stopped};
+use crate::args::{ExportPngArgs, FetchArgs, GlobalArgs};
+
+/// Save a PNG copy of every shortcut's icon that's there to the export directory.
+pub async fn run(
+    global: &GlobalArgs,
+    args: ExportPngArgs,
+    cancellation: &Cancellation,
+) -> Result<ExitCode> {
+    let check_sigint = &|| cancellation.check();
+    let dirs_with_shortcuts = shortcut_dirs(&args.shortcuts)?;
+    let local_icon_dir = icon_dir(global, "Exporting icons in")?;
+    info!(
+        "Exporting PNG copies of icons to {}",
+        args.export_dir.display()
+    );
+
+    // Exporting only reads the icons already there, so (like checking) it makes no requests,
+    // and the only thing it writes is the PNGs
+    let fetch = FetchArgs {
+        no_cache: true,
+        no_names: true,
+        ..FetchArgs::default()
+    };
+    let mut options = options(
+        global,
+        &args.shortcuts,
+        fetch,
+        dirs_with_shortcuts,
+        local_icon_dir,
+        cancellation,
+    )?;
+    options.check = true;
+    options.manifest = None;
+    options.export_png = Some(PngExport {
+        dir: args.export_dir,
+        overwrite: args.force,
+    });
+
+    let (report, result) = process(options, global.json, &args.report).await;
+    if let Err(error) = result {
+        return stopped(error, check_sigint);
+    }
+
+    Ok(items_failed(&report).unwrap_or(ExitCode::SUCCESS))
+}
@@ -0,0 +1,69 @@
+//! `fix-shortcuts`: repairing every problem with the shortcuts that keeps their icons from showing.
+
+use std::process::ExitCode;
+
+use anyhow::Result;
+use log::*;
+use retrieve_missing_steam_game_icons::report::FixReport;
+use retrieve_missing_steam_game_icons::{FixOptions, fix_shortcuts};
+
+use super::{EXIT_ITEMS_FAILED, icon_dir, shortcut_dirs, stopped};
+use crate::args::{FixShortcutsArgs, GlobalArgs};
+
+/// Repair the `.url` shortcuts so they all point at their game's icon in the icon directory,
+/// showing what changed in each.
+pub fn run(
+    global: &GlobalArgs,
+    args: FixShortcutsArgs,
+    check_sigint: &impl Fn() -> Result<()>,
+) -> Result<ExitCode> {
+    let dirs_with_shortcuts = shortcut_dirs(&args.shortcuts)?;
+    let local_icon_dir = icon_dir(global, "Fixing shortcuts for icons in")?;
+
+    let options = FixOptions {
+        recursive: args.shortcuts.recursive,
+        follow_links: args.shortcuts.follow_links,
+        exclusions: args.shortcuts.exclusions(),
+        game_ids: args.shortcuts.game_ids(),
+        dry_run: args.dry_run,
+        backup_shortcuts: args.backup_shortcuts,
+    };
+    let mut report = FixReport {
+        dry_run: args.dry_run,
+        ..FixReport::default()
+    };
+    let result = fix_shortcuts(
+        &dirs_with_shortcuts,
+        &local_icon_dir,
+        &options,
+        &mut report,
+        check_sigint,
+    );
+
+    // Show what changed in each shortcut on stdout, like a diff
+    for shortcut in &report.fixed {
+        println!("{}", shortcut.header());
+        for line in &shortcut.diff {
+            println!("{line}");
+        }
+    }
+    report.log_summary();
+    if let Err(error) = result {
+        return stopped(error, check_sigint);
+    }
+
+    let not_fixed = report.failed.len() + report.left_alone.len();
+    if not_fixed > 0 {
+        error!("Failed to fix {not_fixed} shortcut(s)");
+    }
+    if report.unreadable_entries > 0 {
+        error!(
+            "Failed to read {} entries in the shortcut directories",
+            report.unreadable_entries
+        );
+    }
+    if not_fixed > 0 || report.unreadable_entries > 0 {
+        return Ok(ExitCode::from(EXIT_ITEMS_FAILED));
+    }
+    Ok(ExitCode::SUCCESS)
+}
@@ -0,0 +1,128 @@
+//! `list`: showing every shortcut with its game, icon, and whether the icon is there.
+
+use std::cmp::Ordering;
+use std::io::{self, IsTerminal as _};
+use std::process::ExitCode;
+
+use anyhow::Result;
+use retrieve_missing_steam_game_icons::cancel::Cancellation;
+use retrieve_missing_steam_game_icons::report::{self, ListedShortcut};
+
+use super::{icon_dir, options, process, shortcut_dirs, stopped};
+use crate::args::{FetchArgs, GlobalArgs, ListArgs, ReportArgs, SortKey};
+
+/// Headings of the columns in the table of shortcuts
+const HEADINGS: [&str; 5] = ["NAME", "APPID", "ICON", "STATUS", "SHORTCUT"];
+
+/// Print one row per shortcut: aligned in a table for a person, or tab-separated for scripts.
+pub async fn run(
+    global: &GlobalArgs,
+    args: ListArgs,
+    cancellation: &Cancellation,
+) -> Result<ExitCode> {
+    let check_sigint = &|| cancellation.check();
+    let dirs_with_shortcuts = shortcut_dirs(&args.shortcuts)?;
+    let local_icon_dir = icon_dir(global, "Listing shortcuts for icons in")?;
+
+    // Listing only checks what's there, so (like checking) it makes no requests and writes nothing
+    let fetch = FetchArgs {
+        no_cache: true,
+        no_names: true,
+        ..FetchArgs::default()
+    };
+    let mut options = options(
+        global,
+        &args.shortcuts,
+        fetch,
+        dirs_with_shortcuts,
+        local_icon_dir,
+        cancellation,
+    )?;
+    options.check = true;
+    options.manifest = None;
+
+    let (report, result) = process(options, false, &ReportArgs::default()).await;
+    if let Err(error) = result {
+        return stopped(error, check_sigint);
+    }
+
+    let mut shortcuts = report.listed_shortcuts();
+    shortcuts.sort_by(|a, b| compare(a, b, args.sort).then_with(|| a.shortcut.cmp(&b.shortcut)));
+
+    if global.json {
+        println!("{}", report::listed_shortcuts_json(&shortcuts));
+    } else {
+        let rows: Vec<_> = shortcuts.iter().map(row).collect();
+        if io::stdout().is_terminal() {
+            print_table(&rows);
+        } else {
+            for row in &rows {
+                println!("{}", row.join("\t"));
+            }
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Order `a` and `b` by `key`.
+fn compare(a: &ListedShortcut, b: &ListedShortcut, key: SortKey) -> Ordering {
+    // Unnamed games (and shortcuts whose game isn't known) go last
+    let by_name = || match (&a.game_name, &b.game_name) {
+        (Some(a), Some(b)) => a.to_lowercase().cmp(&b.to_lowercase()),
+        (a, b) => b.is_some().cmp(&a.is_some()),
+    };
+    let by_game_id = || {
+        let game_id = |shortcut: &ListedShortcut| {
+            shortcut
+                .game_id
+                .as_deref()
+                .and_then(|game_id| game_id.parse::<u64>().ok())
+                .map_or((1, 0), |game_id| (0, game_id))
+        };
+        game_id(a).cmp(&game_id(b))
+    };
+    match key {
+        SortKey::Name => by_name().then_with(by_game_id),
+        SortKey::GameId => by_game_id(),
+        SortKey::Status => a
+            .status
+            .cmp(&b.status)
+            .then_with(by_name)
+            .then_with(by_game_id),
+    }
+}
+
+/// Columns of `shortcut`'s row (empty if unknown), in the order of [`HEADINGS`].
+fn row(shortcut: &ListedShortcut) -> [String; 5] {
+    [
+        shortcut.game_name.clone().unwrap_or_default(),
+        shortcut.game_id.clone().unwrap_or_default(),
+        shortcut.icon_filename.clone().unwrap_or_default(),
+        shortcut.status.name().to_owned(),
+        shortcut.shortcut.display().to_string(),
+    ]
+}
+
+/// Print `rows` under [`HEADINGS`], with each column as wide as its widest value.
+fn print_table(rows: &[[String; 5]]) {
+    let mut widths = HEADINGS.map(|heading| heading.chars().count());
+    for row in rows {
+        for (width, column) in widths.iter_mut().zip(row) {
+            *width = (*width).max(column.chars().count());
+        }
+    }
+
+    let headings = HEADINGS.map(str::to_owned);
+    for row in [&headings].into_iter().chain(rows) {
+        let mut line = String::new();
+        for (index, (column, width)) in row.iter().zip(widths).enumerate() {
+            // The last column isn't padded, so lines don't end with spaces
+            if index + 1 < row.len() {
+                line.push_str(&format!("{column:<width$}  "));
+            } else {
+                line.push_str(column);
+            }
+        }
+        println!("{line}");
+    }
+}
@@ -0,0 +1,41 @@
+//! `undo`: removing the icons downloaded before.
+
+use std::process::ExitCode;
+
+use anyhow::Result;
+use log::*;
+use retrieve_missing_steam_game_icons::manifest::{self, Manifest};
+use retrieve_missing_steam_game_icons::platform;
+use retrieve_missing_steam_game_icons::report::UndoReport;
+
+use super::{EXIT_ITEMS_FAILED, stopped};
+use crate::args::UndoArgs;
+
+/// Remove the icons downloaded before (see [`retrieve_missing_steam_game_icons::undo`]).
+pub fn run(args: UndoArgs, check_sigint: &impl Fn() -> Result<()>) -> Result<ExitCode> {
+    let manifest_path = platform::cache_dir()?.join(manifest::FILENAME);
+    info!(
+        "Undoing the downloads recorded in {}",
+        manifest_path.display()
+    );
+    let manifest = Manifest::load(Some(manifest_path));
+
+    let mut report = UndoReport::default();
+    let result = retrieve_missing_steam_game_icons::undo(
+        &manifest,
+        &args.game_ids(),
+        args.dry_run,
+        &mut report,
+        check_sigint,
+    );
+    report.log_summary();
+    if let Err(error) = result {
+        return stopped(error, check_sigint);
+    }
+
+    if !report.failed.is_empty() {
+        error!("Failed to remove {} icon(s)", report.failed.len());
+        return Ok(ExitCode::from(EXIT_ITEMS_FAILED));
+    }
+    Ok(ExitCode::SUCCESS)
+}
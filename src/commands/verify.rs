@@ -0,0 +1,109 @@
+//! `verify`: checking every icon in the icon directory, optionally downloading damaged ones again.
+
+use std::process::ExitCode;
+
+use anyhow::Result;
+use log::*;
+use retrieve_missing_steam_game_icons::cancel::Cancellation;
+use retrieve_missing_steam_game_icons::report::VerifyReport;
+use retrieve_missing_steam_game_icons::verify;
+
+use super::{
+    EXIT_PROBLEMS_FOUND,
+    ensure_icon_dir_writable,
+    icon_dir,
+    items_failed,
+    options,
+    process,
+    shortcut_dirs,
+    stopped,
+};
+use crate::args::{GlobalArgs, VerifyArgs};
+
+/// Check every icon in the icon directory,
+/// only going on to download damaged ones again if asked.
+pub async fn run(
+    global: &GlobalArgs,
+    args: VerifyArgs,
+    cancellation: &Cancellation,
+) -> Result<ExitCode> {
+    let check_sigint = &|| cancellation.check();
+    // Find the shortcuts only if they're needed to fix anything
+    let dirs_with_shortcuts = if args.fix {
+        shortcut_dirs(&args.shortcuts)?
+    } else {
+        Vec::new()
+    };
+    let local_icon_dir = icon_dir(global, "Verifying icons in")?;
+    if args.fix && !args.dry_run {
+        ensure_icon_dir_writable(&local_icon_dir, "`--icon-dir`")?;
+    }
+
+    let mut report = VerifyReport::default();
+    let result = verify::verify_icons(&local_icon_dir, &mut report, check_sigint);
+    report.log_summary();
+    if global.json && !args.fix {
+        println!("{}", report.to_json());
+    }
+    if let Err(error) = result {
+        return stopped(error, check_sigint);
+    }
+
+    if report.damaged.is_empty() {
+        return Ok(ExitCode::SUCCESS);
+    }
+    if !args.fix {
+        error!("Found {} damaged icon(s)", report.damaged.len());
+        return Ok(ExitCode::from(EXIT_PROBLEMS_FOUND));
+    }
+    info!("Downloading {} damaged icon(s) again", report.damaged.len());
+    let damaged_icons: Vec<_> = report.damaged.into_iter().map(|(path, _)| path).collect();
+
+    let mut options = options(
+        global,
+        &args.shortcuts,
+        args.fetch,
+        dirs_with_shortcuts,
+        local_icon_dir,
+        cancellation,
+    )?;
+    options.dry_run = args.dry_run;
+    options.installed_games = args.installed_games;
+    options.fix_shortcuts = args.fix_shortcuts;
+    options.backup_shortcuts = args.backup_shortcuts;
+    if args.dry_run {
+        options.manifest = None;
+    }
+    options.only_icons = Some(
+        damaged_icons
+            .iter()
+            .filter_map(|path| Some(path.file_name()?.to_string_lossy().to_lowercase()))
+            .collect(),
+    );
+
+    let (report, result) = process(options, global.json, &args.report).await;
+    if let Err(error) = result {
+        return stopped(error, check_sigint);
+    }
+    if let Some(exit_code) = items_failed(&report) {
+        return Ok(exit_code);
+    }
+
+    // Icons no shortcut uses can't be downloaded again, since there's no telling which game
+    // they're for
+    let still_damaged: Vec<_> = damaged_icons
+        .iter()
+        .filter(|path| verify::check_icon(path).is_err())
+        .collect();
+    for path in &still_damaged {
+        warn!(
+            "`{}` is still damaged, since no shortcut uses it",
+            path.display()
+        );
+    }
+    if !still_damaged.is_empty() {
+        error!("{} icon(s) are still damaged", still_damaged.len());
+        return Ok(ExitCode::from(EXIT_PROBLEMS_FOUND));
+    }
+    Ok(ExitCode::SUCCESS)
+}
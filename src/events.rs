@@ -0,0 +1,189 @@
+//! Structured progress events for programs wrapping (or embedding) the script,
+//! passed to an [`EventHandler`] as the run goes
+//! (e.g. written to stdout as one JSON object per line).
+//!
+//! Each event is an object with its kind under `event` (e.g. `{"event":"scan_started",...}`),
+//! so wrappers can read them back with [`Event`] (or any JSON parser).
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::{self, Write as _};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+
+use crate::report::{ItemReport, RunReport};
+
+/// Something that happened during a run.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    /// Shortcut directories are about to be scanned
+    ScanStarted {
+        /// Directories being scanned for shortcuts
+        locations: Vec<String>,
+    },
+
+    /// A Steam game's shortcut was read
+    ShortcutParsed {
+        /// Path to the shortcut (or app manifest, for installed games)
+        shortcut: String,
+
+        /// Steam game ID
+        game_id: String,
+
+        /// Filename the shortcut's icon is saved as
+        icon_filename: String,
+    },
+
+    /// An icon started downloading
+    DownloadStarted {
+        /// Path to the shortcut the icon is for
+        shortcut: String,
+
+        /// Steam game ID
+        game_id: String,
+
+        /// URL the icon is downloaded from (on the CDN's first host)
+        url: String,
+    },
+
+    /// An icon finished downloading (or turned out not to be on the CDN)
+    DownloadFinished {
+        /// Path to the shortcut the icon is for
+        shortcut: String,
+
+        /// Steam game ID
+        game_id: String,
+
+        /// What happened to the shortcut (e.g. `downloaded`, or `not-found`)
+        action: String,
+
+        /// Size of the downloaded icon
+        bytes: u64,
+
+        /// How long the download took, in milliseconds
+        duration_ms: u64,
+    },
+
+    /// A shortcut couldn't be processed or its icon failed to download
+    ItemFailed {
+        /// Path to the shortcut (or app manifest, for installed games)
+        shortcut: String,
+
+        /// Steam game ID, if known
+        game_id: Option<String>,
+
+        /// Why it failed
+        reason: String,
+    },
+
+    /// The run is over
+    RunFinished {
+        /// Totals of the run, by name (the same as in the JSON report)
+        totals: BTreeMap<String, u64>,
+
+        /// Why the run stopped early, if it did
+        error: Option<String>,
+    },
+}
+
+impl Event {
+    /// Event for `shortcut` having been read (see [`Event::ShortcutParsed`]).
+    pub fn shortcut_parsed(shortcut: &Path, game_id: &str, icon_filename: &str) -> Self {
+        Self::ShortcutParsed {
+            shortcut: shortcut.to_string_lossy().into_owned(),
+            game_id: game_id.to_owned(),
+            icon_filename: icon_filename.to_owned(),
+        }
+    }
+
+    /// Event for the failed shortcut in `item` (see [`Event::ItemFailed`]).
+    pub fn item_failed(item: &ItemReport) -> Self {
+        Self::ItemFailed {
+            shortcut: item.shortcut.to_string_lossy().into_owned(),
+            game_id: item.game_id.clone(),
+            reason: item.error.clone().unwrap_or_default(),
+        }
+    }
+
+    /// Event for a download for `item` having finished after `duration`
+    /// (see [`Event::DownloadFinished`]).
+    pub fn download_finished(item: &ItemReport, duration: Duration) -> Self {
+        Self::DownloadFinished {
+            shortcut: item.shortcut.to_string_lossy().into_owned(),
+            game_id: item.game_id.clone().unwrap_or_default(),
+            action: item.action.name().to_owned(),
+            bytes: item.bytes,
+            duration_ms: duration.as_millis() as u64,
+        }
+    }
+
+    /// Event for the run in `report` being over, early if it stopped with `error`
+    /// (see [`Event::RunFinished`]).
+    pub fn run_finished(report: &RunReport, error: Option<&anyhow::Error>) -> Self {
+        Self::RunFinished {
+            totals: report
+                .totals()
+                .into_iter()
+                .map(|(name, total)| (name.to_owned(), total))
+                .collect(),
+            error: error.map(|error| format!("{error:#}")),
+        }
+    }
+}
+
+/// Something to tell about each event as the run goes (e.g. a callback, or a channel).
+/// Clones all pass events to the same place.
+#[derive(Clone)]
+pub struct EventHandler {
+    /// Called with each event, as it happens
+    handle: Arc<dyn Fn(&Event) + Send + Sync>,
+}
+
+impl EventHandler {
+    /// Handler calling `handle` with each event
+    /// (from whichever task the event happened in, so it should be quick).
+    pub fn new(handle: impl Fn(&Event) + Send + Sync + 'static) -> Self {
+        Self {
+            handle: Arc::new(handle),
+        }
+    }
+
+    /// Handler sending each event down a channel, along with the receiving end.
+    /// Events are dropped once the receiver is.
+    pub fn channel() -> (Self, mpsc::UnboundedReceiver<Event>) {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let handler = Self::new(move |event| {
+            let _ = sender.send(event.clone());
+        });
+        (handler, receiver)
+    }
+
+    /// Handler writing each event to stdout as a line of JSON.
+    /// Events that can't be written are dropped, rather than stopping the run.
+    pub fn stdout() -> Self {
+        Self::new(|event| {
+            let Ok(json) = serde_json::to_string(event) else {
+                return;
+            };
+            let mut stdout = io::stdout().lock();
+            let _ = writeln!(stdout, "{json}");
+            let _ = stdout.flush();
+        })
+    }
+
+    /// Tell about `event`.
+    pub fn emit(&self, event: &Event) {
+        (self.handle)(event);
+    }
+}
+
+impl fmt::Debug for EventHandler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EventHandler").finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,160 @@
+//! Extracting games' icons from their installed executables (`--extract-from-exe`),
+//! for games whose icons are gone from the CDN (e.g. ones that were delisted).
+
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::path::{Component, Path, PathBuf};
+use std::sync::OnceLock;
+
+use anyhow::{Context as _, Result};
+use log::*;
+
+use crate::{icns, ico, installed, pe};
+
+/// Icon extracted from a game's executable.
+pub struct ExeIcon {
+    /// Executable the icon was extracted from
+    pub executable: PathBuf,
+
+    /// The icon, as an ICO file
+    pub ico: Vec<u8>,
+}
+
+impl ExeIcon {
+    /// The icon in the format of `icon_path` (going by its extension): an ICO, a PNG, or an ICNS.
+    pub fn converted_for(&self, icon_path: &Path) -> Result<Vec<u8>> {
+        match icon_path
+            .extension()
+            .and_then(|extension| extension.to_str())
+        {
+            Some("png") => ico::to_png(&self.ico),
+            Some("icns") => ico::to_png(&self.ico).and_then(|png| icns::from_png(&png)),
+            _ => Ok(self.ico.clone()),
+        }
+    }
+}
+
+/// Finds the icons embedded in games' executables.
+pub trait ExeIconSource {
+    /// Icon of `game_id`'s main executable,
+    /// or `None` if the game isn't installed or its executable has no icon.
+    fn exe_icon(&self, game_id: &str) -> Result<Option<ExeIcon>>;
+}
+
+/// Finds the executables of the games Steam has installed,
+/// going by how Steam launches each one (or else its largest executable).
+pub struct InstalledExecutables {
+    /// Steam install the games were installed by
+    install_dir: PathBuf,
+
+    /// Directories the games are installed in, by app ID
+    game_dirs: HashMap<u32, PathBuf>,
+
+    /// Executables each game is launched through (relative to its directory), by app ID,
+    /// only read once an icon is needed (since Steam's app info takes a while to read)
+    launch_executables: OnceLock<HashMap<u32, Vec<String>>>,
+}
+
+impl InstalledExecutables {
+    /// Find the games installed by the Steam install at `install_dir`.
+    pub fn new(install_dir: &Path) -> Self {
+        let game_dirs = installed::game_dirs(install_dir);
+        debug!("Found {} installed game(s)", game_dirs.len());
+        Self {
+            install_dir: install_dir.to_owned(),
+            game_dirs,
+            launch_executables: OnceLock::new(),
+        }
+    }
+
+    /// Executables `app_id` may use as its main one, installed in `game_dir`, best first.
+    fn candidates(&self, app_id: u32, game_dir: &Path) -> Vec<PathBuf> {
+        let launch_executables = self.launch_executables.get_or_init(|| {
+            let app_ids: HashSet<_> = self.game_dirs.keys().copied().collect();
+            installed::launch_executables(&self.install_dir, &app_ids).unwrap_or_else(|error| {
+                warn!("{error:#}, so executables are found by size instead");
+                HashMap::new()
+            })
+        });
+
+        // Launch options name the executable relative to the game's directory,
+        // which they're never allowed to leave
+        let mut candidates: Vec<_> = launch_executables
+            .get(&app_id)
+            .into_iter()
+            .flatten()
+            .filter_map(|executable| {
+                let relative: PathBuf = executable.split(['\\', '/']).collect();
+                let is_inside = relative
+                    .components()
+                    .all(|component| matches!(component, Component::Normal(_)));
+                is_inside.then(|| game_dir.join(relative))
+            })
+            .filter(|path| is_exe(path) && path.is_file())
+            .collect();
+
+        // Otherwise, the largest executable at the top of the directory is most likely the game
+        if candidates.is_empty() {
+            let mut executables: Vec<_> = fs::read_dir(game_dir)
+                .into_iter()
+                .flatten()
+                .flatten()
+                .filter(|entry| is_exe(&entry.path()) && !is_helper(&entry.path()))
+                .filter_map(|entry| Some((entry.metadata().ok()?.len(), entry.path())))
+                .collect();
+            executables.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+            candidates.extend(executables.into_iter().map(|(_, path)| path));
+        }
+        candidates
+    }
+}
+
+impl ExeIconSource for InstalledExecutables {
+    fn exe_icon(&self, game_id: &str) -> Result<Option<ExeIcon>> {
+        let Some((app_id, game_dir)) = game_id
+            .parse()
+            .ok()
+            .and_then(|app_id| Some((app_id, self.game_dirs.get(&app_id)?)))
+        else {
+            debug!("Game #{game_id} isn't installed, so there's no executable to extract from");
+            return Ok(None);
+        };
+
+        for executable in self.candidates(app_id, game_dir) {
+            debug!(
+                "Looking for an icon in `{}` for game #{game_id}",
+                executable.display()
+            );
+            let mut file = File::open(&executable)
+                .with_context(|| format!("Failed to open `{}`", executable.display()))?;
+            match pe::extract_icon(&mut file) {
+                Ok(Some(ico)) => return Ok(Some(ExeIcon { executable, ico })),
+                Ok(None) => debug!("`{}` has no icon", executable.display()),
+                Err(error) => debug!(
+                    "Failed to extract icon from `{}`: {error:#}",
+                    executable.display()
+                ),
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Whether `path` is named like a Windows executable.
+fn is_exe(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("exe"))
+}
+
+/// Whether the executable at `path` is named like something other than the game
+/// (e.g. an uninstaller or crash reporter).
+fn is_helper(path: &Path) -> bool {
+    let filename = path
+        .file_name()
+        .map(|filename| filename.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    filename.starts_with("unins")
+        || ["crash", "setup", "redist", "install", "report"]
+            .iter()
+            .any(|helper| filename.contains(helper))
+}
@@ -0,0 +1,60 @@
+//! Exporting PNG copies of icons (`--export-png`),
+//! for launchers that want PNGs rather than ICO files (e.g. Playnite).
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context as _, Result};
+
+use crate::download::{self, Outcome};
+use crate::{icns, ico, paths, png};
+
+/// Where to export PNG copies of icons to.
+#[derive(Debug, Clone)]
+pub struct PngExport {
+    /// Directory the PNGs are saved in, as `<appid>.png` (created if needed)
+    pub dir: PathBuf,
+
+    /// Whether to replace PNGs exported before
+    pub overwrite: bool,
+}
+
+impl PngExport {
+    /// Where `game_id`'s PNG is exported to.
+    fn png_path(&self, game_id: &str) -> Result<PathBuf> {
+        let filename = format!("{game_id}.png");
+        paths::ensure_plain_filename(&filename, ".png")?;
+        Ok(self.dir.join(filename))
+    }
+
+    /// Export a PNG copy of `game_id`'s icon at `icon_path`, returning where it was saved,
+    /// or `None` if it was already exported (and isn't being replaced).
+    pub fn export(&self, game_id: &str, icon_path: &Path) -> Result<Option<PathBuf>> {
+        let png_path = self.png_path(game_id)?;
+        if !self.overwrite && download::icon_exists(&png_path)? {
+            return Ok(None);
+        }
+
+        let icon = fs::read(paths::to_long_path(icon_path)).context("Failed to read icon")?;
+        let png = to_png(&icon)?;
+        fs::create_dir_all(&self.dir)
+            .with_context(|| format!("Failed to create `{}`", self.dir.display()))?;
+        let source = icon_path.to_string_lossy();
+        match download::save_contents(&png, &source, &png_path, self.overwrite)? {
+            Outcome::Saved { .. } => Ok(Some(png_path)),
+            _ => Ok(None),
+        }
+    }
+}
+
+/// Convert an icon (an ICO, ICNS, or PNG) to a PNG of its largest (and then deepest) image.
+pub fn to_png(icon: &[u8]) -> Result<Vec<u8>> {
+    if icon.starts_with(&png::SIGNATURE) {
+        png::dimensions(icon)?;
+        Ok(icon.to_vec())
+    } else if icns::validate(icon).is_ok() {
+        icns::to_png(icon).context("Failed to convert ICNS icon to a PNG")
+    } else {
+        ico::to_png(icon).context("Failed to convert icon to a PNG")
+    }
+}
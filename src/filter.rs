@@ -0,0 +1,302 @@
+//! Filters limiting which games are processed.
+
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+
+use anyhow::{Context as _, Error, Result, bail};
+
+/// Game IDs to process, given as individual IDs and ranges of IDs.
+/// An empty filter lets every game through.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GameIdFilter {
+    /// Ranges of allowed game IDs (individual IDs are ranges of one)
+    ranges: Vec<RangeInclusive<u64>>,
+}
+
+impl GameIdFilter {
+    /// Whether any games are filtered out at all.
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    /// Allow the games allowed by `other` as well.
+    pub fn merge(&mut self, other: Self) {
+        self.ranges.extend(other.ranges);
+    }
+
+    /// Whether the game with the given ID should be processed.
+    pub fn matches(&self, game_id: &str) -> bool {
+        self.is_empty() || self.contains(game_id)
+    }
+
+    /// Whether the game with the given ID is explicitly in the filter.
+    pub fn contains(&self, game_id: &str) -> bool {
+        game_id
+            .parse::<u64>()
+            .is_ok_and(|game_id| self.ranges.iter().any(|range| range.contains(&game_id)))
+    }
+}
+
+impl FromStr for GameIdFilter {
+    type Err = Error;
+
+    /// Parse a comma-separated list of game IDs and ranges of IDs (e.g. `620,440-450`).
+    fn from_str(value: &str) -> Result<Self> {
+        let parse_id = |id: &str| -> Result<u64> {
+            id.trim()
+                .parse()
+                .with_context(|| format!("`{id}` is not a game ID"))
+        };
+
+        let mut ranges = Vec::new();
+        for part in value.split(',') {
+            let range = match part.split_once('-') {
+                Some((start, end)) => parse_id(start)?..=parse_id(end)?,
+                None => parse_id(part)?..=parse_id(part)?,
+            };
+            if range.is_empty() {
+                bail!("Range `{part}` doesn't contain any game IDs");
+            }
+            ranges.push(range);
+        }
+
+        Ok(Self { ranges })
+    }
+}
+
+/// Games and shortcuts to leave alone, even if they'd otherwise be processed.
+#[derive(Debug, Clone, Default)]
+pub struct Exclusions {
+    /// Games to leave alone
+    game_ids: GameIdFilter,
+
+    /// Globs (supporting `*` and `?`) matching the filenames of shortcuts to leave alone
+    shortcut_patterns: Vec<String>,
+}
+
+impl Exclusions {
+    /// Leave alone the games in `game_ids` as well.
+    pub fn exclude_game_ids(&mut self, game_ids: GameIdFilter) {
+        self.game_ids.merge(game_ids);
+    }
+
+    /// Add the exclusions listed in an ignore file:
+    /// one game ID (or range of IDs) or shortcut filename glob per line,
+    /// with blank lines and lines starting with `#` ignored.
+    pub fn add_ignore_file(&mut self, contents: &str) -> Result<()> {
+        for (index, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            // Anything that looks like game IDs must be valid game IDs
+            let is_game_ids = line
+                .chars()
+                .all(|char| char.is_ascii_digit() || matches!(char, '-' | ',' | ' '));
+            if is_game_ids {
+                let game_ids = line
+                    .parse()
+                    .with_context(|| format!("Invalid game IDs on line {}", index + 1))?;
+                self.exclude_game_ids(game_ids);
+            } else {
+                self.shortcut_patterns.push(line.to_owned());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Whether the game with the given ID should be left alone.
+    pub fn excludes_game(&self, game_id: &str) -> bool {
+        self.game_ids.contains(game_id)
+    }
+
+    /// Whether the shortcut with the given filename should be left alone.
+    pub fn excludes_shortcut(&self, filename: &str) -> bool {
+        self.shortcut_patterns
+            .iter()
+            .any(|pattern| glob_matches(pattern, filename))
+    }
+}
+
+/// Whether `name` matches the glob `pattern`,
+/// where `*` matches any number of characters and `?` matches exactly one.
+/// Like filenames, matching is case-insensitive on Windows.
+fn glob_matches(pattern: &str, name: &str) -> bool {
+    let normalize = |value: &str| -> Vec<char> {
+        if cfg!(windows) {
+            value.to_lowercase().chars().collect()
+        } else {
+            value.chars().collect()
+        }
+    };
+    let (pattern, name) = (normalize(pattern), normalize(name));
+
+    // Match greedily, backtracking to the most recent `*` on a mismatch
+    let (mut pattern_index, mut name_index) = (0, 0);
+    let mut backtrack = None;
+    while name_index < name.len() {
+        match pattern.get(pattern_index) {
+            Some('*') => {
+                backtrack = Some((pattern_index, name_index));
+                pattern_index += 1;
+            }
+            Some(&char) if char == '?' || char == name[name_index] => {
+                pattern_index += 1;
+                name_index += 1;
+            }
+            _ => match backtrack {
+                Some((star_index, star_name_index)) => {
+                    pattern_index = star_index + 1;
+                    name_index = star_name_index + 1;
+                    backtrack = Some((star_index, star_name_index + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[pattern_index..].iter().all(|&char| char == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Exclusions listed in an ignore file with `lines`.
+    fn ignore_file(lines: &[&str]) -> Exclusions {
+        let mut exclusions = Exclusions::default();
+        exclusions.add_ignore_file(&lines.join("\n")).unwrap();
+        exclusions
+    }
+
+    #[test]
+    fn parses_game_ids_and_ranges() {
+        let filter: GameIdFilter = "620, 440-450,70".parse().unwrap();
+        for game_id in ["620", "440", "445", "450", "70"] {
+            assert!(filter.contains(game_id), "{game_id}");
+        }
+        for game_id in ["439", "451", "7", "", "abc", "-440"] {
+            assert!(!filter.contains(game_id), "{game_id}");
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_game_ids() {
+        for value in ["", "abc", "440-", "-440", "450-440", "440,,620", "1.5"] {
+            assert!(value.parse::<GameIdFilter>().is_err(), "{value}");
+        }
+    }
+
+    #[test]
+    fn empty_filter_matches_everything() {
+        let filter = GameIdFilter::default();
+        assert!(filter.is_empty());
+        assert!(filter.matches("440"));
+        assert!(!filter.contains("440"));
+    }
+
+    #[test]
+    fn merges_filters() {
+        let mut filter: GameIdFilter = "440".parse().unwrap();
+        filter.merge("620".parse().unwrap());
+        assert!(filter.matches("440") && filter.matches("620"));
+        assert!(!filter.matches("570"));
+    }
+
+    #[test]
+    fn excludes_game_ids() {
+        let mut exclusions = Exclusions::default();
+        assert!(!exclusions.excludes_game("440"));
+        exclusions.exclude_game_ids("440,500-510".parse().unwrap());
+        assert!(exclusions.excludes_game("440"));
+        assert!(exclusions.excludes_game("505"));
+        assert!(!exclusions.excludes_game("620"));
+        assert!(!exclusions.excludes_shortcut("440.url"));
+    }
+
+    #[test]
+    fn reads_ignore_file() {
+        let exclusions = ignore_file(&[
+            "# Games I don't want icons for",
+            "",
+            "440",
+            "   ",
+            "  500-510, 620  ",
+            "Team Fortress*.url",
+            "  # Indented comment",
+            "Game ?.desktop",
+        ]);
+
+        for game_id in ["440", "505", "620"] {
+            assert!(exclusions.excludes_game(game_id), "{game_id}");
+        }
+        assert!(!exclusions.excludes_game("570"));
+        assert!(exclusions.excludes_shortcut("Team Fortress 2.url"));
+        assert!(exclusions.excludes_shortcut("Game 1.desktop"));
+        assert!(!exclusions.excludes_shortcut("Game 10.desktop"));
+        assert!(!exclusions.excludes_shortcut("# Games I don't want icons for"));
+        assert!(!exclusions.excludes_shortcut(""));
+    }
+
+    #[test]
+    fn reads_ignore_file_with_crlf_line_endings() {
+        let mut exclusions = Exclusions::default();
+        exclusions
+            .add_ignore_file("# Comment\r\n440\r\nPortal*.url\r\n")
+            .unwrap();
+        assert!(exclusions.excludes_game("440"));
+        assert!(exclusions.excludes_shortcut("Portal 2.url"));
+    }
+
+    #[test]
+    fn rejects_invalid_game_ids_in_ignore_file() {
+        let mut exclusions = Exclusions::default();
+        let error = exclusions
+            .add_ignore_file("# Comment\n440\n450-440\n")
+            .unwrap_err();
+        assert_eq!(error.to_string(), "Invalid game IDs on line 3");
+    }
+
+    #[test]
+    fn combines_exclusions() {
+        let mut exclusions = ignore_file(&["440"]);
+        exclusions.exclude_game_ids("620".parse().unwrap());
+        exclusions.add_ignore_file("570").unwrap();
+        for game_id in ["440", "570", "620"] {
+            assert!(exclusions.excludes_game(game_id), "{game_id}");
+        }
+    }
+
+    #[test]
+    fn matches_globs() {
+        for (pattern, name) in [
+            ("*", ""),
+            ("*", "Portal 2.url"),
+            ("Portal*", "Portal 2.url"),
+            ("*.url", "Portal 2.url"),
+            ("P?rtal 2.url", "Portal 2.url"),
+            ("*2*", "Portal 2.url"),
+            ("a*b*c", "aXbYbZc"),
+            ("**.url", "Portal 2.url"),
+        ] {
+            assert!(glob_matches(pattern, name), "{pattern} {name}");
+        }
+        for (pattern, name) in [
+            ("", "Portal 2.url"),
+            ("Portal", "Portal 2.url"),
+            ("*.desktop", "Portal 2.url"),
+            ("?", ""),
+            ("Portal 2.url?", "Portal 2.url"),
+            ("a*b*c", "aXbYbZ"),
+        ] {
+            assert!(!glob_matches(pattern, name), "{pattern} {name}");
+        }
+    }
+
+    #[test]
+    fn matches_globs_case_insensitively_only_on_windows() {
+        assert_eq!(glob_matches("portal*", "Portal 2.url"), cfg!(windows));
+    }
+}
@@ -0,0 +1,292 @@
+//! Running a command once a run is done (`--post-run`), with the results of the run
+//! filled in for its placeholders (quoted for the shell that runs it)
+//! and set in its environment.
+
+use std::path::Path;
+use std::process::{Command, ExitStatus};
+
+use anyhow::{Context as _, Result, bail};
+
+use crate::report::{Action, RunReport};
+
+/// Placeholders that can be used in a post-run command (see [`values`])
+pub const PLACEHOLDERS: [&str; 4] = ["downloaded", "failed", "skipped", "report_path"];
+
+/// Prefix of the environment variables with the same values as the placeholders
+/// (e.g. `STEAM_ICONS_DOWNLOADED` for `{downloaded}`)
+pub const ENV_PREFIX: &str = "STEAM_ICONS_";
+
+/// Command to run once a run is done, checked to only use known placeholders.
+#[derive(Debug, Clone)]
+pub struct PostRunCommand {
+    template: String,
+}
+
+impl PostRunCommand {
+    /// Check that `template` only uses known placeholders (like `{downloaded}`),
+    /// with any literal braces doubled (`{{` and `}}`).
+    pub fn parse(template: &str) -> Result<Self> {
+        fill_in(template, &[], |_| Ok(String::new()))?;
+        Ok(Self {
+            template: template.to_owned(),
+        })
+    }
+
+    /// Run the command through the platform's shell, with the results of the run in `values`,
+    /// waiting for it to exit.
+    pub fn run(&self, values: &[(&str, String)]) -> Result<ExitStatus> {
+        let command = fill_in(&self.template, values, quote_for_shell)?;
+        let mut shell = shell_command(&command);
+        for (name, value) in values {
+            shell.env(format!("{ENV_PREFIX}{}", name.to_uppercase()), value);
+        }
+        shell
+            .status()
+            .context("Failed to start the post-run command")
+    }
+}
+
+/// Values of the placeholders for `report`: the number of icons saved, of shortcuts that failed,
+/// and of entries skipped for not being Steam shortcuts,
+/// and the path of the report file (`report_path`, or nothing if none was written).
+pub fn values(report: &RunReport, report_path: Option<&Path>) -> [(&'static str, String); 4] {
+    [
+        ("downloaded", report.saved().to_string()),
+        ("failed", report.failed().to_string()),
+        ("skipped", report.count(Action::Skipped).to_string()),
+        (
+            "report_path",
+            report_path.map_or_else(String::new, |path| path.to_string_lossy().into_owned()),
+        ),
+    ]
+}
+
+/// Replace each `{placeholder}` in `template` with its value in `values`, as quoted by `quote`,
+/// and each `{{` or `}}` with a literal brace.
+/// Placeholders without a value are left empty, as long as they're known.
+pub fn fill_in(
+    template: &str,
+    values: &[(&str, String)],
+    quote: impl Fn(&str) -> Result<String>,
+) -> Result<String> {
+    let mut filled = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find(['{', '}']) {
+        filled.push_str(&rest[..start]);
+        let brace = &rest[start..=start];
+        rest = &rest[start + 1..];
+
+        // Doubled braces are literal ones
+        if let Some(after) = rest.strip_prefix(brace) {
+            filled.push_str(brace);
+            rest = after;
+            continue;
+        }
+        if brace == "}" {
+            bail!("Unmatched `}}` in post-run command (use `}}}}` for a literal one)");
+        }
+
+        let Some(end) = rest.find('}') else {
+            bail!("Unclosed `{{` in post-run command (use `{{{{` for a literal one)");
+        };
+        let name = &rest[..end];
+        if !PLACEHOLDERS.contains(&name) {
+            let known: Vec<_> = PLACEHOLDERS
+                .iter()
+                .map(|known| format!("`{{{known}}}`"))
+                .collect();
+            bail!(
+                "Unknown placeholder `{{{name}}}` in post-run command (expected one of {})",
+                known.join(", ")
+            );
+        }
+        let value = values
+            .iter()
+            .find(|(known, _)| *known == name)
+            .map_or("", |(_, value)| value.as_str());
+        filled.push_str(&quote(value).with_context(|| format!("Failed to fill in `{{{name}}}`"))?);
+        rest = &rest[end + 1..];
+    }
+    filled.push_str(rest);
+    Ok(filled)
+}
+
+/// `value` quoted for `sh`, so it's passed as a single word exactly as it is.
+pub fn quote_sh(value: &str) -> String {
+    let plain = |c: char| c.is_ascii_alphanumeric() || "_-./:,+=@".contains(c);
+    if !value.is_empty() && value.chars().all(plain) {
+        return value.to_owned();
+    }
+
+    // Nothing is special within single quotes, so only they need escaping (by closing them)
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+/// `value` quoted for `cmd.exe`, so it's passed as a single argument exactly as it is,
+/// or an error if it contains `"` or `%` (which `cmd.exe` interprets even within quotes)
+/// or a line break (which ends the command).
+pub fn quote_cmd(value: &str) -> Result<String> {
+    if value.contains(['"', '%', '\n', '\r']) {
+        bail!(
+            "`{value}` can't be passed to `cmd.exe` as it is, since it contains `\"`, `%` or a \
+             line break (use the environment variable instead)"
+        );
+    }
+
+    let plain = |c: char| c.is_ascii_alphanumeric() || r"_-.\:".contains(c);
+    if !value.is_empty() && value.chars().all(plain) {
+        return Ok(value.to_owned());
+    }
+    Ok(format!("\"{value}\""))
+}
+
+/// `value` quoted for the platform's shell.
+#[cfg(windows)]
+fn quote_for_shell(value: &str) -> Result<String> {
+    quote_cmd(value)
+}
+
+/// `value` quoted for the platform's shell.
+#[cfg(not(windows))]
+fn quote_for_shell(value: &str) -> Result<String> {
+    Ok(quote_sh(value))
+}
+
+/// `command` run by `cmd.exe`, passed exactly as it is.
+#[cfg(windows)]
+fn shell_command(command: &str) -> Command {
+    use std::os::windows::process::CommandExt as _;
+
+    // `/S` has `cmd.exe` strip only the outer quotes, leaving the rest as it was
+    let mut shell = Command::new("cmd.exe");
+    shell
+        .args(["/D", "/S", "/C"])
+        .raw_arg(format!("\"{command}\""));
+    shell
+}
+
+/// `command` run by `sh`.
+#[cfg(not(windows))]
+fn shell_command(command: &str) -> Command {
+    let mut shell = Command::new("sh");
+    shell.arg("-c").arg(command);
+    shell
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn results() -> Vec<(&'static str, String)> {
+        vec![
+            ("downloaded", "3".to_owned()),
+            ("report_path", "/tmp/it's a report.json".to_owned()),
+        ]
+    }
+
+    fn fill_in_sh(template: &str) -> Result<String> {
+        fill_in(template, &results(), |value| Ok(quote_sh(value)))
+    }
+
+    #[test]
+    fn fills_in_placeholders() {
+        let cases = [
+            ("echo", "echo"),
+            ("echo {downloaded}", "echo 3"),
+            ("{downloaded}{downloaded}", "33"),
+            ("echo {report_path}", r"echo '/tmp/it'\''s a report.json'"),
+            // Known placeholders without a value are left empty
+            ("echo {failed}", "echo ''"),
+        ];
+        for (template, filled) in cases {
+            assert_eq!(fill_in_sh(template).unwrap(), filled, "{template}");
+        }
+    }
+
+    #[test]
+    fn fills_in_doubled_braces() {
+        let cases = [
+            ("{{", "{"),
+            ("}}", "}"),
+            ("{{downloaded}}", "{downloaded}"),
+            ("{{{downloaded}}}", "{3}"),
+            ("awk '{{ print }}'", "awk '{ print }'"),
+        ];
+        for (template, filled) in cases {
+            assert_eq!(fill_in_sh(template).unwrap(), filled, "{template}");
+        }
+    }
+
+    #[test]
+    fn rejects_bad_placeholders() {
+        for template in [
+            "{unknown}",
+            "{}",
+            "{ downloaded }",
+            "{downloaded",
+            "{",
+            "}",
+            "a } b",
+        ] {
+            assert!(fill_in_sh(template).is_err(), "{template}");
+            assert!(PostRunCommand::parse(template).is_err(), "{template}");
+        }
+    }
+
+    #[test]
+    fn passes_on_quoting_errors() {
+        let values = [("report_path", "100%".to_owned())];
+        assert!(fill_in("{report_path}", &values, quote_cmd).is_err());
+        assert!(fill_in("{downloaded}", &values, quote_cmd).is_ok());
+    }
+
+    #[test]
+    fn quotes_for_sh() {
+        let cases = [
+            ("", "''"),
+            ("plain-value_1.0", "plain-value_1.0"),
+            ("/tmp/report.json", "/tmp/report.json"),
+            ("two words", "'two words'"),
+            ("it's", r"'it'\''s'"),
+            ("$HOME `id` \\", r"'$HOME `id` \'"),
+            ("line\nbreak", "'line\nbreak'"),
+        ];
+        for (value, quoted) in cases {
+            assert_eq!(quote_sh(value), quoted, "{value}");
+        }
+    }
+
+    #[test]
+    fn quotes_for_cmd() {
+        let cases = [
+            ("", "\"\""),
+            (r"C:\report.json", r"C:\report.json"),
+            (
+                r"C:\Program Files\report.json",
+                r#""C:\Program Files\report.json""#,
+            ),
+            ("a & b", "\"a & b\""),
+        ];
+        for (value, quoted) in cases {
+            assert_eq!(quote_cmd(value).unwrap(), quoted, "{value}");
+        }
+    }
+
+    #[test]
+    fn rejects_unquotable_for_cmd() {
+        for value in ["say \"hi\"", "100%", "%PATH%", "line\nbreak", "line\rbreak"] {
+            assert!(quote_cmd(value).is_err(), "{value:?}");
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn runs_with_values_filled_in_and_in_environment() {
+        let command = PostRunCommand::parse(
+            r#"test {downloaded} = 3 && test {report_path} = "$STEAM_ICONS_REPORT_PATH""#,
+        )
+        .unwrap();
+        assert!(command.run(&results()).unwrap().success());
+        assert!(!command.run(&[]).unwrap().success());
+    }
+}
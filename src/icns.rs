@@ -0,0 +1,89 @@
+use anyhow::{Result, bail};
+
+use crate::png;
+
+/// Bytes every ICNS file starts with
+const MAGIC: [u8; 4] = *b"icns";
+
+/// Size of the header preceding each element (and the file itself)
+const HEADER_LEN: usize = 8;
+
+/// Check that `bytes` is (structurally) an ICNS file.
+pub fn validate(bytes: &[u8]) -> Result<()> {
+    if bytes.len() < HEADER_LEN || bytes[..MAGIC.len()] != MAGIC {
+        bail!("Missing ICNS header");
+    }
+
+    // The header records the length of the whole file
+    let len = u32::from_be_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]) as usize;
+    if len != bytes.len() {
+        bail!(
+            "ICNS file is {} bytes long, but its header says {len}",
+            bytes.len()
+        );
+    }
+
+    Ok(())
+}
+
+/// Wrap a square PNG in an ICNS file, as used for macOS app bundle icons.
+pub fn from_png(png: &[u8]) -> Result<Vec<u8>> {
+    let (width, height) = png::dimensions(png)?;
+    if width != height {
+        bail!("ICNS icons must be square, not {width}x{height}");
+    }
+
+    // Element types that hold PNG data, by size
+    let kind = match width {
+        16 => b"icp4",
+        32 => b"icp5",
+        64 => b"icp6",
+        128 => b"ic07",
+        256 => b"ic08",
+        512 => b"ic09",
+        1024 => b"ic10",
+        _ => bail!("ICNS icons can't be {width}x{height}"),
+    };
+
+    let element_len = HEADER_LEN + png.len();
+    let mut icns = Vec::with_capacity(HEADER_LEN + element_len);
+    icns.extend_from_slice(&MAGIC);
+    icns.extend_from_slice(&((HEADER_LEN + element_len) as u32).to_be_bytes());
+    icns.extend_from_slice(kind);
+    icns.extend_from_slice(&(element_len as u32).to_be_bytes());
+    icns.extend_from_slice(png);
+    Ok(icns)
+}
+
+/// The largest PNG in an ICNS file (the only kind of image [`from_png`] saves).
+pub fn to_png(bytes: &[u8]) -> Result<Vec<u8>> {
+    validate(bytes)?;
+
+    let mut largest: Option<(u32, &[u8])> = None;
+    let mut offset = HEADER_LEN;
+    while offset + HEADER_LEN <= bytes.len() {
+        let len = u32::from_be_bytes([
+            bytes[offset + 4],
+            bytes[offset + 5],
+            bytes[offset + 6],
+            bytes[offset + 7],
+        ]) as usize;
+        if len < HEADER_LEN || offset + len > bytes.len() {
+            bail!("ICNS element at offset {offset} is {len} bytes long, which doesn't fit");
+        }
+
+        // Other elements hold older formats of image, or aren't images at all
+        let data = &bytes[offset + HEADER_LEN..offset + len];
+        if let Ok((width, _)) = png::dimensions(data)
+            && largest.is_none_or(|(largest, _)| width > largest)
+        {
+            largest = Some((width, data));
+        }
+        offset += len;
+    }
+
+    match largest {
+        Some((_, png)) => Ok(png.to_vec()),
+        None => bail!("ICNS contains no PNGs"),
+    }
+}
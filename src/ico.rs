@@ -0,0 +1,552 @@
+use std::ops::Range;
+
+use anyhow::{Context as _, Result, bail};
+
+use crate::png;
+
+/// Bytes every ICO file starts with (reserved field, then the ICO image type)
+const MAGIC: [u8; 4] = [0x00, 0x00, 0x01, 0x00];
+
+/// Size of the header preceding the image directory
+const HEADER_LEN: usize = 6;
+
+/// Size of each entry in the image directory
+const DIRECTORY_ENTRY_LEN: usize = 16;
+
+/// Size of the `BITMAPINFOHEADER` that starts non-PNG images
+const BITMAP_HEADER_LEN: usize = 40;
+
+/// Image stored within an ICO file.
+struct Image<'a> {
+    /// Width in pixels
+    width: u32,
+
+    /// Height in pixels
+    height: u32,
+
+    /// Bits per pixel, as recorded in the image directory
+    bit_count: u16,
+
+    /// Either a PNG or a headered bitmap without the file header
+    data: &'a [u8],
+}
+
+/// Entry in an ICO file's image directory.
+struct DirectoryEntry {
+    /// Width in pixels
+    width: u32,
+
+    /// Height in pixels
+    height: u32,
+
+    /// Bits per pixel
+    bit_count: u16,
+
+    /// Where the image is within the file
+    data: Range<usize>,
+}
+
+/// Check that `bytes` is (structurally) an ICO file.
+pub fn validate(bytes: &[u8]) -> Result<()> {
+    directory(bytes, bytes.len()).map(|_| ())
+}
+
+/// Check that a file `len` bytes long is (structurally) an ICO file from `head`, its first bytes,
+/// so the rest of it doesn't need to be in memory.
+/// `head` needs to include the whole image directory, which only takes a few hundred bytes
+/// for real icons.
+pub fn validate_head(head: &[u8], len: usize) -> Result<()> {
+    directory(head, len).map(|_| ())
+}
+
+/// Read the image directory of an ICO file.
+fn images(bytes: &[u8]) -> Result<Vec<Image<'_>>> {
+    Ok(directory(bytes, bytes.len())?
+        .into_iter()
+        .map(|entry| Image {
+            width: entry.width,
+            height: entry.height,
+            bit_count: entry.bit_count,
+            data: &bytes[entry.data],
+        })
+        .collect())
+}
+
+/// Read the image directory from `head`, the start of an ICO file `len` bytes long,
+/// making sure every image is within the file.
+fn directory(head: &[u8], len: usize) -> Result<Vec<DirectoryEntry>> {
+    if head.len() < HEADER_LEN || head[..MAGIC.len()] != MAGIC {
+        bail!("Missing ICO header");
+    }
+
+    // Make sure the image directory fits in the file
+    let image_count = usize::from(u16::from_le_bytes([head[4], head[5]]));
+    if image_count == 0 {
+        bail!("ICO contains no images");
+    }
+    let images_start = HEADER_LEN + image_count * DIRECTORY_ENTRY_LEN;
+    if len < images_start {
+        bail!("ICO image directory is truncated");
+    }
+    if head.len() < images_start {
+        bail!("ICO image directory is too large to check ({image_count} images)");
+    }
+
+    // Make sure every image is non-empty and within the file
+    let mut entries = Vec::with_capacity(image_count);
+    for (index, entry) in head[HEADER_LEN..images_start]
+        .chunks_exact(DIRECTORY_ENTRY_LEN)
+        .enumerate()
+    {
+        let size = u32::from_le_bytes([entry[8], entry[9], entry[10], entry[11]]) as usize;
+        let offset = u32::from_le_bytes([entry[12], entry[13], entry[14], entry[15]]) as usize;
+        if size == 0 {
+            bail!("ICO image #{index} is empty");
+        } else if offset < images_start || offset.saturating_add(size) > len {
+            bail!("ICO image #{index} is outside of the file");
+        }
+
+        // Dimensions of 0 mean 256 pixels
+        entries.push(DirectoryEntry {
+            width: if entry[0] == 0 { 256 } else { entry[0].into() },
+            height: if entry[1] == 0 { 256 } else { entry[1].into() },
+            bit_count: u16::from_le_bytes([entry[6], entry[7]]),
+            data: offset..offset + size,
+        });
+    }
+
+    Ok(entries)
+}
+
+/// Convert the largest (and then deepest) image in an ICO file to a PNG.
+pub fn to_png(bytes: &[u8]) -> Result<Vec<u8>> {
+    let image = images(bytes)?
+        .into_iter()
+        .max_by_key(|image| (image.width * image.height, image.bit_count))
+        .context("ICO contains no images")?;
+
+    // Modern icons often embed PNGs directly
+    if image.data.starts_with(&png::SIGNATURE) {
+        return Ok(image.data.to_vec());
+    }
+
+    let (width, height, rgba) = decode_bitmap(image.data)?;
+    Ok(png::encode_rgba(width, height, &rgba))
+}
+
+/// Wrap a PNG in an ICO file, as Windows has been able to load since Vista.
+pub fn from_png(png: &[u8]) -> Result<Vec<u8>> {
+    from_pngs(&[png])
+}
+
+/// Wrap PNGs in an ICO file, one image each, in order.
+pub fn from_pngs(pngs: &[&[u8]]) -> Result<Vec<u8>> {
+    let Ok(image_count) = u16::try_from(pngs.len()) else {
+        bail!("ICO files can't hold {} images", pngs.len());
+    };
+    let images_start = HEADER_LEN + pngs.len() * DIRECTORY_ENTRY_LEN;
+    let mut ico =
+        Vec::with_capacity(images_start + pngs.iter().map(|png| png.len()).sum::<usize>());
+    ico.extend_from_slice(&MAGIC);
+    ico.extend_from_slice(&image_count.to_le_bytes());
+
+    let mut offset = images_start;
+    for png in pngs {
+        let (width, height) = png::dimensions(png)?;
+        if width == 0 || height == 0 || width > 256 || height > 256 {
+            bail!("ICO icons can't be {width}x{height}");
+        }
+
+        // Dimensions of 256 are recorded as 0, then no palette, one plane, and 32 bits per pixel
+        ico.extend_from_slice(&[width as u8, height as u8, 0, 0]);
+        ico.extend_from_slice(&1u16.to_le_bytes());
+        ico.extend_from_slice(&32u16.to_le_bytes());
+        // Sizes and offsets are 32-bit, so everything has to be within the first 4 GiB
+        let (Ok(size), Ok(offset_u32)) = (u32::try_from(png.len()), u32::try_from(offset)) else {
+            bail!("ICO files can't be larger than 4 GiB");
+        };
+        if offset_u32.checked_add(size).is_none() {
+            bail!("ICO files can't be larger than 4 GiB");
+        }
+        ico.extend_from_slice(&size.to_le_bytes());
+        ico.extend_from_slice(&offset_u32.to_le_bytes());
+        offset += png.len();
+    }
+    for png in pngs {
+        ico.extend_from_slice(png);
+    }
+    Ok(ico)
+}
+
+/// The PNG wrapped in an ICO file by [`from_png`] (its only image), or `None` if it isn't one.
+pub fn embedded_png(bytes: &[u8]) -> Option<&[u8]> {
+    match images(bytes).ok()?.as_slice() {
+        [image] if image.data.starts_with(&png::SIGNATURE) => Some(image.data),
+        _ => None,
+    }
+}
+
+/// Decode a headered bitmap (as stored in ICO files) into RGBA pixels, top row first.
+fn decode_bitmap(data: &[u8]) -> Result<(u32, u32, Vec<u8>)> {
+    if data.len() < BITMAP_HEADER_LEN {
+        bail!("ICO bitmap header is truncated");
+    }
+    let read_u16 = |offset: usize| u16::from_le_bytes([data[offset], data[offset + 1]]);
+    let read_u32 = |offset: usize| {
+        u32::from_le_bytes([
+            data[offset],
+            data[offset + 1],
+            data[offset + 2],
+            data[offset + 3],
+        ])
+    };
+
+    let header_len = read_u32(0) as usize;
+    if header_len < BITMAP_HEADER_LEN {
+        bail!("ICO bitmap header is too short");
+    }
+    let width = read_u32(4);
+    // The height covers both the color bitmap and the transparency mask
+    let height = read_u32(8) / 2;
+    let bit_count = read_u16(14);
+    let compression = read_u32(16);
+    let colors_used = read_u32(32) as usize;
+    if compression != 0 {
+        bail!("Compressed ICO bitmaps aren't supported");
+    } else if width == 0 || height == 0 || width > 1024 || height > 1024 {
+        bail!("ICO bitmap has unexpected dimensions {width}x{height}");
+    } else if ![1, 4, 8, 24, 32].contains(&bit_count) {
+        bail!("ICO bitmaps with {bit_count} bits per pixel aren't supported");
+    }
+
+    // Locate the palette, color bitmap, and transparency mask
+    let palette_len = if bit_count <= 8 {
+        if colors_used == 0 {
+            1 << bit_count
+        } else {
+            colors_used
+        }
+    } else {
+        0
+    };
+    let (width_px, height_px) = (width as usize, height as usize);
+    let color_stride = (width_px * usize::from(bit_count)).div_ceil(32) * 4;
+    let mask_stride = width_px.div_ceil(32) * 4;
+    let palette_start = header_len;
+    let color_start = palette_start + palette_len * 4;
+    let mask_start = color_start + color_stride * height_px;
+    let mask_end = mask_start + mask_stride * height_px;
+    let has_mask = data.len() >= mask_end;
+    if data.len() < mask_start {
+        bail!("ICO bitmap is truncated");
+    }
+    let palette = &data[palette_start..color_start];
+
+    // Rows are stored bottom-up
+    let mut rgba = Vec::with_capacity(width_px * height_px * 4);
+    for y in (0..height_px).rev() {
+        let row = &data[color_start + y * color_stride..][..color_stride];
+        for x in 0..width_px {
+            let (blue, green, red, alpha) = match bit_count {
+                32 => (row[x * 4], row[x * 4 + 1], row[x * 4 + 2], row[x * 4 + 3]),
+                24 => (row[x * 3], row[x * 3 + 1], row[x * 3 + 2], 0xff),
+                _ => {
+                    let bits = usize::from(bit_count);
+                    let bit_offset = x * bits;
+                    let byte = row[bit_offset / 8];
+                    let shift = 8 - bits - bit_offset % 8;
+                    let index = usize::from(byte >> shift) & ((1 << bits) - 1);
+                    match palette.get(index * 4..index * 4 + 3) {
+                        Some(color) => (color[0], color[1], color[2], 0xff),
+                        None => bail!("ICO bitmap references a color outside of its palette"),
+                    }
+                }
+            };
+            rgba.extend_from_slice(&[red, green, blue, alpha]);
+        }
+    }
+
+    // Apply the transparency mask (if any), unless the bitmap has its own alpha channel
+    let has_alpha = bit_count == 32 && rgba.chunks_exact(4).any(|pixel| pixel[3] != 0);
+    if !has_alpha {
+        for y in 0..height_px {
+            let row_start = mask_start + (height_px - 1 - y) * mask_stride;
+            for x in 0..width_px {
+                let transparent = has_mask && data[row_start + x / 8] & (0x80 >> (x % 8)) != 0;
+                rgba[(y * width_px + x) * 4 + 3] = if transparent { 0 } else { 0xff };
+            }
+        }
+    }
+
+    Ok((width, height, rgba))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// ICO header for `count` images, followed by a directory entry for each of `entries`
+    /// (its size and offset, for a 16x16 32-bit image), padded with zeros to `len` bytes.
+    fn ico(count: u16, entries: &[(u32, u32)], len: usize) -> Vec<u8> {
+        let mut ico = MAGIC.to_vec();
+        ico.extend_from_slice(&count.to_le_bytes());
+        for (size, offset) in entries {
+            ico.extend_from_slice(&[16, 16, 0, 0, 1, 0, 32, 0]);
+            ico.extend_from_slice(&size.to_le_bytes());
+            ico.extend_from_slice(&offset.to_le_bytes());
+        }
+        ico.resize(len.max(ico.len()), 0);
+        ico
+    }
+
+    /// Headered bitmap with `bit_count` bits per pixel, `palette`, and rows of `pixels`
+    /// (bottom row first, each already padded), followed by `mask`.
+    fn bitmap(
+        width: u32,
+        height: u32,
+        bit_count: u16,
+        palette: &[[u8; 4]],
+        pixels: &[u8],
+        mask: &[u8],
+    ) -> Vec<u8> {
+        let mut bitmap = Vec::new();
+        bitmap.extend_from_slice(&40u32.to_le_bytes());
+        bitmap.extend_from_slice(&width.to_le_bytes());
+        bitmap.extend_from_slice(&(height * 2).to_le_bytes());
+        bitmap.extend_from_slice(&1u16.to_le_bytes());
+        bitmap.extend_from_slice(&bit_count.to_le_bytes());
+        bitmap.extend_from_slice(&[0; 16]);
+        bitmap.extend_from_slice(&(palette.len() as u32).to_le_bytes());
+        bitmap.extend_from_slice(&[0; 4]);
+        bitmap.extend(palette.iter().flatten());
+        bitmap.extend_from_slice(pixels);
+        bitmap.extend_from_slice(mask);
+        bitmap
+    }
+
+    #[test]
+    fn accepts_valid_icons() {
+        assert!(validate(&ico(1, &[(10, 22)], 32)).is_ok());
+        assert!(validate(&ico(2, &[(10, 38), (10, 48)], 58)).is_ok());
+        let png = png::encode_rgba(1, 1, &[0, 0, 0, 0xff]);
+        assert!(validate(&from_png(&png).unwrap()).is_ok());
+    }
+
+    #[test]
+    fn rejects_missing_header() {
+        assert!(validate(b"").is_err());
+        assert!(validate(&[0, 0, 1]).is_err());
+        assert!(validate(b"\x89PNG\r\n\x1a\n").is_err());
+        // Cursors have a different image type
+        assert!(validate(&[0, 0, 2, 0, 1, 0]).is_err());
+    }
+
+    #[test]
+    fn rejects_no_images() {
+        assert!(validate(&ico(0, &[], 32)).is_err());
+    }
+
+    #[test]
+    fn rejects_truncated_directory() {
+        // Two images need 38 bytes of header and directory
+        let mut truncated = ico(2, &[(10, 38), (10, 48)], 58);
+        truncated.truncate(37);
+        assert!(validate(&truncated).is_err());
+        assert!(validate_head(&truncated, 58).is_err());
+    }
+
+    #[test]
+    fn rejects_image_overlapping_directory() {
+        assert!(validate(&ico(1, &[(10, 21)], 32)).is_err());
+        assert!(validate(&ico(1, &[(10, 0)], 32)).is_err());
+    }
+
+    #[test]
+    fn rejects_image_past_end() {
+        assert!(validate(&ico(1, &[(11, 22)], 32)).is_err());
+        assert!(validate(&ico(1, &[(10, 23)], 32)).is_err());
+        assert!(validate(&ico(1, &[(u32::MAX, 22)], 32)).is_err());
+        assert!(validate(&ico(1, &[(10, u32::MAX)], 32)).is_err());
+    }
+
+    #[test]
+    fn rejects_empty_image() {
+        assert!(validate(&ico(1, &[(0, 22)], 32)).is_err());
+    }
+
+    #[test]
+    fn checks_head_against_whole_length() {
+        let ico = ico(1, &[(1000, 22)], 22);
+        assert!(validate_head(&ico, 1022).is_ok());
+        assert!(validate_head(&ico, 1021).is_err());
+        // The directory has to be in the head, even if the file is long enough
+        assert!(validate_head(&ico[..21], 1022).is_err());
+    }
+
+    #[test]
+    fn decodes_32_bit_bitmap() {
+        // Bottom row is red then green, top row is blue then transparent
+        let pixels = [
+            [0, 0, 0xff, 0xff, 0, 0xff, 0, 0xff],
+            [0xff, 0, 0, 0xff, 0, 0, 0, 0],
+        ]
+        .concat();
+        let bitmap = bitmap(2, 2, 32, &[], &pixels, &[0; 8]);
+        let (width, height, rgba) = decode_bitmap(&bitmap).unwrap();
+        assert_eq!((width, height), (2, 2));
+        assert_eq!(
+            rgba,
+            [
+                [0, 0, 0xff, 0xff, 0, 0, 0, 0],
+                [0xff, 0, 0, 0xff, 0, 0xff, 0, 0xff]
+            ]
+            .concat()
+        );
+    }
+
+    #[test]
+    fn decodes_paletted_bitmap_with_mask() {
+        // One row of two pixels, using colors 1 and 0, with the second masked out
+        let palette = [[0xff, 0, 0, 0], [0, 0, 0xff, 0]];
+        let bitmap = bitmap(
+            2,
+            1,
+            1,
+            &palette,
+            &[0b1000_0000, 0, 0, 0],
+            &[0b0100_0000, 0, 0, 0],
+        );
+        let (_, _, rgba) = decode_bitmap(&bitmap).unwrap();
+        assert_eq!(rgba, [0xff, 0, 0, 0xff, 0, 0, 0xff, 0]);
+    }
+
+    #[test]
+    fn decodes_bitmap_without_mask() {
+        let bitmap = bitmap(1, 1, 24, &[], &[0, 0xff, 0, 0], &[]);
+        let (_, _, rgba) = decode_bitmap(&bitmap).unwrap();
+        assert_eq!(rgba, [0, 0xff, 0, 0xff]);
+    }
+
+    #[test]
+    fn rejects_malformed_bitmaps() {
+        let valid = bitmap(1, 1, 24, &[], &[0, 0xff, 0, 0], &[]);
+        assert!(decode_bitmap(&valid).is_ok());
+
+        // Truncated header and pixels
+        assert!(decode_bitmap(&valid[..39]).is_err());
+        assert!(decode_bitmap(&valid[..43]).is_err());
+
+        // Header claiming to be too short
+        let mut short_header = valid.clone();
+        short_header[0] = 12;
+        assert!(decode_bitmap(&short_header).is_err());
+
+        // Compressed
+        let mut compressed = valid.clone();
+        compressed[16] = 1;
+        assert!(decode_bitmap(&compressed).is_err());
+
+        // Unsupported bit depth
+        let mut sixteen_bit = valid.clone();
+        sixteen_bit[14] = 16;
+        assert!(decode_bitmap(&sixteen_bit).is_err());
+
+        // No pixels, and far too many
+        assert!(decode_bitmap(&bitmap(0, 1, 24, &[], &[0; 4], &[])).is_err());
+        assert!(decode_bitmap(&bitmap(1025, 1, 24, &[], &[], &[])).is_err());
+
+        // Color outside of the palette
+        let outside_palette = bitmap(1, 1, 1, &[[0; 4]], &[0b1000_0000, 0, 0, 0], &[]);
+        assert!(decode_bitmap(&outside_palette).is_err());
+    }
+
+    #[test]
+    fn round_trips_png() {
+        let png = png::encode_rgba(2, 1, &[0xff, 0, 0, 0xff, 0, 0, 0xff, 0x80]);
+        let ico = from_png(&png).unwrap();
+        assert_eq!(to_png(&ico).unwrap(), png);
+        assert_eq!(embedded_png(&ico), Some(png.as_slice()));
+    }
+
+    #[test]
+    fn wraps_pngs_in_order() {
+        let small = png::encode_rgba(1, 1, &[0; 4]);
+        let large = png::encode_rgba(256, 256, &vec![0; 256 * 256 * 4]);
+        let ico = from_pngs(&[&small, &large]).unwrap();
+        assert!(validate(&ico).is_ok());
+        let images = images(&ico).unwrap();
+        assert_eq!(images.len(), 2);
+        assert_eq!((images[0].width, images[0].height), (1, 1));
+        assert_eq!(images[0].data, small);
+        assert_eq!((images[1].width, images[1].height), (256, 256));
+        assert_eq!(images[1].data, large);
+
+        // The largest image is the one converted, and more than one isn't a wrapped PNG
+        assert_eq!(to_png(&ico).unwrap(), large);
+        assert_eq!(embedded_png(&ico), None);
+    }
+
+    #[test]
+    fn rejects_unwrappable_pngs() {
+        assert!(from_pngs(&[]).is_ok_and(|ico| validate(&ico).is_err()));
+        assert!(from_png(b"not a png").is_err());
+        assert!(from_png(&png::encode_rgba(257, 1, &vec![0; 257 * 4])).is_err());
+        assert!(from_png(&png::encode_rgba(0, 0, &[])).is_err());
+
+        let png = png::encode_rgba(1, 1, &[0; 4]);
+        let too_many = vec![png.as_slice(); usize::from(u16::MAX) + 1];
+        assert!(from_pngs(&too_many).is_err());
+        assert!(from_pngs(&too_many[1..]).is_ok());
+    }
+
+    #[test]
+    fn converts_bitmap_icon_to_png() {
+        let bitmap = bitmap(1, 1, 24, &[], &[0, 0xff, 0, 0], &[0; 4]);
+        let mut ico = ico(1, &[(bitmap.len() as u32, 22)], 22);
+        ico.extend_from_slice(&bitmap);
+        let png = to_png(&ico).unwrap();
+        assert_eq!(png, png::encode_rgba(1, 1, &[0, 0xff, 0, 0xff]));
+    }
+
+    /// ICO of `bitmaps`, each recorded in the directory as `size` pixels square,
+    /// with `bit_count` bits per pixel.
+    fn ico_of_bitmaps(bitmaps: &[(u8, u16, &[u8])]) -> Vec<u8> {
+        let mut ico = MAGIC.to_vec();
+        ico.extend_from_slice(&(bitmaps.len() as u16).to_le_bytes());
+        let mut offset = HEADER_LEN + bitmaps.len() * DIRECTORY_ENTRY_LEN;
+        for (size, bit_count, bitmap) in bitmaps {
+            ico.extend_from_slice(&[*size, *size, 0, 0, 1, 0]);
+            ico.extend_from_slice(&bit_count.to_le_bytes());
+            ico.extend_from_slice(&(bitmap.len() as u32).to_le_bytes());
+            ico.extend_from_slice(&(offset as u32).to_le_bytes());
+            offset += bitmap.len();
+        }
+        for (_, _, bitmap) in bitmaps {
+            ico.extend_from_slice(bitmap);
+        }
+        ico
+    }
+
+    #[test]
+    fn converts_deepest_of_same_size_images() {
+        // A green 24-bit image and a red 32-bit one, both the same size
+        let shallow = bitmap(1, 1, 24, &[], &[0, 0xff, 0, 0], &[0; 4]);
+        let deep = bitmap(1, 1, 32, &[], &[0, 0, 0xff, 0xff], &[0; 4]);
+        let red = png::encode_rgba(1, 1, &[0xff, 0, 0, 0xff]);
+        for bitmaps in [
+            [(32, 24, shallow.as_slice()), (32, 32, &deep)],
+            [(32, 32, &deep), (32, 24, &shallow)],
+        ] {
+            assert_eq!(to_png(&ico_of_bitmaps(&bitmaps)).unwrap(), red);
+        }
+    }
+
+    #[test]
+    fn converts_largest_image_whatever_its_depth() {
+        let small_deep = bitmap(1, 1, 32, &[], &[0, 0, 0xff, 0xff], &[0; 4]);
+        let large_shallow = bitmap(1, 1, 24, &[], &[0, 0xff, 0, 0], &[0; 4]);
+        let green = png::encode_rgba(1, 1, &[0, 0xff, 0, 0xff]);
+        let bitmaps = [(16, 32, small_deep.as_slice()), (32, 24, &large_shallow)];
+        assert_eq!(to_png(&ico_of_bitmaps(&bitmaps)).unwrap(), green);
+    }
+}
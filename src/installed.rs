@@ -0,0 +1,367 @@
+//! Finding the games Steam has installed, for fetching icons without shortcuts.
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context as _, Result};
+use log::*;
+
+use crate::shortcut::Shortcut;
+use crate::{binary_vdf, libraries, vdf};
+
+/// Game installed by Steam.
+pub struct InstalledGame {
+    /// App manifest the game was found through
+    pub manifest: PathBuf,
+
+    /// Name of the game, if its manifest records one
+    pub name: Option<String>,
+
+    /// Details of the game's icon, as if read from a shortcut
+    pub shortcut: Shortcut,
+}
+
+/// Find the games installed in every library of the Steam install at `install_dir`,
+/// along with the icon each one uses.
+/// Games without an icon (e.g. tools and redistributables) are left out.
+pub fn installed_games(install_dir: &Path) -> Result<Vec<InstalledGame>> {
+    // Find each game's ID through its manifest
+    // (only the first copy counts if a game is somehow in several libraries)
+    let mut manifests = Vec::new();
+    let mut app_ids = HashSet::new();
+    for library_dir in libraries::library_dirs(install_dir) {
+        let steamapps_dir = library_dir.join("steamapps");
+        let entries = match steamapps_dir.read_dir() {
+            Ok(entries) => entries,
+            Err(error) => {
+                warn!(
+                    "Failed to read Steam library `{}`: {error}",
+                    library_dir.display()
+                );
+                continue;
+            }
+        };
+        for entry in entries {
+            let path = entry?.path();
+            if !is_manifest(&path) {
+                continue;
+            }
+            match read_manifest(&path) {
+                Ok(AppManifest { app_id, .. }) if !app_ids.insert(app_id) => {
+                    debug!("Skipping app #{app_id}, which is in another library too");
+                }
+                Ok(AppManifest { app_id, name, .. }) => manifests.push((path, app_id, name)),
+                Err(error) => warn!("Failed to read `{}`: {error:#}", path.display()),
+            }
+        }
+    }
+    debug!("Found {} installed app(s)", manifests.len());
+
+    let client_icons = client_icons(install_dir, &app_ids)?;
+    let mut games = Vec::new();
+    for (manifest, app_id, name) in manifests {
+        let Some(icon_filename) = client_icons.get(&app_id).cloned() else {
+            debug!("Skipping app #{app_id}, which has no icon");
+            continue;
+        };
+        games.push(InstalledGame {
+            manifest,
+            name,
+            shortcut: Shortcut {
+                game_id: app_id.to_string(),
+                cdn_filename: Some(icon_filename.clone()),
+                icon_filename,
+                icon_dir: None,
+            },
+        });
+    }
+
+    Ok(games)
+}
+
+/// Filenames of the icons on the CDN (e.g. `<hash>.ico`) of the apps in `app_ids`,
+/// as recorded in Steam's cache of app details in the Steam install at `install_dir`.
+/// Apps without an icon (or that Steam knows nothing about) are left out.
+pub fn client_icons(install_dir: &Path, app_ids: &HashSet<u32>) -> Result<HashMap<u32, String>> {
+    let appinfo_path = install_dir.join("appcache").join("appinfo.vdf");
+    let appinfo = fs::read(&appinfo_path)
+        .with_context(|| format!("Failed to read `{}`", appinfo_path.display()))?;
+    let apps = binary_vdf::parse_appinfo(&appinfo, app_ids)
+        .with_context(|| format!("Failed to parse `{}`", appinfo_path.display()))?;
+
+    let mut client_icons = HashMap::new();
+    for (app_id, app) in apps {
+        let client_icon = app
+            .get("appinfo")
+            .and_then(|appinfo| appinfo.get("common")?.get("clienticon")?.as_str())
+            .filter(|icon| !icon.is_empty());
+
+        // Steam names icons after their filename on the CDN
+        if let Some(client_icon) = client_icon {
+            client_icons.insert(app_id, format!("{client_icon}.ico"));
+        }
+    }
+    Ok(client_icons)
+}
+
+/// Names of the games installed in every library of the Steam install at `install_dir`,
+/// keyed by game ID.
+/// Unlike [`installed_games`], this only reads the app manifests.
+pub fn game_names(install_dir: &Path) -> HashMap<String, String> {
+    let mut names = HashMap::new();
+    for library_dir in libraries::library_dirs(install_dir) {
+        let Ok(entries) = library_dir.join("steamapps").read_dir() else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if !is_manifest(&path) {
+                continue;
+            }
+            match read_manifest(&path) {
+                Ok(AppManifest {
+                    app_id,
+                    name: Some(name),
+                    ..
+                }) => {
+                    names.insert(app_id.to_string(), name);
+                }
+                Ok(_) => {}
+                Err(error) => debug!("Failed to read `{}`: {error:#}", path.display()),
+            }
+        }
+    }
+    names
+}
+
+/// Directories the games installed in every library of the Steam install at `install_dir`
+/// are installed in (`steamapps\common\<installdir>` within their library), keyed by app ID.
+/// Only the first copy counts if a game is somehow in several libraries.
+pub fn game_dirs(install_dir: &Path) -> HashMap<u32, PathBuf> {
+    let mut game_dirs = HashMap::new();
+    for library_dir in libraries::library_dirs(install_dir) {
+        let steamapps_dir = library_dir.join("steamapps");
+        let Ok(entries) = steamapps_dir.read_dir() else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if !is_manifest(&path) {
+                continue;
+            }
+            match read_manifest(&path) {
+                Ok(AppManifest {
+                    app_id,
+                    install_dir: Some(game_dir),
+                    ..
+                }) => {
+                    game_dirs
+                        .entry(app_id)
+                        .or_insert_with(|| steamapps_dir.join("common").join(game_dir));
+                }
+                Ok(_) => {}
+                Err(error) => debug!("Failed to read `{}`: {error:#}", path.display()),
+            }
+        }
+    }
+    game_dirs
+}
+
+/// Executables the apps in `app_ids` are launched through on Windows, in Steam's order
+/// (as paths relative to each app's directory, e.g. `bin\game.exe`),
+/// as recorded in Steam's cache of app details in the Steam install at `install_dir`.
+/// Apps Steam knows nothing about are left out.
+pub fn launch_executables(
+    install_dir: &Path,
+    app_ids: &HashSet<u32>,
+) -> Result<HashMap<u32, Vec<String>>> {
+    let appinfo_path = install_dir.join("appcache").join("appinfo.vdf");
+    let appinfo = fs::read(&appinfo_path)
+        .with_context(|| format!("Failed to read `{}`", appinfo_path.display()))?;
+    let apps = binary_vdf::parse_appinfo(&appinfo, app_ids)
+        .with_context(|| format!("Failed to parse `{}`", appinfo_path.display()))?;
+
+    let mut launch_executables = HashMap::new();
+    for (app_id, app) in apps {
+        let Some(launch) = app
+            .get("appinfo")
+            .and_then(|appinfo| appinfo.get("config")?.get("launch"))
+        else {
+            continue;
+        };
+
+        // Launch options without an OS list apply to every OS
+        let executables = launch
+            .entries()
+            .iter()
+            .filter(|(_, option)| {
+                option
+                    .get("config")
+                    .and_then(|config| config.get("oslist")?.as_str())
+                    .is_none_or(|os_list| os_list.is_empty() || os_list.contains("windows"))
+            })
+            .filter_map(|(_, option)| option.get("executable")?.as_str())
+            .filter(|executable| !executable.is_empty())
+            .map(str::to_owned)
+            .collect();
+        launch_executables.insert(app_id, executables);
+    }
+    Ok(launch_executables)
+}
+
+/// Whether `path` is an app manifest (e.g. `appmanifest_620.acf`).
+fn is_manifest(path: &Path) -> bool {
+    path.file_name().is_some_and(|filename| {
+        let filename = filename.to_string_lossy();
+        filename.starts_with("appmanifest_") && filename.ends_with(".acf")
+    })
+}
+
+/// What an app manifest says about an installed app.
+struct AppManifest {
+    /// App ID
+    app_id: u32,
+
+    /// Name of the app, if recorded
+    name: Option<String>,
+
+    /// Directory the app is installed in within `steamapps\common`, if recorded
+    install_dir: Option<String>,
+}
+
+/// Read the app ID, name and install directory (if any) from the app manifest at `path`.
+fn read_manifest(path: &Path) -> Result<AppManifest> {
+    let text = fs::read_to_string(path).context("Failed to read file")?;
+    let manifest = vdf::parse_text(&text)?;
+    let state = manifest.get("AppState").context("Missing `AppState`")?;
+    let app_id = state
+        .get("appid")
+        .and_then(vdf::Value::as_str)
+        .context("Missing `AppState.appid`")?;
+    let app_id = app_id
+        .parse()
+        .with_context(|| format!("Invalid app ID `{app_id}`"))?;
+    let name = state.get("name").and_then(vdf::Value::as_str);
+    let install_dir = state
+        .get("installdir")
+        .and_then(vdf::Value::as_str)
+        .filter(|install_dir| !install_dir.is_empty());
+    Ok(AppManifest {
+        app_id,
+        name: name.map(str::to_owned),
+        install_dir: install_dir.map(str::to_owned),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    /// Manifest of Portal 2 as Steam writes it
+    const MANIFEST: &str = include_str!("../tests/fixtures/appmanifest_620.acf");
+
+    /// Icon of Portal 2 on the CDN, without its extension
+    const CLIENT_ICON: &str = "2e478fc6874d06ae5baf0d147f6f21203291aa02";
+
+    /// Steam install with Portal 2 and a tool without an icon installed,
+    /// along with a damaged manifest and a file that isn't a manifest.
+    fn install() -> TempDir {
+        let dir = TempDir::new().unwrap();
+        let steamapps = dir.path().join("steamapps");
+        fs::create_dir(&steamapps).unwrap();
+        fs::write(steamapps.join("appmanifest_620.acf"), MANIFEST).unwrap();
+        let tool = ["\"AppState\"", "{", "\t\"appid\"\t\t\"228980\"", "}"].join("\n");
+        fs::write(steamapps.join("appmanifest_228980.acf"), tool).unwrap();
+        fs::write(steamapps.join("appmanifest_1.acf"), "\"AppState\" {").unwrap();
+
+        let appcache = dir.path().join("appcache");
+        fs::create_dir(&appcache).unwrap();
+        fs::write(
+            appcache.join("appinfo.vdf"),
+            appinfo(&[(620, Some(CLIENT_ICON)), (228980, None)]),
+        )
+        .unwrap();
+        dir
+    }
+
+    /// `appinfo.vdf` (with keys inline) listing `apps` along with their client icons (if any).
+    fn appinfo(apps: &[(u32, Option<&str>)]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend(0x07564428_u32.to_le_bytes());
+        bytes.extend(1_u32.to_le_bytes());
+        for &(app_id, client_icon) in apps {
+            // Details Steam keeps about the app, which aren't read
+            let mut entry = vec![0; 60];
+            entry.extend(b"\x00appinfo\0\x00common\0");
+            if let Some(client_icon) = client_icon {
+                entry.extend(b"\x01clienticon\0");
+                entry.extend(client_icon.as_bytes());
+                entry.push(0);
+            }
+            entry.extend(b"\x08\x08\x08");
+
+            bytes.extend(app_id.to_le_bytes());
+            bytes.extend(u32::try_from(entry.len()).unwrap().to_le_bytes());
+            bytes.extend(entry);
+        }
+        bytes.extend(0_u32.to_le_bytes());
+        bytes
+    }
+
+    #[test]
+    fn finds_installed_games_with_icons() {
+        let install = install();
+
+        let games = installed_games(install.path()).unwrap();
+
+        assert_eq!(games.len(), 1);
+        let game = &games[0];
+        assert_eq!(
+            game.manifest,
+            install.path().join("steamapps").join("appmanifest_620.acf")
+        );
+        assert_eq!(game.name.as_deref(), Some("Portal 2"));
+        assert_eq!(game.shortcut.game_id, "620");
+        assert_eq!(game.shortcut.icon_filename, format!("{CLIENT_ICON}.ico"));
+        assert_eq!(
+            game.shortcut.cdn_filename.as_deref(),
+            Some(format!("{CLIENT_ICON}.ico").as_str())
+        );
+        assert!(game.shortcut.icon_dir.is_none());
+    }
+
+    #[test]
+    fn fails_without_app_details() {
+        let install = install();
+        fs::remove_file(install.path().join("appcache").join("appinfo.vdf")).unwrap();
+        assert!(installed_games(install.path()).is_err());
+    }
+
+    #[test]
+    fn reads_names_of_installed_games() {
+        let install = install();
+        let names = game_names(install.path());
+        assert_eq!(
+            names,
+            HashMap::from([("620".to_owned(), "Portal 2".to_owned())])
+        );
+    }
+
+    #[test]
+    fn finds_game_dirs() {
+        let install = install();
+        let game_dirs = game_dirs(install.path());
+        let common = install.path().join("steamapps").join("common");
+        assert_eq!(game_dirs, HashMap::from([(620, common.join("Portal 2"))]));
+    }
+
+    #[test]
+    fn recognizes_manifests() {
+        assert!(is_manifest(Path::new("steamapps/appmanifest_620.acf")));
+        assert!(!is_manifest(Path::new("steamapps/appmanifest_620.acf.bak")));
+        assert!(!is_manifest(Path::new("steamapps/libraryfolders.vdf")));
+    }
+}
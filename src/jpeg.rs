@@ -0,0 +1,151 @@
+use anyhow::{Context as _, Result, bail};
+use jpeg_decoder::{Decoder, PixelFormat};
+
+/// Bytes every JPEG file starts with (the start of image marker, then the next marker's prefix)
+pub const SIGNATURE: [u8; 3] = [0xff, 0xd8, 0xff];
+
+/// Largest width or height decoded, which is far more than any icon needs
+const MAX_DIMENSION: u16 = 1024;
+
+/// Decode a JPEG (as Steam caches icons) into RGBA pixels, top row first,
+/// returning its width and height along with them.
+/// JPEGs in CMYK aren't supported.
+pub fn decode(bytes: &[u8]) -> Result<(u32, u32, Vec<u8>)> {
+    if !bytes.starts_with(&SIGNATURE) {
+        bail!("Missing JPEG header");
+    }
+
+    // Check the dimensions before decoding anything, so huge images aren't decoded
+    let mut decoder = Decoder::new(bytes);
+    decoder.read_info().context("Failed to read JPEG header")?;
+    let info = decoder.info().context("JPEG has no frame")?;
+    let (width, height) = (info.width, info.height);
+    if width == 0 || height == 0 || width > MAX_DIMENSION || height > MAX_DIMENSION {
+        bail!("JPEG has unexpected dimensions {width}x{height}");
+    }
+    let pixels = decoder.decode().context("Failed to decode JPEG")?;
+
+    let pixel_count = usize::from(width) * usize::from(height);
+    let mut rgba = Vec::with_capacity(pixel_count * 4);
+    match info.pixel_format {
+        PixelFormat::L8 => {
+            for &luma in &pixels {
+                rgba.extend_from_slice(&[luma, luma, luma, 0xff]);
+            }
+        }
+        // Samples are big-endian, so the high byte comes first
+        PixelFormat::L16 => {
+            for sample in pixels.chunks_exact(2) {
+                rgba.extend_from_slice(&[sample[0], sample[0], sample[0], 0xff]);
+            }
+        }
+        PixelFormat::RGB24 => {
+            for pixel in pixels.chunks_exact(3) {
+                rgba.extend_from_slice(&[pixel[0], pixel[1], pixel[2], 0xff]);
+            }
+        }
+        PixelFormat::CMYK32 => bail!("CMYK JPEGs aren't supported"),
+    }
+
+    Ok((width.into(), height.into(), rgba))
+}
+
+#[cfg(test)]
+mod tests {
+    use jpeg_encoder::{ColorType, Encoder};
+
+    use super::*;
+
+    /// Width and height of the test image.
+    const SIZE: u16 = 16;
+
+    /// Left half red and right half blue, as RGB.
+    fn image() -> Vec<u8> {
+        (0..SIZE * SIZE)
+            .flat_map(|index| {
+                if index % SIZE < SIZE / 2 {
+                    [0xff, 0, 0]
+                } else {
+                    [0, 0, 0xff]
+                }
+            })
+            .collect()
+    }
+
+    /// `image` encoded as a JPEG.
+    fn encode(image: &[u8], color_type: ColorType, progressive: bool) -> Vec<u8> {
+        let mut jpeg = Vec::new();
+        let mut encoder = Encoder::new(&mut jpeg, 100);
+        encoder.set_progressive(progressive);
+        encoder.encode(image, SIZE, SIZE, color_type).unwrap();
+        jpeg
+    }
+
+    /// Whether every channel of `actual` is close to `expected`, given JPEG's loss.
+    fn is_close(actual: &[u8], expected: &[u8]) -> bool {
+        actual.len() == expected.len()
+            && actual
+                .iter()
+                .zip(expected)
+                .all(|(actual, expected)| actual.abs_diff(*expected) <= 8)
+    }
+
+    #[test]
+    fn decodes_color() {
+        for progressive in [false, true] {
+            let image = image();
+            let jpeg = encode(&image, ColorType::Rgb, progressive);
+            let (width, height, rgba) = decode(&jpeg).unwrap();
+            assert_eq!((width, height), (SIZE.into(), SIZE.into()));
+
+            // Stay clear of the edge between the colors, which blurs
+            for (index, (actual, expected)) in
+                rgba.chunks_exact(4).zip(image.chunks_exact(3)).enumerate()
+            {
+                if (index as u16 % SIZE).abs_diff(SIZE / 2) > 2 {
+                    assert!(is_close(&actual[..3], expected), "{actual:?} at {index}");
+                }
+                assert_eq!(actual[3], 0xff);
+            }
+        }
+    }
+
+    #[test]
+    fn decodes_grayscale() {
+        let image = vec![0x80; usize::from(SIZE) * usize::from(SIZE)];
+        let (_, _, rgba) = decode(&encode(&image, ColorType::Luma, false)).unwrap();
+        for pixel in rgba.chunks_exact(4) {
+            assert!(is_close(pixel, &[0x80, 0x80, 0x80, 0xff]), "{pixel:?}");
+        }
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        assert!(decode(b"").is_err());
+        assert!(decode(&SIGNATURE).is_err());
+        assert!(decode(b"\x89PNG\r\n\x1a\n").is_err());
+    }
+
+    #[test]
+    fn survives_truncation() {
+        let jpeg = encode(&image(), ColorType::Rgb, false);
+        for len in 0..jpeg.len() {
+            // Whether or not what's left decodes, it mustn't panic
+            let _ = decode(&jpeg[..len]);
+        }
+    }
+
+    #[test]
+    fn survives_corruption() {
+        for progressive in [false, true] {
+            let jpeg = encode(&image(), ColorType::Rgb, progressive);
+            for index in 0..jpeg.len() {
+                for value in [0x00, 0x01, 0x7f, 0xff] {
+                    let mut corrupted = jpeg.clone();
+                    corrupted[index] = value;
+                    let _ = decode(&corrupted);
+                }
+            }
+        }
+    }
+}
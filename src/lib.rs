@@ -0,0 +1,3643 @@
+#![feature(once_cell_try)]
+
+//! Download missing icons for Steam game shortcuts.
+//!
+//! [`retrieve_missing_icons`] processes whole directories of shortcuts,
+//! while the modules expose the individual steps (parsing shortcuts, downloading icons, etc.).
+
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{BufReader, ErrorKind, Write as _};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant, SystemTime};
+
+use anyhow::{Context as _, Result, bail};
+use futures_util::{StreamExt as _, stream};
+use log::*;
+use reqwest::header::{HeaderMap, HeaderValue};
+
+use crate::art::ArtDownload;
+use crate::budget::{Budget, BudgetExhausted};
+use crate::cancel::Cancellation;
+use crate::download::{
+    BlockedByCdn,
+    Cdn,
+    HttpSettings,
+    IconPathConflict,
+    IconSource,
+    Outcome,
+    ProxyConfig,
+    SaveOptions,
+    SavedIcon,
+    Timeouts,
+    Validators,
+};
+use crate::events::{Event, EventHandler};
+use crate::exe_icon::{ExeIconSource, InstalledExecutables};
+use crate::export::PngExport;
+use crate::filter::{Exclusions, GameIdFilter};
+use crate::installed::InstalledGame;
+use crate::manifest::{Manifest, ManifestEntry};
+use crate::names::GameNames;
+use crate::negative_cache::NegativeCache;
+use crate::platform::{ICON_EXTENSION, SHORTCUT_EXTENSION, SHORTCUTS_ARE_DIRS};
+use crate::rate_limit::{BandwidthLimiter, RateLimited, RateLimiter};
+use crate::report::{
+    Action,
+    ArtReport,
+    CleanReport,
+    FailureKind,
+    FixReport,
+    FixedShortcut,
+    ItemReport,
+    RunReport,
+    UndoReport,
+};
+use crate::resolver::{IconResolver, WebIconResolver};
+use crate::shortcut::{Encoding, NonSteamGame, Shortcut, UnrecognizedIconDir};
+use crate::updates::Comparison;
+use crate::watch::ShortcutWatcher;
+
+pub mod art;
+pub mod binary_vdf;
+pub mod budget;
+pub mod cancel;
+pub mod download;
+pub mod events;
+pub mod exe_icon;
+pub mod export;
+pub mod filter;
+pub mod hook;
+pub mod icns;
+pub mod ico;
+pub mod installed;
+pub mod jpeg;
+pub mod libraries;
+pub mod library_cache;
+pub mod manifest;
+pub mod max_age;
+pub mod names;
+pub mod negative_cache;
+pub mod non_steam;
+pub mod paths;
+pub mod pe;
+pub mod placeholder;
+pub mod platform;
+pub mod png;
+pub mod progress;
+pub mod rate_limit;
+pub mod repair;
+pub mod report;
+pub mod resolver;
+pub mod sha1;
+pub mod shell;
+pub mod shortcut;
+#[cfg(test)]
+mod test_server;
+pub mod updates;
+pub mod vdf;
+pub mod verify;
+pub mod watch;
+
+/// File in the shortcut directory listing games and shortcuts to leave alone
+pub const IGNORE_FILENAME: &str = ".steamiconignore";
+
+/// Number of icons downloaded at once, unless specified otherwise
+pub const DEFAULT_CONCURRENCY: usize = 6;
+
+/// Number of times a failed download is retried, unless specified otherwise
+pub const DEFAULT_RETRIES: u32 = 3;
+
+/// Milliseconds between requests to Steam, unless specified otherwise
+pub const DEFAULT_DELAY_MS: u64 = 150;
+
+/// Seconds to spend connecting to Steam before giving up, unless specified otherwise
+pub const DEFAULT_CONNECT_TIMEOUT_SECS: u64 = 10;
+
+/// Seconds to spend on a whole request to Steam before giving up, unless specified otherwise
+pub const DEFAULT_REQUEST_TIMEOUT_SECS: u64 = 60;
+
+/// Number of times in a row to pause for being rate limited before giving up,
+/// unless specified otherwise
+pub const DEFAULT_MAX_RATE_LIMIT_PAUSES: u32 = 5;
+
+/// How many levels of subdirectories to descend into when scanning recursively
+const MAX_SCAN_DEPTH: usize = 16;
+
+/// How many downloads in a row the CDN can block before giving up on the rest
+const MAX_CONSECUTIVE_BLOCKED: usize = 5;
+
+/// How a run should go about processing shortcuts.
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// Directories containing the shortcuts to process
+    pub dirs_with_shortcuts: Vec<PathBuf>,
+
+    /// Directory icons are saved in (unless a shortcut says otherwise)
+    pub local_icon_dir: PathBuf,
+
+    /// Also process shortcuts in subdirectories
+    pub recursive: bool,
+
+    /// Follow symlinks (and junctions) to shortcuts, and to directories when `recursive`
+    pub follow_links: bool,
+
+    /// Only report which icons would be downloaded,
+    /// without writing anything or making any requests
+    pub dry_run: bool,
+
+    /// Only find which icons are missing,
+    /// without looking up or downloading them (or writing anything at all,
+    /// apart from PNG copies of the icons already there if `export_png` says to)
+    pub check: bool,
+
+    /// How many icons to download at once
+    pub concurrency: usize,
+
+    /// How many times to retry a failed download
+    pub retries: u32,
+
+    /// File remembering which games have no icon on the CDN, unless that's not wanted
+    pub negative_cache: Option<PathBuf>,
+
+    /// File recording every icon downloaded, if one should be kept
+    pub manifest: Option<PathBuf>,
+
+    /// How to connect to Steam (timeouts and proxy)
+    pub http: HttpSettings,
+
+    /// Spaces out requests to Steam (shared with anything else making them)
+    pub rate_limiter: RateLimiter,
+
+    /// Caps how fast icons are downloaded, if at all
+    pub bandwidth_limiter: Option<BandwidthLimiter>,
+
+    /// Caps how much the run downloads, if at all (shared between every download,
+    /// so items left once it's used up are reported as over budget)
+    pub budget: Option<Budget>,
+
+    /// Stop the run at the first failure (cancelling downloads in progress and removing what
+    /// they'd downloaded), rather than carrying on with everything else
+    pub fail_fast: bool,
+
+    /// Base URLs of Steam's CDN, tried in order until one works
+    /// (see [`download::parse_cdn_base_url`])
+    pub cdn_base_urls: Vec<String>,
+
+    /// Download icons even if they already exist, replacing them
+    pub force: bool,
+
+    /// Check downloaded icons against the hash the CDN names them after
+    pub verify: bool,
+
+    /// Convert icons the CDN serves as PNGs into the ICO files shortcuts expect
+    pub convert_pngs: bool,
+
+    /// Only process shortcuts for these games
+    pub game_ids: GameIdFilter,
+
+    /// Only process shortcuts whose icons are in the icon directory with these filenames
+    /// (in lowercase, since they're compared case-insensitively), if given
+    pub only_icons: Option<HashSet<String>>,
+
+    /// Never process these games or shortcuts
+    /// (taking precedence over `game_ids`, and added to by the ignore file)
+    pub exclusions: Exclusions,
+
+    /// Also process every game Steam has installed, even without a shortcut
+    pub installed_games: bool,
+
+    /// Create shortcuts for games Steam has installed that don't have one
+    pub create_shortcuts: bool,
+
+    /// Point shortcuts with an icon in the wrong directory at the icon directory,
+    /// instead of failing them
+    pub fix_shortcuts: bool,
+
+    /// Keep a copy of shortcuts before fixing them
+    pub backup_shortcuts: bool,
+
+    /// Save icons missing from the icon directory here instead (e.g. since only administrators
+    /// can write to the icon directory), pointing their shortcuts here too (keeping a copy of
+    /// each shortcut first), which only `.url` shortcuts can be
+    pub user_icon_dir: Option<PathBuf>,
+
+    /// Copy icons from Steam's library cache here (`appcache\librarycache`)
+    /// instead of downloading them, so nothing is requested at all
+    pub library_cache_dir: Option<PathBuf>,
+
+    /// When the CDN has no icon for an installed game, extract it from the game's executable
+    pub extract_from_exe: bool,
+
+    /// Download icons older than this again, only replacing them if they changed
+    /// (and otherwise updating their modification time, so they aren't downloaded again for
+    /// as long)
+    pub max_age: Option<Duration>,
+
+    /// Ask the CDN about icons that already exist (with `HEAD` requests),
+    /// downloading them again if it says they've changed
+    pub check_updates: bool,
+
+    /// When the CDN has no icon for a game (and none was extracted), generate a placeholder,
+    /// which is replaced once the CDN has an icon after all
+    pub placeholder: bool,
+
+    /// Export a PNG copy of every icon saved (or already there), if asked to,
+    /// for launchers that want PNGs rather than ICO files
+    pub export_png: Option<PngExport>,
+
+    /// Also download these kinds of artwork for every Steam game processed, if asked to
+    /// (replacing any already there if `force` is set)
+    pub art: Option<ArtDownload>,
+
+    /// Look up the names of games whose icons are being downloaded in the Steam store,
+    /// if they aren't named on disk
+    pub look_up_names: bool,
+
+    /// Tell the Windows shell about shortcuts whose icons were saved,
+    /// so it shows them without waiting to be refreshed
+    pub shell_notify: bool,
+
+    /// Keep processing shortcuts as they're created or changed
+    pub watch: bool,
+
+    /// Show a progress bar on stderr
+    pub show_progress: bool,
+
+    /// Stops the run early once cancelled (e.g. when the script is asked to exit)
+    pub cancellation: Cancellation,
+
+    /// Told about each step of the run as it happens, if anything is
+    pub events: Option<EventHandler>,
+}
+
+impl Options {
+    /// Options for downloading the missing icons for the shortcuts in `dirs_with_shortcuts`
+    /// to `local_icon_dir` (and nothing more), with the same defaults as the script.
+    pub fn new(dirs_with_shortcuts: Vec<PathBuf>, local_icon_dir: PathBuf) -> Self {
+        Self {
+            dirs_with_shortcuts,
+            local_icon_dir,
+            recursive: false,
+            follow_links: false,
+            dry_run: false,
+            check: false,
+            concurrency: DEFAULT_CONCURRENCY,
+            retries: DEFAULT_RETRIES,
+            negative_cache: None,
+            manifest: None,
+            http: HttpSettings {
+                timeouts: Timeouts {
+                    connect: Duration::from_secs(DEFAULT_CONNECT_TIMEOUT_SECS),
+                    request: Duration::from_secs(DEFAULT_REQUEST_TIMEOUT_SECS),
+                },
+                proxy: ProxyConfig::Environment,
+                user_agent: HeaderValue::from_static(download::DEFAULT_USER_AGENT),
+                headers: HeaderMap::new(),
+                ca_certs: Vec::new(),
+                insecure: false,
+            },
+            rate_limiter: RateLimiter::new(
+                Duration::from_millis(DEFAULT_DELAY_MS),
+                DEFAULT_MAX_RATE_LIMIT_PAUSES,
+            ),
+            bandwidth_limiter: None,
+            budget: None,
+            fail_fast: false,
+            cdn_base_urls: download::DEFAULT_CDN_BASE_URLS.map(str::to_owned).to_vec(),
+            force: false,
+            verify: true,
+            convert_pngs: true,
+            game_ids: GameIdFilter::default(),
+            only_icons: None,
+            exclusions: Exclusions::default(),
+            installed_games: false,
+            create_shortcuts: false,
+            fix_shortcuts: false,
+            backup_shortcuts: false,
+            user_icon_dir: None,
+            library_cache_dir: None,
+            extract_from_exe: false,
+            max_age: None,
+            check_updates: false,
+            placeholder: false,
+            export_png: None,
+            art: None,
+            look_up_names: true,
+            shell_notify: true,
+            watch: false,
+            show_progress: false,
+            cancellation: Cancellation::new(),
+            events: None,
+        }
+    }
+
+    /// Tell whatever's listening about `event`, if anything is.
+    fn emit(&self, event: impl FnOnce() -> Event) {
+        if let Some(events) = &self.events {
+            events.emit(&event());
+        }
+    }
+}
+
+/// A run that stopped early, along with what it got done before stopping.
+#[derive(Debug)]
+pub struct RunFailed {
+    /// Everything that happened before the run stopped
+    pub report: RunReport,
+
+    /// Why the run stopped
+    pub error: anyhow::Error,
+}
+
+impl fmt::Display for RunFailed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:#}", self.error)
+    }
+}
+
+impl Error for RunFailed {}
+
+/// Error for when the run stopped at its first failure, as asked.
+#[derive(Debug)]
+pub struct FailedFast;
+
+impl fmt::Display for FailedFast {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Stopping the run at its first failure")
+    }
+}
+
+impl Error for FailedFast {}
+
+/// How to go about finding icons no shortcut uses anymore.
+#[derive(Debug, Clone)]
+pub struct CleanOptions {
+    /// Also look for shortcuts in subdirectories
+    pub recursive: bool,
+
+    /// Follow symlinks (and junctions) to shortcuts, and to directories when `recursive`
+    pub follow_links: bool,
+
+    /// Never look at these shortcuts
+    pub exclusions: Exclusions,
+
+    /// Also keep the icons of every game Steam has installed, even without a shortcut
+    pub installed_games: bool,
+
+    /// Keep icons modified more recently than this,
+    /// in case their shortcuts are only elsewhere for now
+    pub min_age: Duration,
+}
+
+/// Options for repairing shortcuts (see [`fix_shortcuts`]).
+#[derive(Debug, Default)]
+pub struct FixOptions {
+    /// Also look for shortcuts in subdirectories
+    pub recursive: bool,
+
+    /// Follow symlinks (and junctions) to directories when `recursive`
+    pub follow_links: bool,
+
+    /// Never look at these games or shortcuts
+    pub exclusions: Exclusions,
+
+    /// Only repair shortcuts for these games
+    pub game_ids: GameIdFilter,
+
+    /// Report what would be repaired without changing anything
+    pub dry_run: bool,
+
+    /// Keep a copy of each shortcut alongside it before it's repaired
+    pub backup_shortcuts: bool,
+}
+
+/// An icon in the icon directory that no shortcut uses.
+#[derive(Debug, Clone)]
+pub struct OrphanedIcon {
+    /// Path to the icon
+    pub path: PathBuf,
+
+    /// Size of the icon
+    pub bytes: u64,
+}
+
+/// An icon that needs to be downloaded for a shortcut.
+struct MissingIcon {
+    /// Path to the shortcut
+    shortcut: PathBuf,
+
+    /// Steam game ID
+    game_id: String,
+
+    /// Name of the game, if known
+    game_name: Option<String>,
+
+    /// Filename the icon is saved as locally
+    icon_filename: String,
+
+    /// Where the icon is downloaded from
+    /// (only its name, when it's copied from Steam's library cache instead)
+    source: IconSource,
+
+    /// URL of the icon on the CDN's first host, for the logs
+    /// (empty when it's copied from Steam's library cache instead)
+    url: String,
+
+    /// Icon in Steam's library cache to copy instead of downloading (when offline)
+    cached_icon: Option<PathBuf>,
+
+    /// Where the icon should be saved
+    icon_path: PathBuf,
+
+    /// Whether an existing icon is being replaced
+    replacing: bool,
+
+    /// Whether the existing icon is being replaced because it's damaged
+    damaged: bool,
+
+    /// Whether the existing icon is being replaced because it's only a placeholder
+    placeholder: bool,
+
+    /// Whether the existing icon is being downloaded again because it's older than the maximum
+    /// age (and is only replaced if it changed)
+    stale: bool,
+
+    /// Whether the existing icon is being checked against what the CDN says about it
+    /// (`--check-updates`), and so is only downloaded again if the CDN says it changed
+    checking_update: bool,
+}
+
+impl MissingIcon {
+    /// Whether an intact icon is already there, which is kept if the CDN no longer has one.
+    fn keeps_existing(&self) -> bool {
+        self.stale || self.checking_update
+    }
+}
+
+/// Everything found while scanning a shortcut directory.
+struct ScannedDir {
+    /// Paths of the entries found
+    entries: Vec<PathBuf>,
+
+    /// Number of entries (or subdirectories) that couldn't be read
+    unreadable: usize,
+}
+
+/// Something that may be missing its icon.
+enum Entry {
+    /// Shortcut (or anything else in a shortcut directory) that hasn't been read yet
+    Shortcut(PathBuf),
+
+    /// Game Steam has installed, found through its app manifest
+    InstalledGame(InstalledGame),
+}
+
+impl Entry {
+    /// Path to the shortcut or app manifest, for logs and reports.
+    fn path(&self) -> &Path {
+        match self {
+            Self::Shortcut(path) => path,
+            Self::InstalledGame(game) => &game.manifest,
+        }
+    }
+}
+
+/// Settings shared by every pass over the shortcuts.
+struct Processor<'a, R> {
+    /// How to go about processing shortcuts
+    options: &'a Options,
+
+    /// Directories containing the shortcuts
+    dirs_with_shortcuts: &'a [PathBuf],
+
+    /// Directory icons are saved in (unless a shortcut says otherwise)
+    local_icon_dir: &'a Path,
+
+    /// Looks up icons for shortcuts that don't say which to download
+    resolver: &'a R,
+
+    /// Games and shortcuts to leave alone, including those in the ignore file
+    exclusions: Exclusions,
+
+    /// Names of games, for the logs and report
+    names: GameNames,
+
+    /// Games recently found to have no icon on the CDN
+    negative_cache: NegativeCache,
+
+    /// Every icon downloaded, in this run and earlier ones
+    manifest: Manifest,
+
+    /// Extracts icons the CDN doesn't have from games' executables, if asked to
+    exe_icons: Option<&'a dyn ExeIconSource>,
+}
+
+/// Find and download the missing icons for every shortcut in the shortcut directories
+/// (looking up those shortcuts don't name on the web), then keep doing so for new shortcuts
+/// if watching, until done or cancelled.
+/// Everything that happened is in the report, even if the run stops early.
+pub async fn retrieve_missing_icons(options: Options) -> Result<RunReport, RunFailed> {
+    let mut report = RunReport::default();
+    let result = match WebIconResolver::new(&options.http, options.rate_limiter.clone()) {
+        Ok(resolver) => run(&options, &resolver, &mut report).await,
+        Err(error) => Err(error),
+    };
+    progress::finish();
+    options.emit(|| Event::run_finished(&report, result.as_ref().err()));
+
+    match result {
+        Ok(()) => Ok(report),
+        Err(error) => Err(RunFailed { report, error }),
+    }
+}
+
+/// Find and download the missing icons for every shortcut in the shortcut directories,
+/// then keep doing so for new shortcuts if watching.
+/// Icons shortcuts don't name are looked up with `resolver`.
+/// Downloads in progress are cut short once the run is cancelled.
+pub async fn run(
+    options: &Options,
+    resolver: &impl IconResolver,
+    report: &mut RunReport,
+) -> Result<()> {
+    let dirs_with_shortcuts = options.dirs_with_shortcuts.as_slice();
+    let local_icon_dir = options.local_icon_dir.as_path();
+    let cancellation = &options.cancellation;
+    let check_sigint = &|| cancellation.check();
+
+    // Clean up after downloads interrupted in previous runs that no longer need resuming
+    if !options.check {
+        remove_partial_downloads(local_icon_dir, options.dry_run, check_sigint)?;
+    }
+
+    // Add the exclusions listed in the shortcut directories, if any
+    let mut exclusions = options.exclusions.clone();
+    for dir in dirs_with_shortcuts {
+        let ignore_file = dir.join(IGNORE_FILENAME);
+        if ignore_file.is_file() {
+            info!("Reading exclusions from {}", ignore_file.display());
+            read_ignore_file(&ignore_file, &mut exclusions)
+                .with_context(|| format!("Failed to read `{}`", ignore_file.display()))?;
+        }
+    }
+
+    // Find the games Steam has installed, if needed
+    let installed_games = if options.installed_games || options.create_shortcuts {
+        let install_dir = platform::steam_install_dir(local_icon_dir)?;
+        info!(
+            "Finding games installed by Steam in {}",
+            install_dir.display()
+        );
+        installed::installed_games(&install_dir)
+            .context("Failed to find the games Steam has installed")?
+    } else {
+        Vec::new()
+    };
+
+    // Find the games' executables, if icons the CDN doesn't have are to be extracted from them
+    let exe_icons = if options.extract_from_exe {
+        let install_dir = platform::steam_install_dir(local_icon_dir)
+            .context("Failed to find the Steam install to extract icons from games' executables")?;
+        Some(InstalledExecutables::new(&install_dir))
+    } else {
+        None
+    };
+
+    // Name games in the logs with what Steam has on disk, if it can be found
+    // (anything else is only looked up once it turns out an icon needs downloading)
+    let mut known_names = HashMap::new();
+    if let Ok(install_dir) = platform::steam_install_dir(local_icon_dir) {
+        if installed_games.is_empty() {
+            known_names.extend(installed::game_names(&install_dir));
+        }
+        known_names.extend(non_steam::game_names(&install_dir));
+    }
+    known_names.extend(installed_games.iter().filter_map(|game| {
+        let name = game.name.clone()?;
+        Some((game.shortcut.game_id.clone(), name))
+    }));
+
+    let processor = Processor {
+        options,
+        dirs_with_shortcuts,
+        local_icon_dir,
+        resolver,
+        exclusions,
+        names: GameNames::new(
+            known_names,
+            options.look_up_names,
+            &options.http,
+            options.rate_limiter.clone(),
+        )?,
+        negative_cache: NegativeCache::load(options.negative_cache.clone()),
+        manifest: Manifest::load(options.manifest.clone()),
+        exe_icons: exe_icons
+            .as_ref()
+            .map(|exe_icons| exe_icons as &dyn ExeIconSource),
+    };
+
+    // Create any missing shortcuts first, so their icons are retrieved along with the rest
+    if options.create_shortcuts {
+        processor.create_missing_shortcuts(&installed_games, check_sigint)?;
+    }
+
+    // Find everything in the shortcut directories (and subdirectories, if requested)
+    report.locations = dirs_with_shortcuts.to_vec();
+    options.emit(|| Event::ScanStarted {
+        locations: dirs_with_shortcuts
+            .iter()
+            .map(|dir| dir.to_string_lossy().into_owned())
+            .collect(),
+    });
+    let mut entries = Vec::new();
+    for dir in dirs_with_shortcuts {
+        let scanned = scan_dir(dir, options.recursive, options.follow_links, check_sigint)?;
+        report.unreadable_entries += scanned.unreadable;
+        entries.extend(scanned.entries.into_iter().map(Entry::Shortcut));
+    }
+
+    // Add the games Steam has installed, if requested
+    // (after the shortcuts, so a game with both is only processed for its shortcut)
+    if options.installed_games {
+        entries.extend(installed_games.into_iter().map(Entry::InstalledGame));
+    }
+
+    // Show progress as each shortcut is finished with, if requested
+    if options.show_progress {
+        progress::start(entries.len());
+    }
+
+    processor.process(entries, report, cancellation).await?;
+    progress::finish();
+
+    if options.watch {
+        processor.watch(report, cancellation).await?;
+    }
+
+    Ok(())
+}
+
+/// Remove the icons in `manifest` that were downloaded for the games in `game_ids`
+/// (or only report which would be removed if `dry_run`), forgetting them once they're gone.
+/// Icons are only removed if they're still exactly as downloaded,
+/// and nothing that isn't in the manifest is ever touched.
+pub fn undo(
+    manifest: &Manifest,
+    game_ids: &GameIdFilter,
+    dry_run: bool,
+    report: &mut UndoReport,
+    check_sigint: &impl Fn() -> Result<()>,
+) -> Result<()> {
+    report.dry_run = dry_run;
+    let entries = manifest.entries();
+    for entry in entries
+        .iter()
+        .filter(|entry| game_ids.matches(&entry.game_id))
+    {
+        // Stop if the script needs to exit (still forgetting the icons already removed)
+        if check_sigint().is_err() {
+            break;
+        }
+
+        let icon_path = &entry.icon_path;
+        let contents = match fs::read(icon_path) {
+            Ok(contents) => contents,
+            Err(error) if error.kind() == ErrorKind::NotFound => {
+                info!(
+                    "Icon for game #{} at {} is already gone",
+                    entry.game_id,
+                    icon_path.display()
+                );
+                if !dry_run {
+                    manifest.remove(icon_path);
+                }
+                report.missing.push(icon_path.clone());
+                continue;
+            }
+            Err(error) => {
+                error!("Failed to read `{}`: {error}", icon_path.display());
+                report.failed.push(icon_path.clone());
+                continue;
+            }
+        };
+
+        // Leave icons that were changed since (e.g. replaced by Steam) alone
+        if sha1::hex_digest(&contents) != entry.sha1 {
+            warn!(
+                "Leaving the icon for game #{} at {}, since it changed after it was downloaded",
+                entry.game_id,
+                icon_path.display()
+            );
+            report.modified.push(icon_path.clone());
+            continue;
+        }
+
+        if dry_run {
+            info!(
+                "Would remove icon for game #{} at {}",
+                entry.game_id,
+                icon_path.display()
+            );
+            report.removed.push(icon_path.clone());
+            continue;
+        }
+        match fs::remove_file(icon_path) {
+            Ok(()) => {
+                info!(
+                    "Removed icon for game #{} at {}",
+                    entry.game_id,
+                    icon_path.display()
+                );
+                manifest.remove(icon_path);
+                report.removed.push(icon_path.clone());
+            }
+            Err(error) => {
+                error!("Failed to remove `{}`: {error}", icon_path.display());
+                report.failed.push(icon_path.clone());
+            }
+        }
+    }
+
+    if !dry_run {
+        manifest.save()?;
+    }
+    check_sigint()
+}
+
+/// Find the icons in `local_icon_dir` that none of the shortcuts in `dirs_with_shortcuts` use
+/// (nor any game Steam has installed, if asked), leaving out those that are too new.
+/// Nothing is found if any shortcut can't be read, since its icon could be any of them.
+pub fn find_orphaned_icons(
+    dirs_with_shortcuts: &[PathBuf],
+    local_icon_dir: &Path,
+    options: &CleanOptions,
+    report: &mut CleanReport,
+    check_sigint: &impl Fn() -> Result<()>,
+) -> Result<Vec<OrphanedIcon>> {
+    // Gather the icons that are still used
+    // (case-insensitively, since that's how Windows finds them)
+    let mut used_icons = HashSet::new();
+    let mut unreadable = 0;
+    for dir in dirs_with_shortcuts {
+        let scanned = scan_dir(dir, options.recursive, options.follow_links, check_sigint)?;
+        unreadable += scanned.unreadable;
+        for path in scanned.entries {
+            check_sigint()?;
+            let is_excluded = path.file_name().is_some_and(|filename| {
+                options
+                    .exclusions
+                    .excludes_shortcut(&filename.to_string_lossy())
+            });
+            if is_excluded {
+                debug!("Skipping excluded shortcut `{}`", path.display());
+                continue;
+            }
+            match extract_game_id_and_icon_filename(
+                &path,
+                dir,
+                local_icon_dir,
+                None,
+                options.follow_links,
+            ) {
+                Ok(Some(shortcut)) => {
+                    used_icons.insert(shortcut.icon_filename.to_lowercase());
+                }
+                Ok(None) => {}
+                // Steam doesn't keep icons for non-Steam games in the icon directory
+                Err(error) if error.is::<NonSteamGame>() => {}
+                Err(error) => {
+                    error!("Failed to read `{}`: {error:#}", path.display());
+                    unreadable += 1;
+                }
+            }
+        }
+    }
+    if unreadable > 0 {
+        bail!(
+            "Not removing any icons, since {unreadable} shortcut(s) or entries couldn't be read \
+             (fix or exclude them first)"
+        );
+    }
+    if options.installed_games {
+        let install_dir = platform::steam_install_dir(local_icon_dir)?;
+        info!(
+            "Keeping the icons of games installed by Steam in {}",
+            install_dir.display()
+        );
+        let installed_games = installed::installed_games(&install_dir)
+            .context("Failed to find the games Steam has installed")?;
+        used_icons.extend(
+            installed_games
+                .into_iter()
+                .map(|game| game.shortcut.icon_filename.to_lowercase()),
+        );
+    }
+
+    // Only ever consider icons directly in the icon directory
+    let mut orphaned_icons = Vec::new();
+    for entry in local_icon_dir.read_dir()? {
+        check_sigint()?;
+        let entry = entry?;
+        let filename = entry.file_name().to_string_lossy().to_lowercase();
+        let metadata = entry.metadata()?;
+        if !metadata.is_file()
+            || !filename.ends_with(ICON_EXTENSION)
+            || used_icons.contains(&filename)
+        {
+            continue;
+        }
+
+        // Keep anything modified too recently (or at an unknown time) to be sure about
+        let path = entry.path();
+        let age = metadata
+            .modified()
+            .ok()
+            .and_then(|modified| modified.elapsed().ok());
+        if age.is_none_or(|age| age < options.min_age) {
+            debug!("Keeping `{}`, since it's too new", path.display());
+            report.too_new += 1;
+            continue;
+        }
+
+        orphaned_icons.push(OrphanedIcon {
+            path,
+            bytes: metadata.len(),
+        });
+    }
+    orphaned_icons.sort_unstable_by(|a, b| a.path.cmp(&b.path));
+    Ok(orphaned_icons)
+}
+
+/// Remove `orphaned_icons` (found with [`find_orphaned_icons`]).
+pub fn remove_orphaned_icons(
+    orphaned_icons: &[OrphanedIcon],
+    report: &mut CleanReport,
+    check_sigint: &impl Fn() -> Result<()>,
+) -> Result<()> {
+    for icon in orphaned_icons {
+        check_sigint()?;
+        match fs::remove_file(&icon.path) {
+            Ok(()) => {
+                info!("Removed `{}`", icon.path.display());
+                report.record_removed(icon);
+            }
+            Err(error) => {
+                error!("Failed to remove `{}`: {error}", icon.path.display());
+                report.failed.push(icon.path.clone());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Repair every problem with the `.url` shortcuts in `dirs_with_shortcuts`
+/// that keeps their icons from showing (see [`repair::repair`]),
+/// so they all point at their game's icon in `local_icon_dir`.
+/// Shortcuts that can't be fully understood are left alone.
+pub fn fix_shortcuts(
+    dirs_with_shortcuts: &[PathBuf],
+    local_icon_dir: &Path,
+    options: &FixOptions,
+    report: &mut FixReport,
+    check_sigint: &impl Fn() -> Result<()>,
+) -> Result<()> {
+    // Find the shortcuts to repair first, so Steam's cache of app details can be read in one go
+    let mut shortcuts = Vec::new();
+    let mut app_ids = HashSet::new();
+    for dir in dirs_with_shortcuts {
+        let scanned = scan_dir(dir, options.recursive, options.follow_links, check_sigint)?;
+        report.unreadable_entries += scanned.unreadable;
+        for path in scanned.entries {
+            check_sigint()?;
+            let Some(filename) = path.file_name().map(|name| name.to_string_lossy()) else {
+                continue;
+            };
+            if !filename.to_lowercase().ends_with(repair::EXTENSION) {
+                continue;
+            }
+            if options.exclusions.excludes_shortcut(&filename) {
+                debug!("Skipping excluded shortcut `{}`", path.display());
+                continue;
+            }
+
+            // Leave alone games that are excluded or weren't asked for
+            // (anything unreadable is still checked, so it's reported)
+            let game_id = File::open(&path)
+                .map_err(anyhow::Error::from)
+                .and_then(|file| shortcut::find_game_id(BufReader::new(file)));
+            if let Ok(Some(game_id)) = &game_id {
+                if options.exclusions.excludes_game(game_id) || !options.game_ids.matches(game_id) {
+                    continue;
+                }
+                app_ids.extend(game_id.parse::<u32>().ok());
+            }
+            shortcuts.push(path);
+        }
+    }
+
+    // Steam's cache of app details is only read if a shortcut needs it
+    let mut client_icons: Option<HashMap<u32, String>> = None;
+    let mut lookup_icon = |game_id: &str| {
+        let client_icons = client_icons.get_or_insert_with(|| {
+            platform::steam_install_dir(local_icon_dir)
+                .and_then(|install_dir| installed::client_icons(&install_dir, &app_ids))
+                .unwrap_or_else(|error| {
+                    warn!("Failed to find which icons Steam's games use: {error:#}");
+                    HashMap::new()
+                })
+        });
+        client_icons.get(&game_id.parse().ok()?).cloned()
+    };
+
+    for path in shortcuts {
+        check_sigint()?;
+        report.checked += 1;
+        let repair = match repair::repair_shortcut(&path, local_icon_dir, &mut lookup_icon) {
+            Ok(repair) => repair,
+            Err(error) => {
+                warn!("Leaving `{}` alone: {error:#}", path.display());
+                report.left_alone.push((path, format!("{error:#}")));
+                continue;
+            }
+        };
+        if repair.problems.is_empty() {
+            continue;
+        }
+
+        if !options.dry_run {
+            let result = repair::write_shortcut(
+                &path,
+                repair.encoding,
+                &repair.fixed,
+                options.backup_shortcuts,
+            );
+            if let Err(error) = result {
+                error!("Failed to fix `{}`: {error:#}", path.display());
+                report.failed.push(path);
+                continue;
+            }
+        }
+        report.fixed.push(FixedShortcut {
+            diff: repair::diff(&repair.original, &repair.fixed),
+            problems: repair.problems,
+            path,
+        });
+    }
+    Ok(())
+}
+
+impl<R: IconResolver> Processor<'_, R> {
+    /// Find and download the missing icons for the given shortcuts and games.
+    async fn process(
+        &self,
+        entries: impl IntoIterator<Item = Entry>,
+        report: &mut RunReport,
+        cancellation: &Cancellation,
+    ) -> Result<()> {
+        let batch_start = report.items.len();
+        let mut pending_downloads = Vec::new();
+        let mut queued_icons = HashSet::new();
+        let mut seen_shortcuts = HashSet::new();
+        for entry in entries {
+            fail_fast(self.options, report)?;
+
+            // Find the icon the shortcut needs, if it's missing
+            // (a broken shortcut shouldn't stop the rest from being processed,
+            // but looking it up shouldn't hold up exiting)
+            let path = entry.path().to_owned();
+            let result = cancellation
+                .or_cancelled(self.find_missing_icon(entry, &mut seen_shortcuts, report))
+                .await?;
+            let missing_icon = match result {
+                Ok(Some(missing_icon)) => missing_icon,
+                Ok(None) => continue,
+                Err(error) => {
+                    error!("Failed to process `{}`: {error:#}", path.display());
+                    let failure = if error.is::<IconPathConflict>() {
+                        FailureKind::Write
+                    } else {
+                        FailureKind::Shortcut
+                    };
+                    let item = ItemReport {
+                        error: Some(format!("{error:#}")),
+                        failure: Some(failure),
+                        ..ItemReport::new(&path, Action::Failed)
+                    };
+                    self.options.emit(|| Event::item_failed(&item));
+                    report.record(item);
+                    continue;
+                }
+            };
+
+            // Make sure the icon isn't already being downloaded for another shortcut
+            // (icons with the same name in different directories are each downloaded)
+            if !queued_icons.insert(missing_icon.icon_path.clone()) {
+                info!(
+                    "Skipping duplicate shortcut for {}, since its icon is already queued for \
+                     download",
+                    self.names.describe(&missing_icon.game_id)
+                );
+                report.record(missing_icon.report(Action::Duplicate));
+                continue;
+            }
+
+            // Don't ask the CDN again for icons it recently said it doesn't have
+            // (though its executable, if asked, may have one)
+            if let Some(age) = self.negative_cache.known_missing(&missing_icon.game_id) {
+                if missing_icon.keeps_existing() {
+                    info!(
+                        "Keeping the existing icon for {}, since the CDN had no icon for it {} \
+                         day(s) ago",
+                        missing_icon.describe(),
+                        age.as_secs() / (24 * 60 * 60)
+                    );
+                    report.record(missing_icon.report(Action::AlreadyExists));
+                    continue;
+                }
+                let extracted = match self.exe_icons {
+                    Some(exe_icons) if !self.options.dry_run => {
+                        extract_exe_icon(exe_icons, &missing_icon, self.options, &self.manifest)
+                    }
+                    _ => None,
+                };
+                let generated = extracted.or_else(|| {
+                    (self.options.placeholder && !self.options.dry_run)
+                        .then(|| save_placeholder(&missing_icon, self.options, &self.manifest))?
+                });
+                if let Some(item) = generated {
+                    report.record(item);
+                    continue;
+                }
+                info!(
+                    "Skipping {}, since the CDN had no icon for it {} day(s) ago",
+                    self.names.describe(&missing_icon.game_id),
+                    age.as_secs() / (24 * 60 * 60)
+                );
+                report.record(missing_icon.report(Action::KnownMissing));
+                continue;
+            }
+
+            pending_downloads.push(missing_icon);
+        }
+        fail_fast(self.options, report)?;
+
+        // Name the games that need icons, looking up any that aren't named on disk
+        // (all at once, and only now, so runs with nothing to download make no requests,
+        // and never when doing a dry run, which makes none at all)
+        if !self.options.dry_run {
+            let look_up_names = self.names.look_up(
+                pending_downloads
+                    .iter()
+                    .map(|missing_icon| missing_icon.game_id.as_str()),
+                self.options.concurrency,
+            );
+            cancellation.or_cancelled(look_up_names).await?;
+            for missing_icon in &mut pending_downloads {
+                missing_icon.game_name = self.names.get(&missing_icon.game_id);
+            }
+        }
+
+        // Only download icons already there again if the CDN says they've changed
+        // (which a dry run can't ask, so it only says which would be checked)
+        let pending_downloads = if self.options.dry_run {
+            pending_downloads
+        } else {
+            check_for_updates(
+                pending_downloads,
+                self.options,
+                &self.negative_cache,
+                report,
+                cancellation,
+            )
+            .await?
+        };
+
+        // Only report what would be downloaded when doing a dry run
+        if self.options.dry_run {
+            for missing_icon in &pending_downloads {
+                match &missing_icon.cached_icon {
+                    None if missing_icon.checking_update => info!(
+                        "Would ask the CDN whether the icon for {} has changed, and if so \
+                         download it again from {} to {}",
+                        missing_icon.describe(),
+                        missing_icon.url,
+                        missing_icon.icon_path.display()
+                    ),
+                    Some(cached_icon) => info!(
+                        "Would copy icon for {} from `{}` to {}",
+                        missing_icon.describe(),
+                        cached_icon.display(),
+                        missing_icon.icon_path.display()
+                    ),
+                    None => info!(
+                        "Would download icon for {} from {} to {}",
+                        missing_icon.describe(),
+                        missing_icon.url,
+                        missing_icon.icon_path.display()
+                    ),
+                }
+                report.record(missing_icon.report(Action::Pending));
+            }
+            info!(
+                "Dry run complete, {} icon(s) would be downloaded",
+                pending_downloads.len()
+            );
+            return self.retrieve_art(batch_start, report, cancellation).await;
+        }
+
+        let first_item = report.items.len();
+        let result = if self.options.library_cache_dir.is_some() {
+            copy_cached_icons(
+                pending_downloads,
+                self.options,
+                &self.manifest,
+                report,
+                cancellation,
+            )
+        } else {
+            download_icons(
+                pending_downloads,
+                self.options,
+                &self.negative_cache,
+                self.exe_icons,
+                &self.manifest,
+                report,
+                cancellation,
+            )
+            .await
+        };
+
+        // Remember the icons the CDN didn't have and the ones downloaded,
+        // even if downloading was cut short
+        if let Err(error) = self.negative_cache.save() {
+            warn!("{error:#}");
+        }
+        if let Err(error) = self.manifest.save() {
+            warn!("{error:#}");
+        }
+
+        // Have the shell show the new icons right away (once it's been told about each shortcut)
+        let saved_any = report.items[first_item..].iter().any(|item| {
+            matches!(
+                item.action,
+                Action::Downloaded
+                    | Action::Redownloaded
+                    | Action::Repaired
+                    | Action::Updated
+                    | Action::Placeholder
+            )
+        });
+        if self.options.shell_notify && saved_any {
+            shell::refresh_icons();
+        }
+        result?;
+
+        // Then any other artwork asked for, now the icons (which shortcuts need) are done
+        self.retrieve_art(batch_start, report, cancellation).await
+    }
+
+    /// Download the artwork asked for (if any) for the Steam games whose shortcuts were reported
+    /// on from `first_item` on, or only report what would be downloaded when doing a dry run.
+    async fn retrieve_art(
+        &self,
+        first_item: usize,
+        report: &mut RunReport,
+        cancellation: &Cancellation,
+    ) -> Result<()> {
+        let Some(art) = &self.options.art else {
+            return Ok(());
+        };
+        if self.options.check {
+            return Ok(());
+        }
+
+        // Every Steam game processed has artwork, whether or not its icon was missing
+        let mut seen_games = HashSet::new();
+        let game_ids: Vec<_> = report.items[first_item..]
+            .iter()
+            .filter(|item| {
+                !matches!(
+                    item.action,
+                    Action::Excluded | Action::FilteredOut | Action::NonSteam
+                )
+            })
+            .filter_map(|item| item.game_id.clone())
+            .filter(|game_id| seen_games.insert(game_id.clone()))
+            .collect();
+        if game_ids.is_empty() {
+            return Ok(());
+        }
+        if !self.options.dry_run {
+            fs::create_dir_all(&art.dir)
+                .with_context(|| format!("Failed to create `{}`", art.dir.display()))?;
+        }
+
+        // Only download what isn't there yet, unless it's being replaced anyway
+        let mut pending_art = Vec::new();
+        for game_id in &game_ids {
+            for &kind in &art.kinds {
+                let art_path = art.dir.join(kind.filename(game_id));
+                let art_report = |action| ArtReport {
+                    game_id: game_id.clone(),
+                    kind,
+                    action,
+                    bytes: 0,
+                    error: None,
+                };
+                match download::icon_exists(&art_path) {
+                    Ok(true) if !self.options.force => {
+                        debug!("{kind} already exists for {}", self.names.describe(game_id));
+                        report.art.push(art_report(Action::AlreadyExists));
+                    }
+                    Ok(_) if self.options.dry_run => {
+                        info!(
+                            "Would download {kind} for {} to {}",
+                            self.names.describe(game_id),
+                            art_path.display()
+                        );
+                        report.art.push(art_report(Action::Pending));
+                    }
+                    Ok(_) => pending_art.push((game_id.as_str(), kind, art_path)),
+                    Err(error) => {
+                        error!(
+                            "Failed to download {kind} for {}: {error:#}",
+                            self.names.describe(game_id)
+                        );
+                        report.art.push(ArtReport {
+                            error: Some(format!("{error:#}")),
+                            ..art_report(Action::Failed)
+                        });
+                    }
+                }
+            }
+        }
+        fail_fast(self.options, report)?;
+        if pending_art.is_empty() {
+            return Ok(());
+        }
+
+        let cdn = Cdn::new(
+            self.options.cdn_base_urls.clone(),
+            &self.options.http,
+            self.options.rate_limiter.clone(),
+            self.options.bandwidth_limiter.clone(),
+            self.options.budget.clone(),
+        )?;
+        let cdn = &cdn;
+        let options = self.options;
+        let in_flight = &InFlight::default();
+        let mut downloads = stream::iter(pending_art)
+            .map(|(game_id, kind, art_path)| async move {
+                let result = match cancellation.check().and_then(|()| start_download(options)) {
+                    Ok(()) => {
+                        in_flight.start(&art_path);
+                        download::download_art(
+                            cdn,
+                            game_id,
+                            kind,
+                            &art_path,
+                            options.force,
+                            options.retries,
+                            cancellation,
+                        )
+                        .await
+                    }
+                    Err(error) => Err(error),
+                };
+                (game_id, kind, art_path, result)
+            })
+            .buffer_unordered(options.concurrency);
+        while let Some((game_id, kind, art_path, result)) =
+            cancellation.or_cancelled(downloads.next()).await?
+        {
+            in_flight.finish(&art_path);
+            let game_description = self.names.describe(game_id);
+            let art_report = |action| ArtReport {
+                game_id: game_id.to_owned(),
+                kind,
+                action,
+                bytes: 0,
+                error: None,
+            };
+            let art_report = match result {
+                Ok(Outcome::Saved { bytes, .. }) => {
+                    info!(
+                        "Downloaded {kind} for {game_description} to {}",
+                        art_path.display()
+                    );
+                    ArtReport {
+                        bytes,
+                        ..art_report(Action::Downloaded)
+                    }
+                }
+                Ok(Outcome::SavedMeanwhile | Outcome::Unchanged) => {
+                    art_report(Action::AlreadyExists)
+                }
+                Ok(Outcome::NotFound) => {
+                    info!("No {kind} on the CDN for {game_description}");
+                    art_report(Action::NotFound)
+                }
+                // Every other download would be rate limited too
+                Err(error) if error.is::<RateLimited>() => return Err(error),
+                Err(error) if error.is::<BudgetExhausted>() => {
+                    info!(
+                        "Leaving the {kind} for {game_description} for the next run, since the \
+                         download budget is used up"
+                    );
+                    art_report(Action::OverBudget)
+                }
+                Err(error) => {
+                    // Failures caused by the script exiting aren't the download's fault
+                    cancellation.check()?;
+
+                    error!("Failed to download {kind} for {game_description}: {error:#}");
+                    ArtReport {
+                        error: Some(format!("{error:#}")),
+                        ..art_report(Action::Failed)
+                    }
+                }
+            };
+            report.art.push(art_report);
+
+            if let Err(error) = fail_fast(options, report) {
+                drop(downloads);
+                in_flight.discard();
+                return Err(error);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Process shortcuts as they're created or changed,
+    /// until the script needs to exit.
+    async fn watch(&self, report: &mut RunReport, cancellation: &Cancellation) -> Result<()> {
+        let mut watcher = ShortcutWatcher::new(self.dirs_with_shortcuts, self.options.recursive)?;
+        for dir in self.dirs_with_shortcuts {
+            info!("Watching {} for new shortcuts", dir.display());
+        }
+        info!("Press `Ctrl` + `c` to stop watching");
+
+        loop {
+            let shortcuts: Vec<_> = tokio::select! {
+                shortcuts = watcher.changed_shortcuts() => shortcuts?,
+                () = cancellation.cancelled() => {
+                    info!("Stopped watching for new shortcuts");
+                    return Ok(());
+                }
+            };
+
+            // Problems with one batch of shortcuts shouldn't stop the watching
+            debug!("Processing {} new or changed shortcut(s)", shortcuts.len());
+            if let Err(error) = self
+                .process(
+                    shortcuts.into_iter().map(Entry::Shortcut),
+                    report,
+                    cancellation,
+                )
+                .await
+            {
+                cancellation.check()?;
+                error!("{error:#}");
+            }
+        }
+    }
+}
+
+impl<R: IconResolver> Processor<'_, R> {
+    /// Create shortcuts in the first shortcut directory for the installed `games`
+    /// that don't have a shortcut in any of them, never replacing an existing file.
+    fn create_missing_shortcuts(
+        &self,
+        games: &[InstalledGame],
+        check_sigint: &impl Fn() -> Result<()>,
+    ) -> Result<()> {
+        let Some(target_dir) = self.dirs_with_shortcuts.first() else {
+            return Ok(());
+        };
+
+        // Find the games that already have shortcuts, however broken
+        let mut existing = HashSet::new();
+        for dir in self.dirs_with_shortcuts {
+            for path in scan_dir(
+                dir,
+                self.options.recursive,
+                self.options.follow_links,
+                check_sigint,
+            )?
+            .entries
+            {
+                if !platform::has_shortcut_extension(&path) {
+                    continue;
+                }
+                let game_id = File::open(&path)
+                    .map_err(anyhow::Error::from)
+                    .and_then(|file| shortcut::find_game_id(BufReader::new(file)));
+                match game_id {
+                    Ok(game_id) => existing.extend(game_id),
+                    Err(error) => debug!("Failed to read `{}`: {error:#}", path.display()),
+                }
+            }
+        }
+
+        let mut created = 0;
+        for game in games {
+            // Check if the script needs to exit
+            check_sigint()?;
+
+            // Leave alone games that have a shortcut, are excluded, or weren't asked for
+            let game_id = &game.shortcut.game_id;
+            let game_description = names::describe(game_id, game.name.as_deref());
+            if existing.contains(game_id)
+                || self.exclusions.excludes_game(game_id)
+                || !self.options.game_ids.matches(game_id)
+            {
+                continue;
+            }
+
+            // Name the shortcut after the game, like Steam does
+            let name = game
+                .name
+                .as_deref()
+                .map(paths::sanitize_filename)
+                .filter(|name| !name.is_empty())
+                .unwrap_or_else(|| format!("Game {game_id}"));
+            let filename = format!("{name}{SHORTCUT_EXTENSION}");
+            if self.exclusions.excludes_shortcut(&filename) {
+                continue;
+            }
+            let path = target_dir.join(&filename);
+            let icon_path = self.local_icon_dir.join(&game.shortcut.icon_filename);
+            let contents = shortcut::format_internet_shortcut(game_id, &icon_path);
+
+            if self.options.dry_run {
+                info!(
+                    "Would create shortcut {} for {game_description}",
+                    path.display()
+                );
+                continue;
+            }
+
+            // Never replace anything, even if it appeared since the directory was scanned
+            match File::create_new(&path) {
+                Ok(mut file) => {
+                    file.write_all(contents.as_bytes())
+                        .with_context(|| format!("Failed to write `{}`", path.display()))?;
+                    info!("Created shortcut {} for {game_description}", path.display());
+                    created += 1;
+                }
+                Err(error) if error.kind() == ErrorKind::AlreadyExists => {
+                    warn!(
+                        "Not creating a shortcut for {game_description}, since `{}` already exists",
+                        path.display()
+                    );
+                }
+                Err(error) => {
+                    return Err(error)
+                        .with_context(|| format!("Failed to create `{}`", path.display()));
+                }
+            }
+        }
+
+        if !self.options.dry_run {
+            info!("Created {created} shortcut(s)");
+        }
+        Ok(())
+    }
+}
+
+/// Add the exclusions listed in the ignore file at `path`.
+fn read_ignore_file(path: &Path, exclusions: &mut Exclusions) -> Result<()> {
+    let file = File::open(path).context("Failed to open file")?;
+    let contents = shortcut::read_text(BufReader::new(file))?;
+    exclusions.add_ignore_file(&contents)
+}
+
+/// List the entries in `dir`, depth-first through subdirectories if `recursive`.
+/// Symlinks and junctions are only descended into if `follow_links`,
+/// and then never to a directory that's already been scanned, so the walk can't loop.
+/// Only failing to read `dir` itself is an error;
+/// entries (and subdirectories) that can't be read are logged, counted and skipped.
+fn scan_dir(
+    dir: &Path,
+    recursive: bool,
+    follow_links: bool,
+    check_sigint: &impl Fn() -> Result<()>,
+) -> Result<ScannedDir> {
+    let mut scanned = ScannedDir {
+        entries: Vec::new(),
+        unreadable: 0,
+    };
+    let top_dir = dir;
+    let mut visited = HashSet::new();
+    if follow_links && let Ok(dir) = fs::canonicalize(dir) {
+        visited.insert(dir);
+    }
+    let mut dirs_to_scan = vec![(dir.to_owned(), 0)];
+    while let Some((dir, depth)) = dirs_to_scan.pop() {
+        let read_dir = match fs::read_dir(paths::to_long_path(&dir)) {
+            Ok(read_dir) => read_dir,
+            Err(error) if dir == top_dir => {
+                return Err(error).with_context(|| format!("Failed to read `{}`", dir.display()));
+            }
+            Err(error) => {
+                warn!("Skipping directory `{}`: {error}", dir.display());
+                scanned.unreadable += 1;
+                continue;
+            }
+        };
+        for entry in read_dir {
+            // Check if the script needs to exit
+            check_sigint()?;
+
+            // One bad entry (e.g. a flaky file on a network drive) shouldn't hide the rest
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(error) => {
+                    warn!("Skipping an entry in `{}`: {error}", dir.display());
+                    scanned.unreadable += 1;
+                    continue;
+                }
+            };
+            // (kept as the directory was given, rather than as it was opened)
+            let path = dir.join(entry.file_name());
+
+            // The ignore file lives alongside the shortcuts, but isn't one
+            if entry.file_name() == IGNORE_FILENAME {
+                continue;
+            }
+
+            // Queue up subdirectories to be scanned next
+            // (app bundle shortcuts are directories, but are processed as a whole)
+            let is_bundle = SHORTCUTS_ARE_DIRS && platform::has_shortcut_extension(&path);
+            let is_dir = match entry.file_type() {
+                // Broken links are left for reading the shortcut to warn about
+                Ok(file_type) if follow_links && file_type.is_symlink() => {
+                    fs::metadata(paths::to_long_path(&path)).is_ok_and(|metadata| metadata.is_dir())
+                }
+                Ok(file_type) => file_type.is_dir(),
+                Err(error) if recursive => {
+                    warn!("Skipping `{}`: {error}", path.display());
+                    scanned.unreadable += 1;
+                    continue;
+                }
+                // Whether it's a directory only matters when descending into them
+                Err(_) => false,
+            };
+            if recursive && is_dir && !is_bundle {
+                if follow_links {
+                    let target = match fs::canonicalize(paths::to_long_path(&path)) {
+                        Ok(target) => target,
+                        Err(error) => {
+                            warn!("Skipping directory `{}`: {error}", path.display());
+                            scanned.unreadable += 1;
+                            continue;
+                        }
+                    };
+                    if !visited.insert(target) {
+                        warn!(
+                            "Skipping `{}`, since it leads to a directory already scanned",
+                            path.display()
+                        );
+                        continue;
+                    }
+                }
+                if depth < MAX_SCAN_DEPTH {
+                    dirs_to_scan.push((path, depth + 1));
+                } else {
+                    warn!(
+                        "Skipping directory `{}` nested deeper than {MAX_SCAN_DEPTH} levels",
+                        path.display()
+                    );
+                }
+                continue;
+            }
+
+            scanned.entries.push(path);
+        }
+    }
+
+    Ok(scanned)
+}
+
+/// Remove the temporary files left in `icon_dir` by interrupted downloads
+/// whose icon has been saved since, or just report them if `dry_run`.
+/// Any others are kept, so their downloads can be resumed.
+fn remove_partial_downloads(
+    icon_dir: &Path,
+    dry_run: bool,
+    check_sigint: &impl Fn() -> Result<()>,
+) -> Result<()> {
+    let partial_extension = format!("{ICON_EXTENSION}{}", download::PARTIAL_EXTENSION);
+    for entry in icon_dir
+        .read_dir()
+        .context("Failed to read icon directory")?
+    {
+        // Check if the script needs to exit
+        check_sigint()?;
+
+        let entry = entry?;
+        let is_partial = entry
+            .file_name()
+            .to_string_lossy()
+            .ends_with(&partial_extension);
+        if !is_partial || !entry.file_type()?.is_file() {
+            continue;
+        }
+
+        let path = entry.path();
+        let icon_path = path.with_file_name(
+            entry
+                .file_name()
+                .to_string_lossy()
+                .strip_suffix(download::PARTIAL_EXTENSION)
+                .unwrap_or_default(),
+        );
+        if !icon_path.exists() {
+            debug!(
+                "Keeping interrupted download `{}` to resume it",
+                path.display()
+            );
+            continue;
+        }
+        if dry_run {
+            info!("Would remove interrupted download `{}`", path.display());
+            continue;
+        }
+
+        info!("Removing interrupted download `{}`", path.display());
+        if let Err(error) = fs::remove_file(&path) {
+            warn!(
+                "Failed to remove interrupted download `{}`: {error}",
+                path.display()
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Ask the CDN (a few at a time) about the icons already there that are being checked for updates
+/// (see [`MissingIcon::checking_update`]), returning the pending downloads without those it says
+/// haven't changed.
+/// Icons the CDN couldn't say anything useful about are kept as they are, with a warning,
+/// as are any it no longer has.
+async fn check_for_updates(
+    pending_downloads: Vec<MissingIcon>,
+    options: &Options,
+    negative_cache: &NegativeCache,
+    report: &mut RunReport,
+    cancellation: &Cancellation,
+) -> Result<Vec<MissingIcon>> {
+    let (to_check, mut pending_downloads): (Vec<_>, Vec<_>) = pending_downloads
+        .into_iter()
+        .partition(|missing_icon| missing_icon.checking_update);
+    if to_check.is_empty() {
+        return Ok(pending_downloads);
+    }
+
+    let cdn = Cdn::new(
+        options.cdn_base_urls.clone(),
+        &options.http,
+        options.rate_limiter.clone(),
+        options.bandwidth_limiter.clone(),
+        options.budget.clone(),
+    )?;
+    let cdn = &cdn;
+    let mut checks = stream::iter(to_check)
+        .map(|missing_icon| async move {
+            let result = download::probe_icon(
+                cdn,
+                &missing_icon.game_id,
+                &missing_icon.source.cdn_filename,
+                options.retries,
+                cancellation,
+            )
+            .await
+            .and_then(|remote| {
+                remote
+                    .map(|remote| updates::compare(&missing_icon.icon_path, &remote))
+                    .transpose()
+            });
+            (missing_icon, result)
+        })
+        .buffer_unordered(options.concurrency);
+    while let Some((missing_icon, result)) = cancellation.or_cancelled(checks.next()).await? {
+        match result {
+            Ok(Some(Comparison::Changed(reason))) => {
+                info!(
+                    "Icon for {} has changed on the CDN ({reason}), so it'll be downloaded again",
+                    missing_icon.describe()
+                );
+                pending_downloads.push(missing_icon);
+                continue;
+            }
+            Ok(Some(Comparison::Same)) => {
+                info!(
+                    "Icon already exists for {} and hasn't changed on the CDN",
+                    missing_icon.describe()
+                );
+            }
+            Ok(Some(Comparison::Unknown)) => warn!(
+                "Can't tell whether the icon for {} has changed, since the CDN didn't say its \
+                 size or when it was modified, skipping it",
+                missing_icon.describe()
+            ),
+            Ok(None) => {
+                negative_cache.record_missing(&missing_icon.game_id);
+                info!(
+                    "Keeping the existing icon for {}, since the CDN no longer has one",
+                    missing_icon.describe()
+                );
+            }
+            // Every other request would be rate limited too
+            Err(error) if error.is::<RateLimited>() => return Err(error),
+            Err(error) => {
+                // Failures caused by the script exiting aren't the CDN's fault
+                cancellation.check()?;
+                warn!(
+                    "Failed to check whether the icon for {} has changed, skipping it: {error:#}",
+                    missing_icon.describe()
+                );
+            }
+        }
+        let exported_png = options
+            .export_png
+            .as_ref()
+            .filter(|_| !options.dry_run)
+            .and_then(|export| {
+                export_png(
+                    export,
+                    &missing_icon.game_id,
+                    &missing_icon.describe(),
+                    &missing_icon.icon_path,
+                )
+            });
+        report.record(ItemReport {
+            exported_png,
+            ..missing_icon.report(Action::AlreadyExists)
+        });
+    }
+
+    Ok(pending_downloads)
+}
+
+/// Download the missing icons, a few at a time,
+/// extracting those the CDN doesn't have from their games' executables if `exe_icons` is given.
+async fn download_icons(
+    pending_downloads: Vec<MissingIcon>,
+    options: &Options,
+    negative_cache: &NegativeCache,
+    exe_icons: Option<&dyn ExeIconSource>,
+    manifest: &Manifest,
+    report: &mut RunReport,
+    cancellation: &Cancellation,
+) -> Result<()> {
+    // Share one client between all downloads so connections to the CDN are reused
+    let cdn = Cdn::new(
+        options.cdn_base_urls.clone(),
+        &options.http,
+        options.rate_limiter.clone(),
+        options.bandwidth_limiter.clone(),
+        options.budget.clone(),
+    )?;
+
+    // Keep track of how long downloads take, adding to any earlier runs (e.g. when watching)
+    let started = Instant::now();
+    let previous_download_time = report.download_time;
+
+    let cdn = &cdn;
+    let in_flight = &InFlight::default();
+    let mut downloads = stream::iter(pending_downloads)
+        .map(|missing_icon| async move {
+            // Don't start any new downloads if the script needs to exit
+            let started = Instant::now();
+            let result = match cancellation.check().and_then(|()| start_download(options)) {
+                Ok(()) => {
+                    in_flight.start(&missing_icon.icon_path);
+                    info!("Downloading icon for {}", missing_icon.describe());
+                    options.emit(|| Event::DownloadStarted {
+                        shortcut: missing_icon.shortcut.to_string_lossy().into_owned(),
+                        game_id: missing_icon.game_id.clone(),
+                        url: missing_icon.url.clone(),
+                    });
+                    download::download_icon(
+                        cdn,
+                        &missing_icon.game_id,
+                        &missing_icon.source,
+                        &missing_icon.icon_path,
+                        SaveOptions {
+                            overwrite: missing_icon.replacing,
+                            convert_pngs: options.convert_pngs,
+                            only_if_changed: missing_icon.keeps_existing(),
+                        },
+                        options.retries,
+                        cancellation,
+                    )
+                    .await
+                    .with_context(|| {
+                        format!("Failed to download icon for {}", missing_icon.describe())
+                    })
+                }
+                Err(error) => Err(error),
+            };
+            (missing_icon, result, started.elapsed())
+        })
+        .buffer_unordered(options.concurrency);
+    let mut consecutive_blocked = 0;
+    loop {
+        // Stop waiting on downloads as soon as the script needs to exit
+        // (dropping them aborts their requests, leaving their partial files to be resumed by the
+        // next run, and icons are only ever moved into place once they're complete)
+        let Some((missing_icon, result, duration)) =
+            cancellation.or_cancelled(downloads.next()).await?
+        else {
+            break;
+        };
+
+        report.download_time = previous_download_time + started.elapsed();
+        in_flight.finish(&missing_icon.icon_path);
+
+        // Keep count of how many downloads in a row the CDN blocked
+        let blocked = result
+            .as_ref()
+            .is_err_and(|error| error.downcast_ref::<BlockedByCdn>().is_some());
+        consecutive_blocked = if blocked { consecutive_blocked + 1 } else { 0 };
+
+        match result {
+            Ok(Outcome::Saved {
+                bytes,
+                sha1,
+                url,
+                validators,
+            }) => {
+                let item = missing_icon.saved(bytes, &sha1, &url, validators, options, manifest);
+                options.emit(|| Event::download_finished(&item, duration));
+                report.record(item);
+            }
+            Ok(Outcome::SavedMeanwhile) => {
+                info!(
+                    "Icon for {} was created by someone else meanwhile, skipping",
+                    missing_icon.describe()
+                );
+                let item = missing_icon.report(Action::AlreadyExists);
+                options.emit(|| Event::download_finished(&item, duration));
+                report.record(item);
+            }
+            Ok(Outcome::Unchanged) => {
+                info!("Icon for {} is unchanged", missing_icon.describe());
+                let item = missing_icon.report(Action::Unchanged);
+                options.emit(|| Event::download_finished(&item, duration));
+                report.record(item);
+            }
+            // Icons already there are kept if the CDN no longer has them
+            Ok(Outcome::NotFound) if missing_icon.keeps_existing() => {
+                negative_cache.record_missing(&missing_icon.game_id);
+                info!(
+                    "Keeping the existing icon for {}, since the CDN no longer has one",
+                    missing_icon.describe()
+                );
+                let item = missing_icon.report(Action::AlreadyExists);
+                options.emit(|| Event::download_finished(&item, duration));
+                report.record(item);
+            }
+            Ok(Outcome::NotFound) => {
+                negative_cache.record_missing(&missing_icon.game_id);
+                let item = exe_icons
+                    .and_then(|exe_icons| {
+                        extract_exe_icon(exe_icons, &missing_icon, options, manifest)
+                    })
+                    .or_else(|| {
+                        options
+                            .placeholder
+                            .then(|| save_placeholder(&missing_icon, options, manifest))?
+                    })
+                    .unwrap_or_else(|| missing_icon.report(Action::NotFound));
+                options.emit(|| Event::download_finished(&item, duration));
+                report.record(item);
+            }
+            // Every other download would be rate limited too
+            Err(error) if error.is::<RateLimited>() => return Err(error),
+            Err(error) if error.is::<BudgetExhausted>() => {
+                info!(
+                    "Leaving the icon for {} for the next run, since the download budget is used \
+                     up",
+                    missing_icon.describe()
+                );
+                let item = missing_icon.report(Action::OverBudget);
+                options.emit(|| Event::download_finished(&item, duration));
+                report.record(item);
+            }
+            Err(error) => {
+                // Failures caused by the script exiting aren't the download's fault
+                cancellation.check()?;
+
+                error!("{error:#}");
+                let item = ItemReport {
+                    error: Some(format!("{error:#}")),
+                    failure: Some(download::failure_kind(&error)),
+                    ..missing_icon.report(Action::Failed)
+                };
+                options.emit(|| Event::item_failed(&item));
+                report.record(item);
+            }
+        }
+
+        // Every other download would almost certainly be blocked too
+        if consecutive_blocked >= MAX_CONSECUTIVE_BLOCKED {
+            bail!(
+                "Stopping downloads, since the CDN blocked the last {consecutive_blocked} of them"
+            );
+        }
+
+        if let Err(error) = fail_fast(options, report) {
+            drop(downloads);
+            in_flight.discard();
+            return Err(error);
+        }
+    }
+
+    Ok(())
+}
+
+/// Fail if anything in `report` failed and the run should stop at its first failure,
+/// so nothing new is started once something has.
+fn fail_fast(options: &Options, report: &RunReport) -> Result<()> {
+    let failed = report.failed() > 0
+        || report.count_art(Action::Failed) > 0
+        || report.unreadable_entries > 0;
+    if options.fail_fast && failed {
+        return Err(FailedFast.into());
+    }
+    Ok(())
+}
+
+/// Where the downloads in progress are being saved,
+/// so what they downloaded can be removed if they're cut short by failing fast
+/// (rather than kept to be resumed, as when the script is asked to exit).
+#[derive(Debug, Default)]
+struct InFlight(Mutex<HashSet<PathBuf>>);
+
+impl InFlight {
+    /// Note that a download to `path` has started.
+    fn start(&self, path: &Path) {
+        self.lock().insert(path.to_owned());
+    }
+
+    /// Note that the download to `path` has finished (one way or another).
+    fn finish(&self, path: &Path) {
+        self.lock().remove(path);
+    }
+
+    /// Remove what the downloads still in progress downloaded,
+    /// once they've been dropped.
+    fn discard(&self) {
+        for path in self.lock().drain() {
+            let partial_path = download::partial_path(&path);
+            if let Err(error) = fs::remove_file(paths::to_long_path(&partial_path))
+                && error.kind() != ErrorKind::NotFound
+            {
+                warn!(
+                    "Failed to remove temporary file `{}`: {error}",
+                    partial_path.display()
+                );
+            }
+        }
+    }
+
+    /// Lock the paths, even if a download panicked while holding them.
+    fn lock(&self) -> MutexGuard<'_, HashSet<PathBuf>> {
+        self.0.lock().unwrap_or_else(|error| error.into_inner())
+    }
+}
+
+/// Take a download from the run's budget (if any) before starting it.
+fn start_download(options: &Options) -> Result<()> {
+    if let Some(budget) = &options.budget {
+        budget.start_download()?;
+    }
+    Ok(())
+}
+
+/// Copy the missing icons from Steam's library cache, converting them as needed.
+fn copy_cached_icons(
+    pending_copies: Vec<MissingIcon>,
+    options: &Options,
+    manifest: &Manifest,
+    report: &mut RunReport,
+    cancellation: &Cancellation,
+) -> Result<()> {
+    for missing_icon in pending_copies {
+        cancellation.check()?;
+        fail_fast(options, report)?;
+        let Some(cached_icon) = &missing_icon.cached_icon else {
+            continue;
+        };
+
+        info!(
+            "Copying icon for {} from `{}`",
+            missing_icon.describe(),
+            cached_icon.display()
+        );
+        let source = cached_icon.to_string_lossy();
+        let result = fs::read(paths::to_long_path(cached_icon))
+            .context("Failed to read cached icon")
+            .and_then(|cached| library_cache::convert(&cached, &missing_icon.icon_path))
+            .and_then(|contents| {
+                download::save_contents(
+                    &contents,
+                    &source,
+                    &missing_icon.icon_path,
+                    missing_icon.replacing,
+                )
+            })
+            .with_context(|| format!("Failed to copy icon for {}", missing_icon.describe()));
+        match result {
+            Ok(Outcome::Saved {
+                bytes, sha1, url, ..
+            }) => {
+                report.record(ItemReport {
+                    source: Some(url.clone()),
+                    ..missing_icon.saved(
+                        bytes,
+                        &sha1,
+                        &url,
+                        Validators::default(),
+                        options,
+                        manifest,
+                    )
+                });
+            }
+            Ok(_) => {
+                info!(
+                    "Icon for {} was created by someone else meanwhile, skipping",
+                    missing_icon.describe()
+                );
+                report.record(missing_icon.report(Action::AlreadyExists));
+            }
+            Err(error) => {
+                error!("{error:#}");
+                let item = ItemReport {
+                    error: Some(format!("{error:#}")),
+                    failure: Some(download::failure_kind(&error)),
+                    ..missing_icon.report(Action::Failed)
+                };
+                options.emit(|| Event::item_failed(&item));
+                report.record(item);
+            }
+        }
+    }
+
+    fail_fast(options, report)
+}
+
+/// Extract the icon for a game the CDN has no icon for from the game's executable,
+/// returning the report for its shortcut, or `None` if there's no icon to extract
+/// (e.g. since the game isn't installed).
+fn extract_exe_icon(
+    exe_icons: &dyn ExeIconSource,
+    missing_icon: &MissingIcon,
+    options: &Options,
+    manifest: &Manifest,
+) -> Option<ItemReport> {
+    let exe_icon = match exe_icons.exe_icon(&missing_icon.game_id) {
+        Ok(Some(exe_icon)) => exe_icon,
+        Ok(None) => {
+            info!(
+                "No executable to extract an icon from for {}",
+                missing_icon.describe()
+            );
+            return None;
+        }
+        Err(error) => {
+            warn!(
+                "Failed to extract icon for {} from its executable: {error:#}",
+                missing_icon.describe()
+            );
+            return None;
+        }
+    };
+
+    // Make it clear the icon isn't the one Steam would have
+    let source = exe_icon.executable.to_string_lossy().into_owned();
+    info!(
+        "Extracting icon for {} from its executable `{source}` instead of Steam's CDN",
+        missing_icon.describe()
+    );
+    let result = exe_icon
+        .converted_for(&missing_icon.icon_path)
+        .and_then(|contents| {
+            download::save_contents(
+                &contents,
+                &source,
+                &missing_icon.icon_path,
+                missing_icon.replacing,
+            )
+        })
+        .with_context(|| {
+            format!(
+                "Failed to save icon for {} extracted from `{source}`",
+                missing_icon.describe()
+            )
+        });
+    let item = match result {
+        Ok(Outcome::Saved {
+            bytes, sha1, url, ..
+        }) => missing_icon.saved(bytes, &sha1, &url, Validators::default(), options, manifest),
+        Ok(_) => missing_icon.report(Action::AlreadyExists),
+        Err(error) => {
+            error!("{error:#}");
+            ItemReport {
+                error: Some(format!("{error:#}")),
+                failure: Some(download::failure_kind(&error)),
+                ..missing_icon.report(Action::Failed)
+            }
+        }
+    };
+    Some(ItemReport {
+        source: Some(source),
+        ..item
+    })
+}
+
+/// Generate a placeholder icon for a game the CDN has no icon for,
+/// returning the report for its shortcut, or `None` if there's a real icon that shouldn't be
+/// replaced by one (when downloading again with `force`).
+fn save_placeholder(
+    missing_icon: &MissingIcon,
+    options: &Options,
+    manifest: &Manifest,
+) -> Option<ItemReport> {
+    if missing_icon.replacing && !missing_icon.damaged && !missing_icon.placeholder {
+        return None;
+    }
+
+    // Placeholders never change, so there's nothing to do if the same one is already there
+    let result = placeholder::icon_for(
+        &missing_icon.icon_path,
+        &missing_icon.game_id,
+        missing_icon.game_name.as_deref(),
+    )
+    .and_then(|contents| {
+        let existing = missing_icon
+            .placeholder
+            .then(|| fs::read(paths::to_long_path(&missing_icon.icon_path)).ok())
+            .flatten();
+        if existing.is_some_and(|existing| existing == contents) {
+            return Ok(Outcome::SavedMeanwhile);
+        }
+        info!(
+            "Generating placeholder icon for {}",
+            missing_icon.describe()
+        );
+        download::save_contents(
+            &contents,
+            placeholder::SOURCE,
+            &missing_icon.icon_path,
+            missing_icon.replacing,
+        )
+    })
+    .with_context(|| {
+        format!(
+            "Failed to save placeholder icon for {}",
+            missing_icon.describe()
+        )
+    });
+    let item = match result {
+        // Nothing was downloaded, so it doesn't add to the bytes downloaded
+        Ok(Outcome::Saved {
+            bytes, sha1, url, ..
+        }) => ItemReport {
+            action: Action::Placeholder,
+            bytes: 0,
+            ..missing_icon.saved(bytes, &sha1, &url, Validators::default(), options, manifest)
+        },
+        Ok(_) => {
+            info!(
+                "Placeholder icon for {} is already there",
+                missing_icon.describe()
+            );
+            missing_icon.report(Action::AlreadyExists)
+        }
+        Err(error) => {
+            error!("{error:#}");
+            ItemReport {
+                error: Some(format!("{error:#}")),
+                failure: Some(download::failure_kind(&error)),
+                ..missing_icon.report(Action::Failed)
+            }
+        }
+    };
+    Some(ItemReport {
+        source: Some(placeholder::SOURCE.to_owned()),
+        ..item
+    })
+}
+
+/// Whether the icon at `icon_path` is a placeholder, going by `manifest`
+/// (and it hasn't been changed since, e.g. by hand).
+fn is_placeholder(manifest: &Manifest, icon_path: &Path) -> bool {
+    recorded_entry(manifest, icon_path).is_some_and(|entry| entry.url == placeholder::SOURCE)
+}
+
+/// What `manifest` recorded about the icon at `icon_path`,
+/// as long as the icon is still what was recorded.
+fn recorded_entry(manifest: &Manifest, icon_path: &Path) -> Option<ManifestEntry> {
+    manifest.entry(icon_path).filter(|entry| {
+        fs::read(paths::to_long_path(icon_path))
+            .is_ok_and(|contents| sha1::hex_digest(&contents) == entry.sha1)
+    })
+}
+
+/// Export a PNG copy of the icon at `icon_path` for `game_id` (described as `game_description`),
+/// returning where it was saved, or `None` if it wasn't (e.g. since it already was).
+/// Failing to export a copy never fails the shortcut, since its icon is still there.
+fn export_png(
+    export: &PngExport,
+    game_id: &str,
+    game_description: &str,
+    icon_path: &Path,
+) -> Option<PathBuf> {
+    match export.export(game_id, icon_path) {
+        Ok(Some(png_path)) => {
+            info!(
+                "Exported PNG copy of icon for {game_description} to {}",
+                png_path.display()
+            );
+            Some(png_path)
+        }
+        Ok(None) => {
+            debug!("PNG copy of icon for {game_description} was already exported");
+            None
+        }
+        Err(error) => {
+            warn!("Failed to export PNG copy of icon for {game_description}: {error:#}");
+            None
+        }
+    }
+}
+
+impl MissingIcon {
+    /// Record the icon saved for the shortcut (`bytes` long, with the hash `sha1`, from `url`,
+    /// which identified it with `validators`) in `manifest`, tell the shell about the shortcut,
+    /// and return its report.
+    fn saved(
+        &self,
+        bytes: u64,
+        sha1: &[u8; 20],
+        url: &str,
+        validators: Validators,
+        options: &Options,
+        manifest: &Manifest,
+    ) -> ItemReport {
+        let entry = ManifestEntry {
+            etag: validators.etag,
+            last_modified: validators.last_modified,
+            ..ManifestEntry::new(
+                &self.game_id,
+                &self.icon_path,
+                bytes,
+                &sha1::to_hex(sha1),
+                url,
+            )
+        };
+        if let Err(error) = manifest.record(entry) {
+            warn!("{error:#}");
+        }
+        if options.shell_notify {
+            shell::notify_updated(&self.shortcut);
+        }
+        let exported_png = options.export_png.as_ref().and_then(|export| {
+            export_png(export, &self.game_id, &self.describe(), &self.icon_path)
+        });
+
+        let action = if self.damaged {
+            Action::Repaired
+        } else if self.checking_update {
+            Action::Updated
+        } else if self.replacing {
+            Action::Redownloaded
+        } else {
+            Action::Downloaded
+        };
+        ItemReport {
+            bytes,
+            exported_png,
+            ..self.report(action)
+        }
+    }
+
+    /// Report for the shortcut with the given action.
+    fn report(&self, action: Action) -> ItemReport {
+        ItemReport {
+            game_id: Some(self.game_id.clone()),
+            game_name: self.game_name.clone(),
+            icon_filename: Some(self.icon_filename.clone()),
+            ..ItemReport::new(&self.shortcut, action)
+        }
+    }
+
+    /// The game as it should appear in logs.
+    fn describe(&self) -> String {
+        names::describe(&self.game_id, self.game_name.as_deref())
+    }
+}
+
+impl<R: IconResolver> Processor<'_, R> {
+    /// Shortcut directory `path` is within.
+    fn shortcut_dir<'b>(&'b self, path: &'b Path) -> &'b Path {
+        self.dirs_with_shortcuts
+            .iter()
+            .find(|dir| path.starts_with(dir))
+            .map_or(path, PathBuf::as_path)
+    }
+
+    /// Point the `IconFile` of the `.url` shortcut at `path` at the icon directory,
+    /// returning the fixed shortcut.
+    /// The rest of the file is left exactly as it was,
+    /// and the original is kept alongside it if backups were requested.
+    fn fix_shortcut(&self, path: &Path) -> Result<Shortcut> {
+        let (encoding, fixed, shortcut) = replace_icon_dir(path, self.local_icon_dir)?;
+        if self.options.dry_run {
+            info!("Would fix the icon directory of {}", path.display());
+            return Ok(shortcut);
+        }
+
+        repair::write_shortcut(path, encoding, &fixed, self.options.backup_shortcuts)?;
+        info!("Fixed the icon directory of {}", path.display());
+
+        Ok(shortcut)
+    }
+
+    /// Point the `IconFile` of the `.url` shortcut at `path` at `user_icon_dir`,
+    /// always keeping the original alongside it, and recording it in `report`.
+    /// Returns the fixed shortcut.
+    fn point_at_user_icon_dir(
+        &self,
+        path: &Path,
+        user_icon_dir: &Path,
+        report: &mut RunReport,
+    ) -> Result<Shortcut> {
+        let (encoding, fixed, shortcut) = replace_icon_dir(path, user_icon_dir)?;
+        let shortcut = Shortcut {
+            icon_dir: Some(user_icon_dir.to_owned()),
+            ..shortcut
+        };
+        if self.options.dry_run {
+            info!(
+                "Would point {} at the per-user icon directory",
+                path.display()
+            );
+        } else {
+            repair::write_shortcut(path, encoding, &fixed, true)?;
+            info!("Pointed {} at the per-user icon directory", path.display());
+        }
+        report.repointed_shortcuts.push(path.to_owned());
+
+        Ok(shortcut)
+    }
+
+    /// Find the icon a shortcut needs if it hasn't been downloaded yet or is damaged
+    /// (or regardless, if forced).
+    /// Shortcuts that don't need anything downloaded are recorded in `report`.
+    /// Shortcuts for the same game and icon as one in `seen_shortcuts` are skipped as duplicates.
+    async fn find_missing_icon(
+        &self,
+        entry: Entry,
+        seen_shortcuts: &mut HashSet<(String, PathBuf)>,
+        report: &mut RunReport,
+    ) -> Result<Option<MissingIcon>> {
+        let (options, exclusions) = (self.options, &self.exclusions);
+
+        let (path, mut shortcut, is_shortcut) = match entry {
+            Entry::Shortcut(path) => {
+                // Leave alone any shortcuts that are ignored, without even reading them
+                let is_excluded = path.file_name().is_some_and(|filename| {
+                    exclusions.excludes_shortcut(&filename.to_string_lossy())
+                });
+                if is_excluded {
+                    debug!("Skipping excluded shortcut `{}`", path.display());
+                    report.record(ItemReport::new(&path, Action::Excluded));
+                    return Ok(None);
+                }
+
+                // Extract the game ID and icon filename from the shortcut
+                // (there's nothing to download for non-Steam games)
+                let shortcut = match extract_game_id_and_icon_filename(
+                    &path,
+                    self.shortcut_dir(&path),
+                    self.local_icon_dir,
+                    options.user_icon_dir.as_deref(),
+                    options.follow_links,
+                ) {
+                    Ok(Some(shortcut)) => shortcut,
+                    Ok(None) => {
+                        report.record(ItemReport::new(&path, Action::Skipped));
+                        return Ok(None);
+                    }
+                    Err(error) => {
+                        if let Some(NonSteamGame { game_id }) = error.downcast_ref() {
+                            let game_name = self.names.get(game_id);
+                            match &game_name {
+                                Some(name) => {
+                                    info!("Skipping shortcut for non-Steam game `{name}`")
+                                }
+                                None => info!("Skipping shortcut for non-Steam game #{game_id}"),
+                            }
+                            report.record(ItemReport {
+                                game_id: Some(game_id.clone()),
+                                game_name,
+                                ..ItemReport::new(&path, Action::NonSteam)
+                            });
+                            return Ok(None);
+                        } else if options.fix_shortcuts
+                            && error.downcast_ref::<UnrecognizedIconDir>().is_some()
+                        {
+                            self.fix_shortcut(&path)
+                                .with_context(|| format!("Failed to fix shortcut ({error:#})"))?
+                        } else {
+                            return Err(error);
+                        }
+                    }
+                };
+                (path, shortcut, true)
+            }
+
+            // Installed games were already found with their icons
+            Entry::InstalledGame(game) => (game.manifest, game.shortcut, false),
+        };
+        let path = path.as_path();
+        options.emit(|| Event::shortcut_parsed(path, &shortcut.game_id, &shortcut.icon_filename));
+        let game_name = self.names.get(&shortcut.game_id);
+        let game_description = names::describe(&shortcut.game_id, game_name.as_deref());
+
+        // Leave alone any games that are excluded or weren't asked for
+        // (exclusions win when a game is both)
+        if exclusions.excludes_game(&shortcut.game_id) {
+            debug!("Skipping excluded {game_description}");
+            report.record(ItemReport {
+                game_id: Some(shortcut.game_id),
+                game_name,
+                icon_filename: Some(shortcut.icon_filename),
+                ..ItemReport::new(path, Action::Excluded)
+            });
+            return Ok(None);
+        } else if !options.game_ids.matches(&shortcut.game_id) {
+            debug!("Skipping {game_description} due to `--game-id`");
+            report.record(ItemReport {
+                game_id: Some(shortcut.game_id),
+                game_name,
+                icon_filename: Some(shortcut.icon_filename),
+                ..ItemReport::new(path, Action::FilteredOut)
+            });
+            return Ok(None);
+        }
+
+        // Never let a shortcut decide to save its icon outside of the icon directory
+        paths::ensure_plain_filename(&shortcut.icon_filename, ICON_EXTENSION)
+            .context("Refusing to save icon")?;
+
+        // Shortcuts whose icon isn't (intact) in the icon directory are pointed at the per-user
+        // icon directory instead, if there is one, so the icon can be saved there
+        if let Some(user_icon_dir) = &options.user_icon_dir
+            && is_shortcut
+            && SHORTCUT_EXTENSION == repair::EXTENSION
+            && shortcut.icon_dir.is_none()
+            && !options.check
+        {
+            let icon_path = self.local_icon_dir.join(&shortcut.icon_filename);
+            let intact = !options.force
+                && download::icon_exists(&icon_path)?
+                && verify::check_icon(&icon_path).is_ok();
+            if !intact {
+                shortcut = self
+                    .point_at_user_icon_dir(path, user_icon_dir, report)
+                    .context("Failed to point shortcut at the per-user icon directory")?;
+            }
+        }
+
+        // Only handle each game's icon once, even if it has shortcuts in several places
+        let icon_path = shortcut
+            .icon_dir
+            .as_deref()
+            .unwrap_or(self.local_icon_dir)
+            .join(&shortcut.icon_filename);
+        if let Some(only_icons) = &options.only_icons {
+            let is_wanted = only_icons.contains(&shortcut.icon_filename.to_lowercase())
+                && shortcut
+                    .icon_dir
+                    .as_deref()
+                    .is_none_or(|icon_dir| paths::same_dir(icon_dir, self.local_icon_dir));
+            if !is_wanted {
+                debug!("Skipping {game_description}, since its icon isn't being fixed");
+                report.record(ItemReport {
+                    game_id: Some(shortcut.game_id),
+                    game_name,
+                    icon_filename: Some(shortcut.icon_filename),
+                    ..ItemReport::new(path, Action::FilteredOut)
+                });
+                return Ok(None);
+            }
+        }
+        if !seen_shortcuts.insert((shortcut.game_id.clone(), icon_path.clone())) {
+            info!("Skipping duplicate shortcut for {game_description}");
+            report.record(ItemReport {
+                game_id: Some(shortcut.game_id),
+                game_name,
+                icon_filename: Some(shortcut.icon_filename),
+                ..ItemReport::new(path, Action::Duplicate)
+            });
+            return Ok(None);
+        }
+
+        // Make sure the icon doesn't already exist, unless it's being replaced anyway
+        // (damaged icons, e.g. left behind by an interrupted download, are treated as missing,
+        // but anything other than a file in the way is an error, since it's never replaced)
+        let replacing = download::icon_exists(&icon_path)?;
+        let damaged = replacing
+            && !options.force
+            && match verify::check_icon(&icon_path) {
+                Ok(()) => false,
+                Err(error) => {
+                    warn!(
+                        "Existing icon for {game_description} is damaged and will be replaced: \
+                         {error}"
+                    );
+                    true
+                }
+            };
+        // Placeholders (unless only checking) are replaced in case the CDN has an icon by now
+        let placeholder = replacing
+            && !damaged
+            && !options.force
+            && !options.check
+            && is_placeholder(&self.manifest, &icon_path);
+        if placeholder {
+            info!("Icon for {game_description} is a placeholder, so it'll be replaced if possible");
+        }
+
+        // Icons older than the maximum age are downloaded again, in case they've changed
+        let stale = replacing
+            && !damaged
+            && !placeholder
+            && !options.force
+            && !options.check
+            && options.max_age.is_some_and(|max_age| {
+                fs::metadata(paths::to_long_path(&icon_path))
+                    .and_then(|metadata| metadata.modified())
+                    .is_ok_and(|modified| max_age::is_stale(modified, SystemTime::now(), max_age))
+            });
+        if stale {
+            info!(
+                "Icon for {game_description} is older than the maximum age, so it'll be refreshed"
+            );
+        }
+        // With `--check-updates`, the CDN is asked whether the rest have changed
+        let checking_update = replacing
+            && !damaged
+            && !placeholder
+            && !stale
+            && !options.force
+            && !options.check
+            && options.check_updates;
+        if replacing && !damaged && !placeholder && !stale && !checking_update && !options.force {
+            info!("Icon already exists for {game_description}");
+            let exported_png = options
+                .export_png
+                .as_ref()
+                .filter(|_| !options.dry_run)
+                .and_then(|export| {
+                    export_png(export, &shortcut.game_id, &game_description, &icon_path)
+                });
+            report.record(ItemReport {
+                game_id: Some(shortcut.game_id),
+                game_name,
+                icon_filename: Some(shortcut.icon_filename),
+                exported_png,
+                ..ItemReport::new(path, Action::AlreadyExists)
+            });
+            return Ok(None);
+        }
+
+        // When only checking, that's all there is to know
+        // (finding out which icon to download could mean a request)
+        if options.check {
+            info!("Icon missing for {game_description}");
+            report.record(ItemReport {
+                game_id: Some(shortcut.game_id),
+                game_name,
+                icon_filename: Some(shortcut.icon_filename),
+                ..ItemReport::new(path, Action::Missing)
+            });
+            return Ok(None);
+        }
+
+        // Offline, the icon can only come from Steam's library cache
+        let cached_icon = match &options.library_cache_dir {
+            Some(library_cache_dir) => {
+                let cached_icon = library_cache::find_icon(library_cache_dir, &shortcut.game_id)
+                    .context("Failed to look in Steam's library cache")?;
+                if cached_icon.is_none() {
+                    warn!("No icon in Steam's library cache for {game_description}");
+                    report.record(ItemReport {
+                        game_id: Some(shortcut.game_id),
+                        game_name,
+                        icon_filename: Some(shortcut.icon_filename),
+                        ..ItemReport::new(path, Action::UnavailableOffline)
+                    });
+                    return Ok(None);
+                }
+                cached_icon
+            }
+            None => None,
+        };
+
+        if cached_icon.is_some() {
+            return Ok(Some(MissingIcon {
+                shortcut: path.to_owned(),
+                game_id: shortcut.game_id,
+                game_name,
+                source: IconSource {
+                    cdn_filename: shortcut.icon_filename.clone(),
+                    expected_hash: None,
+                    saved: None,
+                },
+                icon_filename: shortcut.icon_filename,
+                url: String::new(),
+                cached_icon,
+                icon_path,
+                replacing,
+                damaged,
+                placeholder,
+                stale,
+                checking_update,
+            }));
+        }
+
+        // The icon can only be downloaded if its name on the CDN is known,
+        // so look it up if the shortcut doesn't say (or names something that can't be on the CDN)
+        let cdn_filename = match shortcut.cdn_filename {
+            Some(cdn_filename) if resolver::is_cdn_filename(&cdn_filename) => Some(cdn_filename),
+            // Looking it up means a request, which a dry run never makes
+            _ if options.dry_run => {
+                info!(
+                    "Would look up which icon to download for {game_description} and download it \
+                     to {}",
+                    icon_path.display()
+                );
+                report.record(ItemReport {
+                    game_id: Some(shortcut.game_id),
+                    game_name,
+                    icon_filename: Some(shortcut.icon_filename),
+                    ..ItemReport::new(path, Action::Pending)
+                });
+                return Ok(None);
+            }
+            cdn_filename => self
+                .resolver
+                .cdn_filename(&shortcut.game_id)
+                .await
+                .or(cdn_filename),
+        };
+        let Some(cdn_filename) = cdn_filename else {
+            if checking_update {
+                info!(
+                    "Icon already exists for {game_description} (and can't be checked for \
+                     updates, since neither the shortcut nor Steam's app info says which icon it \
+                     is)"
+                );
+                report.record(ItemReport {
+                    game_id: Some(shortcut.game_id),
+                    game_name,
+                    icon_filename: Some(shortcut.icon_filename),
+                    ..ItemReport::new(path, Action::AlreadyExists)
+                });
+                return Ok(None);
+            }
+            bail!(
+                "Icon for {game_description} is missing, and neither the shortcut nor Steam's app \
+                 info says which icon to download"
+            );
+        };
+        let base_url = options
+            .cdn_base_urls
+            .first()
+            .map_or(download::DEFAULT_CDN_BASE_URLS[0], String::as_str);
+        let url = download::icon_url(base_url, &shortcut.game_id, &cdn_filename)
+            .context("Refusing to download icon")?;
+
+        // Icons on the CDN are named after their hash, so transfers can be checked against it
+        let expected_hash = cdn_filename
+            .strip_suffix(".ico")
+            .filter(|_| options.verify && resolver::is_cdn_filename(&cdn_filename))
+            .map(str::to_ascii_lowercase);
+
+        // An icon downloaded again is only transferred if it changed since it was downloaded
+        // (as long as it's still what was downloaded then)
+        let saved = (replacing && !damaged && !placeholder)
+            .then(|| recorded_entry(&self.manifest, &icon_path))
+            .flatten()
+            .map(|entry| SavedIcon {
+                url: entry.url,
+                validators: Validators {
+                    etag: entry.etag,
+                    last_modified: entry.last_modified,
+                },
+            });
+
+        Ok(Some(MissingIcon {
+            shortcut: path.to_owned(),
+            game_id: shortcut.game_id,
+            game_name,
+            icon_filename: shortcut.icon_filename,
+            source: IconSource {
+                cdn_filename,
+                expected_hash,
+                saved,
+            },
+            url,
+            cached_icon: None,
+            icon_path,
+            replacing,
+            damaged,
+            placeholder,
+            stale,
+            checking_update,
+        }))
+    }
+}
+
+/// Point the `IconFile` of the `.url` shortcut at `path` at `icon_dir`,
+/// leaving the rest of the file exactly as it was,
+/// and returning its encoding and fixed contents along with the fixed shortcut (without writing it).
+fn replace_icon_dir(path: &Path, icon_dir: &Path) -> Result<(Encoding, String, Shortcut)> {
+    let bytes = fs::read(paths::to_long_path(path)).context("Failed to read file")?;
+    let (encoding, _) = Encoding::detect(&bytes);
+    let text = shortcut::read_text(bytes.as_slice())?;
+    let fixed = shortcut::replace_icon_dir(&text, icon_dir)?
+        .context("Shortcut has no `IconFile` to fix")?;
+
+    // Make sure the fix worked before writing anything
+    let shortcut = shortcut::parse_internet_shortcut(
+        fixed.as_bytes(),
+        path.parent().unwrap_or(path),
+        icon_dir,
+    )?;
+    Ok((encoding, fixed, shortcut))
+}
+
+/// Extract the steam game ID and icon filename from shortcut files.
+/// The icon must be located in `local_icon_dir` (or `user_icon_dir`, if given).
+/// Symlinks (and junctions) are skipped, unless `follow_links`.
+pub fn extract_game_id_and_icon_filename(
+    path: &Path,
+    dir_with_shortcuts: &Path,
+    local_icon_dir: &Path,
+    user_icon_dir: Option<&Path>,
+    follow_links: bool,
+) -> Result<Option<Shortcut>> {
+    // The filename is only needed for the logs, so names that aren't valid Unicode are fine
+    // (kept relative to the shortcut directory so nested shortcuts can be told apart in logs)
+    let filename = path
+        .strip_prefix(dir_with_shortcuts)
+        .unwrap_or(path)
+        .to_string_lossy();
+
+    // Skip non-shortcut files
+    // (cloud-only placeholders are reparse points, but still files once downloaded)
+    let long_path = paths::to_long_path(path);
+    let metadata = fs::symlink_metadata(&long_path).context("Failed to read metadata")?;
+    let metadata = if follow_links && metadata.is_symlink() {
+        match fs::metadata(&long_path) {
+            Ok(metadata) => metadata,
+            Err(error) => {
+                warn!("Skipping broken link `{filename}`: {error}");
+                return Ok(None);
+            }
+        }
+    } else {
+        metadata
+    };
+    let is_placeholder = platform::is_cloud_placeholder(&metadata);
+    let is_shortcut = platform::has_shortcut_extension(path)
+        && if SHORTCUTS_ARE_DIRS {
+            metadata.is_dir()
+        } else {
+            metadata.is_file() || is_placeholder
+        };
+    if !is_shortcut {
+        if metadata.is_symlink() {
+            warn!("Skipping symlink `{filename}`");
+        } else if metadata.is_dir() {
+            warn!("Skipping directory `{filename}`");
+        } else if !metadata.is_file() {
+            warn!("Skipping non-file `{filename}`");
+        } else {
+            warn!("Skipping non-shortcut file `{filename}`");
+        }
+        return Ok(None);
+    }
+
+    // Parse the shortcut
+    // (opening a placeholder downloads it, which fails if its provider isn't running)
+    if is_placeholder {
+        debug!("Downloading cloud-only shortcut `{filename}`");
+    }
+    let shortcut = match (platform::read_shortcut(path, local_icon_dir), user_icon_dir) {
+        // Shortcuts already pointed at the per-user icon directory are just as good
+        (Err(error), Some(user_icon_dir)) if error.is::<UnrecognizedIconDir>() => {
+            platform::read_shortcut(path, user_icon_dir).map(|shortcut| Shortcut {
+                icon_dir: Some(user_icon_dir.to_owned()),
+                ..shortcut
+            })
+        }
+        (result, _) => result,
+    };
+    let shortcut = shortcut.with_context(|| {
+        if is_placeholder {
+            format!("Failed to read cloud-only shortcut (is OneDrive running?): {filename}")
+        } else {
+            format!("Failed to parse shortcut: {filename}")
+        }
+    })?;
+
+    Ok(Some(shortcut))
+}
+
+/// Runs over shortcut directories laid out in temporary directories,
+/// with Steam stood in for by a local server that every request is sent to
+/// (using `.desktop` shortcuts, which is what Steam creates on Linux).
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use std::cell::RefCell;
+    use std::ffi::OsStr;
+    use std::future;
+    use std::os::unix::ffi::OsStrExt as _;
+    use std::sync::Once;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::test_server::{Response, TestServer};
+
+    /// Resolver that always finds the same icon (if any), counting how often it's asked.
+    struct StubResolver {
+        /// Filename of every game's icon on the CDN
+        cdn_filename: Option<String>,
+
+        /// Number of lookups so far
+        lookups: AtomicUsize,
+    }
+
+    impl StubResolver {
+        fn new(cdn_filename: Option<&str>) -> Self {
+            Self {
+                cdn_filename: cdn_filename.map(str::to_owned),
+                lookups: AtomicUsize::new(0),
+            }
+        }
+
+        fn lookups(&self) -> usize {
+            self.lookups.load(Ordering::Relaxed)
+        }
+    }
+
+    impl IconResolver for StubResolver {
+        fn cdn_filename(&self, _game_id: &str) -> impl Future<Output = Option<String>> + Send {
+            self.lookups.fetch_add(1, Ordering::Relaxed);
+            future::ready(self.cdn_filename.clone())
+        }
+    }
+
+    /// Shortcut and icon directories for a run, removed once dropped.
+    struct Layout {
+        /// Directory containing the others
+        _dir: TempDir,
+
+        /// Directory containing the shortcuts
+        shortcuts: PathBuf,
+
+        /// Directory icons are saved in
+        icons: PathBuf,
+    }
+
+    impl Layout {
+        fn new() -> Self {
+            let dir = TempDir::new().unwrap();
+            let shortcuts = dir.path().join("Desktop");
+            let icons = dir.path().join("icons");
+            fs::create_dir(&shortcuts).unwrap();
+            fs::create_dir(&icons).unwrap();
+            Self {
+                _dir: dir,
+                shortcuts,
+                icons,
+            }
+        }
+
+        /// Create a shortcut to `game_id` like the ones Steam creates.
+        fn add_shortcut(&self, game_id: &str) {
+            self.add_shortcut_named(OsStr::new(&format!("{game_id}.desktop")), game_id, game_id);
+        }
+
+        /// Create a shortcut to `game_id` like the ones Steam creates, named `filename`,
+        /// using the icon for `icon_id` (usually the same game).
+        fn add_shortcut_named(&self, filename: &OsStr, game_id: &str, icon_id: &str) -> PathBuf {
+            let contents = format!(
+                "[Desktop Entry]\nName=Game {game_id}\nExec=steam \
+                 steam://rungameid/{game_id}\nIcon=steam_icon_{icon_id}\nType=Application\n"
+            );
+            let path = self.shortcuts.join(filename);
+            fs::write(&path, contents).unwrap();
+            path
+        }
+
+        /// Path the icon for `game_id` is saved to.
+        fn icon_path(&self, game_id: &str) -> PathBuf {
+            self.icons.join(format!("steam_icon_{game_id}.png"))
+        }
+
+        /// Names of everything in the icon directory.
+        fn icon_dir_contents(&self) -> Vec<String> {
+            fs::read_dir(&self.icons)
+                .unwrap()
+                .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+                .collect()
+        }
+
+        /// Options for a run over the layout, sending every request to `server`.
+        fn options(&self, server: &TestServer) -> Options {
+            let mut options = Options::new(vec![self.shortcuts.clone()], self.icons.clone());
+            options.http.proxy = ProxyConfig::Url(server.url());
+            options.cdn_base_urls = vec![server.url()];
+            options.rate_limiter = RateLimiter::new(Duration::ZERO, DEFAULT_MAX_RATE_LIMIT_PAUSES);
+            options.retries = 0;
+            options.shell_notify = false;
+            options
+        }
+    }
+
+    /// Icon as the CDN serves it (an ICO wrapping a PNG), and its filename there (its hash).
+    fn cdn_icon() -> (String, Vec<u8>) {
+        let ico = ico::from_png(&png::encode_rgba(1, 1, &[0xff, 0, 0, 0xff])).unwrap();
+        (format!("{}.ico", sha1::hex_digest(&ico)), ico)
+    }
+
+    #[tokio::test]
+    async fn dry_run_makes_no_requests_or_files() {
+        let (cdn_filename, ico) = cdn_icon();
+        let server = TestServer::start(move |_, _| Response::ok("image/x-icon", ico.clone())).await;
+        let layout = Layout::new();
+        layout.add_shortcut("440");
+        let resolver = StubResolver::new(Some(&cdn_filename));
+        let mut options = layout.options(&server);
+        options.dry_run = true;
+
+        let mut report = RunReport::default();
+        run(&options, &resolver, &mut report).await.unwrap();
+
+        assert!(server.requests().is_empty());
+        assert!(layout.icon_dir_contents().is_empty());
+        assert_eq!(report.count(Action::Pending), 1);
+    }
+
+    /// Save an icon for `game_id` as if it was downloaded before.
+    fn save_existing_icon(layout: &Layout, game_id: &str) {
+        let png = png::encode_rgba(1, 1, &[0, 0xff, 0, 0xff]);
+        fs::write(layout.icon_path(game_id), png).unwrap();
+    }
+
+    /// Run with `--check-updates` over an existing icon for a shortcut,
+    /// with the CDN answering `HEAD` requests with `head` (and serving its icon otherwise),
+    /// returning the report and the methods of the requests made.
+    async fn check_updates(head: Response, dry_run: bool) -> (RunReport, Vec<String>) {
+        let (cdn_filename, ico) = cdn_icon();
+        let server = TestServer::start(move |request, _| match request.method.as_str() {
+            "HEAD" => head.clone(),
+            _ => Response::ok("image/x-icon", ico.clone()),
+        })
+        .await;
+        let layout = Layout::new();
+        layout.add_shortcut("440");
+        save_existing_icon(&layout, "440");
+        let resolver = StubResolver::new(Some(&cdn_filename));
+        let mut options = layout.options(&server);
+        options.look_up_names = false;
+        options.check_updates = true;
+        options.dry_run = dry_run;
+
+        let mut report = RunReport::default();
+        run(&options, &resolver, &mut report).await.unwrap();
+        let methods = server
+            .requests()
+            .into_iter()
+            .map(|request| request.method)
+            .collect();
+        (report, methods)
+    }
+
+    #[tokio::test]
+    async fn check_updates_downloads_changed_icons() {
+        let head = Response::ok("image/x-icon", "")
+            .header("Last-Modified", "Fri, 01 Jan 2100 00:00:00 GMT");
+        let (report, methods) = check_updates(head, false).await;
+        assert_eq!(methods, ["HEAD", "GET"]);
+        assert_eq!(report.count(Action::Updated), 1);
+    }
+
+    #[tokio::test]
+    async fn check_updates_skips_unchanged_icons() {
+        let head = Response::ok("image/x-icon", "")
+            .header("Last-Modified", "Thu, 01 Jan 1970 00:00:00 GMT");
+        let (report, methods) = check_updates(head, false).await;
+        assert_eq!(methods, ["HEAD"]);
+        assert_eq!(report.count(Action::AlreadyExists), 1);
+    }
+
+    #[tokio::test]
+    async fn check_updates_skips_icons_without_validators() {
+        let (report, methods) = check_updates(Response::ok("image/x-icon", ""), false).await;
+        assert_eq!(methods, ["HEAD"]);
+        assert_eq!(report.count(Action::AlreadyExists), 1);
+    }
+
+    #[tokio::test]
+    async fn check_updates_skips_icons_the_cdn_cant_say_about() {
+        let (report, methods) = check_updates(Response::new(500), false).await;
+        assert_eq!(methods, ["HEAD"]);
+        assert_eq!(report.count(Action::AlreadyExists), 1);
+    }
+
+    #[tokio::test]
+    async fn dry_run_doesnt_check_for_updates() {
+        let head = Response::ok("image/x-icon", "")
+            .header("Last-Modified", "Fri, 01 Jan 2100 00:00:00 GMT");
+        let (report, methods) = check_updates(head, true).await;
+        assert!(methods.is_empty());
+        assert_eq!(report.count(Action::Pending), 1);
+    }
+
+    #[tokio::test]
+    async fn dry_run_doesnt_look_up_icons() {
+        let server = TestServer::start(|_, _| Response::new(404)).await;
+        let layout = Layout::new();
+        layout.add_shortcut("440");
+        layout.add_shortcut("570");
+        let resolver = StubResolver::new(None);
+        let mut options = layout.options(&server);
+        options.dry_run = true;
+
+        let mut report = RunReport::default();
+        run(&options, &resolver, &mut report).await.unwrap();
+
+        assert_eq!(resolver.lookups(), 0);
+        assert!(server.requests().is_empty());
+        assert_eq!(report.count(Action::Pending), 2);
+    }
+
+    #[tokio::test]
+    async fn downloads_icon_found_by_resolver() {
+        let (cdn_filename, ico) = cdn_icon();
+        let server = TestServer::start(move |_, _| Response::ok("image/x-icon", ico.clone())).await;
+        let layout = Layout::new();
+        layout.add_shortcut("440");
+        let resolver = StubResolver::new(Some(&cdn_filename));
+        let mut options = layout.options(&server);
+        options.look_up_names = false;
+
+        let mut report = RunReport::default();
+        run(&options, &resolver, &mut report).await.unwrap();
+
+        assert_eq!(resolver.lookups(), 1);
+        assert_eq!(server.requests().len(), 1);
+        assert_eq!(report.count(Action::Downloaded), 1);
+        let icon = fs::read(layout.icon_path("440")).unwrap();
+        assert_eq!(png::dimensions(&icon).unwrap(), (1, 1));
+    }
+
+    #[tokio::test]
+    async fn downloads_shared_icon_once() {
+        let (cdn_filename, ico) = cdn_icon();
+        let server = TestServer::start(move |_, _| Response::ok("image/x-icon", ico.clone())).await;
+        let layout = Layout::new();
+        layout.add_shortcut("440");
+        layout.add_shortcut_named(OsStr::new("441.desktop"), "441", "440");
+        let resolver = StubResolver::new(Some(&cdn_filename));
+        let mut options = layout.options(&server);
+        options.look_up_names = false;
+
+        let mut report = RunReport::default();
+        run(&options, &resolver, &mut report).await.unwrap();
+
+        assert_eq!(server.requests().len(), 1);
+        assert_eq!(report.count(Action::Downloaded), 1);
+        assert_eq!(report.count(Action::Duplicate), 1);
+        assert_eq!(report.count(Action::AlreadyExists), 0);
+        assert_eq!(layout.icon_dir_contents(), ["steam_icon_440.png"]);
+    }
+
+    #[tokio::test]
+    async fn downloads_only_missing_icons() {
+        let (cdn_filename, ico) = cdn_icon();
+        let server = TestServer::start(move |request, _| {
+            if request.path.contains("/730/") {
+                Response::new(404)
+            } else {
+                Response::ok("image/x-icon", ico.clone())
+            }
+        })
+        .await;
+        let layout = Layout::new();
+        layout.add_shortcut("440");
+        save_existing_icon(&layout, "440");
+        layout.add_shortcut("570");
+        layout.add_shortcut("730");
+        // Anything else on the desktop is left alone
+        fs::write(layout.shortcuts.join("notes.txt"), "steam_icon_570").unwrap();
+        let resolver = StubResolver::new(Some(&cdn_filename));
+        let mut options = layout.options(&server);
+        options.look_up_names = false;
+
+        let mut report = RunReport::default();
+        run(&options, &resolver, &mut report).await.unwrap();
+
+        // Requests go through the server as a proxy, so they're for whole URLs
+        let mut urls: Vec<_> = server
+            .requests()
+            .into_iter()
+            .map(|request| request.path)
+            .collect();
+        urls.sort();
+        let expected = ["570", "730"]
+            .map(|game_id| download::icon_url(&server.url(), game_id, &cdn_filename).unwrap());
+        assert_eq!(urls, expected);
+        assert_eq!(report.count(Action::AlreadyExists), 1);
+        assert_eq!(report.count(Action::Downloaded), 1);
+        assert_eq!(report.count(Action::NotFound), 1);
+        assert_eq!(report.count(Action::Failed), 0);
+        let icon = fs::read(layout.icon_path("570")).unwrap();
+        assert_eq!(png::dimensions(&icon).unwrap(), (1, 1));
+        // The icon that was already there is left alone
+        let icon = fs::read(layout.icon_path("440")).unwrap();
+        assert_eq!(icon, png::encode_rgba(1, 1, &[0, 0xff, 0, 0xff]));
+        assert!(!layout.icon_path("730").exists());
+    }
+
+    #[tokio::test]
+    async fn skips_icon_saved_meanwhile() {
+        let (cdn_filename, ico) = cdn_icon();
+        let layout = Layout::new();
+        layout.add_shortcut("440");
+
+        // Another run saves the icon while this one is downloading it
+        let icon_path = layout.icon_path("440");
+        let server = TestServer::start(move |_, _| {
+            fs::write(&icon_path, png::encode_rgba(1, 1, &[0, 0xff, 0, 0xff])).unwrap();
+            Response::ok("image/x-icon", ico.clone())
+        })
+        .await;
+        let resolver = StubResolver::new(Some(&cdn_filename));
+        let mut options = layout.options(&server);
+        options.look_up_names = false;
+
+        let mut report = RunReport::default();
+        run(&options, &resolver, &mut report).await.unwrap();
+
+        assert_eq!(server.requests().len(), 1);
+        assert_eq!(report.count(Action::AlreadyExists), 1);
+        assert_eq!(report.count(Action::Failed), 0);
+        assert_eq!(layout.icon_dir_contents(), ["steam_icon_440.png"]);
+        let icon = fs::read(layout.icon_path("440")).unwrap();
+        assert_eq!(icon, png::encode_rgba(1, 1, &[0, 0xff, 0, 0xff]));
+    }
+
+    #[tokio::test]
+    async fn exclusions_win_over_game_ids() {
+        let (cdn_filename, ico) = cdn_icon();
+        let server = TestServer::start(move |_, _| Response::ok("image/x-icon", ico.clone())).await;
+        let layout = Layout::new();
+        for game_id in ["440", "570", "620"] {
+            layout.add_shortcut(game_id);
+        }
+        fs::write(
+            layout.shortcuts.join(IGNORE_FILENAME),
+            "# Leave this one alone\n440\n",
+        )
+        .unwrap();
+        let resolver = StubResolver::new(Some(&cdn_filename));
+        let mut options = layout.options(&server);
+        options.look_up_names = false;
+        options.game_ids = "440,620".parse().unwrap();
+
+        let mut report = RunReport::default();
+        run(&options, &resolver, &mut report).await.unwrap();
+
+        assert_eq!(report.count(Action::Excluded), 1);
+        assert_eq!(report.count(Action::FilteredOut), 1);
+        assert_eq!(report.count(Action::Downloaded), 1);
+        assert_eq!(layout.icon_dir_contents(), ["steam_icon_620.png"]);
+    }
+
+    #[tokio::test]
+    async fn reports_directory_in_the_way_of_icon() {
+        let (cdn_filename, ico) = cdn_icon();
+        let server = TestServer::start(move |_, _| Response::ok("image/x-icon", ico.clone())).await;
+        let layout = Layout::new();
+        layout.add_shortcut("440");
+        layout.add_shortcut("570");
+        fs::create_dir(layout.icon_path("440")).unwrap();
+        let resolver = StubResolver::new(Some(&cdn_filename));
+        let mut options = layout.options(&server);
+        options.look_up_names = false;
+
+        let mut report = RunReport::default();
+        run(&options, &resolver, &mut report).await.unwrap();
+
+        // The directory is left alone, without stopping the rest of the shortcuts
+        assert_eq!(report.count(Action::Failed), 1);
+        assert_eq!(report.count(Action::Downloaded), 1);
+        let failed = report
+            .items
+            .iter()
+            .find(|item| item.action == Action::Failed)
+            .unwrap();
+        assert_eq!(failed.failure, Some(FailureKind::Write));
+        assert!(failed.error.as_deref().unwrap().contains("is a directory"));
+        assert!(layout.icon_path("440").is_dir());
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn fails_shortcut_without_icon_from_resolver() {
+        let server = TestServer::start(|_, _| Response::new(404)).await;
+        let layout = Layout::new();
+        layout.add_shortcut("440");
+        let resolver = StubResolver::new(None);
+        let mut options = layout.options(&server);
+        options.look_up_names = false;
+
+        let mut report = RunReport::default();
+        run(&options, &resolver, &mut report).await.unwrap();
+
+        assert_eq!(resolver.lookups(), 1);
+        assert!(server.requests().is_empty());
+        assert_eq!(report.count(Action::Failed), 1);
+        assert!(layout.icon_dir_contents().is_empty());
+    }
+
+    /// Logger keeping the warnings logged on each thread, for its test to check.
+    struct WarningCollector;
+
+    thread_local! {
+        static WARNINGS: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+    }
+
+    impl log::Log for WarningCollector {
+        fn enabled(&self, metadata: &log::Metadata<'_>) -> bool {
+            metadata.level() <= log::Level::Warn
+        }
+
+        fn log(&self, record: &log::Record<'_>) {
+            if self.enabled(record.metadata()) {
+                WARNINGS.with_borrow_mut(|warnings| warnings.push(record.args().to_string()));
+            }
+        }
+
+        fn flush(&self) {}
+    }
+
+    /// Call `f`, returning what it returned and the warnings it logged (on this thread).
+    fn collect_warnings<T>(f: impl FnOnce() -> T) -> (T, Vec<String>) {
+        static INIT: Once = Once::new();
+        INIT.call_once(|| {
+            log::set_logger(&WarningCollector).unwrap();
+            log::set_max_level(log::LevelFilter::Warn);
+        });
+        WARNINGS.take();
+        let result = f();
+        (result, WARNINGS.take())
+    }
+
+    /// Layout with a shortcut for 440 next to a subdirectory (with a shortcut for 620)
+    /// that can't be read, returning the subdirectory, or `None` if permissions don't apply.
+    fn layout_with_unreadable_subdir(layout: &Layout) -> Option<PathBuf> {
+        use std::os::unix::fs::PermissionsExt as _;
+
+        // Permissions don't apply to root
+        // SAFETY: `geteuid` has no preconditions and always succeeds
+        if unsafe { libc::geteuid() } == 0 {
+            return None;
+        }
+        layout.add_shortcut("440");
+        let subdir = layout.shortcuts.join("Locked");
+        fs::create_dir(&subdir).unwrap();
+        fs::write(subdir.join("620.desktop"), "").unwrap();
+        fs::set_permissions(&subdir, fs::Permissions::from_mode(0o000)).unwrap();
+        Some(subdir)
+    }
+
+    #[test]
+    fn scan_skips_unreadable_subdirectory() {
+        use std::os::unix::fs::PermissionsExt as _;
+
+        let layout = Layout::new();
+        let Some(subdir) = layout_with_unreadable_subdir(&layout) else {
+            return;
+        };
+
+        let (scanned, warnings) =
+            collect_warnings(|| scan_dir(&layout.shortcuts, true, false, &|| Ok(())));
+
+        fs::set_permissions(&subdir, fs::Permissions::from_mode(0o755)).unwrap();
+        let scanned = scanned.unwrap();
+        assert_eq!(scanned.entries, [layout.shortcuts.join("440.desktop")]);
+        assert_eq!(scanned.unreadable, 1);
+        assert_eq!(
+            warnings,
+            [format!(
+                "Skipping directory `{}`: Permission denied (os error 13)",
+                subdir.display()
+            )]
+        );
+    }
+
+    #[tokio::test]
+    async fn reports_unreadable_subdirectory() {
+        use std::os::unix::fs::PermissionsExt as _;
+
+        let (cdn_filename, ico) = cdn_icon();
+        let server = TestServer::start(move |_, _| Response::ok("image/x-icon", ico.clone())).await;
+        let layout = Layout::new();
+        let Some(subdir) = layout_with_unreadable_subdir(&layout) else {
+            return;
+        };
+        let resolver = StubResolver::new(Some(&cdn_filename));
+        let mut options = layout.options(&server);
+        options.look_up_names = false;
+        options.recursive = true;
+
+        let mut report = RunReport::default();
+        let result = run(&options, &resolver, &mut report).await;
+
+        // The rest of the shortcuts are still processed
+        fs::set_permissions(&subdir, fs::Permissions::from_mode(0o755)).unwrap();
+        result.unwrap();
+        assert_eq!(report.unreadable_entries, 1);
+        assert_eq!(report.count(Action::Downloaded), 1);
+        assert_eq!(layout.icon_dir_contents(), ["steam_icon_440.png"]);
+    }
+
+    /// Download the icons for shortcuts to 440 and 620 (with 570's icon already there),
+    /// recording them in a manifest, and return where it's kept.
+    async fn download_into_manifest(layout: &Layout) -> PathBuf {
+        let (cdn_filename, ico) = cdn_icon();
+        let server = TestServer::start(move |_, _| Response::ok("image/x-icon", ico.clone())).await;
+        for game_id in ["440", "570", "620"] {
+            layout.add_shortcut(game_id);
+        }
+        save_existing_icon(layout, "570");
+        let resolver = StubResolver::new(Some(&cdn_filename));
+        let mut options = layout.options(&server);
+        options.look_up_names = false;
+        let manifest_path = layout.shortcuts.with_file_name("manifest.json");
+        options.manifest = Some(manifest_path.clone());
+
+        let mut report = RunReport::default();
+        run(&options, &resolver, &mut report).await.unwrap();
+        assert_eq!(report.count(Action::Downloaded), 2);
+        manifest_path
+    }
+
+    /// Game IDs of the icons recorded in the manifest at `path`.
+    fn manifest_game_ids(path: &Path) -> Vec<String> {
+        Manifest::load(Some(path.to_owned()))
+            .entries()
+            .into_iter()
+            .map(|entry| entry.game_id)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn undo_only_removes_downloaded_icons() {
+        let layout = Layout::new();
+        let manifest_path = download_into_manifest(&layout).await;
+        let manifest = Manifest::load(Some(manifest_path.clone()));
+
+        let mut report = UndoReport::default();
+        undo(
+            &manifest,
+            &GameIdFilter::default(),
+            false,
+            &mut report,
+            &|| Ok(()),
+        )
+        .unwrap();
+
+        // The icon that was already there isn't in the manifest, so it's left alone
+        assert_eq!(
+            report.removed,
+            [layout.icon_path("440"), layout.icon_path("620")]
+        );
+        assert_eq!(layout.icon_dir_contents(), ["steam_icon_570.png"]);
+        assert!(manifest_game_ids(&manifest_path).is_empty());
+    }
+
+    #[tokio::test]
+    async fn undo_only_removes_icons_of_games_asked_for() {
+        let layout = Layout::new();
+        let manifest_path = download_into_manifest(&layout).await;
+        let manifest = Manifest::load(Some(manifest_path.clone()));
+
+        let mut report = UndoReport::default();
+        let game_ids = "620".parse().unwrap();
+        undo(&manifest, &game_ids, false, &mut report, &|| Ok(())).unwrap();
+
+        assert_eq!(report.removed, [layout.icon_path("620")]);
+        assert!(layout.icon_path("440").exists());
+        assert_eq!(manifest_game_ids(&manifest_path), ["440"]);
+    }
+
+    #[tokio::test]
+    async fn undo_leaves_icons_changed_since_download() {
+        let layout = Layout::new();
+        let manifest_path = download_into_manifest(&layout).await;
+        save_existing_icon(&layout, "440");
+        fs::remove_file(layout.icon_path("620")).unwrap();
+        let manifest = Manifest::load(Some(manifest_path.clone()));
+
+        let mut report = UndoReport::default();
+        undo(
+            &manifest,
+            &GameIdFilter::default(),
+            false,
+            &mut report,
+            &|| Ok(()),
+        )
+        .unwrap();
+
+        // Changed icons are still remembered, but ones already gone are forgotten
+        assert!(report.removed.is_empty());
+        assert_eq!(report.modified, [layout.icon_path("440")]);
+        assert_eq!(report.missing, [layout.icon_path("620")]);
+        assert!(layout.icon_path("440").exists());
+        assert_eq!(manifest_game_ids(&manifest_path), ["440"]);
+    }
+
+    #[tokio::test]
+    async fn dry_run_undo_removes_nothing() {
+        let layout = Layout::new();
+        let manifest_path = download_into_manifest(&layout).await;
+        let manifest = Manifest::load(Some(manifest_path.clone()));
+
+        let mut report = UndoReport::default();
+        undo(
+            &manifest,
+            &GameIdFilter::default(),
+            true,
+            &mut report,
+            &|| Ok(()),
+        )
+        .unwrap();
+
+        assert_eq!(
+            report.removed,
+            [layout.icon_path("440"), layout.icon_path("620")]
+        );
+        assert_eq!(layout.icon_dir_contents().len(), 3);
+        assert_eq!(manifest_game_ids(&manifest_path), ["440", "620"]);
+    }
+
+    /// Options for finding orphaned icons, keeping any modified in the last `min_age`.
+    fn clean_options(min_age: Duration) -> CleanOptions {
+        CleanOptions {
+            recursive: false,
+            follow_links: false,
+            exclusions: Exclusions::default(),
+            installed_games: false,
+            min_age,
+        }
+    }
+
+    /// Layout with shortcuts to 440 and 620, icons for 440, 570 and 730,
+    /// and a few other things in the icon directory that aren't icons.
+    fn layout_with_orphaned_icons() -> Layout {
+        let layout = Layout::new();
+        layout.add_shortcut("440");
+        layout.add_shortcut("620");
+        for game_id in ["440", "570", "730"] {
+            save_existing_icon(&layout, game_id);
+        }
+        fs::write(layout.icons.join("notes.txt"), "").unwrap();
+        fs::create_dir(layout.icons.join("steam_icon_10.png")).unwrap();
+        layout
+    }
+
+    #[test]
+    fn finds_icons_no_shortcut_uses() {
+        let layout = layout_with_orphaned_icons();
+
+        let mut report = CleanReport::default();
+        let orphaned_icons = find_orphaned_icons(
+            std::slice::from_ref(&layout.shortcuts),
+            &layout.icons,
+            &clean_options(Duration::ZERO),
+            &mut report,
+            &|| Ok(()),
+        )
+        .unwrap();
+
+        let paths: Vec<_> = orphaned_icons.iter().map(|icon| &icon.path).collect();
+        assert_eq!(paths, [&layout.icon_path("570"), &layout.icon_path("730")]);
+        assert_eq!(report.too_new, 0);
+    }
+
+    #[test]
+    fn keeps_orphaned_icons_that_are_too_new() {
+        let layout = layout_with_orphaned_icons();
+
+        let mut report = CleanReport::default();
+        let orphaned_icons = find_orphaned_icons(
+            std::slice::from_ref(&layout.shortcuts),
+            &layout.icons,
+            &clean_options(Duration::from_secs(60 * 60)),
+            &mut report,
+            &|| Ok(()),
+        )
+        .unwrap();
+
+        assert!(orphaned_icons.is_empty());
+        assert_eq!(report.too_new, 2);
+    }
+
+    #[test]
+    fn finds_no_orphaned_icons_when_a_shortcut_cant_be_read() {
+        let layout = layout_with_orphaned_icons();
+        fs::write(
+            layout.shortcuts.join("broken.desktop"),
+            "[Desktop Entry]\nIcon=",
+        )
+        .unwrap();
+
+        let mut report = CleanReport::default();
+        let error = find_orphaned_icons(
+            std::slice::from_ref(&layout.shortcuts),
+            &layout.icons,
+            &clean_options(Duration::ZERO),
+            &mut report,
+            &|| Ok(()),
+        )
+        .unwrap_err();
+
+        assert!(error.to_string().starts_with("Not removing any icons"));
+    }
+
+    #[test]
+    fn removes_orphaned_icons() {
+        let layout = layout_with_orphaned_icons();
+        let mut report = CleanReport::default();
+        let orphaned_icons = find_orphaned_icons(
+            std::slice::from_ref(&layout.shortcuts),
+            &layout.icons,
+            &clean_options(Duration::ZERO),
+            &mut report,
+            &|| Ok(()),
+        )
+        .unwrap();
+
+        remove_orphaned_icons(&orphaned_icons, &mut report, &|| Ok(())).unwrap();
+
+        assert_eq!(
+            report.removed,
+            [layout.icon_path("570"), layout.icon_path("730")]
+        );
+        let mut contents = layout.icon_dir_contents();
+        contents.sort();
+        assert_eq!(
+            contents,
+            ["notes.txt", "steam_icon_10.png", "steam_icon_440.png"]
+        );
+    }
+
+    #[test]
+    fn recognizes_shortcuts_named_with_invalid_unicode() {
+        let layout = Layout::new();
+        let path = layout.add_shortcut_named(OsStr::from_bytes(b"\xff.desktop"), "440", "440");
+        let shortcut =
+            extract_game_id_and_icon_filename(&path, &layout.shortcuts, &layout.icons, None, false)
+                .unwrap()
+                .unwrap();
+        assert_eq!(shortcut.game_id, "440");
+
+        // Only the extension matters, not whether the rest of the name is valid
+        let path = layout.add_shortcut_named(OsStr::from_bytes(b"\xff.desktop\xff"), "440", "440");
+        let shortcut =
+            extract_game_id_and_icon_filename(&path, &layout.shortcuts, &layout.icons, None, false)
+                .unwrap();
+        assert!(shortcut.is_none());
+    }
+}
@@ -0,0 +1,152 @@
+//! Finding every Steam library (directory games are installed to) through `libraryfolders.vdf`.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context as _, Result};
+use log::*;
+
+use crate::{paths, vdf};
+
+/// Directories of every Steam library, starting with the install at `install_dir` itself.
+/// Libraries that can't be found (e.g. on unplugged drives) are skipped with a warning.
+pub fn library_dirs(install_dir: &Path) -> Vec<PathBuf> {
+    let mut dirs = vec![install_dir.to_owned()];
+
+    let path = install_dir.join("steamapps").join("libraryfolders.vdf");
+    let libraries = match fs::read_to_string(&path) {
+        Ok(text) => match parse_library_folders(&text) {
+            Ok(libraries) => libraries,
+            Err(error) => {
+                warn!("Failed to parse `{}`: {error:#}", path.display());
+                return dirs;
+            }
+        },
+        Err(error) => {
+            debug!("Failed to read `{}`: {error}", path.display());
+            return dirs;
+        }
+    };
+
+    for dir in libraries {
+        if dirs.iter().any(|existing| paths::same_dir(existing, &dir)) {
+            continue;
+        } else if !dir.join("steamapps").is_dir() {
+            warn!(
+                "Skipping Steam library `{}`, which can't be found",
+                dir.display()
+            );
+            continue;
+        }
+        debug!("Found Steam library `{}`", dir.display());
+        dirs.push(dir);
+    }
+
+    dirs
+}
+
+/// Parse the library directories listed in the contents of a `libraryfolders.vdf` file.
+/// Libraries are numbered, and older files list each one's path directly,
+/// while newer ones use objects with a `path` key (alongside the apps installed there).
+pub fn parse_library_folders(text: &str) -> Result<Vec<PathBuf>> {
+    let root = vdf::parse_text(text)?;
+    let folders = root
+        .get("libraryfolders")
+        .context("Missing `libraryfolders`")?;
+
+    let mut dirs = Vec::new();
+    for (key, library) in folders.entries() {
+        // Other keys hold details like `TimeNextStatsReport`
+        if key.is_empty() || !key.bytes().all(|byte| byte.is_ascii_digit()) {
+            continue;
+        }
+        let dir = library
+            .as_str()
+            .or_else(|| library.get("path")?.as_str())
+            .with_context(|| format!("Library `{key}` has no path"))?;
+        dirs.push(PathBuf::from(dir));
+    }
+
+    Ok(dirs)
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn parses_old_layout() {
+        let text = include_str!("../tests/fixtures/libraryfolders_old.vdf");
+        assert_eq!(
+            parse_library_folders(text).unwrap(),
+            [
+                PathBuf::from(r"D:\SteamLibrary"),
+                PathBuf::from(r"E:\Games\Steam")
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_new_layout() {
+        let text = include_str!("../tests/fixtures/libraryfolders.vdf");
+        assert_eq!(
+            parse_library_folders(text).unwrap(),
+            [
+                PathBuf::from(r"C:\Program Files (x86)\Steam"),
+                PathBuf::from(r"D:\SteamLibrary")
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_library_without_path() {
+        let text = r#""libraryfolders" { "0" { "label" "Games" } }"#;
+        assert!(parse_library_folders(text).is_err());
+        assert!(parse_library_folders(r#""other" {}"#).is_err());
+    }
+
+    /// `libraryfolders.vdf` (in the new layout) listing `dirs`.
+    fn library_folders(dirs: &[&Path]) -> String {
+        let mut text = String::from("\"libraryfolders\"\n{\n");
+        for (index, dir) in dirs.iter().enumerate() {
+            let path = dir.to_string_lossy().replace('\\', r"\\");
+            text.push_str(&format!(
+                "\t\"{index}\"\n\t{{\n\t\t\"path\"\t\t\"{path}\"\n\t}}\n"
+            ));
+        }
+        text.push_str("}\n");
+        text
+    }
+
+    /// Create a Steam library at `dir`.
+    fn create_library(dir: &Path) {
+        fs::create_dir_all(dir.join("steamapps")).unwrap();
+    }
+
+    #[test]
+    fn finds_every_library_once() {
+        let dir = TempDir::new().unwrap();
+        let install = dir.path().join("Steam");
+        let library = dir.path().join("SteamLibrary");
+        let missing = dir.path().join("Unplugged");
+        create_library(&install);
+        create_library(&library);
+        let text = library_folders(&[&install, &library, &missing, &library]);
+        fs::write(install.join("steamapps").join("libraryfolders.vdf"), text).unwrap();
+
+        assert_eq!(library_dirs(&install), [install, library]);
+    }
+
+    #[test]
+    fn finds_install_without_library_folders() {
+        let dir = TempDir::new().unwrap();
+        create_library(dir.path());
+        assert_eq!(library_dirs(dir.path()), [dir.path()]);
+
+        // Even if they can't be read
+        fs::write(dir.path().join("steamapps").join("libraryfolders.vdf"), "{").unwrap();
+        assert_eq!(library_dirs(dir.path()), [dir.path()]);
+    }
+}
@@ -0,0 +1,102 @@
+//! Finding games' icons in Steam's library cache (`appcache\librarycache`),
+//! for installing them without downloading anything.
+//!
+//! Older Steam clients keep the cache flat, with each game's icon as `<appid>_icon.jpg`,
+//! while newer ones give each game its own folder, with the icon named after its hash
+//! (alongside other artwork named after what it is, like `header.jpg`).
+
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context as _, Result, bail};
+use log::*;
+
+use crate::{icns, ico, jpeg, png};
+
+/// Extensions the cache stores icons with
+const EXTENSIONS: [&str; 2] = ["jpg", "png"];
+
+/// Find the icon for the game `game_id` in the library cache at `library_cache_dir`,
+/// or `None` if it isn't cached.
+pub fn find_icon(library_cache_dir: &Path, game_id: &str) -> Result<Option<PathBuf>> {
+    // Only app IDs have cached icons, and anything else could lead outside of the cache
+    if game_id.is_empty() || !game_id.bytes().all(|byte| byte.is_ascii_digit()) {
+        return Ok(None);
+    }
+
+    // Newer clients keep each game's artwork in its own folder
+    let app_dir = library_cache_dir.join(game_id);
+    match fs::read_dir(&app_dir) {
+        Ok(entries) => {
+            let mut icons = Vec::new();
+            for entry in entries {
+                let path = entry
+                    .with_context(|| format!("Failed to list `{}`", app_dir.display()))?
+                    .path();
+                if is_hash_named_icon(&path) && path.is_file() {
+                    icons.push(path);
+                }
+            }
+
+            // There should only be one, but pick the same one every time if there isn't
+            icons.sort();
+            if icons.len() > 1 {
+                debug!(
+                    "Found {} icons for game #{game_id} in `{}`, using the first",
+                    icons.len(),
+                    app_dir.display()
+                );
+            }
+            if let Some(icon) = icons.into_iter().next() {
+                return Ok(Some(icon));
+            }
+        }
+        Err(error) if error.kind() == ErrorKind::NotFound => {}
+        Err(error) => {
+            return Err(error).with_context(|| format!("Failed to list `{}`", app_dir.display()));
+        }
+    }
+
+    // Older clients keep everything in one folder
+    Ok(EXTENSIONS
+        .iter()
+        .map(|extension| library_cache_dir.join(format!("{game_id}_icon.{extension}")))
+        .find(|path| path.is_file()))
+}
+
+/// Whether `path` is named like an icon in a game's folder (its 40 character hex hash).
+fn is_hash_named_icon(path: &Path) -> bool {
+    let (Some(stem), Some(extension)) = (
+        path.file_stem().and_then(|stem| stem.to_str()),
+        path.extension().and_then(|extension| extension.to_str()),
+    ) else {
+        return false;
+    };
+    stem.len() == 40
+        && stem.bytes().all(|byte| byte.is_ascii_hexdigit())
+        && EXTENSIONS.contains(&extension.to_ascii_lowercase().as_str())
+}
+
+/// Convert a cached icon (a JPEG or PNG) to the format of `icon_path` (going by its extension):
+/// an ICO, a PNG, or an ICNS.
+pub fn convert(cached: &[u8], icon_path: &Path) -> Result<Vec<u8>> {
+    let png = if cached.starts_with(&png::SIGNATURE) {
+        cached.to_vec()
+    } else if cached.starts_with(&jpeg::SIGNATURE) {
+        let (width, height, rgba) = jpeg::decode(cached)?;
+        png::encode_rgba(width, height, &rgba)
+    } else {
+        bail!("Cached icon is neither a JPEG nor a PNG");
+    };
+
+    match icon_path
+        .extension()
+        .and_then(|extension| extension.to_str())
+    {
+        Some("ico") => ico::from_png(&png),
+        Some("png") => Ok(png),
+        Some("icns") => icns::from_png(&png),
+        _ => bail!("Don't know how to save icons as `{}`", icon_path.display()),
+    }
+}
@@ -1,215 +1,162 @@
-#![feature(once_cell_try)]
+use std::future::Future;
+use std::pin::Pin;
+use std::process::ExitCode;
 
-use std::env;
-use std::fs::{DirEntry, File};
-use std::io::{BufRead as _, BufReader, Write};
-use std::path::PathBuf;
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::{Arc, OnceLock};
-
-use anyhow::{Context as _, Result, bail};
-use env_logger::Env;
+use anyhow::{Context as _, Result};
+use env_logger::{Env, Target};
+use futures_util::future;
 use log::*;
-use regex::Regex;
+use retrieve_missing_steam_game_icons::cancel::Cancellation;
+use retrieve_missing_steam_game_icons::{platform, progress};
+
+use crate::args::{Cli, Command};
 
-// Path will be different on other platforms
-#[cfg(target_os = "windows")]
-const LOCAL_ICON_DIR: &str = r"C:\Program Files (x86)\Steam\steam\games\";
+mod args;
+mod commands;
 
 #[tokio::main]
-async fn main() -> Result<()> {
+async fn main() -> Result<ExitCode> {
+    // Parse the command line
+    let (global, command) = Cli::from_command_line().into_command();
+
     // Set up logging
+    // (logs go to stderr, but keep them down to problems when stdout is meant for scripts
+    // or is the whole point)
+    let quiet = global.json || matches!(command, Command::List(_));
     let env = Env::default()
-        .default_filter_or("info")
+        .default_filter_or(if quiet { "warn" } else { "info" })
         .default_write_style_or("always");
-    env_logger::try_init_from_env(env)?;
-
-    // Set up SIGINT monitoring
-    let check_sigint = setup_sigint_checker()?;
-
-    // Log the directory being processed
-    let dir_with_shortcuts = env::current_dir()?;
-    info!(
-        "Processing shortcuts in {}",
-        dir_with_shortcuts.as_path().to_string_lossy()
-    );
-
-    // Make sure the icon directory exists
-    let local_icon_dir = PathBuf::from(LOCAL_ICON_DIR);
-    if !local_icon_dir.is_dir() {
-        bail!("Specified local icon directory is not actually a directory");
-    }
-
-    // Loop through the shortcut directory and process all shortcuts
-    for entry in dir_with_shortcuts.read_dir()? {
-        // Check if the script needs to exit
-        check_sigint()?;
-
-        let entry = entry?;
-
-        // Extract the game ID and icon filename from the shortcut
-        let Some((game_id, icon_filename)) = extract_game_id_and_icon_filename(entry)? else {
-            continue;
-        };
-
-        // Make sure the icon doesn't already exist
-        let icon_path = local_icon_dir.join(&icon_filename);
-        if icon_path.exists() {
-            info!("Icon already exists for game #{game_id}");
-            continue;
-        }
-
-        // Build the CDN URL for the icon
-        let icon_url = format!("https://cdn.cloudflare.steamstatic.com/steamcommunity/public/images/apps/{game_id}/{icon_filename}");
-
-        // Download the icon
-        let body = reqwest::get(icon_url).await?.bytes().await?;
-
-        // Save the icon locally
-        let mut file = File::create_new(icon_path).context("Failed to save icon file")?;
-        file.write_all(&body)
-            .context("Failed to write ICO contents to the newly created file")?;
-    }
-
-    Ok(())
-}
-
-/// Extract steam game ID and icon filename from `.url` shortcut files.
-fn extract_game_id_and_icon_filename(entry: DirEntry) -> Result<Option<(String, String)>> {
-    // Bail on unexpected data in the filename
-    let Ok(filename) = entry.file_name().into_string() else {
-        bail!("Filename contains invalid unicode data");
-    };
-
-    // Skip non-shortcut files
-    #[cfg(not(target_os = "windows"))]
-    bail!("Other platforms won't have `.url` files");
-    let metadata = entry.metadata().context("Failed to read metadata")?;
-    if metadata.is_dir() {
-        warn!("Skipping directory `{filename}`");
-        return Ok(None);
-    } else if metadata.is_symlink() {
-        warn!("Skipping symlink `{filename}`");
-        return Ok(None);
-    } else if !metadata.is_file() {
-        warn!("Skipping non-file `{filename}`");
-        return Ok(None);
-    } else if !filename.ends_with(".url") {
-        warn!("Skipping non-shortcut file `{filename}`");
-        return Ok(None);
+    let mut logger = env_logger::Builder::from_env(env);
+    if let Some(level) = global.log_level {
+        logger.filter_level(level);
     }
-
-    // Build the regex for extracting the steam game ID from the shortcut URL
-    static GAME_ID_REGEX: OnceLock<Regex> = OnceLock::new();
-    #[cfg(not(target_os = "windows"))]
-    bail!("Format of entry may be different on other platforms");
-    let game_id_regex =
-        GAME_ID_REGEX.get_or_try_init(|| Regex::new(r"^URL=steam://rungameid/(\d+)$"))?;
-
-    // Build the regex for extracting the icon path from the shortcut IconFile
-    static ICON_PATH_REGEX: OnceLock<Regex> = OnceLock::new();
-    #[cfg(not(target_os = "windows"))]
-    bail!("Format of entry may be different on other platforms");
-    let icon_path_regex =
-        ICON_PATH_REGEX.get_or_try_init(|| Regex::new(r"^IconFile=(.*\\)([^.\\]+\.ico)$"))?;
-
-    // Parse (naively) the shortcut file
-    let file = File::open(entry.path()).context("Failed to open file")?;
-    let lines = BufReader::new(file).lines();
-    let mut game_id: Option<String> = None;
-    let mut icon_filename: Option<String> = None;
-    let mut in_shortcut_section = false;
-    for line in lines {
-        let line = line.context("Failed to read line")?;
-
-        #[cfg(not(target_os = "windows"))]
-        bail!("Parsing the file will be different on other platforms");
-
-        // Find and extract the game ID and icon path
-        // from the "InternetShortcut" section within the shortcut file
-        if &line == "[InternetShortcut]" {
-            in_shortcut_section = true;
-        } else if !in_shortcut_section {
-            continue;
-        } else if line.starts_with("[") {
-            in_shortcut_section = false;
-        } else if let Some(captures) = game_id_regex.captures(&line) {
-            if game_id.is_some() {
-                bail!("Game ID already set for shortcut: {filename}");
-            }
-
-            game_id = Some(
-                captures
-                    .get(1)
-                    .context("Failed to extract icon path")?
-                    .as_str()
-                    .to_owned(),
-            );
-        } else if let Some(captures) = icon_path_regex.captures(&line) {
-            if icon_filename.is_some() {
-                bail!("Icon path and/or name already set for shortcut: {filename}");
-            }
-
-            // Make sure the specified icon directory matches the one being written to
-            let icon_dir = captures
-                .get(1)
-                .context("Failed to extract icon path")?
-                .as_str()
-                .to_owned();
-            if icon_dir != LOCAL_ICON_DIR {
-                bail!("Unrecognized icon directory `{icon_dir}` for shortcut: {filename}");
-            }
-
-            icon_filename = Some(
-                captures
-                    .get(2)
-                    .context("Failed to extract icon path")?
-                    .as_str()
-                    .to_owned(),
-            );
-        }
+    logger
+        .target(Target::Pipe(Box::new(progress::LogWriter)))
+        .try_init()?;
+
+    // Bail before doing anything if shortcuts can't be processed on this platform
+    platform::ensure_supported()?;
+
+    // Set up SIGINT (and other exit signal) monitoring
+    let cancellation = setup_sigint_checker()?;
+    let check_sigint = || cancellation.check();
+
+    match command {
+        Command::Download(args) => commands::download::run(&global, *args, &cancellation).await,
+        Command::Check(args) => commands::check::run(&global, args, &cancellation).await,
+        Command::List(args) => commands::list::run(&global, args, &cancellation).await,
+        Command::Undo(args) => commands::undo::run(args, &check_sigint),
+        Command::Clean(args) => commands::clean::run(&global, args, &check_sigint),
+        Command::Verify(args) => commands::verify::run(&global, args, &cancellation).await,
+        Command::FixShortcuts(args) => commands::fix_shortcuts::run(&global, args, &check_sigint),
+        Command::ExportPng(args) => commands::export_png::run(&global, args, &cancellation).await,
     }
-
-    let (Some(game_id), Some(icon_filename)) = (game_id, icon_filename) else {
-        bail!("Shortcut could not be parsed or was not a Steam shortcut file: {filename}");
-    };
-
-    Ok(Some((game_id, icon_filename)))
 }
 
-/// Basic SIGINT handling.
-/// The returned callback will return an error if the script needs to bail.
+/// Basic SIGINT handling (along with the other signals asking the script to exit).
+/// The returned cancellation is cancelled once the script needs to bail.
 ///
 /// Setup:
 ///
 /// ```rust
-/// let check_sigint = setup_sigint_checker()?;
+/// let cancellation = setup_sigint_checker()?;
 /// ```
 ///
 /// Usage (anywhere exiting is ideal):
 ///
 /// ```rust
-/// check_sigint()?;
+/// cancellation.check()?;
 /// ```
-fn setup_sigint_checker() -> Result<impl Fn() -> Result<()>> {
+fn setup_sigint_checker() -> Result<Cancellation> {
     info!("Press `Ctrl` + `c` at any time to exit");
 
-    let sigint_received: Arc<AtomicBool> = AtomicBool::new(false).into();
+    // Wait for whichever signal comes first
+    let signals = exit_signals()?;
+    let cancellation = Cancellation::new();
+    let signal_cancellation = cancellation.clone();
+    tokio::spawn(async move {
+        let (signal, ..) = future::select_all(signals).await;
+        info!("{signal} received, exiting...");
+        signal_cancellation.cancel(signal);
 
-    let sigint_received_write = sigint_received.clone();
-    ctrlc::set_handler(move || {
-        info!("SIGINT (`Ctrl` + `c`) received, exiting...");
-        sigint_received_write.store(true, Ordering::Relaxed);
-    })
-    .context("Error setting Ctrl-C handler")?;
+        // Keep handling signals (rather than letting them kill the script) while it exits
+        future::pending::<()>().await;
+    });
 
-    let sigint_checker = move || -> Result<()> {
-        if sigint_received.load(Ordering::Relaxed) {
-            bail!("Stopping script due to SIGINT")
-        } else {
-            Ok(())
-        }
-    };
+    Ok(cancellation)
+}
+
+/// Future that completes with the name of a signal once it's received.
+type SignalFuture = Pin<Box<dyn Future<Output = &'static str> + Send>>;
+
+/// Listen for the signals asking the script to exit:
+/// SIGINT (`Ctrl` + `c`), SIGTERM, and SIGHUP (the terminal closing).
+#[cfg(unix)]
+fn exit_signals() -> Result<Vec<SignalFuture>> {
+    use tokio::signal::unix::{SignalKind, signal};
+
+    let signals = [
+        (SignalKind::interrupt(), "SIGINT (`Ctrl` + `c`)"),
+        (SignalKind::terminate(), "SIGTERM"),
+        (SignalKind::hangup(), "SIGHUP"),
+    ];
+    signals
+        .into_iter()
+        .map(|(kind, name)| {
+            let mut signal =
+                signal(kind).with_context(|| format!("Error setting {name} handler"))?;
+            Ok(Box::pin(async move {
+                signal.recv().await;
+                name
+            }) as SignalFuture)
+        })
+        .collect()
+}
 
-    Ok(sigint_checker)
+/// Listen for the console events asking the script to exit:
+/// `Ctrl` + `c`, `Ctrl` + `Break`, and the console window closing.
+#[cfg(windows)]
+fn exit_signals() -> Result<Vec<SignalFuture>> {
+    use tokio::signal::windows::{ctrl_break, ctrl_c, ctrl_close};
+
+    let mut ctrl_c = ctrl_c().context("Error setting Ctrl-C handler")?;
+    let mut ctrl_break = ctrl_break().context("Error setting Ctrl-Break handler")?;
+    let mut ctrl_close = ctrl_close().context("Error setting console close handler")?;
+    Ok(vec![
+        Box::pin(async move {
+            ctrl_c.recv().await;
+            "`Ctrl` + `c`"
+        }) as SignalFuture,
+        Box::pin(async move {
+            ctrl_break.recv().await;
+            "`Ctrl` + `Break`"
+        }) as SignalFuture,
+        Box::pin(async move {
+            ctrl_close.recv().await;
+            "Console window close"
+        }) as SignalFuture,
+    ])
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn exit_signals_cancel_the_run() {
+        // Raised one at a time, since every cancellation set up hears every signal
+        for (signal, name) in [(libc::SIGTERM, "SIGTERM"), (libc::SIGHUP, "SIGHUP")] {
+            let cancellation = setup_sigint_checker().unwrap();
+            assert!(cancellation.check().is_ok());
+
+            // SAFETY: `raise` has no preconditions, and the signal is handled rather than fatal
+            assert_eq!(unsafe { libc::raise(signal) }, 0);
+            tokio::time::timeout(Duration::from_secs(5), cancellation.cancelled())
+                .await
+                .unwrap_or_else(|_| panic!("{name} should cancel the run"));
+            assert_eq!(cancellation.reason().as_deref(), Some(name));
+        }
+    }
 }
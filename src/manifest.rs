@@ -0,0 +1,348 @@
+//! Keeping a record of every icon downloaded, so what was saved where can be audited (or undone).
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write as _};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard, OnceLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context as _, Result, bail};
+use log::*;
+use regex::Regex;
+
+use crate::names::unescape_json;
+use crate::report::json_string;
+
+/// Filename of the manifest, within the platform's cache directory
+pub const FILENAME: &str = "manifest.json";
+
+/// Extension added to the manifest for the file each download is appended to
+/// until the manifest is next rewritten
+const JOURNAL_EXTENSION: &str = ".journal";
+
+/// Extension added to the manifest while it's being rewritten
+const TEMP_EXTENSION: &str = ".tmp";
+
+/// Extension added to a damaged manifest when it's set aside
+const CORRUPT_EXTENSION: &str = ".corrupt";
+
+/// Start of the manifest, before its entries
+const HEADER: &str = "{\"downloads\":[";
+
+/// End of the manifest, after its entries
+const FOOTER: &str = "]}";
+
+/// A single icon that was downloaded and saved.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ManifestEntry {
+    /// Steam game ID
+    pub game_id: String,
+
+    /// Where the icon was saved
+    pub icon_path: PathBuf,
+
+    /// Size of the icon as saved
+    pub bytes: u64,
+
+    /// SHA-1 hash of the icon as saved (in lowercase hex)
+    pub sha1: String,
+
+    /// Where the icon was downloaded from
+    pub url: String,
+
+    /// When the icon was saved (in seconds since the Unix epoch)
+    pub downloaded: u64,
+
+    /// `ETag` the CDN gave the icon, if any
+    pub etag: Option<String>,
+
+    /// `Last-Modified` date the CDN gave the icon, if any
+    pub last_modified: Option<String>,
+}
+
+/// Every icon downloaded, by where it was saved (so downloading one again replaces its entry).
+pub struct Manifest {
+    /// File the manifest is kept in, if it's being kept at all
+    path: Option<PathBuf>,
+
+    /// Icons downloaded so far, by where they were saved
+    entries: Mutex<BTreeMap<PathBuf, ManifestEntry>>,
+}
+
+impl Manifest {
+    /// Manifest kept in the file at `path`, starting with what's already in it
+    /// (including downloads from runs that stopped before rewriting it),
+    /// or a manifest that's never kept if there's no `path`.
+    /// A damaged file is set aside and a new one started, rather than stopping the run.
+    pub fn load(path: Option<PathBuf>) -> Self {
+        let Some(path) = path else {
+            return Self {
+                path: None,
+                entries: Mutex::new(BTreeMap::new()),
+            };
+        };
+
+        let mut rewrite = false;
+        let mut entries = read(&path).unwrap_or_else(|error| {
+            let backup_path = with_extension(&path, CORRUPT_EXTENSION);
+            warn!(
+                "Starting a new manifest of downloaded icons, since `{}` is damaged (moving it to \
+                 `{}`): {error:#}",
+                path.display(),
+                backup_path.display()
+            );
+            if let Err(error) = fs::rename(&path, &backup_path) {
+                warn!("Failed to move `{}`: {error}", path.display());
+            }
+            rewrite = true;
+            BTreeMap::new()
+        });
+
+        // Add any downloads that never made it into the manifest itself
+        match read_journal(&journal_path(&path)) {
+            Ok(journal) => {
+                rewrite |= !journal.is_empty();
+                entries.extend(
+                    journal
+                        .into_iter()
+                        .map(|entry| (entry.icon_path.clone(), entry)),
+                );
+            }
+            Err(error) => warn!("{error:#}"),
+        }
+
+        let manifest = Self {
+            path: Some(path),
+            entries: Mutex::new(entries),
+        };
+        if rewrite && let Err(error) = manifest.save() {
+            warn!("{error:#}");
+        }
+        manifest
+    }
+
+    /// Every icon downloaded so far, in order of where it was saved.
+    pub fn entries(&self) -> Vec<ManifestEntry> {
+        self.lock().values().cloned().collect()
+    }
+
+    /// The icon saved at `icon_path`, if it was recorded.
+    pub fn entry(&self, icon_path: &Path) -> Option<ManifestEntry> {
+        self.lock().get(icon_path).cloned()
+    }
+
+    /// Forget the icon saved at `icon_path` (e.g. once it's been removed again).
+    /// Only takes effect in the file once the manifest is saved.
+    pub fn remove(&self, icon_path: &Path) {
+        self.lock().remove(icon_path);
+    }
+
+    /// Record that an icon was downloaded and saved,
+    /// appending it to the journal straight away so it isn't lost if the script stops.
+    pub fn record(&self, entry: ManifestEntry) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+
+        let journal_path = journal_path(path);
+        let mut entries = self.lock();
+        append_line(&journal_path, &to_json(&entry)).with_context(|| {
+            format!(
+                "Failed to add `{}` to the manifest in `{}`",
+                entry.icon_path.display(),
+                journal_path.display()
+            )
+        })?;
+        entries.insert(entry.icon_path.clone(), entry);
+        Ok(())
+    }
+
+    /// Rewrite the manifest with everything recorded, then clear the journal.
+    pub fn save(&self) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+
+        let entries = self.lock();
+        write(path, entries.values())
+            .with_context(|| format!("Failed to save the manifest to `{}`", path.display()))?;
+
+        // Only once the manifest has everything is the journal no longer needed
+        let journal_path = journal_path(path);
+        match fs::remove_file(&journal_path) {
+            Err(error) if error.kind() != ErrorKind::NotFound => {
+                Err(error).with_context(|| format!("Failed to remove `{}`", journal_path.display()))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Lock the entries.
+    fn lock(&self) -> MutexGuard<'_, BTreeMap<PathBuf, ManifestEntry>> {
+        self.entries.lock().expect("entries lock poisoned")
+    }
+}
+
+impl ManifestEntry {
+    /// Entry for an icon saved just now.
+    pub fn new(game_id: &str, icon_path: &Path, bytes: u64, sha1: &str, url: &str) -> Self {
+        Self {
+            game_id: game_id.to_owned(),
+            icon_path: icon_path.to_owned(),
+            bytes,
+            sha1: sha1.to_owned(),
+            url: url.to_owned(),
+            downloaded: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            etag: None,
+            last_modified: None,
+        }
+    }
+}
+
+/// Read the entries from the manifest at `path` (none if there isn't one yet).
+fn read(path: &Path) -> Result<BTreeMap<PathBuf, ManifestEntry>> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(error) if error.kind() == ErrorKind::NotFound => return Ok(BTreeMap::new()),
+        Err(error) => return Err(error).context("Failed to read file"),
+    };
+
+    // Entries are written one per line between the header and footer
+    let mut lines = text.lines().map(str::trim).filter(|line| !line.is_empty());
+    if lines.next() != Some(HEADER) || lines.next_back() != Some(FOOTER) {
+        bail!("Unrecognized contents");
+    }
+    let mut entries = BTreeMap::new();
+    for (index, line) in lines.enumerate() {
+        let line = line.strip_suffix(',').unwrap_or(line);
+        let entry = parse_entry(line).with_context(|| format!("Invalid entry #{}", index + 1))?;
+        entries.insert(entry.icon_path.clone(), entry);
+    }
+    Ok(entries)
+}
+
+/// Read the entries from the journal at `path` (none if there isn't one).
+/// Invalid lines are skipped, since the last one may have been cut short when the script stopped.
+fn read_journal(path: &Path) -> Result<Vec<ManifestEntry>> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(error) if error.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(error) => {
+            return Err(error).with_context(|| format!("Failed to read `{}`", path.display()));
+        }
+    };
+
+    let mut entries = Vec::new();
+    for line in text.lines().filter(|line| !line.trim().is_empty()) {
+        match parse_entry(line.trim()) {
+            Ok(entry) => entries.push(entry),
+            Err(error) => warn!("Skipping invalid line in `{}`: {error:#}", path.display()),
+        }
+    }
+    Ok(entries)
+}
+
+/// Parse a single entry, as written by `to_json`.
+fn parse_entry(line: &str) -> Result<ManifestEntry> {
+    // Only ever written by `to_json`, so the fields are always in the same order
+    // (with the CDN's validators, which older entries don't have, only there if it gave any)
+    static ENTRY_REGEX: OnceLock<Regex> = OnceLock::new();
+    let entry_regex = ENTRY_REGEX.get_or_try_init(|| {
+        let string = r#""((?:[^"\\]|\\.)*)""#;
+        Regex::new(&format!(
+            concat!(
+                r#"^\{{"game_id":{string},"icon_path":{string},"bytes":(\d+),"#,
+                r#""sha1":"([0-9a-f]{{40}})","url":{string},"downloaded":(\d+)"#,
+                r#"(?:,"etag":{string})?(?:,"last_modified":{string})?\}}$"#,
+            ),
+            string = string
+        ))
+    })?;
+    let Some(captures) = entry_regex.captures(line) else {
+        bail!("Unrecognized entry `{line}`");
+    };
+    Ok(ManifestEntry {
+        game_id: unescape_json(&captures[1]),
+        icon_path: PathBuf::from(unescape_json(&captures[2])),
+        bytes: captures[3].parse().context("Invalid size")?,
+        sha1: captures[4].to_owned(),
+        url: unescape_json(&captures[5]),
+        downloaded: captures[6].parse().context("Invalid timestamp")?,
+        etag: captures.get(7).map(|etag| unescape_json(etag.as_str())),
+        last_modified: captures
+            .get(8)
+            .map(|last_modified| unescape_json(last_modified.as_str())),
+    })
+}
+
+/// Encode an entry as a single line of JSON.
+fn to_json(entry: &ManifestEntry) -> String {
+    let mut json = format!(
+        "{{\"game_id\":{},\"icon_path\":{},\"bytes\":{},\"sha1\":{},\"url\":{},\"downloaded\":{}",
+        json_string(&entry.game_id),
+        json_string(&entry.icon_path.to_string_lossy()),
+        entry.bytes,
+        json_string(&entry.sha1),
+        json_string(&entry.url),
+        entry.downloaded,
+    );
+    if let Some(etag) = &entry.etag {
+        let _ = write!(json, ",\"etag\":{}", json_string(etag));
+    }
+    if let Some(last_modified) = &entry.last_modified {
+        let _ = write!(json, ",\"last_modified\":{}", json_string(last_modified));
+    }
+    json.push('}');
+    json
+}
+
+/// Write `entries` to the manifest at `path`, creating its directory if needed.
+fn write<'a>(path: &Path, entries: impl IntoIterator<Item = &'a ManifestEntry>) -> Result<()> {
+    let mut json = String::from(HEADER);
+    for (index, entry) in entries.into_iter().enumerate() {
+        json.push_str(if index > 0 { ",\n" } else { "\n" });
+        json.push_str(&to_json(entry));
+    }
+    let _ = write!(json, "\n{FOOTER}\n");
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).context("Failed to create directory")?;
+    }
+
+    // Written in full to a temporary file first, so the manifest is never left half-written
+    let temp_path = with_extension(path, TEMP_EXTENSION);
+    fs::write(&temp_path, json).context("Failed to write temporary file")?;
+    fs::rename(&temp_path, path).context("Failed to move temporary file into place")
+}
+
+/// Append `line` to the file at `path`, making sure it's on disk before returning.
+fn append_line(path: &Path, line: &str) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).context("Failed to create directory")?;
+    }
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .context("Failed to open file")?;
+    file.write_all(format!("{line}\n").as_bytes())
+        .context("Failed to write to file")?;
+    file.sync_data().context("Failed to flush file")
+}
+
+/// Path to the journal for the manifest at `path`.
+fn journal_path(path: &Path) -> PathBuf {
+    with_extension(path, JOURNAL_EXTENSION)
+}
+
+/// `path` with `extension` added to the end (keeping any it already has).
+fn with_extension(path: &Path, extension: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(extension);
+    path.into()
+}
@@ -0,0 +1,140 @@
+//! Refreshing icons once they're older than a maximum age (`--max-age`),
+//! since games' icons occasionally change (e.g. when a game is rebranded).
+
+use std::time::{Duration, SystemTime};
+
+use anyhow::{Context as _, Result, bail};
+
+/// Units durations can be given in, by their names, in seconds
+/// (months and years are their average lengths, as with `humantime`).
+const UNITS: [(&[&str], u64); 7] = [
+    (&["s", "sec", "secs", "second", "seconds"], 1),
+    (&["m", "min", "mins", "minute", "minutes"], 60),
+    (&["h", "hr", "hrs", "hour", "hours"], 60 * 60),
+    (&["d", "day", "days"], 24 * 60 * 60),
+    (&["w", "week", "weeks"], 7 * 24 * 60 * 60),
+    (&["M", "month", "months"], 2_630_016),
+    (&["y", "year", "years"], 31_557_600),
+];
+
+/// Parse a duration like `90d`, `12 hours`, or `1w 3d` (numbers, each followed by a unit,
+/// optionally separated by spaces), which must be longer than zero.
+pub fn parse(value: &str) -> Result<Duration> {
+    let mut seconds = 0u64;
+    let mut rest = value.trim();
+    if rest.is_empty() {
+        bail!("Missing duration (e.g. `90d`)");
+    }
+    while !rest.is_empty() {
+        let digits = rest.len() - rest.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+        if digits == 0 {
+            bail!("Expected a number at `{rest}` in `{value}`");
+        }
+        let (number, after_number) = rest.split_at(digits);
+        let number: u64 = number
+            .parse()
+            .with_context(|| format!("`{number}` is too large"))?;
+
+        let after_number = after_number.trim_start();
+        let unit_len = after_number.len()
+            - after_number
+                .trim_start_matches(|c: char| c.is_ascii_alphabetic())
+                .len();
+        let (unit, after_unit) = after_number.split_at(unit_len);
+        let Some(&(_, unit_seconds)) = UNITS.iter().find(|(names, _)| names.contains(&unit)) else {
+            match unit {
+                "" => bail!("Missing unit after `{number}` in `{value}` (e.g. `d` for days)"),
+                unit => {
+                    bail!("Unknown unit `{unit}` in `{value}` (expected s, m, h, d, w, M, or y)")
+                }
+            }
+        };
+        seconds = number
+            .checked_mul(unit_seconds)
+            .and_then(|part| seconds.checked_add(part))
+            .with_context(|| format!("`{value}` is too long"))?;
+        rest = after_unit.trim_start();
+    }
+
+    if seconds == 0 {
+        bail!("`{value}` must be longer than zero");
+    }
+    Ok(Duration::from_secs(seconds))
+}
+
+/// Whether something last modified at `modified` is older than `max_age` at `now`
+/// (anything modified in the future is never stale).
+pub fn is_stale(modified: SystemTime, now: SystemTime, max_age: Duration) -> bool {
+    now.duration_since(modified).is_ok_and(|age| age > max_age)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY: u64 = 24 * 60 * 60;
+
+    #[test]
+    fn parses_durations() {
+        for (value, seconds) in [
+            ("90d", 90 * DAY),
+            ("12 hours", 12 * 60 * 60),
+            ("1w 3d", 10 * DAY),
+            ("1w3d", 10 * DAY),
+            (" 30 s ", 30),
+            ("1M", 2_630_016),
+            ("1y", 31_557_600),
+            ("1m", 60),
+        ] {
+            assert_eq!(
+                parse(value).unwrap(),
+                Duration::from_secs(seconds),
+                "{value}"
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_durations() {
+        for value in [
+            "",
+            " ",
+            "d",
+            "90",
+            "90 fortnights",
+            "0d",
+            "0s 0m",
+            "-1d",
+            "1.5d",
+            "99999999999999999999d",
+            "999999999999y",
+        ] {
+            assert!(parse(value).is_err(), "{value}");
+        }
+    }
+
+    #[test]
+    fn stale_only_once_older_than_max_age() {
+        let max_age = Duration::from_secs(90 * DAY);
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1000 * DAY);
+        let second = Duration::from_secs(1);
+
+        assert!(!is_stale(now, now, max_age));
+        assert!(!is_stale(now - max_age + second, now, max_age));
+        assert!(!is_stale(now - max_age, now, max_age));
+        assert!(is_stale(
+            now - max_age - Duration::from_nanos(1),
+            now,
+            max_age
+        ));
+        assert!(is_stale(now - max_age - second, now, max_age));
+        assert!(is_stale(SystemTime::UNIX_EPOCH, now, max_age));
+    }
+
+    #[test]
+    fn modified_in_the_future_is_never_stale() {
+        let max_age = Duration::from_secs(1);
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(DAY);
+        assert!(!is_stale(now + Duration::from_secs(DAY), now, max_age));
+    }
+}
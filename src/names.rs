@@ -0,0 +1,194 @@
+//! Naming games in the logs and report, so they're more than just a number.
+
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+
+use anyhow::{Context as _, Result};
+use futures_util::{StreamExt as _, stream};
+use log::*;
+use regex::Regex;
+use reqwest::Client;
+
+use crate::download::{self, HttpSettings};
+use crate::rate_limit::RateLimiter;
+
+/// Names of games, from what's on disk and (for games that need it) the Steam store.
+pub struct GameNames {
+    /// Names found without making any requests (e.g. in app manifests), by game ID
+    known: HashMap<String, String>,
+
+    /// Client for looking up names in the store, if allowed
+    client: Option<Client>,
+
+    /// Results of previous lookups (including failures), by game ID
+    cache: Mutex<HashMap<String, Option<String>>>,
+
+    /// Spaces out requests to the store
+    rate_limiter: RateLimiter,
+}
+
+impl GameNames {
+    /// Names starting with the `known` ones,
+    /// looking up any others in the store when asked to if `look_up`
+    /// (with requests made according to `settings` and spaced out by `rate_limiter`).
+    pub fn new(
+        known: HashMap<String, String>,
+        look_up: bool,
+        settings: &HttpSettings,
+        rate_limiter: RateLimiter,
+    ) -> Result<Self> {
+        Ok(Self {
+            known,
+            client: look_up
+                .then(|| download::http_client(settings))
+                .transpose()?,
+            cache: Mutex::new(HashMap::new()),
+            rate_limiter,
+        })
+    }
+
+    /// Name of `game_id`, if it's already known (this never makes a request).
+    pub fn get(&self, game_id: &str) -> Option<String> {
+        if let Some(name) = self.known.get(game_id) {
+            return Some(name.clone());
+        }
+        self.cache
+            .lock()
+            .expect("cache lock poisoned")
+            .get(game_id)
+            .cloned()
+            .flatten()
+    }
+
+    /// Look up the names of `game_ids` that aren't known yet, `concurrency` at a time.
+    /// Failures are only logged, since names are just for show.
+    pub async fn look_up<'a>(
+        &self,
+        game_ids: impl IntoIterator<Item = &'a str>,
+        concurrency: usize,
+    ) {
+        let Some(client) = &self.client else {
+            return;
+        };
+
+        let mut wanted: Vec<_> = game_ids
+            .into_iter()
+            .filter(|game_id| {
+                !self.known.contains_key(*game_id)
+                    && !self
+                        .cache
+                        .lock()
+                        .expect("cache lock poisoned")
+                        .contains_key(*game_id)
+            })
+            .collect();
+        wanted.sort_unstable();
+        wanted.dedup();
+        if wanted.is_empty() {
+            return;
+        }
+
+        debug!("Looking up the names of {} game(s)", wanted.len());
+        let mut lookups = stream::iter(wanted)
+            .map(|game_id| async move {
+                let name = fetch_name(client, &self.rate_limiter, game_id).await;
+                (game_id, name)
+            })
+            .buffer_unordered(concurrency);
+        while let Some((game_id, result)) = lookups.next().await {
+            let name = match result {
+                Ok(name) => name,
+                Err(error) => {
+                    warn!("Failed to look up the name of game #{game_id}: {error:#}");
+                    None
+                }
+            };
+            self.cache
+                .lock()
+                .expect("cache lock poisoned")
+                .insert(game_id.to_owned(), name);
+        }
+    }
+
+    /// `game_id` as it should appear in logs, e.g. `Warframe (230410)` or `game #230410`.
+    pub fn describe(&self, game_id: &str) -> String {
+        describe(game_id, self.get(game_id).as_deref())
+    }
+}
+
+/// `game_id` as it should appear in logs, with its name if known
+/// (e.g. `Warframe (230410)`, or `game #230410` without one).
+pub fn describe(game_id: &str, name: Option<&str>) -> String {
+    match name {
+        Some(name) => format!("{name} ({game_id})"),
+        None => format!("game #{game_id}"),
+    }
+}
+
+/// Fetch the name of `game_id` from the store, if it has one.
+async fn fetch_name(
+    client: &Client,
+    rate_limiter: &RateLimiter,
+    game_id: &str,
+) -> Result<Option<String>> {
+    // Only ever insert digits into the URL (non-Steam games aren't in the store anyway)
+    if game_id.is_empty() || !game_id.bytes().all(|byte| byte.is_ascii_digit()) {
+        return Ok(None);
+    }
+
+    let url =
+        format!("https://store.steampowered.com/api/appdetails?appids={game_id}&filters=basic");
+    let body = rate_limiter
+        .send(|| client.get(&url))
+        .await
+        .and_then(|response| Ok(response.error_for_status()?))
+        .context("Request failed")?
+        .text()
+        .await
+        .context("Failed to read response")?;
+
+    // Pull out (naively) the first name from the JSON response,
+    // which is the game's own (games missing from the store have none)
+    static NAME_REGEX: OnceLock<Regex> = OnceLock::new();
+    let name_regex =
+        NAME_REGEX.get_or_try_init(|| Regex::new(r#""name"\s*:\s*"((?:[^"\\]|\\.)*)""#))?;
+    Ok(name_regex
+        .captures(&body)
+        .map(|captures| unescape_json(&captures[1]))
+        .filter(|name| !name.trim().is_empty()))
+}
+
+/// Decode the escapes in the contents of a JSON string.
+/// Invalid escapes are kept as they are.
+pub fn unescape_json(text: &str) -> String {
+    // Work in UTF-16, since that's what `\u` escapes (including surrogate pairs) are in
+    let mut units = Vec::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(char) = chars.next() {
+        if char != '\\' {
+            units.extend(char.encode_utf16(&mut [0; 2]).iter());
+            continue;
+        }
+        match chars.next() {
+            Some('b') => units.push(0x08),
+            Some('f') => units.push(0x0c),
+            Some('n') => units.push(u16::from(b'\n')),
+            Some('r') => units.push(u16::from(b'\r')),
+            Some('t') => units.push(u16::from(b'\t')),
+            Some('u') => {
+                let hex: String = chars.clone().take(4).collect();
+                let is_hex = hex.len() == 4 && hex.bytes().all(|byte| byte.is_ascii_hexdigit());
+                match u16::from_str_radix(&hex, 16) {
+                    Ok(unit) if is_hex => {
+                        units.push(unit);
+                        chars.nth(3);
+                    }
+                    _ => units.extend("\\u".encode_utf16()),
+                }
+            }
+            Some(escaped) => units.extend(escaped.encode_utf16(&mut [0; 2]).iter()),
+            None => units.push(u16::from(b'\\')),
+        }
+    }
+    String::from_utf16_lossy(&units)
+}
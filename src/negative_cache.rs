@@ -0,0 +1,173 @@
+//! Remembering which games have no icon on the CDN, so they aren't requested on every run.
+
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard, OnceLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context as _, Result, bail};
+use log::*;
+use regex::Regex;
+
+/// Filename of the cache, within the platform's cache directory
+pub const FILENAME: &str = "negative-cache.json";
+
+/// How long a game is remembered as having no icon before it's checked again
+pub const TTL: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// Extension added to the cache while it's being written
+const TEMP_EXTENSION: &str = ".tmp";
+
+/// Games whose icons the CDN said don't exist, and when it said so.
+pub struct NegativeCache {
+    /// File the cache is kept in, if it's being used at all
+    path: Option<PathBuf>,
+
+    /// When each game was last found to have no icon (in seconds since the Unix epoch),
+    /// by game ID
+    entries: Mutex<HashMap<String, u64>>,
+
+    /// Whether anything has been added since the cache was loaded or saved
+    changed: Mutex<bool>,
+}
+
+impl NegativeCache {
+    /// Cache kept in the file at `path`, starting with what's already in it (if anything),
+    /// or a cache that's never used if there's no `path`.
+    /// A damaged file is only warned about, since it's just a cache.
+    pub fn load(path: Option<PathBuf>) -> Self {
+        let entries = match &path {
+            Some(path) => read(path).unwrap_or_else(|error| {
+                warn!(
+                    "Ignoring the cache of games with no icon in `{}`: {error:#}",
+                    path.display()
+                );
+                HashMap::new()
+            }),
+            None => HashMap::new(),
+        };
+        Self {
+            path,
+            entries: Mutex::new(entries),
+            changed: Mutex::new(false),
+        }
+    }
+
+    /// How long ago `game_id` was found to have no icon,
+    /// if that was recent enough to not check again yet.
+    pub fn known_missing(&self, game_id: &str) -> Option<Duration> {
+        self.path.as_ref()?;
+        let checked = *self.lock().get(game_id)?;
+        let age = now().saturating_sub(checked);
+        (age < TTL.as_secs()).then(|| Duration::from_secs(age))
+    }
+
+    /// Remember that the CDN said `game_id` has no icon.
+    /// This should only be called for genuine 404s, never for failed requests.
+    pub fn record_missing(&self, game_id: &str) {
+        // Only Steam games (with numeric IDs) have icons on the CDN to begin with
+        if self.path.is_none() || !game_id.bytes().all(|byte| byte.is_ascii_digit()) {
+            return;
+        }
+        self.lock().insert(game_id.to_owned(), now());
+        *self.changed.lock().expect("changed lock poisoned") = true;
+    }
+
+    /// Write the cache back to its file if anything was added,
+    /// leaving out games that are due to be checked again.
+    pub fn save(&self) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let mut changed = self.changed.lock().expect("changed lock poisoned");
+        if !*changed {
+            return Ok(());
+        }
+
+        let now = now();
+        let mut entries: Vec<_> = self
+            .lock()
+            .iter()
+            .filter(|&(_, &checked)| now.saturating_sub(checked) < TTL.as_secs())
+            .map(|(game_id, &checked)| (game_id.clone(), checked))
+            .collect();
+        entries.sort_unstable();
+        write(path, &entries)
+            .with_context(|| format!("Failed to save the cache to `{}`", path.display()))?;
+        *changed = false;
+        Ok(())
+    }
+
+    /// Lock the entries.
+    fn lock(&self) -> MutexGuard<'_, HashMap<String, u64>> {
+        self.entries.lock().expect("entries lock poisoned")
+    }
+}
+
+/// Remove the cache file at `path`, if there is one.
+pub fn clear(path: &Path) -> Result<()> {
+    match fs::remove_file(path) {
+        Err(error) if error.kind() != ErrorKind::NotFound => {
+            Err(error).with_context(|| format!("Failed to remove `{}`", path.display()))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Read the entries from the cache file at `path` (none if there isn't one yet).
+fn read(path: &Path) -> Result<HashMap<String, u64>> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(error) if error.kind() == ErrorKind::NotFound => return Ok(HashMap::new()),
+        Err(error) => return Err(error).context("Failed to read file"),
+    };
+
+    // Pull out (naively) the game IDs and timestamps from the JSON,
+    // which is only ever written by `write`
+    static ENTRY_REGEX: OnceLock<Regex> = OnceLock::new();
+    let entry_regex = ENTRY_REGEX.get_or_try_init(|| Regex::new(r#""(\d+)"\s*:\s*(\d+)"#))?;
+    if !text.trim_start().starts_with("{\"missing\":") {
+        bail!("Unrecognized contents");
+    }
+    entry_regex
+        .captures_iter(&text)
+        .map(|captures| {
+            let checked = captures[2].parse().context("Invalid timestamp")?;
+            Ok((captures[1].to_owned(), checked))
+        })
+        .collect()
+}
+
+/// Write `entries` (game IDs and timestamps) to the cache file at `path`,
+/// creating its directory if needed.
+fn write(path: &Path, entries: &[(String, u64)]) -> Result<()> {
+    let mut json = String::from("{\"missing\":{");
+    for (index, (game_id, checked)) in entries.iter().enumerate() {
+        if index > 0 {
+            json.push(',');
+        }
+        let _ = write!(json, "\"{game_id}\":{checked}");
+    }
+    json.push_str("}}\n");
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).context("Failed to create directory")?;
+    }
+
+    // Written in full to a temporary file first, so the cache is never left half-written
+    let mut temp_path = path.as_os_str().to_owned();
+    temp_path.push(TEMP_EXTENSION);
+    fs::write(&temp_path, json).context("Failed to write temporary file")?;
+    fs::rename(&temp_path, path).context("Failed to move temporary file into place")
+}
+
+/// Seconds since the Unix epoch.
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
@@ -0,0 +1,61 @@
+//! Naming the games added to Steam from elsewhere ("Add a Non-Steam Game"),
+//! as recorded in each user's `shortcuts.vdf`.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context as _, Result};
+use log::*;
+
+use crate::binary_vdf;
+
+/// Flag Steam sets in the lower half of non-Steam games' 64-bit IDs
+const GAME_ID_FLAG: u64 = 0x02000000;
+
+/// Names of the non-Steam games added to the Steam install at `install_dir` by any user,
+/// keyed by the game ID used in their `steam://rungameid/` URLs.
+pub fn game_names(install_dir: &Path) -> HashMap<String, String> {
+    let mut names = HashMap::new();
+    let Ok(users) = install_dir.join("userdata").read_dir() else {
+        return names;
+    };
+    for user in users.flatten() {
+        let path = user.path().join("config").join("shortcuts.vdf");
+        if !path.is_file() {
+            continue;
+        }
+        match read_shortcuts(&path) {
+            Ok(shortcuts) => names.extend(shortcuts),
+            Err(error) => warn!("Failed to read `{}`: {error:#}", path.display()),
+        }
+    }
+    names
+}
+
+/// Read the game IDs and names of the non-Steam games listed in the `shortcuts.vdf` at `path`.
+fn read_shortcuts(path: &Path) -> Result<Vec<(String, String)>> {
+    let bytes = fs::read(path).context("Failed to read file")?;
+    let root = binary_vdf::parse(&bytes)?;
+    let shortcuts = root.get("shortcuts").context("Missing `shortcuts`")?;
+
+    // Shortcuts are numbered, and older ones may not have an app ID
+    let mut names = Vec::new();
+    for (_, shortcut) in shortcuts.entries() {
+        let (Some(app_id), Some(name)) = (
+            shortcut.get("appid").and_then(|app_id| app_id.as_str()),
+            shortcut.get("AppName").and_then(|name| name.as_str()),
+        ) else {
+            continue;
+        };
+
+        // App IDs are stored signed, but the game ID uses them as unsigned
+        let app_id = app_id
+            .parse::<i32>()
+            .with_context(|| format!("Invalid app ID `{app_id}`"))?;
+        let game_id = (u64::from(app_id as u32) << 32) | GAME_ID_FLAG;
+        names.push((game_id.to_string(), name.to_owned()));
+    }
+
+    Ok(names)
+}
@@ -0,0 +1,450 @@
+//! Helpers for comparing paths the way the platform's filesystem does,
+//! expanding the variables Windows allows in them (and getting past its path length limit),
+//! making sure untrusted filenames stay where they're put,
+//! and checking that directories (and files) can be written to.
+
+use std::fs::{self, File};
+use std::hash::{BuildHasher as _, RandomState};
+use std::path::{Path, PathBuf};
+use std::{io, process};
+
+use anyhow::{Result, bail};
+
+/// Whether `a` and `b` refer to the same directory.
+/// Trailing (and repeated) separators are ignored,
+/// and on Windows, paths are compared case-insensitively with either slash style.
+/// If both directories exist, short (8.3) names and symlinks are resolved as well.
+pub fn same_dir(a: &Path, b: &Path) -> bool {
+    if normalize(a) == normalize(b) {
+        return true;
+    }
+
+    // Let the filesystem decide if the paths look different but may not be
+    match (fs::canonicalize(a), fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => normalize(&a) == normalize(&b),
+        _ => false,
+    }
+}
+
+/// Split a path into the components that matter when comparing it.
+/// A leading separator is kept as an empty component so absolute paths stay absolute.
+fn normalize(path: &Path) -> Vec<String> {
+    let path = path.to_string_lossy();
+    let path = if cfg!(windows) {
+        path.to_lowercase()
+    } else {
+        path.into_owned()
+    };
+
+    path.split(|char| char == '/' || (cfg!(windows) && char == '\\'))
+        .enumerate()
+        .filter(|(index, component)| *index == 0 || !matches!(*component, "" | "."))
+        .map(|(_, component)| component.to_owned())
+        .collect()
+}
+
+/// Expand `%VAR%`-style environment variable references in `path`,
+/// looking up each variable's value with `lookup`.
+/// `%%` stands for a literal `%`, and a `%` without a closing `%` is kept as is.
+pub fn expand_env_vars(path: &str, lookup: impl Fn(&str) -> Option<String>) -> Result<String> {
+    let mut expanded = String::with_capacity(path.len());
+    let mut rest = path;
+    while let Some(start) = rest.find('%') {
+        expanded.push_str(&rest[..start]);
+        let after_start = &rest[start + 1..];
+        let Some(end) = after_start.find('%') else {
+            expanded.push('%');
+            rest = after_start;
+            break;
+        };
+
+        let name = &after_start[..end];
+        if name.is_empty() {
+            expanded.push('%');
+        } else {
+            match lookup(name) {
+                Some(value) => expanded.push_str(&value),
+                None => bail!("Unknown environment variable `%{name}%`"),
+            }
+        }
+        rest = &after_start[end + 1..];
+    }
+    expanded.push_str(rest);
+
+    Ok(expanded)
+}
+
+/// `path` with `suffix` added to the end of its filename (e.g. `Game.url` to `Game.url.bak`).
+pub fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(suffix);
+    PathBuf::from(path)
+}
+
+/// Turn `name` into something usable as a filename on any platform,
+/// dropping reserved characters (and trailing dots and spaces, which Windows ignores).
+pub fn sanitize_filename(name: &str) -> String {
+    let filename: String = name
+        .chars()
+        .filter(|char| !char.is_control() && !r#"<>:"/\|?*"#.contains(*char))
+        .collect();
+    filename.trim().trim_end_matches(['.', ' ']).to_owned()
+}
+
+/// Make sure `filename` is a single, ordinary path component ending in `extension`,
+/// so joining it onto a directory can't escape that directory
+/// (or open a device instead of a file on Windows).
+pub fn ensure_plain_filename(filename: &str, extension: &str) -> Result<()> {
+    if matches!(filename, "" | "." | "..") {
+        bail!("`{filename}` is not a filename");
+    } else if filename.contains(['/', '\\', ':', '\0']) {
+        bail!("Filename `{filename}` contains a path separator or other reserved character");
+    } else if filename.len() <= extension.len() || !filename.ends_with(extension) {
+        bail!("Filename `{filename}` doesn't have the expected `{extension}` extension");
+    } else if is_reserved_name(filename) {
+        bail!("Filename `{filename}` is reserved for a device on Windows");
+    }
+
+    Ok(())
+}
+
+/// Whether Windows treats `filename` as a device (e.g. `CON` or `nul.ico`),
+/// going by the part before its first dot, whatever the extension.
+fn is_reserved_name(filename: &str) -> bool {
+    const RESERVED_NAMES: [&str; 4] = ["CON", "PRN", "AUX", "NUL"];
+    const NUMBERED_PREFIXES: [&str; 2] = ["COM", "LPT"];
+
+    // Windows ignores trailing spaces before the extension
+    let stem = filename.split('.').next().unwrap_or_default().trim_end();
+    RESERVED_NAMES
+        .iter()
+        .any(|reserved| stem.eq_ignore_ascii_case(reserved))
+        || NUMBERED_PREFIXES.iter().any(|prefix| {
+            stem.len() == 4
+                && stem.is_char_boundary(3)
+                && stem[..3].eq_ignore_ascii_case(prefix)
+                && matches!(stem.as_bytes()[3], b'1'..=b'9')
+        })
+}
+
+/// `path` as Windows needs it to be opened when it's longer than `MAX_PATH` (260 characters),
+/// whether or not long paths are enabled system-wide (see [`extended_length_path`]).
+/// Only for handing to the filesystem, since it's no longer how the path was written.
+/// Paths are returned as they are on other platforms.
+pub fn to_long_path(path: &Path) -> PathBuf {
+    if !cfg!(windows) {
+        return path.to_owned();
+    }
+
+    // Windows stops tidying up paths (e.g. `..` and `/`) once they have the prefix, so do it first
+    let Ok(absolute) = std::path::absolute(path) else {
+        return path.to_owned();
+    };
+    match absolute.to_str().and_then(extended_length_path) {
+        Some(long_path) => PathBuf::from(long_path),
+        None => absolute,
+    }
+}
+
+/// Absolute Windows `path` with the extended-length prefix
+/// (`C:\dir` as `\\?\C:\dir`, and `\\server\share` as `\\?\UNC\server\share`),
+/// or `None` if it already has one (or is a device path), or isn't absolute.
+pub fn extended_length_path(path: &str) -> Option<String> {
+    if path.starts_with(r"\\?\") || path.starts_with(r"\\.\") {
+        return None;
+    }
+    if let Some(unc) = path.strip_prefix(r"\\") {
+        return Some(format!(r"\\?\UNC\{unc}"));
+    }
+    let is_drive =
+        matches!(path.as_bytes(), [letter, b':', b'\\', ..] if letter.is_ascii_alphabetic());
+    is_drive.then(|| format!(r"\\?\{path}"))
+}
+
+/// Make sure files can be created (and removed) in `dir`,
+/// by creating and removing a uniquely named empty file there.
+pub fn probe_writable(dir: &Path) -> io::Result<()> {
+    let unique = RandomState::new().hash_one(process::id());
+    let path = dir.join(format!(".write-test-{}-{unique:016x}", process::id()));
+    File::create_new(&path)?;
+    fs::remove_file(&path)
+}
+
+/// Let the owner write to the file at `path` if they can't, returning whether they couldn't.
+#[cfg(unix)]
+pub fn clear_read_only(path: &Path) -> io::Result<bool> {
+    use std::os::unix::fs::PermissionsExt as _;
+
+    let mut permissions = fs::metadata(path)?.permissions();
+    if permissions.mode() & 0o200 != 0 {
+        return Ok(false);
+    }
+    permissions.set_mode(permissions.mode() | 0o200);
+    fs::set_permissions(path, permissions)?;
+    Ok(true)
+}
+
+/// Clear the read-only attribute of the file at `path`, returning whether it was set.
+#[cfg(windows)]
+pub fn clear_read_only(path: &Path) -> io::Result<bool> {
+    let mut permissions = fs::metadata(path)?.permissions();
+    if !permissions.readonly() {
+        return Ok(false);
+    }
+    // Only clears the attribute on Windows (it's Unix where this would let anyone write)
+    #[allow(clippy::permissions_set_readonly_false)]
+    permissions.set_readonly(false);
+    fs::set_permissions(path, permissions)?;
+    Ok(true)
+}
+
+/// Leave the file at `path` alone, since there's no telling how it's made read-only here.
+#[cfg(not(any(unix, windows)))]
+pub fn clear_read_only(_path: &Path) -> io::Result<bool> {
+    Ok(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_plain_filenames() {
+        for filename in [
+            "a.ico",
+            "steam_icon_440.ico",
+            "..a.ico",
+            "CONSOLE.ico",
+            "COM0.ico",
+        ] {
+            assert!(
+                ensure_plain_filename(filename, ".ico").is_ok(),
+                "{filename}"
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_path_components() {
+        for filename in ["", ".", "..", "a/b.ico", "a\\b.ico", "C:x.ico", "../a.ico"] {
+            assert!(
+                ensure_plain_filename(filename, ".ico").is_err(),
+                "{filename}"
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_nul() {
+        assert!(ensure_plain_filename("a\0.ico", ".ico").is_err());
+    }
+
+    #[test]
+    fn rejects_missing_extension() {
+        for filename in ["a", "a.png", ".ico", "a.ico.png", "a.ICO"] {
+            assert!(
+                ensure_plain_filename(filename, ".ico").is_err(),
+                "{filename}"
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_reserved_device_names() {
+        for filename in [
+            "CON.ico",
+            "NUL.ico",
+            "nul.ico",
+            "Aux.ico",
+            "PRN.ico",
+            "COM1.ico",
+            "lpt9.ico",
+            "CON.x.ico",
+            "NUL .ico",
+        ] {
+            assert!(
+                ensure_plain_filename(filename, ".ico").is_err(),
+                "{filename}"
+            );
+        }
+    }
+
+    #[test]
+    fn compares_dirs_ignoring_separators() {
+        let cases = [
+            ("/steam/games", "/steam/games", true),
+            ("/steam/games", "/steam/games/", true),
+            ("/steam/games", "/steam//games", true),
+            ("/steam/games", "/steam/./games/", true),
+            ("/steam/games", "steam/games", false),
+            ("/steam/games", "/steam/game", false),
+            ("/steam/games", "/steam", false),
+        ];
+        for (a, b, same) in cases {
+            assert_eq!(same_dir(Path::new(a), Path::new(b)), same, "{a} and {b}");
+            assert_eq!(same_dir(Path::new(b), Path::new(a)), same, "{b} and {a}");
+        }
+    }
+
+    #[test]
+    fn compares_dirs_like_the_platform() {
+        let cases = [
+            (r"C:\Steam\games", r"c:\steam\GAMES", cfg!(windows)),
+            (r"C:\Steam\games", "C:/Steam/games/", cfg!(windows)),
+            (r"C:\Steam\games", r"C:\Steam\games\\", cfg!(windows)),
+        ];
+        for (a, b, same) in cases {
+            assert_eq!(same_dir(Path::new(a), Path::new(b)), same, "{a} and {b}");
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn compares_dirs_through_links() {
+        let dir = tempfile::tempdir().unwrap();
+        let games = dir.path().join("games");
+        let link = dir.path().join("link");
+        fs::create_dir(&games).unwrap();
+        std::os::unix::fs::symlink(&games, &link).unwrap();
+        assert!(same_dir(&games, &link));
+        assert!(!same_dir(&games, dir.path()));
+    }
+
+    /// Looks up the only variables that are set: `STEAM` and `EMPTY`.
+    fn lookup(name: &str) -> Option<String> {
+        match name {
+            "STEAM" => Some(r"C:\Steam".to_owned()),
+            "EMPTY" => Some(String::new()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn expands_env_vars() {
+        let cases = [
+            ("", ""),
+            (r"C:\games", r"C:\games"),
+            (r"%STEAM%\games", r"C:\Steam\games"),
+            (r"%STEAM%%STEAM%", r"C:\SteamC:\Steam"),
+            ("a%EMPTY%b", "ab"),
+            ("100%%", "100%"),
+            ("%%STEAM%%", "%STEAM%"),
+            ("100%", "100%"),
+            ("%STEAM", "%STEAM"),
+            (r"%STEAM%\100%", r"C:\Steam\100%"),
+        ];
+        for (path, expanded) in cases {
+            assert_eq!(expand_env_vars(path, lookup).unwrap(), expanded, "{path}");
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_env_vars() {
+        for path in ["%UNKNOWN%", r"%STEAM%\%UNKNOWN%"] {
+            assert!(expand_env_vars(path, lookup).is_err(), "{path}");
+        }
+    }
+
+    #[test]
+    fn adds_extended_length_prefix() {
+        let cases = [
+            (r"C:\x", Some(r"\\?\C:\x")),
+            (r"c:\Steam\games", Some(r"\\?\c:\Steam\games")),
+            (r"\\server\share\x", Some(r"\\?\UNC\server\share\x")),
+            (r"\\?\C:\x", None),
+            (r"\\?\UNC\server\share", None),
+            (r"\\.\pipe\x", None),
+            ("C:x", None),
+            (r"\x", None),
+            (r"x\y", None),
+            ("/x", None),
+            ("", None),
+        ];
+        for (path, long_path) in cases {
+            assert_eq!(extended_length_path(path).as_deref(), long_path, "{path}");
+        }
+    }
+
+    #[test]
+    fn leaves_long_paths_alone_elsewhere() {
+        let path = Path::new("games/x");
+        if cfg!(windows) {
+            assert!(to_long_path(path).to_string_lossy().starts_with(r"\\?\"));
+        } else {
+            assert_eq!(to_long_path(path), path);
+        }
+    }
+
+    /// Mark the file at `path` read-only.
+    fn set_read_only(path: &Path) {
+        let mut permissions = fs::metadata(path).unwrap().permissions();
+        permissions.set_readonly(true);
+        fs::set_permissions(path, permissions).unwrap();
+    }
+
+    #[cfg(any(unix, windows))]
+    #[test]
+    fn clears_read_only() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("icon.ico");
+        fs::write(&path, "icon").unwrap();
+        assert!(!clear_read_only(&path).unwrap());
+
+        set_read_only(&path);
+        assert!(clear_read_only(&path).unwrap());
+        assert!(!fs::metadata(&path).unwrap().permissions().readonly());
+        fs::write(&path, "replaced").unwrap();
+
+        // Already writable
+        assert!(!clear_read_only(&path).unwrap());
+    }
+
+    #[test]
+    fn fails_to_clear_read_only_of_missing_file() {
+        let dir = tempfile::TempDir::new().unwrap();
+        assert!(clear_read_only(&dir.path().join("missing.ico")).is_err());
+    }
+
+    /// Names of everything in `dir`.
+    fn dir_contents(dir: &Path) -> Vec<String> {
+        fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn probes_writable_dir_without_leaving_anything() {
+        let dir = tempfile::TempDir::new().unwrap();
+        probe_writable(dir.path()).unwrap();
+        probe_writable(dir.path()).unwrap();
+        assert!(dir_contents(dir.path()).is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn probes_read_only_dir() {
+        use std::os::unix::fs::PermissionsExt as _;
+
+        // Permissions don't apply to root
+        // SAFETY: `geteuid` has no preconditions and always succeeds
+        if unsafe { libc::geteuid() } == 0 {
+            return;
+        }
+        let dir = tempfile::TempDir::new().unwrap();
+        fs::set_permissions(dir.path(), fs::Permissions::from_mode(0o555)).unwrap();
+
+        let result = probe_writable(dir.path());
+
+        fs::set_permissions(dir.path(), fs::Permissions::from_mode(0o755)).unwrap();
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::PermissionDenied);
+        assert!(dir_contents(dir.path()).is_empty());
+    }
+
+    #[test]
+    fn probes_missing_dir() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let error = probe_writable(&dir.path().join("missing")).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::NotFound);
+    }
+}
@@ -0,0 +1,401 @@
+//! Reading the icons embedded in Windows executables (PE files).
+//!
+//! Icons are resources: each icon group (`RT_GROUP_ICON`) lists its images,
+//! which are stored as separate `RT_ICON` resources in the same format as images in ICO files.
+
+use std::collections::HashMap;
+use std::io::{Read, Seek, SeekFrom};
+
+use anyhow::{Context as _, Result, bail};
+
+use crate::ico;
+
+/// Type of resources holding icon images
+const RT_ICON: u32 = 3;
+
+/// Type of resources listing the images of an icon
+const RT_GROUP_ICON: u32 = 14;
+
+/// Index of the resource table in the optional header's data directories
+const RESOURCE_DIRECTORY: usize = 2;
+
+/// Size of each section header
+const SECTION_HEADER_LEN: usize = 40;
+
+/// Largest resource section read, which is far more than any executable's icons need
+const MAX_RESOURCE_SECTION_LEN: usize = 64 * 1024 * 1024;
+
+/// Size of each entry in an icon group
+const GROUP_ENTRY_LEN: usize = 14;
+
+/// Size of each entry in an ICO file's image directory
+const ICO_ENTRY_LEN: usize = 16;
+
+/// Resource table of an executable (and the rest of the section it's in).
+struct Resources {
+    /// Address the table is loaded at, relative to the image base
+    address: u32,
+
+    /// Contents of the section from the start of the table
+    data: Vec<u8>,
+}
+
+/// Entry in a resource directory.
+enum Node {
+    /// Nested directory, at this offset in the resource table
+    Directory(usize),
+
+    /// Resource data entry, at this offset in the resource table
+    Data(usize),
+}
+
+/// Icon group as an ICO file.
+struct Icon {
+    /// The ICO file
+    ico: Vec<u8>,
+
+    /// Size of its largest image (its area, then its bit depth)
+    largest: (u32, u16),
+}
+
+/// Extract the icon group with the largest image (the first of them, if several are as large)
+/// from the executable in `reader`, as an ICO file, or `None` if it has no icons.
+/// Only the headers and the resource section are read, so the rest of the file
+/// (which can be huge for games) doesn't need to be in memory.
+pub fn extract_icon(reader: &mut (impl Read + Seek)) -> Result<Option<Vec<u8>>> {
+    let Some(resources) = read_resources(reader)? else {
+        return Ok(None);
+    };
+
+    // Icon images are referred to by ID, using whichever language comes first
+    let mut images = HashMap::new();
+    for (id, node) in resources.of_type(RT_ICON)? {
+        if let Some(id) = id
+            && let Some(data) = resources.first_data(node)?
+        {
+            images.insert(id, data);
+        }
+    }
+
+    let mut best: Option<Icon> = None;
+    for (_, node) in resources.of_type(RT_GROUP_ICON)? {
+        let Some(group) = resources.first_data(node)? else {
+            continue;
+        };
+        let Some(icon) = build_ico(group, &images)? else {
+            continue;
+        };
+        if best.as_ref().is_none_or(|best| icon.largest > best.largest) {
+            best = Some(icon);
+        }
+    }
+
+    let Some(Icon { ico, .. }) = best else {
+        return Ok(None);
+    };
+    ico::validate(&ico).context("Icon extracted from executable isn't a valid ICO")?;
+    Ok(Some(ico))
+}
+
+/// Read the resource section of the executable in `reader`, or `None` if it has none.
+fn read_resources(reader: &mut (impl Read + Seek)) -> Result<Option<Resources>> {
+    let dos_header = read_at(reader, 0, 64).context("Missing DOS header")?;
+    if !dos_header.starts_with(b"MZ") {
+        bail!("Not a Windows executable (missing `MZ` signature)");
+    }
+    let pe_offset = u64::from(u32_at(&dos_header, 0x3c)?);
+
+    // The COFF header follows the PE signature, then the optional header and section table
+    let headers = read_at(reader, pe_offset, 24).context("Missing PE header")?;
+    if !headers.starts_with(b"PE\0\0") {
+        bail!("Not a Windows executable (missing `PE` signature)");
+    }
+    let section_count = usize::from(u16_at(&headers, 6)?);
+    let optional_header_len = usize::from(u16_at(&headers, 20)?);
+    let optional_header = read_at(reader, pe_offset + 24, optional_header_len)
+        .context("Optional header is truncated")?;
+
+    // 32 and 64-bit executables lay out the optional header differently
+    let directories_start = match u16_at(&optional_header, 0)? {
+        0x10b => 96,
+        0x20b => 112,
+        magic => bail!("Unsupported optional header type {magic:#06x}"),
+    };
+    let directory_count = u32_at(&optional_header, directories_start - 4)? as usize;
+    if directory_count <= RESOURCE_DIRECTORY {
+        return Ok(None);
+    }
+    let resource_address = u32_at(&optional_header, directories_start + RESOURCE_DIRECTORY * 8)?;
+    if resource_address == 0 {
+        return Ok(None);
+    }
+
+    // Find the section the resources are in
+    let sections = read_at(
+        reader,
+        pe_offset + 24 + optional_header_len as u64,
+        section_count * SECTION_HEADER_LEN,
+    )
+    .context("Section table is truncated")?;
+    for section in sections.chunks_exact(SECTION_HEADER_LEN) {
+        let virtual_len = u32_at(section, 8)?;
+        let address = u32_at(section, 12)?;
+        let raw_len = u32_at(section, 16)? as usize;
+        let raw_offset = u32_at(section, 20)?;
+        let section_end = address.saturating_add(virtual_len.max(raw_len as u32));
+        if !(address..section_end).contains(&resource_address) {
+            continue;
+        }
+
+        if raw_len > MAX_RESOURCE_SECTION_LEN {
+            bail!("Resource section is too large ({raw_len} bytes)");
+        }
+        let data =
+            read_at(reader, raw_offset.into(), raw_len).context("Resource section is truncated")?;
+        // Offsets within the resource table are from its start, which needn't be the section's
+        let data = data
+            .get((resource_address - address) as usize..)
+            .context("Resource table is outside of its section")?
+            .to_vec();
+        return Ok(Some(Resources {
+            address: resource_address,
+            data,
+        }));
+    }
+
+    bail!("Resource table isn't in any section");
+}
+
+impl Resources {
+    /// Resources of type `resource_type`, with their IDs (or `None` for named ones).
+    fn of_type(&self, resource_type: u32) -> Result<Vec<(Option<u32>, Node)>> {
+        // The root directory lists the types, each with a directory of the resources of that type
+        let node = self
+            .entries(0)?
+            .into_iter()
+            .find_map(|(id, node)| (id == Some(resource_type)).then_some(node));
+        match node {
+            Some(Node::Directory(offset)) => self.entries(offset),
+            _ => Ok(Vec::new()),
+        }
+    }
+
+    /// Entries of the resource directory at `offset`, in order,
+    /// with their IDs (or `None` for named ones, which come first).
+    fn entries(&self, offset: usize) -> Result<Vec<(Option<u32>, Node)>> {
+        let named_count = usize::from(u16_at(&self.data, offset + 12)?);
+        let id_count = usize::from(u16_at(&self.data, offset + 14)?);
+        let mut entries = Vec::with_capacity(named_count + id_count);
+        for index in 0..named_count + id_count {
+            let entry = offset + 16 + index * 8;
+            let id = u32_at(&self.data, entry)?;
+            let target = u32_at(&self.data, entry + 4)?;
+            let node = if target & 0x8000_0000 != 0 {
+                Node::Directory((target & 0x7fff_ffff) as usize)
+            } else {
+                Node::Data(target as usize)
+            };
+            let id = (id & 0x8000_0000 == 0).then_some(id);
+            entries.push((id, node));
+        }
+        Ok(entries)
+    }
+
+    /// Contents of the resource at `node`, or of the first resource within it
+    /// (e.g. the first language a resource is available in), if any.
+    fn first_data(&self, node: Node) -> Result<Option<&[u8]>> {
+        let offset = match node {
+            Node::Data(offset) => offset,
+            Node::Directory(offset) => {
+                // Resources are never nested more than a level below their name
+                match self.entries(offset)?.into_iter().next() {
+                    Some((_, Node::Data(offset))) => offset,
+                    _ => return Ok(None),
+                }
+            }
+        };
+
+        let address = u32_at(&self.data, offset)?;
+        let len = u32_at(&self.data, offset + 4)? as usize;
+        let start = address
+            .checked_sub(self.address)
+            .context("Resource is outside of the resource section")? as usize;
+        match self.data.get(start..start.saturating_add(len)) {
+            Some(data) => Ok(Some(data)),
+            None => bail!("Resource is outside of the resource section"),
+        }
+    }
+}
+
+/// Build an ICO file from an icon group and the icon images it refers to,
+/// or `None` if none of its images are there.
+fn build_ico(group: &[u8], images: &HashMap<u32, &[u8]>) -> Result<Option<Icon>> {
+    let count = usize::from(u16_at(group, 4)?);
+    let mut entries = Vec::with_capacity(count);
+    for index in 0..count {
+        let entry = group
+            .get(6 + index * GROUP_ENTRY_LEN..6 + (index + 1) * GROUP_ENTRY_LEN)
+            .context("Icon group is truncated")?;
+        let id = u32::from(u16_at(entry, 12)?);
+        if let Some(image) = images.get(&id).filter(|image| !image.is_empty()) {
+            entries.push((entry, *image));
+        }
+    }
+    if entries.is_empty() {
+        return Ok(None);
+    }
+
+    // Entries keep their dimensions, colors and bit depth, but point at where the image ends up
+    let mut offset = 6 + entries.len() * ICO_ENTRY_LEN;
+    let mut ico =
+        Vec::with_capacity(offset + entries.iter().map(|(_, image)| image.len()).sum::<usize>());
+    ico.extend_from_slice(&[0, 0, 1, 0]);
+    ico.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+    let mut largest = (0, 0);
+    for (entry, image) in &entries {
+        ico.extend_from_slice(&entry[..8]);
+        ico.extend_from_slice(&(image.len() as u32).to_le_bytes());
+        ico.extend_from_slice(&(offset as u32).to_le_bytes());
+        offset += image.len();
+
+        // Dimensions of 0 mean 256 pixels
+        let [width, height] = [entry[0], entry[1]].map(|side| match side {
+            0 => 256,
+            side => u32::from(side),
+        });
+        largest = largest.max((width * height, u16::from_le_bytes([entry[6], entry[7]])));
+    }
+    for (_, image) in &entries {
+        ico.extend_from_slice(image);
+    }
+    Ok(Some(Icon { ico, largest }))
+}
+
+/// Read `len` bytes at `offset` in `reader`.
+fn read_at(reader: &mut (impl Read + Seek), offset: u64, len: usize) -> Result<Vec<u8>> {
+    reader.seek(SeekFrom::Start(offset))?;
+    let mut bytes = vec![0; len];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+/// Little-endian `u16` at `offset` in `bytes`.
+fn u16_at(bytes: &[u8], offset: usize) -> Result<u16> {
+    match bytes.get(offset..offset + 2) {
+        Some(value) => Ok(u16::from_le_bytes([value[0], value[1]])),
+        None => bail!("Executable is truncated"),
+    }
+}
+
+/// Little-endian `u32` at `offset` in `bytes`.
+fn u32_at(bytes: &[u8], offset: usize) -> Result<u32> {
+    match bytes.get(offset..offset + 4) {
+        Some(value) => Ok(u32::from_le_bytes([value[0], value[1], value[2], value[3]])),
+        None => bail!("Executable is truncated"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::png;
+
+    /// Minimal 64-bit executable (written by `make_icon_exe.py`) with two icon groups:
+    /// #1 with a 1x1 image, and #2 with that image and a 2x2 one.
+    const FIXTURE: &[u8] = include_bytes!("../tests/fixtures/icon.exe");
+
+    /// Where the PE header starts in the fixture.
+    const PE_OFFSET: usize = 0x40;
+
+    /// Where the optional header starts in the fixture.
+    const OPTIONAL_HEADER_OFFSET: usize = PE_OFFSET + 24;
+
+    /// Where the (only) section header starts in the fixture.
+    const SECTION_HEADER_OFFSET: usize = OPTIONAL_HEADER_OFFSET + 240;
+
+    fn extract(exe: &[u8]) -> Result<Option<Vec<u8>>> {
+        extract_icon(&mut Cursor::new(exe))
+    }
+
+    /// The fixture with `bytes` written at `offset`.
+    fn patched(offset: usize, bytes: &[u8]) -> Vec<u8> {
+        let mut exe = FIXTURE.to_vec();
+        exe[offset..offset + bytes.len()].copy_from_slice(bytes);
+        exe
+    }
+
+    #[test]
+    fn extracts_largest_icon_group() {
+        let ico = extract(FIXTURE).unwrap().unwrap();
+        ico::validate(&ico).unwrap();
+        assert_eq!(u16::from_le_bytes([ico[4], ico[5]]), 2);
+        let png = ico::to_png(&ico).unwrap();
+        assert_eq!(png::dimensions(&png).unwrap(), (2, 2));
+    }
+
+    #[test]
+    fn rejects_missing_signatures() {
+        assert!(extract(b"").is_err());
+        assert!(extract(&patched(0, b"ZM")).is_err());
+        assert!(extract(&patched(PE_OFFSET, b"PE\0\x01")).is_err());
+        // PE header pointing past the end of the file
+        assert!(extract(&patched(0x3c, &0xffffu32.to_le_bytes())).is_err());
+    }
+
+    #[test]
+    fn rejects_unknown_optional_header() {
+        assert!(extract(&patched(OPTIONAL_HEADER_OFFSET, &0x107u16.to_le_bytes())).is_err());
+        // Optional header longer than the file
+        assert!(extract(&patched(PE_OFFSET + 20, &0xffffu16.to_le_bytes())).is_err());
+    }
+
+    #[test]
+    fn finds_nothing_without_resource_table() {
+        let directory_count = OPTIONAL_HEADER_OFFSET + 108;
+        assert!(
+            extract(&patched(directory_count, &2u32.to_le_bytes()))
+                .unwrap()
+                .is_none()
+        );
+        let resource_address = OPTIONAL_HEADER_OFFSET + 112 + RESOURCE_DIRECTORY * 8;
+        assert!(
+            extract(&patched(resource_address, &0u32.to_le_bytes()))
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn rejects_misplaced_resource_section() {
+        // Resource table outside of every section
+        let resource_address = OPTIONAL_HEADER_OFFSET + 112 + RESOURCE_DIRECTORY * 8;
+        assert!(extract(&patched(resource_address, &0x8000u32.to_le_bytes())).is_err());
+        // Section past the end of the file, or too large to read
+        let raw_offset = SECTION_HEADER_OFFSET + 20;
+        assert!(extract(&patched(raw_offset, &0x10000u32.to_le_bytes())).is_err());
+        let raw_len = SECTION_HEADER_OFFSET + 16;
+        assert!(extract(&patched(raw_len, &u32::MAX.to_le_bytes())).is_err());
+        // Section loaded somewhere its resources don't point to
+        let address = SECTION_HEADER_OFFSET + 12;
+        assert!(extract(&patched(address, &0x2000u32.to_le_bytes())).is_err());
+    }
+
+    #[test]
+    fn rejects_truncated_executables() {
+        // Even the section's padding is read
+        for len in 0..FIXTURE.len() {
+            assert!(extract(&FIXTURE[..len]).is_err(), "{len} bytes");
+        }
+    }
+
+    #[test]
+    fn survives_corruption() {
+        for index in 0..FIXTURE.len() {
+            for value in [0x00, 0x01, 0x7f, 0x80, 0xff] {
+                let _ = extract(&patched(index, &[value]));
+            }
+        }
+    }
+}
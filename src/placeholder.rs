@@ -0,0 +1,295 @@
+//! Generating placeholder icons (`--placeholder`) for games the CDN has no icon for,
+//! so their shortcuts at least look intentional: a colored rounded square with the game's
+//! initials (or its app ID) on it.
+//!
+//! Placeholders only depend on the game, so generating one again gives exactly the same bytes.
+
+use std::path::Path;
+
+use anyhow::Result;
+
+use crate::{icns, ico, png, sha1};
+
+/// Source recorded for placeholders (e.g. in the manifest), instead of a URL,
+/// so they can be told apart from real icons and replaced once the CDN has one.
+pub const SOURCE: &str = "placeholder";
+
+/// Sizes of the images in placeholder ICO files.
+const ICO_SIZES: [u32; 3] = [16, 32, 48];
+
+/// Size of placeholders saved as a single image (PNGs and ICNS files).
+const IMAGE_SIZE: u32 = 128;
+
+/// Most initials taken from a game's name.
+const MAX_INITIALS: usize = 2;
+
+/// Width of the font's glyphs, in font pixels.
+const GLYPH_WIDTH: usize = 3;
+
+/// Height of the font's glyphs, in font pixels.
+const GLYPH_HEIGHT: usize = 5;
+
+/// Subsamples taken along each side of a pixel to smooth the square's rounded corners.
+const SUBSAMPLES: u32 = 4;
+
+/// Glyphs for digits then uppercase letters, each row's pixels in its low 3 bits (left first).
+const FONT: [[u8; GLYPH_HEIGHT]; 36] = [
+    [0b111, 0b101, 0b101, 0b101, 0b111], // 0
+    [0b010, 0b110, 0b010, 0b010, 0b111], // 1
+    [0b111, 0b001, 0b111, 0b100, 0b111], // 2
+    [0b111, 0b001, 0b111, 0b001, 0b111], // 3
+    [0b101, 0b101, 0b111, 0b001, 0b001], // 4
+    [0b111, 0b100, 0b111, 0b001, 0b111], // 5
+    [0b111, 0b100, 0b111, 0b101, 0b111], // 6
+    [0b111, 0b001, 0b001, 0b001, 0b001], // 7
+    [0b111, 0b101, 0b111, 0b101, 0b111], // 8
+    [0b111, 0b101, 0b111, 0b001, 0b111], // 9
+    [0b010, 0b101, 0b111, 0b101, 0b101], // A
+    [0b110, 0b101, 0b110, 0b101, 0b110], // B
+    [0b011, 0b100, 0b100, 0b100, 0b011], // C
+    [0b110, 0b101, 0b101, 0b101, 0b110], // D
+    [0b111, 0b100, 0b110, 0b100, 0b111], // E
+    [0b111, 0b100, 0b110, 0b100, 0b100], // F
+    [0b011, 0b100, 0b101, 0b101, 0b011], // G
+    [0b101, 0b101, 0b111, 0b101, 0b101], // H
+    [0b111, 0b010, 0b010, 0b010, 0b111], // I
+    [0b001, 0b001, 0b001, 0b101, 0b010], // J
+    [0b101, 0b101, 0b110, 0b101, 0b101], // K
+    [0b100, 0b100, 0b100, 0b100, 0b111], // L
+    [0b101, 0b111, 0b111, 0b101, 0b101], // M
+    [0b110, 0b101, 0b101, 0b101, 0b101], // N
+    [0b010, 0b101, 0b101, 0b101, 0b010], // O
+    [0b110, 0b101, 0b110, 0b100, 0b100], // P
+    [0b010, 0b101, 0b101, 0b110, 0b011], // Q
+    [0b110, 0b101, 0b110, 0b101, 0b101], // R
+    [0b011, 0b100, 0b010, 0b001, 0b110], // S
+    [0b111, 0b010, 0b010, 0b010, 0b010], // T
+    [0b101, 0b101, 0b101, 0b101, 0b111], // U
+    [0b101, 0b101, 0b101, 0b101, 0b010], // V
+    [0b101, 0b101, 0b111, 0b111, 0b101], // W
+    [0b101, 0b101, 0b010, 0b101, 0b101], // X
+    [0b101, 0b101, 0b010, 0b010, 0b010], // Y
+    [0b111, 0b001, 0b010, 0b100, 0b111], // Z
+];
+
+/// Placeholder for `game_id` (named `game_name`, if known) in the format of `icon_path`
+/// (going by its extension): an ICO with 16, 32 and 48px images, or else a 128px PNG or ICNS.
+pub fn icon_for(icon_path: &Path, game_id: &str, game_name: Option<&str>) -> Result<Vec<u8>> {
+    let label = label(game_id, game_name);
+    let color = color(game_id);
+    match icon_path
+        .extension()
+        .and_then(|extension| extension.to_str())
+    {
+        Some("png") => Ok(render(IMAGE_SIZE, color, &label)),
+        Some("icns") => icns::from_png(&render(IMAGE_SIZE, color, &label)),
+        _ => {
+            let pngs = ICO_SIZES.map(|size| render(size, color, &label));
+            ico::from_pngs(&pngs.each_ref().map(Vec::as_slice))
+        }
+    }
+}
+
+/// Text shown on a game's placeholder: the initials of its name's first words,
+/// or its app ID if it has no name (or none the font can show).
+fn label(game_id: &str, game_name: Option<&str>) -> Vec<u8> {
+    let initials: Vec<u8> = game_name
+        .unwrap_or_default()
+        .split(|character: char| !character.is_ascii_alphanumeric())
+        .filter_map(|word| word.bytes().next())
+        .map(|initial| initial.to_ascii_uppercase())
+        .take(MAX_INITIALS)
+        .collect();
+    if initials.is_empty() {
+        game_id.bytes().filter(u8::is_ascii_digit).collect()
+    } else {
+        initials
+    }
+}
+
+/// Background color of a game's placeholder, with a hue picked by hashing its ID,
+/// and a saturation and brightness that keep white text readable.
+fn color(game_id: &str) -> [u8; 3] {
+    let hash = sha1::digest(game_id.as_bytes());
+    let hue = u32::from(u16::from_be_bytes([hash[0], hash[1]])) % 360;
+    let (max, min) = (166, 75);
+    let rising = (min + (max - min) * (hue % 60) / 60) as u8;
+    let falling = (max - (max - min) * (hue % 60) / 60) as u8;
+    let (max, min) = (max as u8, min as u8);
+    match hue / 60 {
+        0 => [max, rising, min],
+        1 => [falling, max, min],
+        2 => [min, max, rising],
+        3 => [min, falling, max],
+        4 => [rising, min, max],
+        _ => [max, min, falling],
+    }
+}
+
+/// Render a `size`px placeholder with the given background color and label, as a PNG.
+/// The label is left off if it doesn't fit (e.g. long app IDs on the smallest images).
+fn render(size: u32, color: [u8; 3], label: &[u8]) -> Vec<u8> {
+    let side = size as usize;
+    let mut rgba = vec![0; side * side * 4];
+
+    // Rounded square, inset slightly, with its corners smoothed by subsampling
+    let margin = (size / 16).max(1) * 2 * SUBSAMPLES;
+    let (low, high) = (margin, size * 2 * SUBSAMPLES - margin);
+    let radius = size / 5 * 2 * SUBSAMPLES;
+    for y in 0..size {
+        for x in 0..size {
+            let covered = (0..SUBSAMPLES * SUBSAMPLES)
+                .filter(|sample| {
+                    // Subsamples are at the middle of each part of the pixel, in half-parts
+                    let sample_x = (x * SUBSAMPLES + sample % SUBSAMPLES) * 2 + 1;
+                    let sample_y = (y * SUBSAMPLES + sample / SUBSAMPLES) * 2 + 1;
+                    is_in_rounded_square(sample_x, sample_y, low, high, radius)
+                })
+                .count() as u32;
+            let pixel = (y as usize * side + x as usize) * 4;
+            rgba[pixel..pixel + 3].copy_from_slice(&color);
+            rgba[pixel + 3] = (covered * 255 / (SUBSAMPLES * SUBSAMPLES)) as u8;
+        }
+    }
+
+    // The label in white, centered, as large as fits (but no taller than half the square)
+    let glyphs: Vec<_> = label
+        .iter()
+        .filter_map(|character| match character {
+            b'0'..=b'9' => Some(&FONT[usize::from(character - b'0')]),
+            b'A'..=b'Z' => Some(&FONT[usize::from(character - b'A') + 10]),
+            _ => None,
+        })
+        .collect();
+    let text_width = (glyphs.len() * (GLYPH_WIDTH + 1)).saturating_sub(1);
+    let padding = side / 8;
+    let available = side.saturating_sub(2 * padding);
+    let scale = match text_width {
+        0 => 0,
+        _ => (available / text_width).min(side / 2 / GLYPH_HEIGHT),
+    };
+    if scale == 0 {
+        return png::encode_rgba(size, size, &rgba);
+    }
+    let left = (side - text_width * scale) / 2;
+    let top = (side - GLYPH_HEIGHT * scale) / 2;
+    for (index, glyph) in glyphs.iter().enumerate() {
+        for (row, bits) in glyph.iter().enumerate() {
+            for column in 0..GLYPH_WIDTH {
+                if bits & (1 << (GLYPH_WIDTH - 1 - column)) == 0 {
+                    continue;
+                }
+                let x = left + (index * (GLYPH_WIDTH + 1) + column) * scale;
+                let y = top + row * scale;
+                for y in y..y + scale {
+                    for x in x..x + scale {
+                        let pixel = (y * side + x) * 4;
+                        rgba[pixel..pixel + 4].copy_from_slice(&[255; 4]);
+                    }
+                }
+            }
+        }
+    }
+    png::encode_rgba(size, size, &rgba)
+}
+
+/// Whether the point (`x`, `y`) is within the square from `low` to `high` on both axes,
+/// with its corners rounded to `radius`.
+fn is_in_rounded_square(x: u32, y: u32, low: u32, high: u32, radius: u32) -> bool {
+    if !(low..high).contains(&x) || !(low..high).contains(&y) {
+        return false;
+    }
+
+    // Only points beyond the corners' centers can be outside them
+    let distance = |value: u32| {
+        let nearest = value.clamp(low + radius, high - radius);
+        u64::from(value.abs_diff(nearest))
+    };
+    distance(x).pow(2) + distance(y).pow(2) <= u64::from(radius).pow(2)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use super::*;
+
+    #[test]
+    fn ico_placeholders_are_valid() {
+        let ico = icon_for(Path::new("icon.ico"), "440", Some("Team Fortress 2")).unwrap();
+        ico::validate(&ico).unwrap();
+        // The largest image is the one converted
+        assert_eq!(
+            png::dimensions(&ico::to_png(&ico).unwrap()).unwrap(),
+            (48, 48)
+        );
+
+        // Even without an extension, or a name
+        let ico = icon_for(Path::new("icon"), "1234567890", None).unwrap();
+        ico::validate(&ico).unwrap();
+    }
+
+    #[test]
+    fn image_placeholders_are_valid() {
+        let png = icon_for(Path::new("icon.png"), "440", None).unwrap();
+        assert_eq!(png::dimensions(&png).unwrap(), (IMAGE_SIZE, IMAGE_SIZE));
+        let icns = icon_for(Path::new("icon.icns"), "440", None).unwrap();
+        icns::validate(&icns).unwrap();
+    }
+
+    #[test]
+    fn placeholders_are_reproducible() {
+        let path = Path::new("icon.ico");
+        let first = icon_for(path, "440", Some("Team Fortress 2")).unwrap();
+        assert_eq!(
+            icon_for(path, "440", Some("Team Fortress 2")).unwrap(),
+            first
+        );
+        assert_ne!(
+            icon_for(path, "441", Some("Team Fortress 2")).unwrap(),
+            first
+        );
+    }
+
+    #[test]
+    fn labels_use_initials_or_app_id() {
+        assert_eq!(label("440", Some("team fortress 2")), b"TF");
+        assert_eq!(label("440", Some("Portal")), b"P");
+        assert_eq!(label("440", Some("ゲーム")), b"440");
+        assert_eq!(label("440", None), b"440");
+    }
+
+    /// Decode `png` with an independent decoder, returning its dimensions and RGBA pixels.
+    fn decode(png: &[u8]) -> (u32, u32, Vec<u8>) {
+        let mut reader = ::png::Decoder::new(io::Cursor::new(png))
+            .read_info()
+            .unwrap();
+        let mut rgba = vec![0; reader.output_buffer_size().unwrap()];
+        let frame = reader.next_frame(&mut rgba).unwrap();
+        assert_eq!(
+            (frame.color_type, frame.bit_depth),
+            (::png::ColorType::Rgba, ::png::BitDepth::Eight)
+        );
+        rgba.truncate(frame.buffer_size());
+        (frame.width, frame.height, rgba)
+    }
+
+    #[test]
+    fn placeholders_decode_at_their_sizes() {
+        let png = icon_for(Path::new("icon.png"), "440", Some("Team Fortress 2")).unwrap();
+        let (width, height, rgba) = decode(&png);
+        assert_eq!((width, height), (IMAGE_SIZE, IMAGE_SIZE));
+
+        // Transparent around the rounded corners, and the game's color in the middle
+        let side = IMAGE_SIZE as usize;
+        let pixel = |x: usize, y: usize| &rgba[(y * side + x) * 4..][..4];
+        assert_eq!(pixel(0, 0)[3], 0);
+        assert_eq!(pixel(side - 1, side - 1)[3], 0);
+        let [red, green, blue] = color("440");
+        assert_eq!(pixel(side / 2, side / 8), [red, green, blue, 0xff]);
+
+        let ico = icon_for(Path::new("icon.ico"), "440", Some("Team Fortress 2")).unwrap();
+        let (width, height, _) = decode(&ico::to_png(&ico).unwrap());
+        assert_eq!((width, height), (48, 48));
+    }
+}
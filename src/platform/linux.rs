@@ -0,0 +1,420 @@
+use std::env;
+use std::fs::{self, File, Metadata};
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context as _, Result, bail};
+use log::*;
+
+use crate::shortcut::{self, Shortcut};
+
+/// Extension of the shortcut files Steam creates
+pub const SHORTCUT_EXTENSION: &str = ".desktop";
+
+/// Extension of the icons Steam loads for shortcuts
+pub const ICON_EXTENSION: &str = ".png";
+
+/// Whether shortcuts are directories (app bundles) rather than files
+pub const SHORTCUTS_ARE_DIRS: bool = false;
+
+/// Make sure shortcuts on this platform can be processed (they can).
+pub fn ensure_supported() -> Result<()> {
+    Ok(())
+}
+
+/// How to tell someone to run the script elevated
+pub const RUN_ELEVATED: &str = "as root (e.g. with `sudo`)";
+
+/// Whether the script is running as root.
+pub fn is_elevated() -> bool {
+    // SAFETY: `geteuid` has no preconditions and always succeeds
+    unsafe { libc::geteuid() == 0 }
+}
+
+/// Icon theme directory (within a data directory) Steam installs shortcut icons to
+const ICON_THEME_DIR: &str = "icons/hicolor/32x32/apps";
+
+/// Kind of Steam install found on Linux.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InstallKind {
+    /// Installed from the distribution's packages or Valve's installer
+    Native,
+
+    /// Installed from Flathub (`com.valvesoftware.Steam`)
+    Flatpak,
+
+    /// Installed from the Snap Store
+    Snap,
+}
+
+impl InstallKind {
+    /// Human-readable name of the install kind.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Native => "native",
+            Self::Flatpak => "Flatpak",
+            Self::Snap => "Snap",
+        }
+    }
+}
+
+/// Location of a Steam install on Linux.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Install {
+    /// How Steam was installed
+    pub kind: InstallKind,
+
+    /// Data directory Steam sees as `XDG_DATA_HOME`, which icons are installed into
+    pub data_dir: PathBuf,
+
+    /// Steam's own directory (containing `steamapps`)
+    pub root: PathBuf,
+}
+
+impl Install {
+    /// Install of the given kind whose data directory is `data_dir`.
+    fn new(kind: InstallKind, data_dir: PathBuf) -> Self {
+        let root = data_dir.join("Steam");
+        Self {
+            kind,
+            data_dir,
+            root,
+        }
+    }
+
+    /// Directory the icons for this install's `.desktop` shortcuts are loaded from.
+    pub fn icon_dir(&self) -> PathBuf {
+        self.data_dir.join(ICON_THEME_DIR)
+    }
+}
+
+/// Where Steam may be installed for a user, in order of preference:
+/// 1. Native, in `$XDG_DATA_HOME/Steam` (if set) or `~/.local/share/Steam`
+/// 2. Flatpak, in `~/.var/app/com.valvesoftware.Steam/.local/share/Steam`
+/// 3. Snap, in `~/snap/steam/common/.local/share/Steam`
+pub fn install_candidates(home: &Path, data_home: Option<&Path>) -> Vec<Install> {
+    let mut candidates = Vec::new();
+    if let Some(data_home) = data_home {
+        candidates.push(Install::new(InstallKind::Native, data_home.to_owned()));
+    }
+    candidates.push(Install::new(InstallKind::Native, home.join(".local/share")));
+    candidates.push(Install::new(
+        InstallKind::Flatpak,
+        home.join(".var/app/com.valvesoftware.Steam/.local/share"),
+    ));
+    candidates.push(Install::new(
+        InstallKind::Snap,
+        home.join("snap/steam/common/.local/share"),
+    ));
+    candidates
+}
+
+/// Pick the install to use out of `candidates`.
+/// An install with a `steamapps` directory (i.e. one that's actually been used)
+/// is preferred over one that merely exists, and earlier candidates win ties.
+pub fn select_install(candidates: &[Install]) -> Option<&Install> {
+    candidates
+        .iter()
+        .find(|install| install.root.join("steamapps").is_dir())
+        .or_else(|| candidates.iter().find(|install| install.root.is_dir()))
+}
+
+/// Determine the directory the icons for Steam's `.desktop` shortcuts are loaded from,
+/// based on which Steam install is found (see [`install_candidates`]).
+pub fn default_icon_dir() -> Result<PathBuf> {
+    let home = env::var_os("HOME").context("`HOME` is not set")?;
+    let data_home = env::var_os("XDG_DATA_HOME").filter(|dir| !dir.is_empty());
+    find_icon_dir(Path::new(&home), data_home.as_deref().map(Path::new))
+}
+
+/// Determine the icon directory of the Steam install found for the user
+/// with the given home directory and `XDG_DATA_HOME` (if set).
+fn find_icon_dir(home: &Path, data_home: Option<&Path>) -> Result<PathBuf> {
+    let candidates = install_candidates(home, data_home);
+    for candidate in &candidates {
+        debug!(
+            "Checking for a {} Steam install in {}",
+            candidate.kind.name(),
+            candidate.root.display()
+        );
+    }
+
+    match select_install(&candidates) {
+        Some(install) => {
+            info!(
+                "Found {} Steam install in {}",
+                install.kind.name(),
+                install.root.display()
+            );
+            Ok(install.icon_dir())
+        }
+        None => {
+            // Nothing exists, so go with where the icons usually go
+            info!("Steam install not found, using the default icon directory");
+            let native = candidates.first().context("No Steam install candidates")?;
+            Ok(native.icon_dir())
+        }
+    }
+}
+
+/// Desktop Steam creates shortcuts on, as configured by `xdg-user-dirs` (if at all).
+pub fn desktop_dirs() -> Result<Vec<PathBuf>> {
+    let home = PathBuf::from(env::var_os("HOME").context("`HOME` is not set")?);
+    let config_home = match env::var_os("XDG_CONFIG_HOME").filter(|dir| !dir.is_empty()) {
+        Some(config_home) => PathBuf::from(config_home),
+        None => home.join(".config"),
+    };
+    Ok(vec![desktop_dir(&home, &config_home)])
+}
+
+/// Desktops of every user with a home directory in `/home`.
+pub fn all_users_desktop_dirs() -> Result<Vec<PathBuf>> {
+    let mut dirs = Vec::new();
+    for entry in fs::read_dir("/home").context("Failed to list home directories in `/home`")? {
+        // One unreadable home directory shouldn't keep the others from being found
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(error) => {
+                warn!("Skipping unreadable home directory in `/home`: {error}");
+                continue;
+            }
+        };
+        let is_dir = match entry.file_type() {
+            Ok(file_type) => file_type.is_dir(),
+            Err(error) => {
+                warn!(
+                    "Skipping home directory `{}`, which couldn't be checked: {error}",
+                    entry.path().display()
+                );
+                continue;
+            }
+        };
+        if is_dir {
+            let home = entry.path();
+            dirs.push(desktop_dir(&home, &home.join(".config")));
+        }
+    }
+    Ok(dirs)
+}
+
+/// Desktop of the user with the given home and config directories.
+fn desktop_dir(home: &Path, config_home: &Path) -> PathBuf {
+    // Lines look like `XDG_DESKTOP_DIR="$HOME/Desktop"`
+    let configured = fs::read_to_string(config_home.join("user-dirs.dirs"))
+        .ok()
+        .and_then(|user_dirs| {
+            user_dirs.lines().find_map(|line| {
+                let dir = line
+                    .trim()
+                    .strip_prefix("XDG_DESKTOP_DIR=")?
+                    .trim_matches('"');
+                Some(match dir.strip_prefix("$HOME") {
+                    Some(relative) => home.join(relative.trim_start_matches('/')),
+                    None => PathBuf::from(dir),
+                })
+            })
+        });
+
+    configured.unwrap_or_else(|| home.join("Desktop"))
+}
+
+/// Directories Steam creates application menu shortcuts in
+/// (the closest thing to a Start Menu).
+pub fn start_menu_dirs() -> Result<Vec<PathBuf>> {
+    let data_home = match env::var_os("XDG_DATA_HOME").filter(|dir| !dir.is_empty()) {
+        Some(data_home) => PathBuf::from(data_home),
+        None => {
+            PathBuf::from(env::var_os("HOME").context("`HOME` is not set")?).join(".local/share")
+        }
+    };
+    Ok(vec![data_home.join("applications")])
+}
+
+/// Directory to keep this script's cache in, e.g. `~/.cache/retrieve-missing-steam-game-icons`.
+pub fn cache_dir() -> Result<PathBuf> {
+    let cache_home = match env::var_os("XDG_CACHE_HOME").filter(|dir| !dir.is_empty()) {
+        Some(cache_home) => PathBuf::from(cache_home),
+        None => PathBuf::from(env::var_os("HOME").context("`HOME` is not set")?).join(".cache"),
+    };
+    Ok(cache_home.join(env!("CARGO_PKG_NAME")))
+}
+
+/// Fail, since only Steam on Windows saves icons somewhere users usually can't write to.
+pub fn user_icon_dir() -> Result<PathBuf> {
+    bail!("A per-user icon directory is only needed by Steam on Windows")
+}
+
+/// Fail, since only Windows keeps shortcut icons in a cache that can go stale.
+pub fn icon_cache_dir() -> Result<PathBuf> {
+    bail!("Only Windows has an icon cache to rebuild")
+}
+
+/// Fail, since only Steam on Windows loads icons for installed games from its own directory.
+pub fn steam_install_dir(_local_icon_dir: &Path) -> Result<PathBuf> {
+    bail!("Icons for installed games are only needed by Steam on Windows")
+}
+
+/// Whether `metadata` is for a cloud-only placeholder (never on this platform).
+pub fn is_cloud_placeholder(_metadata: &Metadata) -> bool {
+    false
+}
+
+/// Read the `.desktop` file at `path`.
+pub fn read_shortcut(path: &Path, _local_icon_dir: &Path) -> Result<Shortcut> {
+    let file = File::open(path).context("Failed to open file")?;
+    shortcut::parse_desktop_entry(BufReader::new(file))
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    /// Data directory of a native install, within a home directory
+    const NATIVE: &str = ".local/share";
+
+    /// Data directory of a Flatpak install, within a home directory
+    const FLATPAK: &str = ".var/app/com.valvesoftware.Steam/.local/share";
+
+    /// Data directory of a Snap install, within a home directory
+    const SNAP: &str = "snap/steam/common/.local/share";
+
+    /// Create a Steam install in the data directory at `data_dir` within `home`,
+    /// with a `steamapps` directory if it's been `used`.
+    fn add_install(home: &Path, data_dir: &str, used: bool) {
+        let root = home.join(data_dir).join("Steam");
+        fs::create_dir_all(if used { root.join("steamapps") } else { root }).unwrap();
+    }
+
+    #[test]
+    fn lists_candidates_in_order_of_preference() {
+        let home = Path::new("/home/user");
+        let kinds_and_dirs = |data_home| {
+            install_candidates(home, data_home)
+                .into_iter()
+                .map(|install| (install.kind, install.root))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            kinds_and_dirs(None),
+            [
+                (InstallKind::Native, home.join(NATIVE).join("Steam")),
+                (InstallKind::Flatpak, home.join(FLATPAK).join("Steam")),
+                (InstallKind::Snap, home.join(SNAP).join("Steam")),
+            ]
+        );
+
+        let data_home = Path::new("/data");
+        let candidates = kinds_and_dirs(Some(data_home));
+        assert_eq!(candidates.len(), 4);
+        assert_eq!(
+            candidates[0],
+            (InstallKind::Native, data_home.join("Steam"))
+        );
+    }
+
+    #[test]
+    fn defaults_to_native_icon_dir_without_install() {
+        let home = TempDir::new().unwrap();
+        assert_eq!(
+            find_icon_dir(home.path(), None).unwrap(),
+            home.path().join(NATIVE).join(ICON_THEME_DIR)
+        );
+        let data_home = home.path().join("data");
+        assert_eq!(
+            find_icon_dir(home.path(), Some(&data_home)).unwrap(),
+            data_home.join(ICON_THEME_DIR)
+        );
+    }
+
+    #[test]
+    fn finds_flatpak_and_snap_installs() {
+        for data_dir in [FLATPAK, SNAP] {
+            let home = TempDir::new().unwrap();
+            add_install(home.path(), data_dir, false);
+            assert_eq!(
+                find_icon_dir(home.path(), None).unwrap(),
+                home.path().join(data_dir).join(ICON_THEME_DIR),
+                "{data_dir}"
+            );
+        }
+    }
+
+    #[test]
+    fn prefers_used_install() {
+        let home = TempDir::new().unwrap();
+        add_install(home.path(), NATIVE, false);
+        add_install(home.path(), SNAP, true);
+        assert_eq!(
+            find_icon_dir(home.path(), None).unwrap(),
+            home.path().join(SNAP).join(ICON_THEME_DIR)
+        );
+    }
+
+    #[test]
+    fn prefers_earlier_candidates() {
+        let home = TempDir::new().unwrap();
+        add_install(home.path(), NATIVE, true);
+        add_install(home.path(), FLATPAK, true);
+        assert_eq!(
+            find_icon_dir(home.path(), None).unwrap(),
+            home.path().join(NATIVE).join(ICON_THEME_DIR)
+        );
+
+        // Including Steam's data directory being moved with `XDG_DATA_HOME`
+        add_install(home.path(), "data", true);
+        assert_eq!(
+            find_icon_dir(home.path(), Some(&home.path().join("data"))).unwrap(),
+            home.path().join("data").join(ICON_THEME_DIR)
+        );
+    }
+
+    #[test]
+    fn desktop_defaults_to_home() {
+        let home = TempDir::new().unwrap();
+        let config_home = home.path().join(".config");
+        assert_eq!(
+            desktop_dir(home.path(), &config_home),
+            home.path().join("Desktop")
+        );
+
+        // Including when `xdg-user-dirs` doesn't say where it is
+        fs::create_dir(&config_home).unwrap();
+        fs::write(
+            config_home.join("user-dirs.dirs"),
+            "XDG_DOCUMENTS_DIR=\"$HOME/Documents\"\n",
+        )
+        .unwrap();
+        assert_eq!(
+            desktop_dir(home.path(), &config_home),
+            home.path().join("Desktop")
+        );
+    }
+
+    #[test]
+    fn finds_desktop_configured_by_xdg_user_dirs() {
+        let home = TempDir::new().unwrap();
+        let config_home = home.path().join("config");
+        fs::create_dir(&config_home).unwrap();
+        let user_dirs = config_home.join("user-dirs.dirs");
+
+        // Usually relative to the home directory
+        fs::write(
+            &user_dirs,
+            "# Written by xdg-user-dirs-update\nXDG_DESKTOP_DIR=\"$HOME/Schreibtisch\"\n",
+        )
+        .unwrap();
+        assert_eq!(
+            desktop_dir(home.path(), &config_home),
+            home.path().join("Schreibtisch")
+        );
+
+        // But it can be anywhere
+        fs::write(&user_dirs, "  XDG_DESKTOP_DIR=\"/srv/desktop\"\n").unwrap();
+        assert_eq!(
+            desktop_dir(home.path(), &config_home),
+            Path::new("/srv/desktop")
+        );
+    }
+}
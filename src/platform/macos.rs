@@ -0,0 +1,138 @@
+use std::env;
+use std::fs::{self, File, Metadata};
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context as _, Result, bail};
+use log::*;
+
+use crate::shortcut::{self, Shortcut};
+
+/// Extension of the app bundles Steam creates as shortcuts
+pub const SHORTCUT_EXTENSION: &str = ".app";
+
+/// Extension of the icons Steam loads for shortcuts
+pub const ICON_EXTENSION: &str = ".icns";
+
+/// Whether shortcuts are directories (app bundles) rather than files
+pub const SHORTCUTS_ARE_DIRS: bool = true;
+
+/// Make sure shortcuts on this platform can be processed (they can).
+pub fn ensure_supported() -> Result<()> {
+    Ok(())
+}
+
+/// How to tell someone to run the script elevated
+pub const RUN_ELEVATED: &str = "as root (e.g. with `sudo`)";
+
+/// Whether the script is running as root.
+pub fn is_elevated() -> bool {
+    // SAFETY: `geteuid` has no preconditions and always succeeds
+    unsafe { libc::geteuid() == 0 }
+}
+
+/// Determine the directory Steam keeps its data in on macOS.
+/// Icons are saved within each shortcut's app bundle rather than here,
+/// so this only needs to exist for the sake of `--icon-dir`'s checks.
+pub fn default_icon_dir() -> Result<PathBuf> {
+    let home = env::var_os("HOME").context("`HOME` is not set")?;
+    let data_dir = PathBuf::from(home).join("Library/Application Support/Steam");
+    info!("Using Steam data directory {}", data_dir.display());
+    Ok(data_dir)
+}
+
+/// Desktop Steam creates shortcuts on.
+pub fn desktop_dirs() -> Result<Vec<PathBuf>> {
+    let home = env::var_os("HOME").context("`HOME` is not set")?;
+    Ok(vec![PathBuf::from(home).join("Desktop")])
+}
+
+/// Desktops of every user with a home directory in `/Users`.
+pub fn all_users_desktop_dirs() -> Result<Vec<PathBuf>> {
+    let mut dirs = Vec::new();
+    for entry in fs::read_dir("/Users").context("Failed to list home directories in `/Users`")? {
+        // One unreadable home directory shouldn't keep the others from being found
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(error) => {
+                warn!("Skipping unreadable home directory in `/Users`: {error}");
+                continue;
+            }
+        };
+        let is_dir = match entry.file_type() {
+            Ok(file_type) => file_type.is_dir(),
+            Err(error) => {
+                warn!(
+                    "Skipping home directory `{}`, which couldn't be checked: {error}",
+                    entry.path().display()
+                );
+                continue;
+            }
+        };
+        let name = entry.file_name();
+        if is_dir && name != "Shared" && !name.to_string_lossy().starts_with('.') {
+            dirs.push(entry.path().join("Desktop"));
+        }
+    }
+    Ok(dirs)
+}
+
+/// Fail, since Steam only creates shortcuts on the desktop on macOS.
+pub fn start_menu_dirs() -> Result<Vec<PathBuf>> {
+    bail!("Steam doesn't create Start Menu shortcuts on macOS")
+}
+
+/// Directory to keep this script's cache in,
+/// e.g. `~/Library/Caches/retrieve-missing-steam-game-icons`.
+pub fn cache_dir() -> Result<PathBuf> {
+    let home = env::var_os("HOME").context("`HOME` is not set")?;
+    Ok(PathBuf::from(home)
+        .join("Library/Caches")
+        .join(env!("CARGO_PKG_NAME")))
+}
+
+/// Fail, since only Steam on Windows saves icons somewhere users usually can't write to.
+pub fn user_icon_dir() -> Result<PathBuf> {
+    bail!("A per-user icon directory is only needed by Steam on Windows")
+}
+
+/// Fail, since only Windows keeps shortcut icons in a cache that can go stale.
+pub fn icon_cache_dir() -> Result<PathBuf> {
+    bail!("Only Windows has an icon cache to rebuild")
+}
+
+/// Fail, since only Steam on Windows loads icons for installed games from its own directory.
+pub fn steam_install_dir(_local_icon_dir: &Path) -> Result<PathBuf> {
+    bail!("Icons for installed games are only needed by Steam on Windows")
+}
+
+/// Whether `metadata` is for a cloud-only placeholder (never on this platform).
+pub fn is_cloud_placeholder(_metadata: &Metadata) -> bool {
+    false
+}
+
+/// Read the app bundle Steam created as a shortcut at `path`.
+/// The game ID comes from the bundle's launch script,
+/// and the icon is saved within the bundle under the name in its `Info.plist`.
+pub fn read_shortcut(path: &Path, _local_icon_dir: &Path) -> Result<Shortcut> {
+    let contents_dir = path.join("Contents");
+
+    // Extract the game ID from the launch script
+    let script = File::open(contents_dir.join("MacOS").join("run.sh"))
+        .context("Failed to open the launch script")?;
+    let game_id = shortcut::parse_launch_script(BufReader::new(script))?;
+    shortcut::ensure_steam_game(&game_id)?;
+
+    // Extract the icon filename from the bundle's metadata
+    let info =
+        File::open(contents_dir.join("Info.plist")).context("Failed to open `Info.plist`")?;
+    let icon_filename = shortcut::parse_bundle_icon_file(BufReader::new(info))?;
+
+    // App bundles don't record which icon on the CDN they came from
+    Ok(Shortcut {
+        game_id,
+        icon_filename,
+        cdn_filename: None,
+        icon_dir: Some(contents_dir.join("Resources")),
+    })
+}
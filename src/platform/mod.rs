@@ -0,0 +1,65 @@
+//! Everything that differs between the platforms Steam runs on:
+//! where icons are saved, and what Steam's shortcuts look like.
+
+use std::ffi::OsStr;
+use std::path::Path;
+
+#[cfg(target_os = "linux")]
+mod linux;
+#[cfg(target_os = "macos")]
+mod macos;
+#[cfg(not(any(target_os = "windows", target_os = "linux", target_os = "macos")))]
+mod unsupported;
+#[cfg(target_os = "windows")]
+mod windows;
+
+#[cfg(target_os = "linux")]
+pub use linux::*;
+#[cfg(target_os = "macos")]
+pub use macos::*;
+#[cfg(not(any(target_os = "windows", target_os = "linux", target_os = "macos")))]
+pub use unsupported::*;
+#[cfg(target_os = "windows")]
+pub use windows::*;
+
+/// Whether `path` has the extension of Steam's shortcuts, going by its raw filename
+/// (so names that aren't valid Unicode are still recognized).
+pub fn has_shortcut_extension(path: &Path) -> bool {
+    match SHORTCUT_EXTENSION.strip_prefix('.') {
+        Some(extension) => path.extension() == Some(OsStr::new(extension)),
+        // Without a known extension, anything might be a shortcut
+        None => true,
+    }
+}
+
+#[cfg(all(
+    test,
+    any(target_os = "windows", target_os = "linux", target_os = "macos")
+))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recognizes_shortcut_extension() {
+        let shortcut = format!("Portal 2{SHORTCUT_EXTENSION}");
+        assert!(has_shortcut_extension(Path::new(&shortcut)));
+        assert!(has_shortcut_extension(&Path::new("Games").join(&shortcut)));
+        assert!(!has_shortcut_extension(Path::new("Portal 2.txt")));
+        assert!(!has_shortcut_extension(Path::new("Portal 2")));
+        // Only the last extension counts
+        assert!(!has_shortcut_extension(Path::new(&format!(
+            "{shortcut}.bak"
+        ))));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn recognizes_shortcut_extension_of_invalid_unicode_names() {
+        use std::os::unix::ffi::OsStrExt as _;
+
+        let shortcut = [b"\xff".as_slice(), SHORTCUT_EXTENSION.as_bytes()].concat();
+        assert!(has_shortcut_extension(Path::new(OsStr::from_bytes(
+            &shortcut
+        ))));
+    }
+}
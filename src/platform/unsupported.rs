@@ -0,0 +1,79 @@
+use std::env::consts::OS;
+use std::fs::Metadata;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Result, bail};
+
+use crate::shortcut::Shortcut;
+
+/// Extension of the shortcut files Steam creates
+pub const SHORTCUT_EXTENSION: &str = "";
+
+/// Extension of the icons Steam loads for shortcuts
+pub const ICON_EXTENSION: &str = "";
+
+/// Whether shortcuts are directories (app bundles) rather than files
+pub const SHORTCUTS_ARE_DIRS: bool = false;
+
+/// Fail, since there's no telling what shortcuts look like on this platform.
+pub fn ensure_supported() -> Result<()> {
+    bail!("Steam shortcuts on {OS} aren't supported (only Windows, Linux, and macOS are)")
+}
+
+/// How to tell someone to run the script elevated
+pub const RUN_ELEVATED: &str = "with more privileges";
+
+/// Whether the script is running elevated (never known on this platform).
+pub fn is_elevated() -> bool {
+    false
+}
+
+/// Fail, since there's no telling where icons go on this platform.
+pub fn default_icon_dir() -> Result<PathBuf> {
+    ensure_supported().map(|()| PathBuf::new())
+}
+
+/// Fail, since there's no telling where shortcuts go on this platform.
+pub fn desktop_dirs() -> Result<Vec<PathBuf>> {
+    ensure_supported().map(|()| Vec::new())
+}
+
+/// Fail, since there's no telling where shortcuts go on this platform.
+pub fn all_users_desktop_dirs() -> Result<Vec<PathBuf>> {
+    ensure_supported().map(|()| Vec::new())
+}
+
+/// Fail, since there's no telling where shortcuts go on this platform.
+pub fn start_menu_dirs() -> Result<Vec<PathBuf>> {
+    ensure_supported().map(|()| Vec::new())
+}
+
+/// Fail, since there's no telling where caches go on this platform.
+pub fn cache_dir() -> Result<PathBuf> {
+    ensure_supported().map(|()| PathBuf::new())
+}
+
+/// Fail, since there's no telling where icons go on this platform.
+pub fn user_icon_dir() -> Result<PathBuf> {
+    ensure_supported().map(|()| PathBuf::new())
+}
+
+/// Fail, since there's no telling where icons are cached on this platform.
+pub fn icon_cache_dir() -> Result<PathBuf> {
+    ensure_supported().map(|()| PathBuf::new())
+}
+
+/// Fail, since there's no telling where Steam is installed on this platform.
+pub fn steam_install_dir(_local_icon_dir: &Path) -> Result<PathBuf> {
+    ensure_supported().map(|()| PathBuf::new())
+}
+
+/// Whether `metadata` is for a cloud-only placeholder (never on this platform).
+pub fn is_cloud_placeholder(_metadata: &Metadata) -> bool {
+    false
+}
+
+/// Fail, since there's no telling what shortcuts look like on this platform.
+pub fn read_shortcut(_path: &Path, _local_icon_dir: &Path) -> Result<Shortcut> {
+    ensure_supported().map(|()| unreachable!())
+}
@@ -0,0 +1,471 @@
+use std::env;
+use std::fmt::Display;
+use std::fs::{File, Metadata};
+use std::io::BufReader;
+use std::os::windows::fs::MetadataExt as _;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context as _, Result};
+use log::*;
+
+use crate::paths;
+use crate::shortcut::{self, Shortcut};
+
+// Path will be different on other platforms
+// (only used if neither the Steam install nor `Program Files (x86)` can be found)
+const LOCAL_ICON_DIR: &str = r"C:\Program Files (x86)\Steam\steam\games\";
+
+/// Extension of the shortcut files Steam creates
+pub const SHORTCUT_EXTENSION: &str = ".url";
+
+/// Extension of the icons Steam loads for shortcuts
+pub const ICON_EXTENSION: &str = ".ico";
+
+/// Whether shortcuts are directories (app bundles) rather than files
+pub const SHORTCUTS_ARE_DIRS: bool = false;
+
+/// Make sure shortcuts on this platform can be processed (they can).
+pub fn ensure_supported() -> Result<()> {
+    Ok(())
+}
+
+/// How to tell someone to run the script elevated
+pub const RUN_ELEVATED: &str = "as administrator";
+
+/// Whether the script is running as administrator (elevated, when UAC is on).
+pub fn is_elevated() -> bool {
+    // SAFETY: `IsUserAnAdmin` takes no arguments and only checks the process's token
+    unsafe { windows_sys::Win32::UI::Shell::IsUserAnAdmin() != 0 }
+}
+
+/// Determine the directory Steam loads game icons from.
+pub fn default_icon_dir() -> Result<PathBuf> {
+    // Ways to find the icon directory, in order of preference
+    // (after `--icon-dir`, which is handled before getting here)
+    let resolvers: [(&str, &dyn Fn() -> Option<PathBuf>); 3] = [
+        ("the registry", &|| {
+            install_dir_from_registry().map(|install_dir| icon_dir(&install_dir))
+        }),
+        ("the `Program Files (x86)` known folder", &|| {
+            program_files_x86_dir().map(|dir| icon_dir(&dir.join("Steam")))
+        }),
+        ("the default", &|| Some(PathBuf::from(LOCAL_ICON_DIR))),
+    ];
+    resolve_icon_dir(&resolvers).context("Failed to determine the icon directory")
+}
+
+/// Find the icon directory with the first of `resolvers` that can, logging each attempt.
+fn resolve_icon_dir(resolvers: &[(&str, &dyn Fn() -> Option<PathBuf>)]) -> Option<PathBuf> {
+    for (source, resolve) in resolvers {
+        debug!("Looking for the icon directory using {source}");
+        if let Some(dir) = resolve() {
+            debug!("Found icon directory `{}` using {source}", dir.display());
+            return Some(dir);
+        }
+    }
+    None
+}
+
+/// Find the `Program Files (x86)` directory, wherever Windows was installed
+/// (and whatever it's called in the system's language).
+fn program_files_x86_dir() -> Option<PathBuf> {
+    use std::ffi::OsString;
+    use std::os::windows::ffi::OsStringExt as _;
+    use std::{ptr, slice};
+
+    use windows_sys::Win32::System::Com::CoTaskMemFree;
+    use windows_sys::Win32::UI::Shell::{
+        FOLDERID_ProgramFilesX86,
+        KF_FLAG_DEFAULT,
+        SHGetKnownFolderPath,
+    };
+
+    let mut path = ptr::null_mut();
+    // SAFETY: `path` is a valid out pointer, and no token is needed for the current user
+    let result = unsafe {
+        SHGetKnownFolderPath(
+            &FOLDERID_ProgramFilesX86,
+            KF_FLAG_DEFAULT as u32,
+            ptr::null_mut(),
+            &mut path,
+        )
+    };
+
+    // SAFETY: On success, `path` is a null-terminated string that's freed right after copying
+    // (and it needs to be freed even if the call failed)
+    let dir = unsafe {
+        let dir = (result >= 0).then(|| {
+            let len = (0..).take_while(|&i| *path.add(i) != 0).count();
+            OsString::from_wide(slice::from_raw_parts(path, len))
+        });
+        CoTaskMemFree(path.cast());
+        dir
+    };
+
+    if dir.is_none() {
+        debug!("Failed to find the `Program Files (x86)` known folder: {result:#010x}");
+    }
+    dir.map(PathBuf::from)
+}
+
+/// Registry values the Steam installer records the install directory in, in order of preference:
+/// the name of the root key, then the key and value within it
+const INSTALL_DIR_VALUES: [(&str, &str, &str); 2] = [
+    ("HKLM", r"SOFTWARE\WOW6432Node\Valve\Steam", "InstallPath"),
+    ("HKCU", r"Software\Valve\Steam", "SteamPath"),
+];
+
+/// Find the Steam install directory recorded in the registry by the Steam installer.
+pub fn install_dir_from_registry() -> Option<PathBuf> {
+    use windows_registry::{CURRENT_USER, LOCAL_MACHINE};
+
+    install_dir_from_values(|root_name, key, value| {
+        let root = if root_name == "HKLM" {
+            LOCAL_MACHINE
+        } else {
+            CURRENT_USER
+        };
+        root.open(key).and_then(|key| key.get_string(value))
+    })
+}
+
+/// Find the Steam install directory in the first of [`INSTALL_DIR_VALUES`]
+/// that `read_value` (given the root key's name, the key, and the value) can read.
+fn install_dir_from_values<E: Display>(
+    read_value: impl Fn(&str, &str, &str) -> Result<String, E>,
+) -> Option<PathBuf> {
+    for (root_name, key, value) in INSTALL_DIR_VALUES {
+        let install_dir = match read_value(root_name, key, value) {
+            Ok(install_dir) => install_dir,
+            Err(error) => {
+                debug!(r"Failed to read `{root_name}\{key}\{value}` from the registry: {error}");
+                continue;
+            }
+        };
+
+        // `SteamPath` is written with forward slashes
+        let install_dir = PathBuf::from(install_dir.replace('/', r"\"));
+        info!(
+            r"Found Steam install directory `{}` in `{root_name}\{key}\{value}`",
+            install_dir.display()
+        );
+        return Some(install_dir);
+    }
+
+    None
+}
+
+/// Find the Steam install the icon directory belongs to,
+/// falling back to the one recorded in the registry.
+pub fn steam_install_dir(local_icon_dir: &Path) -> Result<PathBuf> {
+    find_steam_install_dir(local_icon_dir, install_dir_from_registry)
+}
+
+/// Find the Steam install the icon directory belongs to,
+/// falling back to the one `install_dir_from_registry` finds.
+fn find_steam_install_dir(
+    local_icon_dir: &Path,
+    install_dir_from_registry: impl FnOnce() -> Option<PathBuf>,
+) -> Result<PathBuf> {
+    // The icon directory is normally `steam\games` within the install
+    let install_dir = local_icon_dir
+        .parent()
+        .and_then(Path::parent)
+        .filter(|install_dir| install_dir.join("steamapps").is_dir());
+    match install_dir {
+        Some(install_dir) => Ok(install_dir.to_owned()),
+        None => install_dir_from_registry().context("Failed to find the Steam install"),
+    }
+}
+
+/// Directory within a Steam install that game icons are loaded from.
+pub fn icon_dir(install_dir: &Path) -> PathBuf {
+    install_dir.join("steam").join("games")
+}
+
+/// Desktops Steam creates shortcuts on:
+/// the current user's (wherever it's been moved to), then the one shared by all users.
+pub fn desktop_dirs() -> Result<Vec<PathBuf>> {
+    use windows_registry::{CURRENT_USER, LOCAL_MACHINE};
+
+    // Where Explorer keeps the (possibly redirected) locations of special folders,
+    // along with their usual values in case they can't be read
+    const KEY: &str = r"Software\Microsoft\Windows\CurrentVersion\Explorer\User Shell Folders";
+    let sources = [
+        (CURRENT_USER, "HKCU", "Desktop", r"%USERPROFILE%\Desktop"),
+        (LOCAL_MACHINE, "HKLM", "Common Desktop", r"%PUBLIC%\Desktop"),
+    ];
+
+    let mut dirs = Vec::new();
+    for (root, root_name, value, default) in sources {
+        let dir = match root.open(KEY).and_then(|key| key.get_string(value)) {
+            Ok(dir) => dir,
+            Err(error) => {
+                debug!(r"Failed to read `{root_name}\{KEY}\{value}` from the registry: {error}");
+                default.to_owned()
+            }
+        };
+
+        // The locations usually refer to environment variables
+        let dir = paths::expand_env_vars(&dir, |name| env::var(name).ok())
+            .with_context(|| format!("Failed to resolve the `{value}` folder `{dir}`"))?;
+        dirs.push(PathBuf::from(dir));
+    }
+
+    Ok(dirs)
+}
+
+/// Desktops of every local user, based on where their profiles are kept.
+/// Relocated desktops can't be found without loading each user's registry,
+/// so only the usual location within each profile is checked,
+/// along with wherever OneDrive would've moved it to.
+pub fn all_users_desktop_dirs() -> Result<Vec<PathBuf>> {
+    // Profiles live alongside the Public profile (usually in `C:\Users`)
+    let profiles_dir = match env::var_os("PUBLIC") {
+        Some(public) => Path::new(&public)
+            .parent()
+            .context("`PUBLIC` has no parent directory")?
+            .to_owned(),
+        None => {
+            let system_drive = env::var("SystemDrive").context("`SystemDrive` is not set")?;
+            PathBuf::from(format!(r"{system_drive}\Users"))
+        }
+    };
+
+    // Profiles that aren't actual users
+    // (`Default User` and `All Users` are links, but may show up as directories anyway)
+    const SPECIAL_PROFILES: [&str; 6] = [
+        "All Users",
+        "Default",
+        "Default User",
+        "defaultuser0",
+        "Public",
+        "WDAGUtilityAccount",
+    ];
+
+    let entries = profiles_dir.read_dir().with_context(|| {
+        format!(
+            "Failed to list user profiles in `{}`",
+            profiles_dir.display()
+        )
+    })?;
+    let mut dirs = Vec::new();
+    for entry in entries {
+        // One unreadable profile shouldn't keep the others from being found
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(error) => {
+                warn!(
+                    "Skipping unreadable profile in `{}`: {error}",
+                    profiles_dir.display()
+                );
+                continue;
+            }
+        };
+        let name = entry.file_name();
+        let is_special = SPECIAL_PROFILES
+            .iter()
+            .any(|special| name.to_string_lossy().eq_ignore_ascii_case(special));
+        let is_dir = match entry.file_type() {
+            Ok(file_type) => file_type.is_dir(),
+            Err(error) => {
+                warn!(
+                    "Skipping profile `{}`, which couldn't be checked: {error}",
+                    entry.path().display()
+                );
+                continue;
+            }
+        };
+        if is_special || !is_dir {
+            debug!("Skipping profile `{}`", entry.path().display());
+            continue;
+        }
+        dirs.push(entry.path().join("Desktop"));
+
+        // OneDrive folders are named after the account (e.g. `OneDrive - Contoso`)
+        let Ok(profile_entries) = entry.path().read_dir() else {
+            continue;
+        };
+        for profile_entry in profile_entries.flatten() {
+            if profile_entry
+                .file_name()
+                .to_string_lossy()
+                .starts_with("OneDrive")
+            {
+                dirs.push(profile_entry.path().join("Desktop"));
+            }
+        }
+    }
+
+    Ok(dirs)
+}
+
+/// Directories Steam creates Start Menu shortcuts in:
+/// the current user's Start Menu, then the one shared by all users.
+pub fn start_menu_dirs() -> Result<Vec<PathBuf>> {
+    let start_menus = [
+        env::var_os("APPDATA").context("`APPDATA` is not set")?,
+        env::var_os("ProgramData").context("`ProgramData` is not set")?,
+    ];
+    Ok(start_menus
+        .into_iter()
+        .map(|dir| PathBuf::from(dir).join(r"Microsoft\Windows\Start Menu\Programs\Steam"))
+        .collect())
+}
+
+/// Directory to keep this script's cache in,
+/// e.g. `%LOCALAPPDATA%\retrieve-missing-steam-game-icons`.
+pub fn cache_dir() -> Result<PathBuf> {
+    let local_app_data = env::var_os("LOCALAPPDATA").context("`LOCALAPPDATA` is not set")?;
+    Ok(PathBuf::from(local_app_data).join(env!("CARGO_PKG_NAME")))
+}
+
+/// Directory to save icons in when the icon directory can't be written to,
+/// e.g. `%LOCALAPPDATA%\SteamGameIcons`.
+pub fn user_icon_dir() -> Result<PathBuf> {
+    let local_app_data = env::var_os("LOCALAPPDATA").context("`LOCALAPPDATA` is not set")?;
+    Ok(PathBuf::from(local_app_data).join("SteamGameIcons"))
+}
+
+/// Directory Explorer caches the icons it shows in (as `iconcache_*.db`),
+/// e.g. `%LOCALAPPDATA%\Microsoft\Windows\Explorer`.
+pub fn icon_cache_dir() -> Result<PathBuf> {
+    let local_app_data = env::var_os("LOCALAPPDATA").context("`LOCALAPPDATA` is not set")?;
+    Ok(PathBuf::from(local_app_data).join(r"Microsoft\Windows\Explorer"))
+}
+
+/// Whether `metadata` is for a cloud-only placeholder (e.g. from OneDrive),
+/// which is downloaded when opened.
+pub fn is_cloud_placeholder(metadata: &Metadata) -> bool {
+    const FILE_ATTRIBUTE_OFFLINE: u32 = 0x1000;
+    const FILE_ATTRIBUTE_RECALL_ON_OPEN: u32 = 0x40000;
+    const FILE_ATTRIBUTE_RECALL_ON_DATA_ACCESS: u32 = 0x400000;
+    metadata.file_attributes()
+        & (FILE_ATTRIBUTE_OFFLINE
+            | FILE_ATTRIBUTE_RECALL_ON_OPEN
+            | FILE_ATTRIBUTE_RECALL_ON_DATA_ACCESS)
+        != 0
+}
+
+/// Read the `.url` shortcut file at `path`.
+pub fn read_shortcut(path: &Path, local_icon_dir: &Path) -> Result<Shortcut> {
+    let file = File::open(paths::to_long_path(path)).context("Failed to open file")?;
+    let shortcut_dir = path.parent().unwrap_or(path);
+    shortcut::parse_internet_shortcut(BufReader::new(file), shortcut_dir, local_icon_dir)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    /// Stand-in for the registry, holding `values` (by the name of their root key).
+    fn registry<'a>(
+        values: &'a [(&'a str, &'a str)],
+    ) -> impl Fn(&str, &str, &str) -> Result<String, String> + 'a {
+        move |root_name, key, value| {
+            values
+                .iter()
+                .find(|(name, _)| *name == root_name)
+                .map(|(_, install_dir)| (*install_dir).to_owned())
+                .ok_or_else(|| format!(r"`{root_name}\{key}\{value}` not found"))
+        }
+    }
+
+    #[test]
+    fn prefers_install_path_for_all_users() {
+        let values = [
+            ("HKCU", "c:/users/steam"),
+            ("HKLM", r"C:\Program Files (x86)\Steam"),
+        ];
+        assert_eq!(
+            install_dir_from_values(registry(&values)),
+            Some(PathBuf::from(r"C:\Program Files (x86)\Steam"))
+        );
+    }
+
+    #[test]
+    fn falls_back_to_steam_path_for_current_user() {
+        let values = [("HKCU", "d:/games/steam")];
+        assert_eq!(
+            install_dir_from_values(registry(&values)),
+            Some(PathBuf::from(r"d:\games\steam"))
+        );
+    }
+
+    #[test]
+    fn finds_no_install_dir_without_registry_values() {
+        assert_eq!(install_dir_from_values(registry(&[])), None);
+    }
+
+    #[test]
+    fn finds_install_containing_icon_dir() {
+        let dir = tempfile::TempDir::new().unwrap();
+        fs::create_dir(dir.path().join("steamapps")).unwrap();
+        let install_dir = find_steam_install_dir(&icon_dir(dir.path()), || {
+            panic!("The registry shouldn't be needed")
+        });
+        assert_eq!(install_dir.unwrap(), dir.path());
+    }
+
+    #[test]
+    fn falls_back_to_install_in_registry() {
+        // The icon directory isn't within an install (e.g. it was given with `--icon-dir`)
+        let dir = tempfile::TempDir::new().unwrap();
+        let install_dir = find_steam_install_dir(&icon_dir(dir.path()), || {
+            Some(PathBuf::from(r"C:\Program Files (x86)\Steam"))
+        });
+        assert_eq!(
+            install_dir.unwrap(),
+            PathBuf::from(r"C:\Program Files (x86)\Steam")
+        );
+
+        let error = find_steam_install_dir(&icon_dir(dir.path()), || None).unwrap_err();
+        assert_eq!(error.to_string(), "Failed to find the Steam install");
+    }
+
+    #[test]
+    fn uses_first_way_that_finds_icon_dir() {
+        let registry = PathBuf::from(r"D:\Steam\steam\games");
+        let resolvers: [(&str, &dyn Fn() -> Option<PathBuf>); 2] = [
+            ("the registry", &|| Some(registry.clone())),
+            ("the default", &|| {
+                panic!("Only the first way should be tried")
+            }),
+        ];
+        assert_eq!(resolve_icon_dir(&resolvers), Some(registry));
+    }
+
+    #[test]
+    fn falls_back_to_program_files_then_default() {
+        let program_files = PathBuf::from(r"E:\Programme (x86)");
+        let resolvers: [(&str, &dyn Fn() -> Option<PathBuf>); 3] = [
+            ("the registry", &|| None),
+            ("the `Program Files (x86)` known folder", &|| {
+                Some(icon_dir(&program_files.join("Steam")))
+            }),
+            ("the default", &|| Some(PathBuf::from(LOCAL_ICON_DIR))),
+        ];
+        assert_eq!(
+            resolve_icon_dir(&resolvers),
+            Some(PathBuf::from(r"E:\Programme (x86)\Steam\steam\games"))
+        );
+
+        let resolvers: [(&str, &dyn Fn() -> Option<PathBuf>); 3] = [
+            ("the registry", &|| None),
+            ("the `Program Files (x86)` known folder", &|| None),
+            ("the default", &|| Some(PathBuf::from(LOCAL_ICON_DIR))),
+        ];
+        assert_eq!(
+            resolve_icon_dir(&resolvers),
+            Some(PathBuf::from(LOCAL_ICON_DIR))
+        );
+    }
+
+    #[test]
+    fn finds_no_icon_dir_without_any_way() {
+        let resolvers: [(&str, &dyn Fn() -> Option<PathBuf>); 1] = [("the registry", &|| None)];
+        assert_eq!(resolve_icon_dir(&resolvers), None);
+    }
+}
@@ -0,0 +1,150 @@
+use anyhow::{Result, bail};
+
+/// Bytes every PNG file starts with
+pub const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+
+/// Largest amount of data a stored (uncompressed) deflate block can hold
+const MAX_STORED_BLOCK_LEN: usize = u16::MAX as usize;
+
+/// Read the dimensions of a PNG from its header.
+pub fn dimensions(png: &[u8]) -> Result<(u32, u32)> {
+    // The header chunk always comes first, right after the signature
+    if !png.starts_with(&SIGNATURE) || png.len() < SIGNATURE.len() + 16 || &png[12..16] != b"IHDR" {
+        bail!("Missing PNG header");
+    }
+    let read_u32 = |offset: usize| {
+        u32::from_be_bytes([
+            png[offset],
+            png[offset + 1],
+            png[offset + 2],
+            png[offset + 3],
+        ])
+    };
+    Ok((read_u32(16), read_u32(20)))
+}
+
+/// Encode 8-bit RGBA pixels (top row first) as a PNG.
+/// The pixel data is stored uncompressed, which is fine for icon-sized images.
+pub fn encode_rgba(width: u32, height: u32, rgba: &[u8]) -> Vec<u8> {
+    let row_len = width as usize * 4;
+    debug_assert_eq!(rgba.len(), row_len * height as usize);
+
+    // Header: dimensions, 8 bits per channel, RGBA, no interlacing
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&width.to_be_bytes());
+    header.extend_from_slice(&height.to_be_bytes());
+    header.extend_from_slice(&[8, 6, 0, 0, 0]);
+
+    // Each row is prefixed by its filter type (none)
+    let mut raw = Vec::with_capacity(rgba.len() + height as usize);
+    for row in rgba.chunks_exact(row_len.max(1)) {
+        raw.push(0);
+        raw.extend_from_slice(row);
+    }
+
+    let mut png = SIGNATURE.to_vec();
+    write_chunk(&mut png, b"IHDR", &header);
+    write_chunk(&mut png, b"IDAT", &zlib_stored(&raw));
+    write_chunk(&mut png, b"IEND", &[]);
+    png
+}
+
+/// Append a chunk with the given type and data.
+fn write_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    let crc = crc32(&png[start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+/// Wrap `data` in a zlib stream without compressing it.
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut zlib = vec![0x78, 0x01];
+    let mut blocks = data.chunks(MAX_STORED_BLOCK_LEN).peekable();
+    if blocks.peek().is_none() {
+        // An empty stream still needs a (final) block
+        zlib.extend_from_slice(&[1, 0x00, 0x00, 0xff, 0xff]);
+    }
+    while let Some(block) = blocks.next() {
+        let is_final = blocks.peek().is_none();
+        let len = block.len() as u16;
+        zlib.push(u8::from(is_final));
+        zlib.extend_from_slice(&len.to_le_bytes());
+        zlib.extend_from_slice(&(!len).to_le_bytes());
+        zlib.extend_from_slice(block);
+    }
+    zlib.extend_from_slice(&adler32(data).to_be_bytes());
+    zlib
+}
+
+/// CRC-32 checksum used by PNG chunks.
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xedb8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+/// Adler-32 checksum used by zlib streams.
+fn adler32(data: &[u8]) -> u32 {
+    const MODULUS: u32 = 65521;
+
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in data {
+        a = (a + u32::from(byte)) % MODULUS;
+        b = (b + a) % MODULUS;
+    }
+    (b << 16) | a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_dimensions_of_encoded_png() {
+        let png = encode_rgba(3, 2, &[0; 3 * 2 * 4]);
+        assert!(png.starts_with(&SIGNATURE));
+        assert_eq!(dimensions(&png).unwrap(), (3, 2));
+    }
+
+    #[test]
+    fn rejects_dimensions_without_header() {
+        let png = encode_rgba(3, 2, &[0; 3 * 2 * 4]);
+        assert!(dimensions(&png[..23]).is_err());
+        assert!(dimensions(&png[1..]).is_err());
+        assert!(dimensions(b"").is_err());
+
+        let mut other_chunk = png.clone();
+        other_chunk[12..16].copy_from_slice(b"IDAT");
+        assert!(dimensions(&other_chunk).is_err());
+    }
+
+    #[test]
+    fn checksums_match_known_values() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+        assert_eq!(adler32(b""), 1);
+        assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
+    }
+
+    #[test]
+    fn stores_large_images_across_blocks() {
+        // Rows of 64 KiB push the pixel data past a single stored block
+        let (width, height) = (16384, 2);
+        let png = encode_rgba(width, height, &vec![0x7f; 16384 * 2 * 4]);
+        assert_eq!(dimensions(&png).unwrap(), (width, height));
+        let zlib = zlib_stored(&vec![0; MAX_STORED_BLOCK_LEN + 1]);
+        // Two block headers, both blocks, and the checksum
+        assert_eq!(zlib.len(), 2 + 5 + MAX_STORED_BLOCK_LEN + 5 + 1 + 4);
+        assert_eq!(zlib[2], 0);
+        assert_eq!(zlib[2 + 5 + MAX_STORED_BLOCK_LEN], 1);
+    }
+}
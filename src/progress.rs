@@ -0,0 +1,134 @@
+use std::io::{self, Write};
+use std::sync::{Mutex, MutexGuard};
+
+use crate::report::{Action, ItemReport, RunReport};
+
+/// Width of the bar itself, in characters
+const BAR_WIDTH: usize = 30;
+
+/// Progress bar kept on the last line of stderr, if one is being shown.
+/// Global so log output (which is also global) can be written around it.
+static PROGRESS: Mutex<Option<Progress>> = Mutex::new(None);
+
+/// State of the progress bar.
+struct Progress {
+    /// Number of shortcuts to process
+    total: usize,
+
+    /// Number of shortcuts processed so far
+    position: usize,
+
+    /// Details shown next to the bar
+    message: String,
+
+    /// Whether the bar is currently on screen
+    drawn: bool,
+}
+
+impl Progress {
+    /// Draw the bar on the current line of stderr.
+    fn draw(&mut self, stderr: &mut impl Write) -> io::Result<()> {
+        let filled = (BAR_WIDTH * self.position)
+            .checked_div(self.total)
+            .unwrap_or(BAR_WIDTH);
+        write!(
+            stderr,
+            "\r\x1b[2K[{}{}] {}/{} {}",
+            "#".repeat(filled),
+            "-".repeat(BAR_WIDTH - filled),
+            self.position,
+            self.total,
+            self.message
+        )?;
+        stderr.flush()?;
+        self.drawn = true;
+        Ok(())
+    }
+
+    /// Remove the bar from the current line of stderr.
+    fn clear(&mut self, stderr: &mut impl Write) -> io::Result<()> {
+        if self.drawn {
+            write!(stderr, "\r\x1b[2K")?;
+            self.drawn = false;
+        }
+        Ok(())
+    }
+}
+
+/// Lock the global progress bar, even if a panic occurred while drawing it.
+fn lock() -> MutexGuard<'static, Option<Progress>> {
+    PROGRESS.lock().unwrap_or_else(|error| error.into_inner())
+}
+
+/// Start showing a progress bar for `total` shortcuts.
+pub fn start(total: usize) {
+    let mut progress = Progress {
+        total,
+        position: 0,
+        message: String::new(),
+        drawn: false,
+    };
+    let _ = progress.draw(&mut io::stderr());
+    *lock() = Some(progress);
+}
+
+/// Update the progress bar after a shortcut has been processed.
+pub fn update(report: &RunReport) {
+    let mut progress = lock();
+    let Some(progress) = progress.as_mut() else {
+        return;
+    };
+
+    let game = match report.items.last().and_then(ItemReport::describe_game) {
+        Some(game) => format!("{game} | "),
+        None => String::new(),
+    };
+    progress.position = report.items.len();
+    progress.message = format!(
+        "{game}downloaded {}, skipped {}, failed {}",
+        report.count(Action::Downloaded)
+            + report.count(Action::Redownloaded)
+            + report.count(Action::Repaired)
+            + report.count(Action::Updated),
+        report.count(Action::Skipped)
+            + report.count(Action::FilteredOut)
+            + report.count(Action::Excluded)
+            + report.count(Action::NonSteam)
+            + report.count(Action::Duplicate)
+            + report.count(Action::KnownMissing)
+            + report.count(Action::AlreadyExists)
+            + report.count(Action::Unchanged)
+            + report.count(Action::OverBudget),
+        report.failed()
+    );
+    let _ = progress.draw(&mut io::stderr());
+}
+
+/// Stop showing the progress bar.
+pub fn finish() {
+    if let Some(mut progress) = lock().take() {
+        let _ = progress.clear(&mut io::stderr());
+    }
+}
+
+/// Log output target that keeps the progress bar below the log lines.
+pub struct LogWriter;
+
+impl Write for LogWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut stderr = io::stderr();
+        if let Some(progress) = lock().as_mut() {
+            progress.clear(&mut stderr)?;
+        }
+        stderr.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let mut stderr = io::stderr();
+        stderr.flush()?;
+        match lock().as_mut() {
+            Some(progress) => progress.draw(&mut stderr),
+            None => Ok(()),
+        }
+    }
+}
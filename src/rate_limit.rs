@@ -0,0 +1,258 @@
+//! Spacing out requests to Steam, so they don't go out back to back,
+//! backing off when Steam says there have been too many,
+//! and capping how fast icons are downloaded.
+
+use std::error::Error;
+use std::fmt::{self, Display};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::Result;
+use log::*;
+use reqwest::header::RETRY_AFTER;
+use reqwest::{RequestBuilder, Response, StatusCode};
+use tokio::time::{self, Instant};
+
+use crate::download;
+
+/// How long to pause for when rate limited without being told how long to wait
+const DEFAULT_PAUSE: Duration = Duration::from_secs(60);
+
+/// Longest pause to honor, however long Steam asks for
+const MAX_PAUSE: Duration = Duration::from_secs(10 * 60);
+
+/// Error for when Steam kept rate limiting requests after pausing for it repeatedly.
+#[derive(Debug)]
+pub struct RateLimited {
+    /// How many pauses in a row there were
+    pub pauses: u32,
+}
+
+impl Display for RateLimited {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Steam is still rate limiting requests after pausing {} time(s) in a row (try again \
+             later)",
+            self.pauses
+        )
+    }
+}
+
+impl Error for RateLimited {}
+
+/// Limits how often requests are made.
+/// Clones share the same budget, so everything making requests can be limited together.
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    /// Minimum time between requests (nothing is spaced out if zero)
+    interval: Duration,
+
+    /// How many times in a row requests can be paused for being rate limited
+    max_pauses: u32,
+
+    /// When the next request may be made, and what's been paused for
+    state: Arc<Mutex<State>>,
+}
+
+/// Where a [`RateLimiter`] is up to.
+#[derive(Debug)]
+struct State {
+    /// When the next request may be made
+    next: Instant,
+
+    /// Number of times requests have been paused, to tell which pause a response belongs to
+    pauses: u32,
+
+    /// Number of times in a row requests have been paused
+    consecutive_pauses: u32,
+}
+
+impl RateLimiter {
+    /// Limiter allowing a request every `interval`,
+    /// and pausing up to `max_pauses` times in a row when rate limited.
+    pub fn new(interval: Duration, max_pauses: u32) -> Self {
+        Self {
+            interval,
+            max_pauses,
+            state: Arc::new(Mutex::new(State {
+                next: Instant::now(),
+                pauses: 0,
+                consecutive_pauses: 0,
+            })),
+        }
+    }
+
+    /// Wait until another request may be made.
+    /// Each call reserves its own turn, so requests made at once are spaced out too
+    /// (and no more than one request per interval is made, however many are made at once).
+    /// Dropping the future (e.g. when the script needs to exit) stops waiting.
+    pub async fn wait(&self) {
+        let turn = {
+            let mut state = self.lock();
+            let turn = state.next.max(Instant::now());
+            state.next = turn + self.interval;
+            turn
+        };
+        time::sleep_until(turn).await;
+    }
+
+    /// Send the request built by `request` once it's allowed,
+    /// pausing every request and sending it again whenever Steam says there have been too many
+    /// (failing with [`RateLimited`] if that keeps happening).
+    pub async fn send(&self, request: impl Fn() -> RequestBuilder) -> Result<Response> {
+        loop {
+            self.wait().await;
+            let pauses = self.lock().pauses;
+            let response = request().send().await.map_err(download::request_error)?;
+            if response.status() != StatusCode::TOO_MANY_REQUESTS {
+                self.lock().consecutive_pauses = 0;
+                return Ok(response);
+            }
+
+            let pause = response
+                .headers()
+                .get(RETRY_AFTER)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| parse_retry_after(value, SystemTime::now()))
+                .unwrap_or(DEFAULT_PAUSE)
+                .min(MAX_PAUSE);
+            self.pause(pauses, pause)?;
+        }
+    }
+
+    /// Hold off every request for `pause` after one sent during pause number `pauses` was
+    /// rate limited.
+    /// Responses to requests sent before the latest pause began don't count towards the limit,
+    /// since they were probably rate limited for the same reason.
+    fn pause(&self, pauses: u32, pause: Duration) -> Result<()> {
+        let mut state = self.lock();
+        if state.pauses == pauses {
+            state.pauses += 1;
+            state.consecutive_pauses += 1;
+            if state.consecutive_pauses > self.max_pauses {
+                return Err(RateLimited {
+                    pauses: self.max_pauses,
+                }
+                .into());
+            }
+            warn!(
+                "Steam is rate limiting requests, pausing them for {:.1}s",
+                pause.as_secs_f32()
+            );
+        }
+        state.next = state.next.max(Instant::now() + pause);
+        Ok(())
+    }
+
+    /// Lock the state, even if a panic poisoned it (it's always left consistent).
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|error| error.into_inner())
+    }
+}
+
+/// Limits how many bytes are downloaded per second, using a token bucket.
+/// Clones share the same bucket, so the total rate of every download at once is what's capped.
+#[derive(Debug, Clone)]
+pub struct BandwidthLimiter {
+    /// Bytes allowed per second (which is also how many can be downloaded in a burst)
+    rate: u64,
+
+    /// Bytes that can be downloaded right away, and when that was last worked out
+    bucket: Arc<Mutex<Bucket>>,
+}
+
+/// Where a [`BandwidthLimiter`] is up to.
+#[derive(Debug)]
+struct Bucket {
+    /// Bytes that can be downloaded right away (negative when downloads are owed time)
+    tokens: f64,
+
+    /// When the tokens were last topped up
+    updated: Instant,
+}
+
+impl BandwidthLimiter {
+    /// Limiter allowing `rate` bytes per second.
+    pub fn new(rate: u64) -> Self {
+        Self {
+            rate,
+            bucket: Arc::new(Mutex::new(Bucket {
+                tokens: rate as f64,
+                updated: Instant::now(),
+            })),
+        }
+    }
+
+    /// Wait until `bytes` that were just downloaded are within the limit.
+    /// The bytes are taken from the bucket straight away,
+    /// so downloads waiting at once queue up behind each other instead of all waking together.
+    /// Dropping the future (e.g. when the script needs to exit) stops waiting.
+    pub async fn consume(&self, bytes: usize) {
+        let wait = {
+            let mut bucket = self
+                .bucket
+                .lock()
+                .unwrap_or_else(|error| error.into_inner());
+            let now = Instant::now();
+            let rate = self.rate as f64;
+            let refill = now.duration_since(bucket.updated).as_secs_f64() * rate;
+            bucket.tokens = (bucket.tokens + refill).min(rate) - bytes as f64;
+            bucket.updated = now;
+            Duration::from_secs_f64((-bucket.tokens).max(0.0) / rate)
+        };
+        if !wait.is_zero() {
+            time::sleep(wait).await;
+        }
+    }
+}
+
+/// How long a `Retry-After` header `value` says to wait from `now`,
+/// whether it's a number of seconds or an HTTP date (e.g. `Sun, 06 Nov 1994 08:49:37 GMT`).
+fn parse_retry_after(value: &str, now: SystemTime) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse() {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    // Dates in the past mean there's no need to wait
+    let date = UNIX_EPOCH + Duration::from_secs(parse_http_date(value)?);
+    Some(date.duration_since(now).unwrap_or_default())
+}
+
+/// Seconds since the Unix epoch of an HTTP date in the preferred format
+/// (e.g. `Sun, 06 Nov 1994 08:49:37 GMT`).
+pub fn parse_http_date(date: &str) -> Option<u64> {
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+
+    let [_, day, month, year, time, "GMT"] = date.split_whitespace().collect::<Vec<_>>()[..] else {
+        return None;
+    };
+    let day: u64 = day.parse().ok()?;
+    let month = MONTHS.iter().position(|name| *name == month)? as u64 + 1;
+    let year: u64 = year.parse().ok()?;
+    let [hours, minutes, seconds] = time.split(':').collect::<Vec<_>>()[..] else {
+        return None;
+    };
+    let (hours, minutes, seconds): (u64, u64, u64) = (
+        hours.parse().ok()?,
+        minutes.parse().ok()?,
+        seconds.parse().ok()?,
+    );
+    if year < 1970 || !(1..=31).contains(&day) || hours > 23 || minutes > 59 || seconds > 60 {
+        return None;
+    }
+
+    // Count the days since the epoch, with years starting in March so leap days come last
+    let (year, month) = if month <= 2 {
+        (year - 1, month + 9)
+    } else {
+        (year, month - 3)
+    };
+    let day_of_year = (153 * month + 2) / 5 + day - 1;
+    let days = year * 365 + year / 4 - year / 100 + year / 400 + day_of_year - 719_468;
+
+    Some(days * 86_400 + hours * 3_600 + minutes * 60 + seconds)
+}
@@ -0,0 +1,756 @@
+//! Repairing `.url` shortcuts that keep Steam's icons from showing, one kind of problem at a time.
+//! Only shortcuts that are fully understood are ever repaired, and every repair is checked by
+//! parsing the result before anything is written.
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::OnceLock;
+use std::{env, fs};
+
+use anyhow::{Context as _, Result, bail};
+use log::*;
+use regex::{Captures, Regex};
+
+use crate::paths;
+use crate::shortcut::{self, Encoding};
+
+/// Extension of the shortcuts that can be repaired
+pub const EXTENSION: &str = ".url";
+
+/// Extension added to shortcuts backed up before being fixed
+pub const BACKUP_EXTENSION: &str = ".bak";
+
+/// Extension added to fixed shortcuts while they're being written
+const TEMP_EXTENSION: &str = ".tmp";
+
+/// Header of the section of a `.url` shortcut that says what it launches
+const SECTION: &str = "[InternetShortcut]";
+
+/// Something wrong with a `.url` shortcut that can be fixed, in the order they're fixed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ShortcutProblem {
+    /// A key is set more than once (to the same value)
+    DuplicateKeys,
+
+    /// There's no `IconFile` at all
+    MissingIconFile,
+
+    /// The `IconFile` is wrapped in quotes
+    QuotedIconFile,
+
+    /// The `IconFile` is in the icon directory, but not written the way Steam writes it
+    /// (e.g. as a short 8.3 path, or with environment variables)
+    UnnormalizedIconDir,
+
+    /// The `IconFile` is in a directory other than the icon directory
+    WrongIconDir,
+
+    /// The `IconFile` names an icon that doesn't exist, rather than the game's current one
+    MissingIcon,
+}
+
+impl ShortcutProblem {
+    /// Human-readable description of the problem.
+    pub fn describe(self) -> &'static str {
+        match self {
+            Self::DuplicateKeys => "duplicate keys",
+            Self::MissingIconFile => "no `IconFile`",
+            Self::QuotedIconFile => "quoted `IconFile`",
+            Self::UnnormalizedIconDir => "icon directory written differently",
+            Self::WrongIconDir => "wrong icon directory",
+            Self::MissingIcon => "icon that doesn't exist",
+        }
+    }
+}
+
+/// A `.url` shortcut's contents, before and after being repaired.
+pub struct Repair {
+    /// Encoding the shortcut is saved in (and written back in)
+    pub encoding: Encoding,
+
+    /// Contents of the shortcut as it was
+    pub original: String,
+
+    /// Contents of the shortcut once repaired
+    pub fixed: String,
+
+    /// What was wrong with the shortcut (nothing, if it's fine)
+    pub problems: Vec<ShortcutProblem>,
+}
+
+/// Read the `.url` shortcut at `path` and repair it (see [`repair`]), without writing anything.
+pub fn repair_shortcut(
+    path: &Path,
+    icon_dir: &Path,
+    lookup_icon: &mut impl FnMut(&str) -> Option<String>,
+) -> Result<Repair> {
+    let bytes = fs::read(paths::to_long_path(path)).context("Failed to read file")?;
+    let (encoding, _) = Encoding::detect(&bytes);
+    let original = shortcut::read_text(bytes.as_slice())?;
+    let (fixed, problems) = repair(
+        &original,
+        path.parent().unwrap_or(path),
+        icon_dir,
+        lookup_icon,
+    )?;
+    Ok(Repair {
+        encoding,
+        original,
+        fixed,
+        problems,
+    })
+}
+
+/// Fix every problem with the `.url` shortcut `text` (in `shortcut_dir`),
+/// so that its `IconFile` is the game's icon in `icon_dir`,
+/// returning the fixed shortcut along with what was wrong with it.
+/// `lookup_icon` finds the filename of a game's icon when the shortcut doesn't say
+/// (or names one that doesn't exist).
+///
+/// Shortcuts that aren't for Steam games are left as they are,
+/// and anything that isn't fully understood is an error, rather than being guessed at.
+pub fn repair(
+    text: &str,
+    shortcut_dir: &Path,
+    icon_dir: &Path,
+    lookup_icon: &mut impl FnMut(&str) -> Option<String>,
+) -> Result<(String, Vec<ShortcutProblem>)> {
+    let Some(game_id) = ensure_understood(text)? else {
+        return Ok((text.to_owned(), Vec::new()));
+    };
+
+    let mut fixed = text.to_owned();
+    let mut problems = Vec::new();
+    let mut apply = |problem, repaired: Option<String>, fixed: &mut String| {
+        if let Some(repaired) = repaired {
+            *fixed = repaired;
+            problems.push(problem);
+        }
+    };
+
+    apply(
+        ShortcutProblem::DuplicateKeys,
+        collapse_duplicate_keys(&fixed),
+        &mut fixed,
+    );
+    if icon_file(&fixed)?.is_none() {
+        let Some(icon_filename) = lookup_icon(&game_id) else {
+            bail!(
+                "Shortcut has no `IconFile`, and Steam doesn't say which icon game #{game_id} uses"
+            );
+        };
+        apply(
+            ShortcutProblem::MissingIconFile,
+            add_icon_file(&fixed, &icon_dir.join(icon_filename)),
+            &mut fixed,
+        );
+    }
+    apply(
+        ShortcutProblem::QuotedIconFile,
+        unquote_icon_file(&fixed)?,
+        &mut fixed,
+    );
+    apply(
+        ShortcutProblem::UnnormalizedIconDir,
+        normalize_icon_dir(&fixed, shortcut_dir, icon_dir)?,
+        &mut fixed,
+    );
+    apply(
+        ShortcutProblem::WrongIconDir,
+        fix_icon_dir(&fixed, shortcut_dir, icon_dir)?,
+        &mut fixed,
+    );
+    let icon_exists = icon_file(&fixed)?
+        .is_some_and(|(_, _, icon_filename)| icon_dir.join(icon_filename).exists());
+    if !icon_exists && let Some(icon_filename) = lookup_icon(&game_id) {
+        apply(
+            ShortcutProblem::MissingIcon,
+            fix_missing_icon(&fixed, icon_dir, &icon_filename)?,
+            &mut fixed,
+        );
+    }
+
+    // Make sure the repairs worked before anything is written
+    shortcut::parse_internet_shortcut(fixed.as_bytes(), shortcut_dir, icon_dir)
+        .context("Shortcut still can't be used once repaired")?;
+
+    Ok((fixed, problems))
+}
+
+/// Make sure everything in the `[InternetShortcut]` section of `text` is understood,
+/// returning the Steam game it launches (if any).
+/// Keys set more than once must be set to the same value every time,
+/// so there's no guessing which one counts.
+pub fn ensure_understood(text: &str) -> Result<Option<String>> {
+    static URL_REGEX: OnceLock<Regex> = OnceLock::new();
+    let url_regex = URL_REGEX.get_or_try_init(|| Regex::new(r"^steam://rungameid/(\d+)$"))?;
+
+    if !text.lines().any(|line| line.eq_ignore_ascii_case(SECTION)) {
+        bail!("Shortcut has no `{SECTION}` section");
+    }
+
+    let mut values = HashMap::new();
+    for (line, in_section) in section_lines(text) {
+        let Some((key, value)) = content(line).split_once('=').filter(|_| in_section) else {
+            continue;
+        };
+        if let Some(previous) = values.insert(key.to_lowercase(), value)
+            && previous != value
+        {
+            bail!("`{key}` is set more than once, to different values");
+        }
+        if key.eq_ignore_ascii_case("IconFile") {
+            parse_icon_file(content(line))?;
+        }
+    }
+
+    // Anything else (e.g. a link to a website, or a non-Steam game) has nothing to fix
+    let game_id = values
+        .get("url")
+        .and_then(|url| url_regex.captures(url))
+        .map(|captures| captures[1].to_owned());
+    Ok(game_id.filter(|game_id| shortcut::ensure_steam_game(game_id).is_ok()))
+}
+
+/// Remove every repeat of a key in the `[InternetShortcut]` section of `text`,
+/// keeping the first (which is the one Windows uses).
+pub fn collapse_duplicate_keys(text: &str) -> Option<String> {
+    let mut seen_keys = Vec::new();
+    let mut collapsed = false;
+    let mut fixed = String::with_capacity(text.len());
+    for (line, in_section) in section_lines(text) {
+        if in_section && let Some((key, _)) = content(line).split_once('=') {
+            let key = key.to_lowercase();
+            if seen_keys.contains(&key) {
+                collapsed = true;
+                continue;
+            }
+            seen_keys.push(key);
+        }
+        fixed.push_str(line);
+    }
+    collapsed.then_some(fixed)
+}
+
+/// Add an `IconFile` pointing at `icon_path` to the end of the `[InternetShortcut]` section
+/// of `text`, if it doesn't have one.
+pub fn add_icon_file(text: &str, icon_path: &Path) -> Option<String> {
+    let lines = section_lines(text);
+    let has_icon_file = lines.iter().any(|&(line, in_section)| {
+        in_section
+            && content(line)
+                .split_once('=')
+                .is_some_and(|(key, _)| key.eq_ignore_ascii_case("IconFile"))
+    });
+    if has_icon_file {
+        return None;
+    }
+
+    // Goes after the last line of the section that isn't blank
+    let header = lines
+        .iter()
+        .position(|&(line, _)| content(line).eq_ignore_ascii_case(SECTION))?;
+    let last = lines
+        .iter()
+        .enumerate()
+        .skip(header + 1)
+        .take_while(|&(_, &(_, in_section))| in_section)
+        .filter(|&(_, &(line, _))| !content(line).trim().is_empty())
+        .last()
+        .map_or(header, |(index, _)| index);
+
+    // Match the shortcut's line endings (Steam uses `\r\n`)
+    let line_ending = if text.contains("\r\n") || !text.contains('\n') {
+        "\r\n"
+    } else {
+        "\n"
+    };
+    let mut fixed = String::with_capacity(text.len() + icon_path.as_os_str().len() + 10);
+    for (index, &(line, _)) in lines.iter().enumerate() {
+        fixed.push_str(line);
+        if index == last {
+            if !line.ends_with('\n') {
+                fixed.push_str(line_ending);
+            }
+            fixed.push_str(&format!("IconFile={}{line_ending}", icon_path.display()));
+        }
+    }
+    Some(fixed)
+}
+
+/// Remove the quotes around the `IconFile` in `text`, if it has any.
+pub fn unquote_icon_file(text: &str) -> Result<Option<String>> {
+    rewrite_icon_file(text, |captures| {
+        (!captures[1].is_empty()).then(|| format!("{}{}", opt(captures, 2), &captures[3]))
+    })
+}
+
+/// Rewrite the directory of the `IconFile` in `text` the way Steam writes it,
+/// if it's the icon directory written another way
+/// (with relative directories relative to `shortcut_dir`).
+pub fn normalize_icon_dir(
+    text: &str,
+    shortcut_dir: &Path,
+    icon_dir: &Path,
+) -> Result<Option<String>> {
+    rewrite_icon_file(text, |captures| {
+        let expected = icon_dir.join(&captures[3]);
+        let current = format!("{}{}", opt(captures, 2), &captures[3]);
+        let in_icon_dir = resolve_dir(opt(captures, 2), shortcut_dir)
+            .is_some_and(|dir| paths::same_dir(&dir, icon_dir));
+        (in_icon_dir && current != expected.to_string_lossy())
+            .then(|| format!("{}{}{}", &captures[1], expected.display(), &captures[4]))
+    })
+}
+
+/// Point the `IconFile` in `text` at `icon_dir` (keeping its filename),
+/// if it's in another directory (with relative directories relative to `shortcut_dir`).
+pub fn fix_icon_dir(text: &str, shortcut_dir: &Path, icon_dir: &Path) -> Result<Option<String>> {
+    rewrite_icon_file(text, |captures| {
+        let in_icon_dir = resolve_dir(opt(captures, 2), shortcut_dir)
+            .is_some_and(|dir| paths::same_dir(&dir, icon_dir));
+        (!in_icon_dir).then(|| {
+            let icon_path = icon_dir.join(&captures[3]);
+            format!("{}{}{}", &captures[1], icon_path.display(), &captures[4])
+        })
+    })
+}
+
+/// Point the `IconFile` in `text` at `icon_filename` in `icon_dir`,
+/// if the icon it names doesn't exist in `icon_dir` (and isn't `icon_filename` already).
+pub fn fix_missing_icon(
+    text: &str,
+    icon_dir: &Path,
+    icon_filename: &str,
+) -> Result<Option<String>> {
+    rewrite_icon_file(text, |captures| {
+        let current = &captures[3];
+        let missing =
+            !current.eq_ignore_ascii_case(icon_filename) && !icon_dir.join(current).exists();
+        missing.then(|| {
+            let icon_path = icon_dir.join(icon_filename);
+            format!("{}{}{}", &captures[1], icon_path.display(), &captures[4])
+        })
+    })
+}
+
+/// Write `text` over the shortcut at `path` in `encoding`,
+/// keeping the original alongside it first if `backup`.
+/// The shortcut is written alongside the original and then swapped in,
+/// so an interruption can't leave a half-written shortcut behind.
+/// Links are written through, so they keep leading to the shortcut.
+pub fn write_shortcut(path: &Path, encoding: Encoding, text: &str, backup: bool) -> Result<()> {
+    let long_path = paths::to_long_path(path);
+    let path = long_path.as_path();
+    let target;
+    let path = if fs::symlink_metadata(path).is_ok_and(|metadata| metadata.is_symlink()) {
+        target = fs::canonicalize(path)
+            .with_context(|| format!("Failed to resolve link `{}`", path.display()))?;
+        target.as_path()
+    } else {
+        path
+    };
+
+    // Keep the first original, rather than replacing it with a later one
+    if backup {
+        let backup = paths::with_suffix(path, BACKUP_EXTENSION);
+        if backup.exists() {
+            debug!("Keeping existing backup {}", backup.display());
+        } else {
+            fs::copy(path, &backup)
+                .with_context(|| format!("Failed to back up to `{}`", backup.display()))?;
+        }
+    }
+
+    let temp = paths::with_suffix(path, TEMP_EXTENSION);
+    fs::write(&temp, encoding.encode(text))
+        .with_context(|| format!("Failed to write `{}`", temp.display()))?;
+    if let Err(error) = fs::rename(&temp, path) {
+        let _ = fs::remove_file(&temp);
+        return Err(error).context("Failed to replace shortcut");
+    }
+    Ok(())
+}
+
+/// Lines removed from `original` (starting with `-`) and added in `fixed` (starting with `+`),
+/// in order, for showing what a repair changed.
+pub fn diff(original: &str, fixed: &str) -> Vec<String> {
+    let original: Vec<_> = original.lines().collect();
+    let fixed: Vec<_> = fixed.lines().collect();
+
+    // Lengths of the longest common runs of lines from each pair of positions to the end
+    let mut common = vec![vec![0_usize; fixed.len() + 1]; original.len() + 1];
+    for i in (0..original.len()).rev() {
+        for j in (0..fixed.len()).rev() {
+            common[i][j] = if original[i] == fixed[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    let mut diff = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < original.len() || j < fixed.len() {
+        if i < original.len() && j < fixed.len() && original[i] == fixed[j] {
+            i += 1;
+            j += 1;
+        } else if i < original.len() && (j == fixed.len() || common[i + 1][j] >= common[i][j + 1]) {
+            diff.push(format!("-{}", original[i]));
+            i += 1;
+        } else {
+            diff.push(format!("+{}", fixed[j]));
+            j += 1;
+        }
+    }
+    diff
+}
+
+/// Lines of `text` (with their line endings),
+/// along with whether each is within the `[InternetShortcut]` section (not counting its header).
+fn section_lines(text: &str) -> Vec<(&str, bool)> {
+    let mut in_section = false;
+    text.split_inclusive('\n')
+        .map(|line| {
+            let content = content(line);
+            if content.eq_ignore_ascii_case(SECTION) {
+                in_section = true;
+                return (line, false);
+            } else if content.starts_with('[') {
+                in_section = false;
+            }
+            (line, in_section)
+        })
+        .collect()
+}
+
+/// `line` without its line ending.
+fn content(line: &str) -> &str {
+    line.trim_end_matches(['\r', '\n'])
+}
+
+/// The quote, directory, and filename of the first `IconFile` in the `[InternetShortcut]`
+/// section of `text`, if it has one.
+fn icon_file(text: &str) -> Result<Option<(String, String, String)>> {
+    for (line, in_section) in section_lines(text) {
+        if in_section && let Some(captures) = parse_icon_file(content(line))? {
+            return Ok(Some((
+                captures[1].to_owned(),
+                opt(&captures, 2).to_owned(),
+                captures[3].to_owned(),
+            )));
+        }
+    }
+    Ok(None)
+}
+
+/// Split the `IconFile` line `line` into its parts (see [`shortcut::icon_path_regex`]),
+/// or `None` if it's another key.
+/// Fails if it's an `IconFile` that isn't understood.
+fn parse_icon_file(line: &str) -> Result<Option<Captures<'_>>> {
+    let is_icon_file = line
+        .split_once('=')
+        .is_some_and(|(key, _)| key.eq_ignore_ascii_case("IconFile"));
+    if !is_icon_file {
+        return Ok(None);
+    }
+    let Some(captures) = shortcut::icon_path_regex()?.captures(line) else {
+        bail!("Unrecognized `{line}`");
+    };
+
+    // Quotes must come in pairs
+    if captures[1] != captures[4] {
+        bail!("Mismatched quotes around icon path in `{line}`");
+    }
+    Ok(Some(captures))
+}
+
+/// Replace the value of each `IconFile` in the `[InternetShortcut]` section of `text`
+/// with what `rewrite` returns for it (given its parts), keeping everything else as it was.
+/// Returns `None` if nothing was rewritten.
+fn rewrite_icon_file(
+    text: &str,
+    rewrite: impl Fn(&Captures) -> Option<String>,
+) -> Result<Option<String>> {
+    let mut rewritten = false;
+    let mut fixed = String::with_capacity(text.len());
+    for (line, in_section) in section_lines(text) {
+        let content = content(line);
+        if in_section
+            && let Some(captures) = parse_icon_file(content)?
+            && let Some(value) = rewrite(&captures)
+            && let Some((key, _)) = content.split_once('=')
+        {
+            fixed.push_str(&format!("{key}={value}{}", &line[content.len()..]));
+            rewritten = true;
+            continue;
+        }
+        fixed.push_str(line);
+    }
+    Ok(rewritten.then_some(fixed))
+}
+
+/// Directory `dir` from an `IconFile` refers to, after expanding variables like
+/// `%ProgramFiles(x86)%` (and relative to `shortcut_dir`),
+/// or `None` if it uses variables that aren't set.
+fn resolve_dir(dir: &str, shortcut_dir: &Path) -> Option<std::path::PathBuf> {
+    let dir = paths::expand_env_vars(dir, |name| env::var(name).ok()).ok()?;
+    Some(shortcut_dir.join(dir))
+}
+
+/// Text of the optional group `index` in `captures` (empty if it didn't match).
+fn opt<'a>(captures: &'a Captures, index: usize) -> &'a str {
+    captures.get(index).map_or("", |group| group.as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use tempfile::TempDir;
+
+    use super::*;
+
+    /// Icon Steam says the game uses
+    const ICON_FILENAME: &str = "0123456789abcdef0123456789abcdef01234567.ico";
+
+    /// Shortcut and icon directories, removed once dropped.
+    struct Layout {
+        /// Directory containing the others
+        _dir: TempDir,
+
+        /// Directory containing the shortcuts
+        shortcuts: PathBuf,
+
+        /// Directory icons are saved in
+        icons: PathBuf,
+    }
+
+    impl Layout {
+        fn new() -> Self {
+            let dir = TempDir::new().unwrap();
+            let shortcuts = dir.path().join("Desktop");
+            let icons = dir.path().join("icons");
+            fs::create_dir(&shortcuts).unwrap();
+            fs::create_dir(&icons).unwrap();
+            fs::write(icons.join(ICON_FILENAME), b"icon").unwrap();
+            Self {
+                _dir: dir,
+                shortcuts,
+                icons,
+            }
+        }
+
+        /// `IconFile` line pointing at `icon_filename` in the icon directory.
+        fn icon_file(&self, icon_filename: &str) -> String {
+            format!("IconFile={}", self.icons.join(icon_filename).display())
+        }
+
+        /// Save a shortcut with `contents` as `Game.url`, repair it
+        /// (with Steam saying the game uses `ICON_FILENAME`), and write it back,
+        /// returning what was wrong with it and the bytes written.
+        fn fix(&self, contents: &[u8], backup: bool) -> (Vec<ShortcutProblem>, Vec<u8>) {
+            let path = self.shortcuts.join("Game.url");
+            fs::write(&path, contents).unwrap();
+            let repair =
+                repair_shortcut(&path, &self.icons, &mut |_| Some(ICON_FILENAME.to_owned()))
+                    .unwrap();
+            write_shortcut(&path, repair.encoding, &repair.fixed, backup).unwrap();
+            (repair.problems, fs::read(&path).unwrap())
+        }
+
+        /// Names of everything in the shortcut directory.
+        fn shortcut_dir_contents(&self) -> Vec<String> {
+            let mut names: Vec<_> = fs::read_dir(&self.shortcuts)
+                .unwrap()
+                .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+                .collect();
+            names.sort();
+            names
+        }
+    }
+
+    /// Shortcut for game #440 like the ones Steam creates,
+    /// with `icon_file` (if any) and lines ending with `line_ending`.
+    fn shortcut(icon_file: Option<&str>, line_ending: &str) -> String {
+        let mut lines = vec![
+            "[{000214A0-0000-0000-C000-000000000046}]",
+            "Prop3=19,0",
+            "[InternetShortcut]",
+            "IDList=",
+            "IconIndex=0",
+            "URL=steam://rungameid/440",
+        ];
+        lines.extend(icon_file);
+        lines
+            .iter()
+            .map(|line| format!("{line}{line_ending}"))
+            .collect()
+    }
+
+    #[test]
+    fn leaves_working_shortcut_alone() {
+        let layout = Layout::new();
+        let original = shortcut(Some(&layout.icon_file(ICON_FILENAME)), "\r\n");
+
+        let (problems, written) = layout.fix(original.as_bytes(), false);
+
+        assert!(problems.is_empty());
+        assert_eq!(written, original.as_bytes());
+    }
+
+    #[test]
+    fn fixes_wrong_icon_dir() {
+        let layout = Layout::new();
+        let original = shortcut(
+            Some(&format!(
+                r"IconFile=C:\Program Files\Steam\steam\games\{ICON_FILENAME}"
+            )),
+            "\r\n",
+        );
+
+        let (problems, written) = layout.fix(original.as_bytes(), false);
+
+        assert_eq!(problems, [ShortcutProblem::WrongIconDir]);
+        let expected = shortcut(Some(&layout.icon_file(ICON_FILENAME)), "\r\n");
+        assert_eq!(written, expected.as_bytes());
+    }
+
+    #[test]
+    fn adds_missing_icon_file() {
+        let layout = Layout::new();
+        for line_ending in ["\r\n", "\n"] {
+            let original = shortcut(None, line_ending);
+
+            let (problems, written) = layout.fix(original.as_bytes(), false);
+
+            assert_eq!(problems, [ShortcutProblem::MissingIconFile]);
+            let expected = shortcut(Some(&layout.icon_file(ICON_FILENAME)), line_ending);
+            assert_eq!(written, expected.as_bytes(), "{line_ending:?}");
+        }
+    }
+
+    #[test]
+    fn adds_icon_file_to_end_of_section() {
+        let layout = Layout::new();
+        let original =
+            "[InternetShortcut]\r\nURL=steam://rungameid/440\r\n\r\n[Other]\r\nKey=1\r\n";
+
+        let (problems, written) = layout.fix(original.as_bytes(), false);
+
+        assert_eq!(problems, [ShortcutProblem::MissingIconFile]);
+        let expected = format!(
+            "[InternetShortcut]\r\nURL=steam://rungameid/440\r\n{}\r\n\r\n[Other]\r\nKey=1\r\n",
+            layout.icon_file(ICON_FILENAME)
+        );
+        assert_eq!(String::from_utf8(written).unwrap(), expected);
+    }
+
+    #[test]
+    fn fixes_icon_that_doesnt_exist() {
+        let layout = Layout::new();
+        let original = shortcut(
+            Some(&layout.icon_file("fedcba9876543210fedcba9876543210fedcba98.ico")),
+            "\r\n",
+        );
+
+        let (problems, written) = layout.fix(original.as_bytes(), false);
+
+        assert_eq!(problems, [ShortcutProblem::MissingIcon]);
+        let expected = shortcut(Some(&layout.icon_file(ICON_FILENAME)), "\r\n");
+        assert_eq!(written, expected.as_bytes());
+    }
+
+    #[test]
+    fn fixes_every_problem_in_order() {
+        let layout = Layout::new();
+        let icon_file = format!(r#"IconFile="C:\Steam\steam\games\{ICON_FILENAME}""#);
+        let original = shortcut(Some(&icon_file), "\r\n") + &icon_file + "\r\n";
+
+        let (problems, written) = layout.fix(original.as_bytes(), false);
+
+        assert_eq!(
+            problems,
+            [
+                ShortcutProblem::DuplicateKeys,
+                ShortcutProblem::QuotedIconFile,
+                ShortcutProblem::WrongIconDir,
+            ]
+        );
+        let expected = shortcut(Some(&layout.icon_file(ICON_FILENAME)), "\r\n");
+        assert_eq!(written, expected.as_bytes());
+    }
+
+    #[test]
+    fn keeps_encoding() {
+        let layout = Layout::new();
+        let original = shortcut(None, "\r\n");
+        for encoding in [Encoding::Utf8Bom, Encoding::Utf16Le, Encoding::Utf16Be] {
+            let (_, written) = layout.fix(&encoding.encode(&original), false);
+
+            let expected = shortcut(Some(&layout.icon_file(ICON_FILENAME)), "\r\n");
+            assert_eq!(written, encoding.encode(&expected), "{}", encoding.name());
+        }
+    }
+
+    #[test]
+    fn backs_up_original_once() {
+        let layout = Layout::new();
+        let original = shortcut(None, "\r\n");
+
+        let (_, written) = layout.fix(original.as_bytes(), true);
+
+        let expected = shortcut(Some(&layout.icon_file(ICON_FILENAME)), "\r\n");
+        assert_eq!(written, expected.as_bytes());
+        let backup = layout.shortcuts.join("Game.url.bak");
+        assert_eq!(fs::read(&backup).unwrap(), original.as_bytes());
+        assert_eq!(layout.shortcut_dir_contents(), ["Game.url", "Game.url.bak"]);
+
+        // A later fix keeps the first backup, rather than the already fixed shortcut
+        let (_, written) = layout.fix(shortcut(None, "\n").as_bytes(), true);
+        assert_eq!(
+            written,
+            shortcut(Some(&layout.icon_file(ICON_FILENAME)), "\n").as_bytes()
+        );
+        assert_eq!(fs::read(&backup).unwrap(), original.as_bytes());
+    }
+
+    #[test]
+    fn writes_no_backup_unless_asked() {
+        let layout = Layout::new();
+        layout.fix(shortcut(None, "\r\n").as_bytes(), false);
+        assert_eq!(layout.shortcut_dir_contents(), ["Game.url"]);
+    }
+
+    #[test]
+    fn leaves_other_shortcuts_alone() {
+        let layout = Layout::new();
+        let original = "[InternetShortcut]\r\nURL=https://example.com/\r\n";
+        let (problems, written) = layout.fix(original.as_bytes(), false);
+        assert!(problems.is_empty());
+        assert_eq!(written, original.as_bytes());
+    }
+
+    #[test]
+    fn rejects_conflicting_keys() {
+        let layout = Layout::new();
+        let original = shortcut(Some(&layout.icon_file(ICON_FILENAME)), "\r\n")
+            + "URL=steam://rungameid/570\r\n";
+        let path = layout.shortcuts.join("Game.url");
+        fs::write(&path, &original).unwrap();
+
+        assert!(repair_shortcut(&path, &layout.icons, &mut |_| None).is_err());
+        assert_eq!(fs::read(&path).unwrap(), original.as_bytes());
+    }
+
+    #[test]
+    fn diffs_changed_lines() {
+        let original = "[InternetShortcut]\nURL=steam://rungameid/440\nIconFile=a.ico\n";
+        let fixed = "[InternetShortcut]\nURL=steam://rungameid/440\nIconFile=b.ico\nIconIndex=0\n";
+        assert_eq!(
+            diff(original, fixed),
+            ["-IconFile=a.ico", "+IconFile=b.ico", "+IconIndex=0"]
+        );
+    }
+}
@@ -0,0 +1,1540 @@
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+use anyhow::{Context as _, Error, Result, bail};
+use log::*;
+
+use crate::art::ArtKind;
+use crate::repair::ShortcutProblem;
+use crate::verify::{DamagedIcon, Problem};
+use crate::{OrphanedIcon, names, paths, progress};
+
+/// Extension added to reports while they're being written
+const TEMP_EXTENSION: &str = ".tmp";
+
+/// Most failures of one kind listed at the end of a run (the rest are only counted)
+const MAX_FAILURES_LISTED: usize = 20;
+
+/// What happened to a shortcut during a run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    /// The icon was downloaded and saved
+    Downloaded,
+
+    /// The icon had already been downloaded, but was downloaded again
+    Redownloaded,
+
+    /// The icon had already been downloaded, but was damaged and downloaded again
+    Repaired,
+
+    /// The icon had already been downloaded
+    AlreadyExists,
+
+    /// The icon had already been downloaded, but had changed on the CDN (going by what it said
+    /// about it), so it was downloaded again
+    Updated,
+
+    /// The icon was downloaded again (since it was older than the maximum age, or was forced to be),
+    /// but hadn't changed, so it was left alone
+    Unchanged,
+
+    /// The entry wasn't a Steam shortcut
+    Skipped,
+
+    /// The shortcut was for a game that wasn't asked for
+    FilteredOut,
+
+    /// The shortcut or its game was excluded
+    Excluded,
+
+    /// The shortcut was for a game added to Steam from elsewhere, with no icon on the CDN
+    NonSteam,
+
+    /// Another shortcut for the same game and icon was already processed
+    Duplicate,
+
+    /// The CDN doesn't have an icon for the game
+    NotFound,
+
+    /// The CDN doesn't have an icon for the game, so a placeholder was generated instead
+    Placeholder,
+
+    /// The CDN recently didn't have an icon for the game, so it wasn't asked again
+    KnownMissing,
+
+    /// Steam's library cache has no icon for the game (when installing icons from it offline)
+    UnavailableOffline,
+
+    /// The icon would have been downloaded if this wasn't a dry run
+    Pending,
+
+    /// The icon wasn't downloaded (or its download was stopped part way),
+    /// since the run's download budget was used up, so it's left for the next run
+    OverBudget,
+
+    /// The icon is missing (when only checking, so it wasn't downloaded)
+    Missing,
+
+    /// The shortcut couldn't be processed or its icon failed to download
+    Failed,
+}
+
+impl Action {
+    /// Name used for the action in machine-readable output.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Downloaded => "downloaded",
+            Self::Redownloaded => "redownloaded",
+            Self::Repaired => "repaired",
+            Self::AlreadyExists => "already-exists",
+            Self::Updated => "updated",
+            Self::Unchanged => "unchanged",
+            Self::Skipped => "skipped",
+            Self::FilteredOut => "filtered-out",
+            Self::Excluded => "excluded",
+            Self::NonSteam => "non-steam",
+            Self::Duplicate => "duplicate",
+            Self::NotFound => "not-found",
+            Self::Placeholder => "placeholder",
+            Self::KnownMissing => "known-missing",
+            Self::UnavailableOffline => "unavailable-offline",
+            Self::Pending => "pending",
+            Self::OverBudget => "over-budget",
+            Self::Missing => "missing",
+            Self::Failed => "failed",
+        }
+    }
+}
+
+/// Why a shortcut failed, for grouping failures at the end of a run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum FailureKind {
+    /// The shortcut couldn't be read or understood
+    Shortcut,
+
+    /// Steam answered with an error (e.g. a 404) or blocked the request
+    Http,
+
+    /// Steam couldn't be reached, or the connection failed part way
+    Network,
+
+    /// What was downloaded wasn't a valid icon (or didn't match its hash)
+    InvalidIcon,
+
+    /// The icon couldn't be written to disk
+    Write,
+
+    /// Anything else
+    Other,
+}
+
+impl FailureKind {
+    /// Heading for failures of this kind in the summary.
+    pub fn heading(self) -> &'static str {
+        match self {
+            Self::Shortcut => "Shortcut errors",
+            Self::Http => "HTTP errors",
+            Self::Network => "Network errors",
+            Self::InvalidIcon => "Invalid icons",
+            Self::Write => "Write errors",
+            Self::Other => "Other errors",
+        }
+    }
+}
+
+/// Format of a report of the run written to a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    /// The same JSON document printed with `--json`
+    Json,
+
+    /// One row per shortcut processed
+    Csv,
+}
+
+impl FromStr for ReportFormat {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.to_lowercase().as_str() {
+            "json" => Ok(Self::Json),
+            "csv" => Ok(Self::Csv),
+            _ => bail!("`{value}` is not a report format (expected `json` or `csv`)"),
+        }
+    }
+}
+
+/// Value of one of a shortcut's fields in a machine-readable report.
+enum Field<'a> {
+    /// Text, if known
+    Text(Option<Cow<'a, str>>),
+
+    /// A number
+    Number(u64),
+}
+
+/// Everything known about a single shortcut that was processed.
+#[derive(Debug, Clone)]
+pub struct ItemReport {
+    /// Path to the shortcut
+    pub shortcut: PathBuf,
+
+    /// Steam game ID, if it could be extracted
+    pub game_id: Option<String>,
+
+    /// Name of the game, if known
+    pub game_name: Option<String>,
+
+    /// Icon filename, if it could be extracted
+    pub icon_filename: Option<String>,
+
+    /// What happened to the shortcut
+    pub action: Action,
+
+    /// Size of the downloaded icon
+    pub bytes: u64,
+
+    /// Where the icon was saved from, if not Steam's CDN
+    /// (e.g. Steam's library cache, or the game's executable)
+    pub source: Option<String>,
+
+    /// Where a PNG copy of the icon was exported to, if one was
+    pub exported_png: Option<PathBuf>,
+
+    /// Why the shortcut failed, if it did
+    pub error: Option<String>,
+
+    /// What kind of failure it was, if it failed
+    pub failure: Option<FailureKind>,
+}
+
+impl ItemReport {
+    /// Names of the fields of each shortcut in machine-readable reports,
+    /// in the order of [`ItemReport::fields`].
+    pub const FIELD_NAMES: [&str; 9] = [
+        "shortcut",
+        "game_id",
+        "game_name",
+        "icon_filename",
+        "action",
+        "bytes",
+        "source",
+        "exported_png",
+        "error",
+    ];
+
+    /// Values of the fields of the shortcut in machine-readable reports,
+    /// shared by every format so they can't drift apart.
+    fn fields(&self) -> [Field<'_>; 9] {
+        [
+            Field::Text(Some(self.shortcut.to_string_lossy())),
+            Field::Text(self.game_id.as_deref().map(Cow::Borrowed)),
+            Field::Text(self.game_name.as_deref().map(Cow::Borrowed)),
+            Field::Text(self.icon_filename.as_deref().map(Cow::Borrowed)),
+            Field::Text(Some(Cow::Borrowed(self.action.name()))),
+            Field::Number(self.bytes),
+            Field::Text(self.source.as_deref().map(Cow::Borrowed)),
+            Field::Text(self.exported_png.as_deref().map(Path::to_string_lossy)),
+            Field::Text(self.error.as_deref().map(Cow::Borrowed)),
+        ]
+    }
+
+    /// Report for `shortcut` with nothing but the action known.
+    pub fn new(shortcut: &Path, action: Action) -> Self {
+        Self {
+            shortcut: shortcut.to_owned(),
+            game_id: None,
+            game_name: None,
+            icon_filename: None,
+            action,
+            bytes: 0,
+            source: None,
+            exported_png: None,
+            error: None,
+            failure: None,
+        }
+    }
+
+    /// The game the shortcut was for as it should appear in logs, if known.
+    pub fn describe_game(&self) -> Option<String> {
+        let game_id = self.game_id.as_deref()?;
+        Some(names::describe(game_id, self.game_name.as_deref()))
+    }
+
+    /// The shortcut, its game (if known) and why it failed, as it should appear in the summary.
+    pub fn describe_failure(&self) -> String {
+        let error = self.error.as_deref().unwrap_or("Unknown error");
+        match self.describe_game() {
+            Some(game) => format!("{} ({game}): {error}", self.shortcut.display()),
+            None => format!("{}: {error}", self.shortcut.display()),
+        }
+    }
+}
+
+/// Shortcuts that failed with the same kind of failure.
+#[derive(Debug)]
+pub struct FailureGroup<'a> {
+    /// What kind of failure they had
+    pub kind: FailureKind,
+
+    /// The failed shortcuts, in the order they failed
+    pub items: Vec<&'a ItemReport>,
+}
+
+/// Whether a shortcut's icon is there, when listing shortcuts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ShortcutStatus {
+    /// The shortcut couldn't be read, or points somewhere its icon can't be
+    ShortcutProblem,
+
+    /// The icon hasn't been downloaded
+    Missing,
+
+    /// The icon has been downloaded
+    Present,
+}
+
+impl ShortcutStatus {
+    /// Name used for the status in output.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::ShortcutProblem => "shortcut-problem",
+            Self::Missing => "missing",
+            Self::Present => "present",
+        }
+    }
+}
+
+/// A Steam game's shortcut, when listing shortcuts.
+#[derive(Debug, Clone)]
+pub struct ListedShortcut {
+    /// Path to the shortcut
+    pub shortcut: PathBuf,
+
+    /// Steam game ID, if it could be extracted
+    pub game_id: Option<String>,
+
+    /// Name of the game, if known
+    pub game_name: Option<String>,
+
+    /// Icon filename, if it could be extracted
+    pub icon_filename: Option<String>,
+
+    /// Whether the icon is there
+    pub status: ShortcutStatus,
+}
+
+/// Machine-readable list of `shortcuts`.
+pub fn listed_shortcuts_json(shortcuts: &[ListedShortcut]) -> String {
+    let mut json = String::from("{\"shortcuts\":[");
+    for (index, shortcut) in shortcuts.iter().enumerate() {
+        if index > 0 {
+            json.push(',');
+        }
+        let _ = write!(
+            json,
+            "{{\"shortcut\":{},\"game_id\":{},\"game_name\":{},\"icon_filename\":{},\"status\":\
+             {}}}",
+            json_string(&shortcut.shortcut.to_string_lossy()),
+            json_optional_string(shortcut.game_id.as_deref()),
+            json_optional_string(shortcut.game_name.as_deref()),
+            json_optional_string(shortcut.icon_filename.as_deref()),
+            json_string(shortcut.status.name()),
+        );
+    }
+    json.push_str("]}");
+    json
+}
+
+/// What happened to one kind of a game's artwork (see [`crate::art`]) during a run.
+#[derive(Debug, Clone)]
+pub struct ArtReport {
+    /// Steam game ID
+    pub game_id: String,
+
+    /// Kind of artwork
+    pub kind: ArtKind,
+
+    /// What happened to it
+    /// (downloaded, already there, would be downloaded, not on the CDN, or failed)
+    pub action: Action,
+
+    /// Size of the downloaded artwork
+    pub bytes: u64,
+
+    /// Why it failed, if it did
+    pub error: Option<String>,
+}
+
+/// Tally of everything that happened during a run.
+#[derive(Debug, Default)]
+pub struct RunReport {
+    /// Directories shortcuts were found in
+    pub locations: Vec<PathBuf>,
+
+    /// Every shortcut processed, in the order they finished
+    pub items: Vec<ItemReport>,
+
+    /// Number of entries in the shortcut directories that couldn't be read (and were skipped)
+    pub unreadable_entries: usize,
+
+    /// Shortcuts pointed at the per-user icon directory (or that would have been, on a dry run)
+    pub repointed_shortcuts: Vec<PathBuf>,
+
+    /// Time spent downloading icons
+    pub download_time: Duration,
+
+    /// Other artwork downloaded for the games (or not), if asked for
+    pub art: Vec<ArtReport>,
+}
+
+impl RunReport {
+    /// Record what happened to a shortcut.
+    pub fn record(&mut self, item: ItemReport) {
+        self.items.push(item);
+        progress::update(self);
+    }
+
+    /// PNG copies of icons exported during the run.
+    pub fn exported_pngs(&self) -> Vec<PathBuf> {
+        self.items
+            .iter()
+            .filter_map(|item| item.exported_png.clone())
+            .collect()
+    }
+
+    /// Number of shortcuts that ended with `action`.
+    pub fn count(&self, action: Action) -> usize {
+        self.items
+            .iter()
+            .filter(|item| item.action == action)
+            .count()
+    }
+
+    /// The Steam games' shortcuts found while only checking, with whether each icon is there.
+    /// Shortcuts that weren't processed (e.g. excluded, or not for Steam games) are left out,
+    /// and duplicates share the status of the first shortcut for the same icon.
+    pub fn listed_shortcuts(&self) -> Vec<ListedShortcut> {
+        let mut statuses = HashMap::new();
+        let mut listed = Vec::new();
+        for item in &self.items {
+            let key = (item.game_id.clone(), item.icon_filename.clone());
+            let status = match item.action {
+                Action::AlreadyExists => ShortcutStatus::Present,
+                Action::Missing => ShortcutStatus::Missing,
+                Action::Failed => ShortcutStatus::ShortcutProblem,
+                Action::Duplicate => match statuses.get(&key) {
+                    Some(&status) => status,
+                    None => continue,
+                },
+                _ => continue,
+            };
+            statuses.entry(key).or_insert(status);
+            listed.push(ListedShortcut {
+                shortcut: item.shortcut.clone(),
+                game_id: item.game_id.clone(),
+                game_name: item.game_name.clone(),
+                icon_filename: item.icon_filename.clone(),
+                status,
+            });
+        }
+        listed
+    }
+
+    /// Number of shortcuts found in `location`.
+    pub fn count_in(&self, location: &Path) -> usize {
+        self.items
+            .iter()
+            .filter(|item| item.shortcut.starts_with(location))
+            .count()
+    }
+
+    /// Number of shortcuts that failed.
+    pub fn failed(&self) -> usize {
+        self.count(Action::Failed)
+    }
+
+    /// Number of pieces of artwork that ended with `action`.
+    pub fn count_art(&self, action: Action) -> usize {
+        self.art.iter().filter(|art| art.action == action).count()
+    }
+
+    /// Games left for the next run since the download budget was used up
+    /// (with icons or other artwork that weren't downloaded), each listed once.
+    pub fn remaining_games(&self) -> Vec<String> {
+        let mut seen = HashSet::new();
+        self.items
+            .iter()
+            .filter(|item| item.action == Action::OverBudget)
+            .filter_map(|item| item.game_id.as_deref())
+            .chain(
+                self.art
+                    .iter()
+                    .filter(|art| art.action == Action::OverBudget)
+                    .map(|art| art.game_id.as_str()),
+            )
+            .filter(|game_id| seen.insert(*game_id))
+            .map(str::to_owned)
+            .collect()
+    }
+
+    /// Whether the run stopped downloading since its budget was used up,
+    /// leaving some of what it should have downloaded for the next run.
+    pub fn is_budget_exhausted(&self) -> bool {
+        !self.remaining_games().is_empty()
+    }
+
+    /// Number of icons saved, whether they were missing, replaced, repaired, or updated.
+    pub fn saved(&self) -> usize {
+        self.count(Action::Downloaded)
+            + self.count(Action::Redownloaded)
+            + self.count(Action::Repaired)
+            + self.count(Action::Updated)
+    }
+
+    /// Total size of the icons downloaded.
+    pub fn downloaded_bytes(&self) -> u64 {
+        self.items.iter().map(|item| item.bytes).sum()
+    }
+
+    /// Average download speed in bytes per second, if anything was downloaded.
+    pub fn download_rate(&self) -> Option<f64> {
+        let seconds = self.download_time.as_secs_f64();
+        let bytes = self.downloaded_bytes();
+        (bytes > 0 && seconds > 0.0).then(|| bytes as f64 / seconds)
+    }
+
+    /// The run in a sentence (e.g. for a notification), like "2 icon(s) downloaded, 1 failed",
+    /// or `None` if nothing was missing, so there was nothing to do.
+    pub fn brief(&self) -> Option<String> {
+        let saved = self.saved();
+        let pending = self.count(Action::Pending);
+        let not_found = self.count(Action::NotFound);
+        let placeholders = self.count(Action::Placeholder);
+        let unavailable_offline = self.count(Action::UnavailableOffline);
+        let over_budget = self.remaining_games().len();
+        let failed = self.failed() + self.unreadable_entries;
+        if saved + pending + not_found + placeholders + unavailable_offline + over_budget + failed
+            == 0
+        {
+            return None;
+        }
+
+        let mut brief = if pending > 0 {
+            format!("{pending} icon(s) would be downloaded")
+        } else {
+            format!("{saved} icon(s) downloaded")
+        };
+        if not_found > 0 {
+            brief.push_str(&format!(", {not_found} not on Steam's CDN"));
+        }
+        if placeholders > 0 {
+            brief.push_str(&format!(", {placeholders} placeholder(s) generated"));
+        }
+        if unavailable_offline > 0 {
+            brief.push_str(&format!(", {unavailable_offline} unavailable offline"));
+        }
+        if over_budget > 0 {
+            brief.push_str(&format!(", {over_budget} game(s) left over budget"));
+        }
+        brief.push_str(&format!(", {failed} failed"));
+        Some(brief)
+    }
+
+    /// Human-readable summary of the run, one line per entry.
+    pub fn summary(&self) -> Vec<String> {
+        let mut summary = vec![
+            "Summary:".to_owned(),
+            format!("  Shortcuts scanned:      {}", self.items.len()),
+        ];
+
+        // Break down where shortcuts came from, if there was more than one place
+        if self.locations.len() > 1 {
+            for location in &self.locations {
+                summary.push(format!(
+                    "    in {}: {}",
+                    location.display(),
+                    self.count_in(location)
+                ));
+            }
+        }
+        if self.unreadable_entries > 0 {
+            summary.push(format!(
+                "  Unreadable entries:     {}",
+                self.unreadable_entries
+            ));
+        }
+
+        summary.extend([
+            format!("  Skipped (not Steam):    {}", self.count(Action::Skipped)),
+            format!(
+                "  Filtered out:           {}",
+                self.count(Action::FilteredOut)
+            ),
+            format!("  Excluded:               {}", self.count(Action::Excluded)),
+            format!("  Non-Steam games:        {}", self.count(Action::NonSteam)),
+            format!(
+                "  Duplicates:             {}",
+                self.count(Action::Duplicate)
+            ),
+            format!(
+                "  Icons already present:  {}",
+                self.count(Action::AlreadyExists)
+            ),
+            format!(
+                "  Icons downloaded:       {} ({})",
+                self.count(Action::Downloaded),
+                format_bytes(self.downloaded_bytes())
+            ),
+            format!(
+                "  Icons re-downloaded:    {}",
+                self.count(Action::Redownloaded)
+            ),
+            format!("  Damaged icons repaired: {}", self.count(Action::Repaired)),
+        ]);
+        let updated = self.count(Action::Updated);
+        if updated > 0 {
+            summary.push(format!("  Icons updated:          {updated}"));
+        }
+        let unchanged = self.count(Action::Unchanged);
+        if unchanged > 0 {
+            summary.push(format!("  Icons unchanged:        {unchanged}"));
+        }
+        summary.extend([format!(
+            "  Icons missing from CDN: {}",
+            self.count(Action::NotFound)
+        )]);
+        summary.extend(self.games_with(Action::NotFound));
+        let placeholders = self.count(Action::Placeholder);
+        if placeholders > 0 {
+            summary.push(format!("  Placeholders generated: {placeholders}"));
+            summary.extend(self.games_with(Action::Placeholder));
+        }
+        summary.push(format!(
+            "  Skipped (no CDN icon):  {}",
+            self.count(Action::KnownMissing)
+        ));
+        summary.extend(self.games_with(Action::KnownMissing));
+        let unavailable_offline = self.count(Action::UnavailableOffline);
+        if unavailable_offline > 0 {
+            summary.push(format!("  Unavailable offline:    {unavailable_offline}"));
+            summary.extend(self.games_with(Action::UnavailableOffline));
+        }
+        if !self.repointed_shortcuts.is_empty() {
+            summary.push(format!(
+                "  Pointed at user icons:  {}",
+                self.repointed_shortcuts.len()
+            ));
+            summary.extend(paths_in(&self.repointed_shortcuts));
+        }
+        let exported_pngs = self.exported_pngs();
+        if !exported_pngs.is_empty() {
+            summary.push(format!("  Exported as PNGs:       {}", exported_pngs.len()));
+            summary.extend(paths_in(&exported_pngs));
+        }
+        summary.extend(self.art_summary());
+        let over_budget = self.count(Action::OverBudget);
+        if over_budget > 0 {
+            summary.push(format!("  Over budget:            {over_budget}"));
+            summary.extend(self.games_with(Action::OverBudget));
+        }
+        summary.push(format!("  Failed:                 {}", self.failed()));
+        if !self.download_time.is_zero() {
+            summary.push(format!(
+                "  Download time:          {:.1}s{}",
+                self.download_time.as_secs_f64(),
+                self.download_rate()
+                    .map(|rate| format!(" ({}/s)", format_bytes(rate as u64)))
+                    .unwrap_or_default()
+            ));
+        }
+
+        let pending = self.count(Action::Pending);
+        if pending > 0 {
+            summary.push(format!("  Pending (dry run):      {pending}"));
+        }
+        let missing = self.count(Action::Missing);
+        if missing > 0 {
+            summary.push(format!("  Icons missing:          {missing}"));
+            summary.extend(self.games_with(Action::Missing));
+        }
+
+        summary
+    }
+
+    /// Lines listing the games whose shortcuts ended with `action`, for the summary
+    /// (or the shortcuts themselves, for those without a known game).
+    fn games_with(&self, action: Action) -> Vec<String> {
+        self.items
+            .iter()
+            .filter(|item| item.action == action)
+            .map(|item| {
+                let game = item
+                    .describe_game()
+                    .unwrap_or_else(|| item.shortcut.display().to_string());
+                format!("    {game}")
+            })
+            .collect()
+    }
+
+    /// Lines breaking down what happened to each kind of artwork, for the summary
+    /// (nothing if no artwork was asked for).
+    fn art_summary(&self) -> Vec<String> {
+        if self.art.is_empty() {
+            return Vec::new();
+        }
+
+        let mut summary = vec!["  Artwork:".to_owned()];
+        for kind in ArtKind::ALL {
+            let art: Vec<_> = self.art.iter().filter(|art| art.kind == kind).collect();
+            if art.is_empty() {
+                continue;
+            }
+            let count = |action| art.iter().filter(|art| art.action == action).count();
+            let downloaded = count(Action::Downloaded);
+            let bytes = art.iter().map(|art| art.bytes).sum();
+            let mut parts = Vec::new();
+            if downloaded > 0 {
+                parts.push(format!("{downloaded} downloaded ({})", format_bytes(bytes)));
+            }
+            for (action, description) in [
+                (Action::Pending, "would be downloaded"),
+                (Action::AlreadyExists, "already present"),
+                (Action::NotFound, "not on the CDN"),
+                (Action::OverBudget, "over budget"),
+                (Action::Failed, "failed"),
+            ] {
+                let count = count(action);
+                if count > 0 {
+                    parts.push(format!("{count} {description}"));
+                }
+            }
+            summary.push(format!(
+                "    {:<20}{}",
+                format!("{kind}:"),
+                parts.join(", ")
+            ));
+        }
+        summary
+    }
+
+    /// The failed shortcuts, grouped by kind of failure (in the order of [`FailureKind`]).
+    pub fn failure_groups(&self) -> Vec<FailureGroup<'_>> {
+        let mut groups = BTreeMap::<_, Vec<_>>::new();
+        for item in self
+            .items
+            .iter()
+            .filter(|item| item.action == Action::Failed)
+        {
+            let kind = item.failure.unwrap_or(FailureKind::Other);
+            groups.entry(kind).or_default().push(item);
+        }
+        groups
+            .into_iter()
+            .map(|(kind, items)| FailureGroup { kind, items })
+            .collect()
+    }
+
+    /// Human-readable list of why shortcuts failed, grouped by kind of failure,
+    /// one line per entry (or none, if nothing failed).
+    /// Only the first few failures of each kind are listed, and the rest are counted.
+    pub fn failures(&self) -> Vec<String> {
+        let groups = self.failure_groups();
+        if groups.is_empty() {
+            return Vec::new();
+        }
+
+        let mut failures = vec!["Failures:".to_owned()];
+        for group in groups {
+            failures.push(format!(
+                "  {} ({}):",
+                group.kind.heading(),
+                group.items.len()
+            ));
+            failures.extend(
+                group
+                    .items
+                    .iter()
+                    .take(MAX_FAILURES_LISTED)
+                    .map(|item| format!("    {}", item.describe_failure())),
+            );
+            if group.items.len() > MAX_FAILURES_LISTED {
+                failures.push(format!(
+                    "    ...and {} more",
+                    group.items.len() - MAX_FAILURES_LISTED
+                ));
+            }
+        }
+        failures
+    }
+
+    /// Log the summary of the run, followed by why any shortcuts failed.
+    pub fn log_summary(&self) {
+        for line in self.summary() {
+            info!("{line}");
+        }
+        for line in self.failures() {
+            error!("{line}");
+        }
+    }
+
+    /// Write the report of the run to `path` in `format`,
+    /// replacing the file all at once so it's never left half-written.
+    pub fn write_to(&self, path: &Path, format: ReportFormat) -> Result<()> {
+        let contents = match format {
+            ReportFormat::Json => self.to_json().into_bytes(),
+            ReportFormat::Csv => self.to_csv()?,
+        };
+        let temp_path = paths::with_suffix(path, TEMP_EXTENSION);
+        fs::write(&temp_path, contents).context("Failed to write temporary file")?;
+        fs::rename(&temp_path, path).context("Failed to move temporary file into place")
+    }
+
+    /// Machine-readable report of every shortcut processed as CSV,
+    /// with the same fields as the JSON report (see [`RunReport::to_json`]).
+    pub fn to_csv(&self) -> Result<Vec<u8>> {
+        let mut writer = csv::Writer::from_writer(Vec::new());
+        writer.write_record(ItemReport::FIELD_NAMES)?;
+        for item in &self.items {
+            writer.write_record(item.fields().map(|field| match field {
+                Field::Text(text) => text.unwrap_or_default().into_owned(),
+                Field::Number(number) => number.to_string(),
+            }))?;
+        }
+        writer.into_inner().context("Failed to write CSV")
+    }
+
+    /// Totals of the run, by name, as used in machine-readable output.
+    /// Totals are counts, apart from the number of bytes downloaded and how long it took.
+    pub fn totals(&self) -> [(&'static str, u64); 28] {
+        [
+            ("scanned", self.items.len() as u64),
+            ("unreadable_entries", self.unreadable_entries as u64),
+            ("skipped", self.count(Action::Skipped) as u64),
+            ("filtered_out", self.count(Action::FilteredOut) as u64),
+            ("excluded", self.count(Action::Excluded) as u64),
+            ("non_steam", self.count(Action::NonSteam) as u64),
+            ("duplicate", self.count(Action::Duplicate) as u64),
+            ("already_exists", self.count(Action::AlreadyExists) as u64),
+            ("downloaded", self.count(Action::Downloaded) as u64),
+            ("redownloaded", self.count(Action::Redownloaded) as u64),
+            ("repaired", self.count(Action::Repaired) as u64),
+            ("updated", self.count(Action::Updated) as u64),
+            ("unchanged", self.count(Action::Unchanged) as u64),
+            ("downloaded_bytes", self.downloaded_bytes()),
+            ("download_time_ms", self.download_time.as_millis() as u64),
+            ("not_found", self.count(Action::NotFound) as u64),
+            ("placeholders", self.count(Action::Placeholder) as u64),
+            ("known_missing", self.count(Action::KnownMissing) as u64),
+            (
+                "unavailable_offline",
+                self.count(Action::UnavailableOffline) as u64,
+            ),
+            ("pending", self.count(Action::Pending) as u64),
+            ("over_budget", self.count(Action::OverBudget) as u64),
+            ("missing", self.count(Action::Missing) as u64),
+            ("repointed_shortcuts", self.repointed_shortcuts.len() as u64),
+            ("exported_pngs", self.exported_pngs().len() as u64),
+            ("art_downloaded", self.count_art(Action::Downloaded) as u64),
+            ("art_over_budget", self.count_art(Action::OverBudget) as u64),
+            ("art_failed", self.count_art(Action::Failed) as u64),
+            ("failed", self.failed() as u64),
+        ]
+    }
+
+    /// Machine-readable report of the run as a JSON document.
+    pub fn to_json(&self) -> String {
+        let mut json = String::from("{\"items\":[");
+        for (index, item) in self.items.iter().enumerate() {
+            if index > 0 {
+                json.push(',');
+            }
+            json.push('{');
+            for (index, (name, field)) in ItemReport::FIELD_NAMES
+                .iter()
+                .zip(item.fields())
+                .enumerate()
+            {
+                if index > 0 {
+                    json.push(',');
+                }
+                let value = match field {
+                    Field::Text(text) => json_optional_string(text.as_deref()),
+                    Field::Number(number) => number.to_string(),
+                };
+                let _ = write!(json, "{}:{value}", json_string(name));
+            }
+            json.push('}');
+        }
+
+        json.push_str("],\"locations\":[");
+        for (index, location) in self.locations.iter().enumerate() {
+            if index > 0 {
+                json.push(',');
+            }
+            let _ = write!(
+                json,
+                "{{\"path\":{},\"scanned\":{}}}",
+                json_string(&location.to_string_lossy()),
+                self.count_in(location)
+            );
+        }
+
+        json.push_str("],\"repointed_shortcuts\":[");
+        for (index, shortcut) in self.repointed_shortcuts.iter().enumerate() {
+            if index > 0 {
+                json.push(',');
+            }
+            json.push_str(&json_string(&shortcut.to_string_lossy()));
+        }
+
+        json.push_str("],\"art\":[");
+        for (index, art) in self.art.iter().enumerate() {
+            if index > 0 {
+                json.push(',');
+            }
+            let _ = write!(
+                json,
+                "{{\"game_id\":{},\"kind\":{},\"action\":{},\"bytes\":{},\"error\":{}}}",
+                json_string(&art.game_id),
+                json_string(art.kind.name()),
+                json_string(art.action.name()),
+                art.bytes,
+                json_optional_string(art.error.as_deref())
+            );
+        }
+
+        let _ = write!(
+            json,
+            "],\"budget_exhausted\":{},\"totals\":{{",
+            self.is_budget_exhausted()
+        );
+        for (index, (name, total)) in self.totals().into_iter().enumerate() {
+            if index > 0 {
+                json.push(',');
+            }
+            let _ = write!(json, "{}:{total}", json_string(name));
+        }
+        json.push_str("}}");
+
+        json
+    }
+}
+
+/// Tally of everything that happened while undoing downloads.
+#[derive(Debug, Default)]
+pub struct UndoReport {
+    /// Icons removed (or that would have been, on a dry run)
+    pub removed: Vec<PathBuf>,
+
+    /// Icons left alone, since they changed after being downloaded
+    pub modified: Vec<PathBuf>,
+
+    /// Icons that were already gone
+    pub missing: Vec<PathBuf>,
+
+    /// Icons that couldn't be checked or removed
+    pub failed: Vec<PathBuf>,
+
+    /// Whether this was a dry run
+    pub dry_run: bool,
+}
+
+impl UndoReport {
+    /// Human-readable summary of the undo, one line per entry.
+    pub fn summary(&self) -> Vec<String> {
+        let removed = if self.dry_run {
+            "Icons to remove:        "
+        } else {
+            "Icons removed:          "
+        };
+        let mut summary = vec![
+            "Summary:".to_owned(),
+            format!("  {removed}{}", self.removed.len()),
+            format!("  Icons already gone:     {}", self.missing.len()),
+            format!("  Icons changed (kept):   {}", self.modified.len()),
+        ];
+        summary.extend(paths_in(&self.modified));
+        summary.push(format!("  Failed:                 {}", self.failed.len()));
+        summary.extend(paths_in(&self.failed));
+        summary
+    }
+
+    /// Log the summary of the undo.
+    pub fn log_summary(&self) {
+        for line in self.summary() {
+            info!("{line}");
+        }
+    }
+}
+
+/// Tally of everything that happened while removing icons no shortcut uses.
+#[derive(Debug, Default)]
+pub struct CleanReport {
+    /// Icons removed (or that would have been, on a dry run)
+    pub removed: Vec<PathBuf>,
+
+    /// Total size of the icons removed
+    pub removed_bytes: u64,
+
+    /// Number of unused icons kept, since they were modified too recently
+    pub too_new: usize,
+
+    /// Icons that couldn't be removed
+    pub failed: Vec<PathBuf>,
+
+    /// Whether this was a dry run
+    pub dry_run: bool,
+}
+
+impl CleanReport {
+    /// Record that `icon` was removed (or would have been).
+    pub fn record_removed(&mut self, icon: &OrphanedIcon) {
+        self.removed.push(icon.path.clone());
+        self.removed_bytes += icon.bytes;
+    }
+
+    /// Human-readable summary of the clean up, one line per entry.
+    pub fn summary(&self) -> Vec<String> {
+        let removed = if self.dry_run {
+            "Icons to remove:        "
+        } else {
+            "Icons removed:          "
+        };
+        let mut summary = vec![
+            "Summary:".to_owned(),
+            format!(
+                "  {removed}{} ({})",
+                self.removed.len(),
+                format_bytes(self.removed_bytes)
+            ),
+            format!("  Kept as too new:        {}", self.too_new),
+            format!("  Failed:                 {}", self.failed.len()),
+        ];
+        summary.extend(paths_in(&self.failed));
+        summary
+    }
+
+    /// Log the summary of the clean up.
+    pub fn log_summary(&self) {
+        for line in self.summary() {
+            info!("{line}");
+        }
+    }
+}
+
+/// A shortcut that was repaired (or would have been, on a dry run).
+#[derive(Debug, Clone)]
+pub struct FixedShortcut {
+    /// Path to the shortcut
+    pub path: PathBuf,
+
+    /// What was wrong with it
+    pub problems: Vec<ShortcutProblem>,
+
+    /// Lines removed (starting with `-`) and added (starting with `+`) by the repair
+    pub diff: Vec<String>,
+}
+
+impl FixedShortcut {
+    /// Header introducing the shortcut's diff, listing what was wrong with it.
+    pub fn header(&self) -> String {
+        let problems: Vec<_> = self
+            .problems
+            .iter()
+            .map(|problem| problem.describe())
+            .collect();
+        format!("{} ({})", self.path.display(), problems.join(", "))
+    }
+}
+
+/// Tally of everything that happened while repairing shortcuts.
+#[derive(Debug, Default)]
+pub struct FixReport {
+    /// Number of shortcuts checked
+    pub checked: usize,
+
+    /// Shortcuts repaired (or that would have been, on a dry run)
+    pub fixed: Vec<FixedShortcut>,
+
+    /// Shortcuts left alone since they couldn't be fully understood, and why
+    pub left_alone: Vec<(PathBuf, String)>,
+
+    /// Shortcuts that couldn't be written
+    pub failed: Vec<PathBuf>,
+
+    /// Number of entries in the shortcut directories that couldn't be read (and were skipped)
+    pub unreadable_entries: usize,
+
+    /// Whether this was a dry run
+    pub dry_run: bool,
+}
+
+impl FixReport {
+    /// Human-readable summary of the repairs, one line per entry.
+    pub fn summary(&self) -> Vec<String> {
+        let fixed = if self.dry_run {
+            "Shortcuts to fix:       "
+        } else {
+            "Shortcuts fixed:        "
+        };
+        let mut summary = vec![
+            "Summary:".to_owned(),
+            format!("  Shortcuts checked:      {}", self.checked),
+        ];
+        if self.unreadable_entries > 0 {
+            summary.push(format!(
+                "  Unreadable entries:     {}",
+                self.unreadable_entries
+            ));
+        }
+        summary.extend([
+            format!("  {fixed}{}", self.fixed.len()),
+            format!("  Left alone:             {}", self.left_alone.len()),
+        ]);
+        summary.extend(
+            self.left_alone
+                .iter()
+                .map(|(path, reason)| format!("    {}: {reason}", path.display())),
+        );
+        summary.push(format!("  Failed:                 {}", self.failed.len()));
+        summary.extend(paths_in(&self.failed));
+        summary
+    }
+
+    /// Log the summary of the repairs.
+    pub fn log_summary(&self) {
+        for line in self.summary() {
+            info!("{line}");
+        }
+    }
+}
+
+/// Tally of the icons checked while verifying the icon directory.
+#[derive(Debug, Default)]
+pub struct VerifyReport {
+    /// Number of icons checked
+    pub checked: usize,
+
+    /// Icons that are damaged, and how
+    pub damaged: Vec<(PathBuf, DamagedIcon)>,
+}
+
+impl VerifyReport {
+    /// Icons with `problem`.
+    fn with_problem(&self, problem: Problem) -> impl Iterator<Item = &(PathBuf, DamagedIcon)> {
+        self.damaged
+            .iter()
+            .filter(move |(_, damaged)| damaged.problem == problem)
+    }
+
+    /// Human-readable summary of the verification, one line per entry,
+    /// with the damaged icons grouped by what's wrong with them.
+    pub fn summary(&self) -> Vec<String> {
+        let mut summary = vec![
+            "Summary:".to_owned(),
+            format!("  Icons checked:          {}", self.checked),
+            format!(
+                "  Intact:                 {}",
+                self.checked - self.damaged.len()
+            ),
+        ];
+        for (problem, label) in Problem::ALL.into_iter().zip([
+            "Unreadable:             ",
+            "Empty:                  ",
+            "Invalid:                ",
+            "Not matching hash:      ",
+        ]) {
+            let damaged: Vec<_> = self.with_problem(problem).collect();
+            summary.push(format!("  {label}{}", damaged.len()));
+            summary.extend(
+                damaged
+                    .iter()
+                    .map(|(path, damaged)| format!("    {}: {damaged}", path.display())),
+            );
+        }
+        summary
+    }
+
+    /// Log the summary of the verification.
+    pub fn log_summary(&self) {
+        for line in self.summary() {
+            info!("{line}");
+        }
+    }
+
+    /// Machine-readable report of the verification as a JSON document.
+    pub fn to_json(&self) -> String {
+        let mut json = String::from("{\"damaged\":[");
+        for (index, (path, damaged)) in self.damaged.iter().enumerate() {
+            if index > 0 {
+                json.push(',');
+            }
+            let _ = write!(
+                json,
+                "{{\"path\":{},\"problem\":{},\"detail\":{}}}",
+                json_string(&path.to_string_lossy()),
+                json_string(damaged.problem.name()),
+                json_string(&damaged.detail),
+            );
+        }
+
+        let _ = write!(
+            json,
+            "],\"totals\":{{\"checked\":{},\"intact\":{}",
+            self.checked,
+            self.checked - self.damaged.len()
+        );
+        for problem in Problem::ALL {
+            let _ = write!(
+                json,
+                ",{}:{}",
+                json_string(problem.name()),
+                self.with_problem(problem).count()
+            );
+        }
+        json.push_str("}}");
+
+        json
+    }
+}
+
+/// Lines listing `paths`, for a summary.
+fn paths_in(paths: &[PathBuf]) -> Vec<String> {
+    paths
+        .iter()
+        .map(|path| format!("    {}", path.display()))
+        .collect()
+}
+
+/// Format a number of bytes using binary units.
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{bytes} {}", UNITS[0])
+    } else {
+        format!("{size:.1} {}", UNITS[unit])
+    }
+}
+
+/// Encode a string as a JSON string literal.
+pub fn json_string(value: &str) -> String {
+    let mut json = String::with_capacity(value.len() + 2);
+    json.push('"');
+    for char in value.chars() {
+        match char {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            char if char.is_control() => {
+                let _ = write!(json, "\\u{:04x}", u32::from(char));
+            }
+            char => json.push(char),
+        }
+    }
+    json.push('"');
+    json
+}
+
+/// Encode an optional string as a JSON string literal or `null`.
+fn json_optional_string(value: Option<&str>) -> String {
+    value.map_or_else(|| "null".to_owned(), json_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Report of shortcut `name` for `game_id`, which ended with `action`.
+    fn item(name: &str, game_id: &str, action: Action) -> ItemReport {
+        ItemReport {
+            game_id: Some(game_id.to_owned()),
+            ..ItemReport::new(Path::new(&format!("{name}.url")), action)
+        }
+    }
+
+    /// Report of shortcut `name` for `game_id`, which failed with `error` of kind `failure`.
+    fn failed(name: &str, game_id: &str, failure: Option<FailureKind>, error: &str) -> ItemReport {
+        ItemReport {
+            error: Some(error.to_owned()),
+            failure,
+            ..item(name, game_id, Action::Failed)
+        }
+    }
+
+    /// Report of a run with every shortcut in `items`, in order.
+    fn report(items: Vec<ItemReport>) -> RunReport {
+        RunReport {
+            items,
+            ..RunReport::default()
+        }
+    }
+
+    #[test]
+    fn groups_failures_by_kind() {
+        let report = report(vec![
+            failed("a", "1", Some(FailureKind::Network), "Timed out"),
+            item("b", "2", Action::Downloaded),
+            failed("c", "3", Some(FailureKind::Shortcut), "Not a shortcut"),
+            failed("d", "4", None, "Something else"),
+            failed("e", "5", Some(FailureKind::Network), "Connection reset"),
+            failed("f", "6", Some(FailureKind::Other), "Anything"),
+        ]);
+
+        // In the order of the kinds, then the order they failed in
+        let groups: Vec<_> = report
+            .failure_groups()
+            .into_iter()
+            .map(|group| {
+                let shortcuts: Vec<_> = group
+                    .items
+                    .iter()
+                    .map(|item| item.shortcut.to_string_lossy().into_owned())
+                    .collect();
+                (group.kind, shortcuts)
+            })
+            .collect();
+        assert_eq!(
+            groups,
+            [
+                (FailureKind::Shortcut, vec!["c.url".to_owned()]),
+                (
+                    FailureKind::Network,
+                    vec!["a.url".to_owned(), "e.url".to_owned()]
+                ),
+                (
+                    FailureKind::Other,
+                    vec!["d.url".to_owned(), "f.url".to_owned()]
+                ),
+            ]
+        );
+        assert_eq!(
+            report.failures(),
+            [
+                "Failures:",
+                "  Shortcut errors (1):",
+                "    c.url (game #3): Not a shortcut",
+                "  Network errors (2):",
+                "    a.url (game #1): Timed out",
+                "    e.url (game #5): Connection reset",
+                "  Other errors (2):",
+                "    d.url (game #4): Something else",
+                "    f.url (game #6): Anything",
+            ]
+        );
+    }
+
+    #[test]
+    fn lists_first_failures_of_each_kind() {
+        let mut items: Vec<_> = (0..MAX_FAILURES_LISTED + 2)
+            .map(|index| failed(&index.to_string(), "440", Some(FailureKind::Http), "404"))
+            .collect();
+        items.push(failed(
+            "write",
+            "620",
+            Some(FailureKind::Write),
+            "Read-only",
+        ));
+        let failures = report(items).failures();
+
+        assert_eq!(failures.len(), 1 + 1 + MAX_FAILURES_LISTED + 1 + 2);
+        assert_eq!(
+            failures[1],
+            format!("  HTTP errors ({}):", MAX_FAILURES_LISTED + 2)
+        );
+        assert_eq!(
+            failures[MAX_FAILURES_LISTED + 1],
+            "    19.url (game #440): 404"
+        );
+        assert_eq!(failures[MAX_FAILURES_LISTED + 2], "    ...and 2 more");
+        assert_eq!(failures[MAX_FAILURES_LISTED + 3], "  Write errors (1):");
+    }
+
+    #[test]
+    fn nothing_failed_lists_no_failures() {
+        let report = report(vec![item("a", "1", Action::Downloaded)]);
+        assert!(report.failure_groups().is_empty());
+        assert!(report.failures().is_empty());
+    }
+
+    /// Every action, in the order they're declared.
+    const ACTIONS: [Action; 19] = [
+        Action::Downloaded,
+        Action::Redownloaded,
+        Action::Repaired,
+        Action::AlreadyExists,
+        Action::Updated,
+        Action::Unchanged,
+        Action::Skipped,
+        Action::FilteredOut,
+        Action::Excluded,
+        Action::NonSteam,
+        Action::Duplicate,
+        Action::NotFound,
+        Action::Placeholder,
+        Action::KnownMissing,
+        Action::UnavailableOffline,
+        Action::Pending,
+        Action::OverBudget,
+        Action::Missing,
+        Action::Failed,
+    ];
+
+    /// Report of a run in which a shortcut in `Desktop` ended with each action,
+    /// each for a game numbered by its position (from 1).
+    fn report_of_every_action() -> RunReport {
+        let mut report = report(
+            ACTIONS
+                .iter()
+                .zip(1..)
+                .map(|(&action, game_id)| {
+                    item(
+                        &format!("Desktop/{}", action.name()),
+                        &game_id.to_string(),
+                        action,
+                    )
+                })
+                .collect(),
+        );
+        report.items[0].bytes = 3 * 1024;
+        report.items[11].game_name = Some("Portal".to_owned());
+        report.items[18].error = Some("Timed out".to_owned());
+        report.locations = vec![PathBuf::from("Desktop"), PathBuf::from("Start Menu")];
+        report.unreadable_entries = 1;
+        report.download_time = Duration::from_secs(2);
+        report
+    }
+
+    #[test]
+    fn summarizes_every_action() {
+        let report = report_of_every_action();
+        for action in ACTIONS {
+            assert_eq!(report.count(action), 1, "{action:?}");
+        }
+        assert_eq!(
+            report.summary(),
+            [
+                "Summary:",
+                "  Shortcuts scanned:      19",
+                "    in Desktop: 19",
+                "    in Start Menu: 0",
+                "  Unreadable entries:     1",
+                "  Skipped (not Steam):    1",
+                "  Filtered out:           1",
+                "  Excluded:               1",
+                "  Non-Steam games:        1",
+                "  Duplicates:             1",
+                "  Icons already present:  1",
+                "  Icons downloaded:       1 (3.0 KiB)",
+                "  Icons re-downloaded:    1",
+                "  Damaged icons repaired: 1",
+                "  Icons updated:          1",
+                "  Icons unchanged:        1",
+                "  Icons missing from CDN: 1",
+                "    Portal (12)",
+                "  Placeholders generated: 1",
+                "    game #13",
+                "  Skipped (no CDN icon):  1",
+                "    game #14",
+                "  Unavailable offline:    1",
+                "    game #15",
+                "  Over budget:            1",
+                "    game #17",
+                "  Failed:                 1",
+                "  Download time:          2.0s (1.5 KiB/s)",
+                "  Pending (dry run):      1",
+                "  Icons missing:          1",
+                "    game #18",
+            ]
+        );
+    }
+
+    #[test]
+    fn summary_leaves_out_what_didnt_happen() {
+        let report = report(vec![item("Desktop/a", "1", Action::AlreadyExists)]);
+        assert_eq!(
+            report.summary(),
+            [
+                "Summary:",
+                "  Shortcuts scanned:      1",
+                "  Skipped (not Steam):    0",
+                "  Filtered out:           0",
+                "  Excluded:               0",
+                "  Non-Steam games:        0",
+                "  Duplicates:             0",
+                "  Icons already present:  1",
+                "  Icons downloaded:       0 (0 B)",
+                "  Icons re-downloaded:    0",
+                "  Damaged icons repaired: 0",
+                "  Icons missing from CDN: 0",
+                "  Skipped (no CDN icon):  0",
+                "  Failed:                 0",
+            ]
+        );
+    }
+
+    #[test]
+    fn briefly_describes_every_action() {
+        // Anything pending means it was a dry run, and unreadable entries count as failures
+        let mut report = report_of_every_action();
+        assert_eq!(
+            report.brief().unwrap(),
+            "1 icon(s) would be downloaded, 1 not on Steam's CDN, 1 placeholder(s) generated, 1 \
+             unavailable offline, 1 game(s) left over budget, 2 failed"
+        );
+
+        // Downloaded, re-downloaded, repaired and updated icons were all saved
+        report.items.retain(|item| item.action != Action::Pending);
+        assert_eq!(
+            report.brief().unwrap(),
+            "4 icon(s) downloaded, 1 not on Steam's CDN, 1 placeholder(s) generated, 1 \
+             unavailable offline, 1 game(s) left over budget, 2 failed"
+        );
+    }
+
+    #[test]
+    fn brief_only_mentions_what_happened() {
+        let report = report(vec![
+            item("a", "1", Action::Downloaded),
+            item("b", "2", Action::AlreadyExists),
+        ]);
+        assert_eq!(report.brief().unwrap(), "1 icon(s) downloaded, 0 failed");
+    }
+
+    #[test]
+    fn no_brief_when_nothing_was_missing() {
+        assert_eq!(RunReport::default().brief(), None);
+        let report = report(
+            [
+                Action::AlreadyExists,
+                Action::Unchanged,
+                Action::Skipped,
+                Action::FilteredOut,
+                Action::Excluded,
+                Action::NonSteam,
+                Action::Duplicate,
+                Action::KnownMissing,
+                Action::Missing,
+            ]
+            .into_iter()
+            .map(|action| item(action.name(), "1", action))
+            .collect(),
+        );
+        assert_eq!(report.brief(), None);
+    }
+}
@@ -0,0 +1,118 @@
+//! Looking up which icon a game uses, for shortcuts that don't say (or say something odd).
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Mutex, OnceLock};
+
+use anyhow::{Context as _, Result};
+use log::*;
+use regex::Regex;
+use reqwest::Client;
+
+use crate::download::{self, HttpSettings};
+use crate::rate_limit::RateLimiter;
+
+/// Finds the filename of a game's icon on the CDN.
+pub trait IconResolver {
+    /// Filename of `game_id`'s icon on the CDN (e.g. `<hash>.ico`), if it can be found.
+    fn cdn_filename(&self, game_id: &str) -> impl Future<Output = Option<String>> + Send;
+}
+
+/// Whether `filename` looks like the name of an icon on the CDN,
+/// which are named after their SHA-1 hash.
+pub fn is_cdn_filename(filename: &str) -> bool {
+    filename
+        .strip_suffix(".ico")
+        .is_some_and(|hash| hash.len() == 40 && hash.bytes().all(|byte| byte.is_ascii_hexdigit()))
+}
+
+/// Looks up icons in the app info Steam uses (its `clienticon`),
+/// remembering the results for the rest of the run.
+///
+/// The store's `appdetails` API doesn't include the icon,
+/// so this uses SteamCMD's public mirror of the app info instead.
+pub struct WebIconResolver {
+    /// Client shared between lookups
+    client: Client,
+
+    /// Results of previous lookups (including failures), by game ID
+    cache: Mutex<HashMap<String, Option<String>>>,
+
+    /// Spaces out lookups
+    rate_limiter: RateLimiter,
+}
+
+impl WebIconResolver {
+    /// Resolver with lookups made according to `settings` and spaced out by `rate_limiter`.
+    pub fn new(settings: &HttpSettings, rate_limiter: RateLimiter) -> Result<Self> {
+        Ok(Self {
+            client: download::http_client(settings)?,
+            cache: Mutex::new(HashMap::new()),
+            rate_limiter,
+        })
+    }
+
+    /// Fetch the filename of `game_id`'s icon on the CDN.
+    async fn fetch(&self, game_id: &str) -> Result<Option<String>> {
+        // Only ever insert digits into the URL
+        if game_id.is_empty() || !game_id.bytes().all(|byte| byte.is_ascii_digit()) {
+            return Ok(None);
+        }
+
+        let url = format!("https://api.steamcmd.net/v1/info/{game_id}");
+        let body = self
+            .rate_limiter
+            .send(|| self.client.get(&url))
+            .await
+            .and_then(|response| Ok(response.error_for_status()?))
+            .context("Request failed")?
+            .text()
+            .await
+            .context("Failed to read response")?;
+
+        // Pull out (naively) the icon from the JSON response
+        static CLIENT_ICON_REGEX: OnceLock<Regex> = OnceLock::new();
+        let client_icon_regex = CLIENT_ICON_REGEX
+            .get_or_try_init(|| Regex::new(r#""clienticon"\s*:\s*"([0-9a-fA-F]{40})""#))?;
+        Ok(client_icon_regex
+            .captures(&body)
+            .map(|captures| format!("{}.ico", &captures[1])))
+    }
+}
+
+impl IconResolver for WebIconResolver {
+    fn cdn_filename(&self, game_id: &str) -> impl Future<Output = Option<String>> + Send {
+        let game_id = game_id.to_owned();
+        async move {
+            if let Some(cdn_filename) = self
+                .cache
+                .lock()
+                .expect("cache lock poisoned")
+                .get(&game_id)
+            {
+                return cdn_filename.clone();
+            }
+
+            // Failing to look up an icon isn't fatal, since it's only needed as a fallback
+            let cdn_filename = match self.fetch(&game_id).await {
+                Ok(Some(cdn_filename)) => {
+                    debug!("Found icon `{cdn_filename}` for game #{game_id}");
+                    Some(cdn_filename)
+                }
+                Ok(None) => {
+                    debug!("No icon found for game #{game_id}");
+                    None
+                }
+                Err(error) => {
+                    warn!("Failed to look up the icon for game #{game_id}: {error:#}");
+                    None
+                }
+            };
+            self.cache
+                .lock()
+                .expect("cache lock poisoned")
+                .insert(game_id, cdn_filename.clone());
+            cdn_filename
+        }
+    }
+}
@@ -0,0 +1,86 @@
+//! Hashing icons with SHA-1, which is how the CDN names them.
+
+use ::sha1::Digest as _;
+
+/// SHA-1 hash of `data`.
+pub fn digest(data: &[u8]) -> [u8; 20] {
+    let mut hasher = Sha1::new();
+    hasher.update(data);
+    hasher.finish()
+}
+
+/// SHA-1 hash of `data` as lowercase hex, like the names of icons on the CDN.
+pub fn hex_digest(data: &[u8]) -> String {
+    to_hex(&digest(data))
+}
+
+/// `hash` as lowercase hex, like the names of icons on the CDN.
+pub fn to_hex(hash: &[u8; 20]) -> String {
+    hash.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// SHA-1 hash of data fed in a piece at a time (e.g. while it's downloaded).
+#[derive(Default)]
+pub struct Sha1(::sha1::Sha1);
+
+impl Sha1 {
+    /// Hash of no data yet.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add `data` to the hash.
+    pub fn update(&mut self, data: &[u8]) {
+        self.0.update(data);
+    }
+
+    /// Hash of all the data added.
+    pub fn finish(self) -> [u8; 20] {
+        self.0.finalize().into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Messages from FIPS 180-2's SHA-1 examples, and their hashes.
+    const VECTORS: [(&[u8], &str); 3] = [
+        (b"", "da39a3ee5e6b4b0d3255bfef95601890afd80709"),
+        (b"abc", "a9993e364706816aba3e25717850c26c9cd0d89d"),
+        (
+            b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq",
+            "84983e441c3bd26ebaae4aa1f95129e5e54670f1",
+        ),
+    ];
+
+    #[test]
+    fn matches_fips_vectors() {
+        for (message, hash) in VECTORS {
+            assert_eq!(hex_digest(message), hash);
+        }
+    }
+
+    #[test]
+    fn matches_fips_vectors_in_pieces() {
+        for (message, hash) in VECTORS {
+            let mut hasher = Sha1::new();
+            for piece in message.chunks(7) {
+                hasher.update(piece);
+            }
+            assert_eq!(to_hex(&hasher.finish()), hash);
+        }
+    }
+
+    #[test]
+    fn matches_million_as() {
+        let mut hasher = Sha1::new();
+        for _ in 0..1000 {
+            hasher.update(&[b'a'; 1000]);
+        }
+        assert_eq!(
+            to_hex(&hasher.finish()),
+            "34aa973cd4c4daa4f61eeb2bdbad27316534016f"
+        );
+    }
+}
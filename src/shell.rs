@@ -0,0 +1,199 @@
+//! Telling the Windows shell (Explorer) about shortcuts whose icons were just saved,
+//! so it shows them right away instead of the blank icons it cached while they were missing.
+//! Nothing needs telling on other platforms, so nothing is done there.
+//!
+//! Rebuilding Explorer's icon cache is offered too, for when even that isn't enough,
+//! as is showing a notification once a run is done (for when there's no console to read).
+//!
+//! This is only ever a nicety, so nothing here fails:
+//! anything that goes wrong is logged and otherwise ignored.
+
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use log::*;
+
+/// Tell the shell the shortcut at `path` has changed, so it loads its icon again.
+#[cfg(windows)]
+pub fn notify_updated(path: &Path) {
+    use std::os::windows::ffi::OsStrExt as _;
+    use std::ptr;
+
+    use windows_sys::Win32::UI::Shell::{SHCNE_UPDATEITEM, SHCNF_PATHW, SHChangeNotify};
+
+    // The shell only recognizes full paths
+    let path = match std::path::absolute(path) {
+        Ok(path) => path,
+        Err(error) => {
+            debug!(
+                "Failed to tell the shell `{}` changed: {error}",
+                path.display()
+            );
+            return;
+        }
+    };
+    let wide: Vec<u16> = path.as_os_str().encode_wide().chain([0]).collect();
+
+    // SAFETY: `wide` is a NUL-terminated UTF-16 path that outlives the call,
+    // and the second item isn't used for this event
+    unsafe {
+        SHChangeNotify(
+            SHCNE_UPDATEITEM as i32,
+            SHCNF_PATHW,
+            wide.as_ptr().cast(),
+            ptr::null(),
+        );
+    }
+    debug!("Told the shell `{}` changed", path.display());
+}
+
+/// Leave the shortcut at `path` alone, since there's no shell to tell.
+#[cfg(not(windows))]
+pub fn notify_updated(_path: &Path) {}
+
+/// Tell the shell to throw out the icons it cached,
+/// waiting for it to handle every earlier notification first.
+#[cfg(windows)]
+pub fn refresh_icons() {
+    use std::ptr;
+
+    use windows_sys::Win32::UI::Shell::{
+        SHCNE_ASSOCCHANGED,
+        SHCNF_FLUSH,
+        SHCNF_IDLIST,
+        SHChangeNotify,
+    };
+
+    // SAFETY: neither item is used for this event
+    unsafe {
+        SHChangeNotify(
+            SHCNE_ASSOCCHANGED as i32,
+            SHCNF_IDLIST | SHCNF_FLUSH,
+            ptr::null(),
+            ptr::null(),
+        );
+    }
+    debug!("Told the shell to refresh its icons");
+}
+
+/// Do nothing, since there's no shell to tell.
+#[cfg(not(windows))]
+pub fn refresh_icons() {}
+
+/// Rebuild Explorer's icon cache, which can keep showing blank icons even after being told
+/// about new ones: refresh it with `ie4uinit.exe -show`, remove the cache files in
+/// `icon_cache_dir` (the ones Explorer isn't using), then tell the shell to refresh its icons.
+/// Only lists what would be done if `dry_run` is set.
+pub fn rebuild_icon_cache(icon_cache_dir: &Path, dry_run: bool) {
+    if dry_run {
+        info!("Would run `ie4uinit.exe -show` to refresh the icon cache");
+    } else {
+        info!("Running `ie4uinit.exe -show` to refresh the icon cache");
+        match Command::new("ie4uinit.exe").arg("-show").status() {
+            Ok(status) if status.success() => {}
+            Ok(status) => warn!("`ie4uinit.exe -show` failed ({status}), carrying on without it"),
+            Err(error) => {
+                warn!("Failed to run `ie4uinit.exe -show` ({error}), carrying on without it")
+            }
+        }
+    }
+
+    let entries = match fs::read_dir(icon_cache_dir) {
+        Ok(entries) => entries,
+        Err(error) => {
+            warn!(
+                "Failed to list the icon cache in `{}`: {error}",
+                icon_cache_dir.display()
+            );
+            return;
+        }
+    };
+    let mut in_use = 0;
+    for entry in entries.flatten() {
+        let filename = entry.file_name().to_string_lossy().to_lowercase();
+        if !(filename.starts_with("iconcache_") && filename.ends_with(".db")) {
+            continue;
+        }
+
+        let path = entry.path();
+        if dry_run {
+            info!("Would remove icon cache file `{}`", path.display());
+            continue;
+        }
+        match fs::remove_file(&path) {
+            Ok(()) => info!("Removed icon cache file `{}`", path.display()),
+            Err(error) => {
+                warn!(
+                    "Failed to remove icon cache file `{}`: {error}",
+                    path.display()
+                );
+                in_use += 1;
+            }
+        }
+    }
+    if in_use > 0 {
+        warn!(
+            "Explorer is probably using the {in_use} icon cache file(s) that couldn't be removed; \
+             to remove them too, end `Windows Explorer` in Task Manager, run this again with \
+             `--rebuild-icon-cache` (from `Run new task`), then start `explorer.exe` again"
+        );
+    }
+
+    if dry_run {
+        info!("Would tell the shell to refresh its icons");
+    } else {
+        refresh_icons();
+    }
+}
+
+/// Title of the notifications shown once a run is done
+const NOTIFICATION_TITLE: &str = "Retrieve missing Steam game icons";
+
+/// Script showing a toast notification with the title and message in `TOAST_TITLE`
+/// and `TOAST_MESSAGE`, through the WinRT toast APIs PowerShell can reach
+/// (as PowerShell, since Windows only shows toasts from apps it knows about)
+#[cfg(windows)]
+const TOAST_SCRIPT: &str = r#"
+$ErrorActionPreference = 'Stop'
+$Manager = [Windows.UI.Notifications.ToastNotificationManager, Windows.UI.Notifications, ContentType = WindowsRuntime]
+$Toast = $Manager::GetTemplateContent([Windows.UI.Notifications.ToastTemplateType]::ToastText02)
+$Text = $Toast.GetElementsByTagName('text')
+[void]$Text.Item(0).AppendChild($Toast.CreateTextNode($env:TOAST_TITLE))
+[void]$Text.Item(1).AppendChild($Toast.CreateTextNode($env:TOAST_MESSAGE))
+$AppId = '{1AC14E77-02E7-4E5D-B744-2EB1AE5198B7}\WindowsPowerShell\v1.0\powershell.exe'
+$Manager::CreateToastNotifier($AppId).Show([Windows.UI.Notifications.ToastNotification]::new($Toast))
+"#;
+
+/// Show a toast notification with `message`.
+#[cfg(windows)]
+pub fn notify(message: &str) {
+    use std::os::windows::process::CommandExt as _;
+
+    // Keep PowerShell's console from flashing up
+    const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+
+    debug!("Showing notification: {message}");
+    let output = Command::new("powershell.exe")
+        .args(["-NoProfile", "-NonInteractive", "-Command", TOAST_SCRIPT])
+        .env("TOAST_TITLE", NOTIFICATION_TITLE)
+        .env("TOAST_MESSAGE", message)
+        .creation_flags(CREATE_NO_WINDOW)
+        .output();
+    match output {
+        Ok(output) if output.status.success() => {}
+        Ok(output) => warn!(
+            "Failed to show a notification ({}): {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ),
+        Err(error) => warn!("Failed to run PowerShell to show a notification: {error}"),
+    }
+}
+
+/// Show nothing, since notifications are only shown on Windows.
+#[cfg(not(windows))]
+pub fn notify(message: &str) {
+    warn!("Notifications are only shown on Windows, so `--notify` does nothing here");
+    debug!("Would have shown notification for {NOTIFICATION_TITLE:?}: {message}");
+}
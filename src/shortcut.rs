@@ -0,0 +1,821 @@
+//! Parsers (and a writer) for the shortcut formats Steam creates.
+//! These only deal with the contents of shortcuts, so they work the same on every platform.
+
+use std::env;
+use std::error::Error;
+use std::fmt::{self, Display};
+use std::io::BufRead;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use anyhow::{Context as _, Result, bail};
+use regex::Regex;
+
+use crate::paths;
+
+/// Text encodings shortcuts may be saved in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    /// UTF-8 (or ASCII) without a byte order mark
+    Utf8,
+
+    /// UTF-8 with a byte order mark
+    Utf8Bom,
+
+    /// Little-endian UTF-16 with a byte order mark
+    Utf16Le,
+
+    /// Big-endian UTF-16 with a byte order mark
+    Utf16Be,
+}
+
+impl Encoding {
+    /// Detect the encoding of text from its byte order mark (if any).
+    /// Returns the encoding and the length of the byte order mark.
+    pub fn detect(bytes: &[u8]) -> (Self, usize) {
+        if bytes.starts_with(&[0xef, 0xbb, 0xbf]) {
+            (Self::Utf8Bom, 3)
+        } else if bytes.starts_with(&[0xff, 0xfe]) {
+            (Self::Utf16Le, 2)
+        } else if bytes.starts_with(&[0xfe, 0xff]) {
+            (Self::Utf16Be, 2)
+        } else {
+            (Self::Utf8, 0)
+        }
+    }
+
+    /// Encode `text`, starting with the byte order mark (if any).
+    pub fn encode(self, text: &str) -> Vec<u8> {
+        match self {
+            Self::Utf8 => text.as_bytes().to_vec(),
+            Self::Utf8Bom => [&[0xef, 0xbb, 0xbf], text.as_bytes()].concat(),
+            Self::Utf16Le => [0xff, 0xfe]
+                .into_iter()
+                .chain(text.encode_utf16().flat_map(u16::to_le_bytes))
+                .collect(),
+            Self::Utf16Be => [0xfe, 0xff]
+                .into_iter()
+                .chain(text.encode_utf16().flat_map(u16::to_be_bytes))
+                .collect(),
+        }
+    }
+
+    /// Human-readable name of the encoding.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Utf8 => "UTF-8",
+            Self::Utf8Bom => "UTF-8 with BOM",
+            Self::Utf16Le => "UTF-16LE",
+            Self::Utf16Be => "UTF-16BE",
+        }
+    }
+}
+
+/// Read all of `reader` as text, decoding it based on its byte order mark (if any).
+pub fn read_text(mut reader: impl BufRead) -> Result<String> {
+    let mut bytes = Vec::new();
+    reader
+        .read_to_end(&mut bytes)
+        .context("Failed to read file")?;
+    let (encoding, bom_len) = Encoding::detect(&bytes);
+    let bytes = &bytes[bom_len..];
+
+    // Decode the text
+    let text = match encoding {
+        Encoding::Utf8 | Encoding::Utf8Bom => String::from_utf8(bytes.to_vec()).ok(),
+        Encoding::Utf16Le | Encoding::Utf16Be if bytes.len() % 2 == 0 => {
+            let units = bytes.chunks_exact(2).map(|unit| match encoding {
+                Encoding::Utf16Le => u16::from_le_bytes([unit[0], unit[1]]),
+                _ => u16::from_be_bytes([unit[0], unit[1]]),
+            });
+            char::decode_utf16(units)
+                .collect::<Result<String, _>>()
+                .ok()
+        }
+        Encoding::Utf16Le | Encoding::Utf16Be => None,
+    };
+    let Some(text) = text else {
+        bail!(
+            "File is not valid text (detected encoding: {})",
+            encoding.name()
+        );
+    };
+
+    // Text files shouldn't contain null characters
+    if text.contains('\0') {
+        bail!(
+            "File contains binary data (detected encoding: {})",
+            encoding.name()
+        );
+    }
+
+    Ok(text)
+}
+
+/// Details extracted from a Steam shortcut.
+pub struct Shortcut {
+    /// Steam game ID
+    pub game_id: String,
+
+    /// Filename the icon is saved as locally
+    pub icon_filename: String,
+
+    /// Filename of the icon on the CDN, if the shortcut records it
+    pub cdn_filename: Option<String>,
+
+    /// Directory the icon is saved in, if not the local icon directory
+    pub icon_dir: Option<PathBuf>,
+}
+
+/// Error for shortcuts to games added to Steam from elsewhere ("Add a Non-Steam Game"),
+/// whose icons aren't on the CDN.
+#[derive(Debug)]
+pub struct NonSteamGame {
+    /// ID Steam gave the game
+    pub game_id: String,
+}
+
+impl Display for NonSteamGame {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Shortcut is for non-Steam game #{}", self.game_id)
+    }
+}
+
+impl Error for NonSteamGame {}
+
+/// Fail with [`NonSteamGame`] if `game_id` is for a game added to Steam from elsewhere.
+/// Steam gives those 64-bit IDs (the shortcut's ID shifted up, with `0x02000000` set),
+/// which are too large to be a real game's.
+pub fn ensure_steam_game(game_id: &str) -> Result<()> {
+    if game_id.parse::<u32>().is_err() {
+        return Err(NonSteamGame {
+            game_id: game_id.to_owned(),
+        }
+        .into());
+    }
+    Ok(())
+}
+
+/// Error for `.url` shortcuts whose icon isn't in the icon directory
+/// (e.g. because Steam has moved since the shortcut was created).
+#[derive(Debug)]
+pub struct UnrecognizedIconDir {
+    /// Directory the shortcut's icon is in
+    pub icon_dir: PathBuf,
+}
+
+impl Display for UnrecognizedIconDir {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Unrecognized icon directory `{}`",
+            self.icon_dir.display()
+        )
+    }
+}
+
+impl Error for UnrecognizedIconDir {}
+
+/// Regex for extracting the icon path from the `IconFile` of a `.url` shortcut
+/// (the path may be wrapped in quotes and may not include a directory).
+/// Captures the opening quote, the directory, the filename, and the closing quote.
+pub fn icon_path_regex() -> Result<&'static Regex> {
+    static ICON_PATH_REGEX: OnceLock<Regex> = OnceLock::new();
+    let icon_path_regex = ICON_PATH_REGEX
+        .get_or_try_init(|| Regex::new(r#"^(?i:IconFile)=("?)(.*[\\/])?([^.\\/"]+\.ico)("?)$"#))?;
+    Ok(icon_path_regex)
+}
+
+/// Parse (naively) the `[InternetShortcut]` section of a `.url` shortcut file.
+/// The icon must be located in `expected_icon_dir`,
+/// with relative icon paths resolved against `shortcut_dir` (the directory containing the shortcut).
+///
+/// Like other INI files, section names and keys are case-insensitive.
+pub fn parse_internet_shortcut(
+    reader: impl BufRead,
+    shortcut_dir: &Path,
+    expected_icon_dir: &Path,
+) -> Result<Shortcut> {
+    // Build the regex for extracting the steam game ID from the shortcut URL
+    static GAME_ID_REGEX: OnceLock<Regex> = OnceLock::new();
+    let game_id_regex =
+        GAME_ID_REGEX.get_or_try_init(|| Regex::new(r"^(?i:URL)=steam://rungameid/(\d+)$"))?;
+
+    let icon_path_regex = icon_path_regex()?;
+
+    let mut game_id: Option<String> = None;
+    let mut icon_path: Option<(String, String)> = None;
+    let mut in_shortcut_section = false;
+    for line in read_text(reader)?.lines() {
+        // Find and extract the game ID and icon path
+        // from the "InternetShortcut" section within the shortcut file
+        if line.eq_ignore_ascii_case("[InternetShortcut]") {
+            in_shortcut_section = true;
+        } else if !in_shortcut_section {
+            continue;
+        } else if line.starts_with("[") {
+            in_shortcut_section = false;
+        } else if let Some(captures) = game_id_regex.captures(line) {
+            if game_id.is_some() {
+                bail!("Game ID already set");
+            }
+
+            game_id = Some(
+                captures
+                    .get(1)
+                    .context("Failed to extract icon path")?
+                    .as_str()
+                    .to_owned(),
+            );
+        } else if let Some(captures) = icon_path_regex.captures(line) {
+            if icon_path.is_some() {
+                bail!("Icon path and/or name already set");
+            }
+
+            // Quotes must come in pairs
+            if captures[1] != captures[4] {
+                bail!("Mismatched quotes around icon path");
+            }
+
+            icon_path = Some((
+                captures
+                    .get(2)
+                    .map_or("", |icon_dir| icon_dir.as_str())
+                    .to_owned(),
+                captures
+                    .get(3)
+                    .context("Failed to extract icon path")?
+                    .as_str()
+                    .to_owned(),
+            ));
+        }
+    }
+
+    // Non-Steam games' icons are wherever they came from, so don't even look
+    if let Some(game_id) = &game_id {
+        ensure_steam_game(game_id)?;
+    }
+    let (Some(game_id), Some((icon_dir, icon_filename))) = (game_id, icon_path) else {
+        bail!("Shortcut could not be parsed or was not a Steam shortcut file");
+    };
+
+    // Make sure the specified icon directory matches the one being written to
+    // (after expanding variables like `%ProgramFiles(x86)%`,
+    // and with relative directories relative to the shortcut)
+    let icon_dir = paths::expand_env_vars(&icon_dir, |name| env::var(name).ok())?;
+    let icon_dir = shortcut_dir.join(icon_dir);
+    if !paths::same_dir(&icon_dir, expected_icon_dir) {
+        return Err(UnrecognizedIconDir { icon_dir }.into());
+    }
+
+    // Steam names icons after their filename on the CDN
+    Ok(Shortcut {
+        game_id,
+        cdn_filename: Some(icon_filename.clone()),
+        icon_filename,
+        icon_dir: None,
+    })
+}
+
+/// Point the `IconFile` in the `[InternetShortcut]` section of a `.url` shortcut's `text`
+/// at `icon_dir` (keeping the icon's filename and any quotes), leaving everything else as it was.
+/// Returns `None` if there's no `IconFile` to change.
+pub fn replace_icon_dir(text: &str, icon_dir: &Path) -> Result<Option<String>> {
+    let icon_path_regex = icon_path_regex()?;
+
+    let mut replaced = false;
+    let mut fixed = String::with_capacity(text.len());
+    let mut in_shortcut_section = false;
+    for line in text.split_inclusive('\n') {
+        // Lines are matched the same way as when parsing, but kept with their line endings
+        let content = line.trim_end_matches(['\r', '\n']);
+        if content.eq_ignore_ascii_case("[InternetShortcut]") {
+            in_shortcut_section = true;
+        } else if content.starts_with("[") {
+            in_shortcut_section = false;
+        } else if in_shortcut_section
+            && let Some(captures) = icon_path_regex.captures(content)
+            && let Some((key, _)) = content.split_once('=')
+        {
+            let quote = &captures[1];
+            let icon_path = icon_dir.join(&captures[3]);
+            let line_ending = &line[content.len()..];
+            fixed.push_str(&format!(
+                "{key}={quote}{}{quote}{line_ending}",
+                icon_path.display()
+            ));
+            replaced = true;
+            continue;
+        }
+        fixed.push_str(line);
+    }
+
+    Ok(replaced.then_some(fixed))
+}
+
+/// Find the game any kind of shortcut launches, without checking anything else about it.
+pub fn find_game_id(reader: impl BufRead) -> Result<Option<String>> {
+    static GAME_ID_REGEX: OnceLock<Regex> = OnceLock::new();
+    let game_id_regex =
+        GAME_ID_REGEX.get_or_try_init(|| Regex::new(r"steam://rungameid/(\d+)(?:\W|$)"))?;
+
+    Ok(read_text(reader)?
+        .lines()
+        .find_map(|line| Some(game_id_regex.captures(line)?[1].to_owned())))
+}
+
+/// Contents of a `.url` shortcut like the ones Steam creates,
+/// launching `game_id` with the icon at `icon_path`.
+pub fn format_internet_shortcut(game_id: &str, icon_path: &Path) -> String {
+    let lines = [
+        "[{000214A0-0000-0000-C000-000000000046}]".to_owned(),
+        "Prop3=19,0".to_owned(),
+        "[InternetShortcut]".to_owned(),
+        "IDList=".to_owned(),
+        "IconIndex=0".to_owned(),
+        format!("URL=steam://rungameid/{game_id}"),
+        format!("IconFile={}", icon_path.display()),
+    ];
+    lines.iter().map(|line| format!("{line}\r\n")).collect()
+}
+
+/// Parse (naively) the `[Desktop Entry]` section of a `.desktop` file created by Steam.
+pub fn parse_desktop_entry(reader: impl BufRead) -> Result<Shortcut> {
+    // Build the regex for extracting the steam game ID from the command or URL
+    static GAME_ID_REGEX: OnceLock<Regex> = OnceLock::new();
+    let game_id_regex = GAME_ID_REGEX
+        .get_or_try_init(|| Regex::new(r"^(?:Exec=.*\s|URL=)steam://rungameid/(\d+)\s*$"))?;
+
+    // Build the regex for extracting the icon name
+    static ICON_REGEX: OnceLock<Regex> = OnceLock::new();
+    let icon_regex = ICON_REGEX.get_or_try_init(|| Regex::new(r"^Icon=(steam_icon_\d+)$"))?;
+
+    let mut game_id: Option<String> = None;
+    let mut icon_name: Option<String> = None;
+    let mut in_entry_section = false;
+    for line in read_text(reader)?.lines() {
+        // Find and extract the game ID and icon name
+        // from the "Desktop Entry" section within the file
+        if line == "[Desktop Entry]" {
+            in_entry_section = true;
+        } else if !in_entry_section {
+            continue;
+        } else if line.starts_with("[") {
+            in_entry_section = false;
+        } else if let Some(captures) = game_id_regex.captures(line) {
+            if game_id.is_some() {
+                bail!("Game ID already set");
+            }
+
+            game_id = Some(
+                captures
+                    .get(1)
+                    .context("Failed to extract game ID")?
+                    .as_str()
+                    .to_owned(),
+            );
+        } else if let Some(captures) = icon_regex.captures(line) {
+            if icon_name.is_some() {
+                bail!("Icon name already set");
+            }
+
+            icon_name = Some(
+                captures
+                    .get(1)
+                    .context("Failed to extract icon name")?
+                    .as_str()
+                    .to_owned(),
+            );
+        }
+    }
+
+    // Non-Steam games' icons are wherever they came from, so don't even look
+    if let Some(game_id) = &game_id {
+        ensure_steam_game(game_id)?;
+    }
+    let (Some(game_id), Some(icon_name)) = (game_id, icon_name) else {
+        bail!("Shortcut could not be parsed or was not a Steam shortcut file");
+    };
+
+    // The icon is looked up by name in the icon theme,
+    // and `.desktop` files don't record which icon on the CDN it came from
+    Ok(Shortcut {
+        game_id,
+        icon_filename: format!("{icon_name}.png"),
+        cdn_filename: None,
+        icon_dir: None,
+    })
+}
+
+/// Extract the game ID from the launch script of an app bundle created by Steam.
+pub fn parse_launch_script(reader: impl BufRead) -> Result<String> {
+    // Build the regex for extracting the steam game ID from the URL the script opens
+    static GAME_ID_REGEX: OnceLock<Regex> = OnceLock::new();
+    let game_id_regex =
+        GAME_ID_REGEX.get_or_try_init(|| Regex::new(r"steam://run(?:gameid)?/(\d+)(?:\W|$)"))?;
+
+    let mut game_id: Option<String> = None;
+    for line in read_text(reader)?.lines() {
+        if let Some(captures) = game_id_regex.captures(line) {
+            if game_id.is_some() {
+                bail!("Game ID already set");
+            }
+
+            game_id = Some(
+                captures
+                    .get(1)
+                    .context("Failed to extract game ID")?
+                    .as_str()
+                    .to_owned(),
+            );
+        }
+    }
+
+    game_id.context("Launch script could not be parsed or doesn't launch a Steam game")
+}
+
+/// Extract (naively) the `CFBundleIconFile` from an app bundle's `Info.plist`.
+pub fn parse_bundle_icon_file(reader: impl BufRead) -> Result<String> {
+    // Build the regex for extracting string values
+    static STRING_REGEX: OnceLock<Regex> = OnceLock::new();
+    let string_regex =
+        STRING_REGEX.get_or_try_init(|| Regex::new(r"^\s*<string>([^<>/\\]+)</string>\s*$"))?;
+
+    // The value is on the line following its key
+    let mut after_key = false;
+    for line in read_text(reader)?.lines() {
+        if line.trim() == "<key>CFBundleIconFile</key>" {
+            after_key = true;
+        } else if after_key {
+            let icon_file = string_regex
+                .captures(line)
+                .and_then(|captures| captures.get(1))
+                .context("Unexpected `CFBundleIconFile` value")?
+                .as_str();
+
+            // The extension is optional
+            return Ok(if icon_file.ends_with(".icns") {
+                icon_file.to_owned()
+            } else {
+                format!("{icon_file}.icns")
+            });
+        }
+    }
+
+    bail!("`Info.plist` doesn't specify an icon file")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Icon directory the shortcuts in these tests expect
+    const ICON_DIR: &str = "/steam/steam/games";
+
+    /// `lines` as the text of a file.
+    fn text(lines: &[&str]) -> String {
+        lines.iter().map(|line| format!("{line}\n")).collect()
+    }
+
+    /// Parse `lines` as a `.url` shortcut in `/desktop`, expecting its icon in [`ICON_DIR`].
+    fn parse(lines: &[&str]) -> Result<Shortcut> {
+        parse_internet_shortcut(
+            text(lines).as_bytes(),
+            Path::new("/desktop"),
+            Path::new(ICON_DIR),
+        )
+    }
+
+    #[test]
+    fn parses_internet_shortcut() {
+        let text = format_internet_shortcut("440", &Path::new(ICON_DIR).join("abc123.ico"));
+        let shortcut =
+            parse_internet_shortcut(text.as_bytes(), Path::new("/desktop"), Path::new(ICON_DIR))
+                .unwrap();
+        assert_eq!(shortcut.game_id, "440");
+        assert_eq!(shortcut.icon_filename, "abc123.ico");
+        assert_eq!(shortcut.cdn_filename.as_deref(), Some("abc123.ico"));
+        assert!(shortcut.icon_dir.is_none());
+    }
+
+    #[test]
+    fn parses_quoted_icon_path() {
+        let shortcut = parse(&[
+            "[InternetShortcut]",
+            "URL=steam://rungameid/440",
+            &format!("IconFile=\"{ICON_DIR}/abc123.ico\""),
+        ])
+        .unwrap();
+        assert_eq!(shortcut.icon_filename, "abc123.ico");
+    }
+
+    #[test]
+    fn parses_any_encoding() {
+        let text = format_internet_shortcut("440", &Path::new(ICON_DIR).join("abc123.ico"));
+        for encoding in [Encoding::Utf8Bom, Encoding::Utf16Le, Encoding::Utf16Be] {
+            let bytes = encoding.encode(&text);
+            let shortcut = parse_internet_shortcut(
+                bytes.as_slice(),
+                Path::new("/desktop"),
+                Path::new(ICON_DIR),
+            )
+            .unwrap();
+            assert_eq!(shortcut.game_id, "440", "{}", encoding.name());
+        }
+    }
+
+    #[test]
+    fn rejects_missing_url() {
+        let icon_file = format!("IconFile={ICON_DIR}/abc123.ico");
+        assert!(parse(&["[InternetShortcut]", &icon_file]).is_err());
+    }
+
+    #[test]
+    fn rejects_missing_icon_file() {
+        assert!(parse(&["[InternetShortcut]", "URL=steam://rungameid/440"]).is_err());
+    }
+
+    #[test]
+    fn rejects_duplicate_keys() {
+        let icon_file = format!("IconFile={ICON_DIR}/abc123.ico");
+        let other_icon_file = format!("IconFile={ICON_DIR}/def456.ico");
+        let duplicate_url = parse(&[
+            "[InternetShortcut]",
+            "URL=steam://rungameid/440",
+            "URL=steam://rungameid/570",
+            &icon_file,
+        ]);
+        assert!(duplicate_url.is_err());
+
+        let duplicate_icon_file = parse(&[
+            "[InternetShortcut]",
+            "URL=steam://rungameid/440",
+            &icon_file,
+            &other_icon_file,
+        ]);
+        assert!(duplicate_icon_file.is_err());
+    }
+
+    #[test]
+    fn rejects_mismatched_quotes() {
+        let icon_file = format!("IconFile=\"{ICON_DIR}/abc123.ico");
+        assert!(
+            parse(&[
+                "[InternetShortcut]",
+                "URL=steam://rungameid/440",
+                &icon_file
+            ])
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn rejects_wrong_icon_dir() {
+        let error = parse(&[
+            "[InternetShortcut]",
+            "URL=steam://rungameid/440",
+            "IconFile=/elsewhere/abc123.ico",
+        ])
+        .err()
+        .unwrap();
+        let error = error.downcast_ref::<UnrecognizedIconDir>().unwrap();
+        assert_eq!(error.icon_dir, Path::new("/elsewhere/"));
+    }
+
+    #[test]
+    fn resolves_relative_icon_dir() {
+        let text = text(&[
+            "[InternetShortcut]",
+            "URL=steam://rungameid/440",
+            "IconFile=icons/abc123.ico",
+        ]);
+        let shortcut = parse_internet_shortcut(
+            text.as_bytes(),
+            Path::new("/desktop"),
+            Path::new("/desktop/icons"),
+        )
+        .unwrap();
+        assert_eq!(shortcut.icon_filename, "abc123.ico");
+    }
+
+    #[test]
+    fn rejects_non_steam_game() {
+        let icon_file = format!("IconFile={ICON_DIR}/abc123.ico");
+        let error = parse(&[
+            "[InternetShortcut]",
+            "URL=steam://rungameid/12345678901234567890",
+            &icon_file,
+        ])
+        .err()
+        .unwrap();
+        let error = error.downcast_ref::<NonSteamGame>().unwrap();
+        assert_eq!(error.game_id, "12345678901234567890");
+    }
+
+    #[test]
+    fn rejects_other_urls() {
+        let icon_file = format!("IconFile={ICON_DIR}/abc123.ico");
+        assert!(parse(&["[InternetShortcut]", "URL=https://example.com/", &icon_file]).is_err());
+    }
+
+    #[test]
+    fn ignores_other_sections() {
+        let icon_file = format!("IconFile={ICON_DIR}/abc123.ico");
+        let other_icon_file = format!("IconFile={ICON_DIR}/def456.ico");
+        let shortcut = parse(&[
+            "URL=steam://rungameid/570",
+            "[Other]",
+            "URL=steam://rungameid/570",
+            "[InternetShortcut]",
+            "URL=steam://rungameid/440",
+            &icon_file,
+            "[After]",
+            &other_icon_file,
+        ])
+        .unwrap();
+        assert_eq!(shortcut.game_id, "440");
+        assert_eq!(shortcut.icon_filename, "abc123.ico");
+
+        let outside = parse(&["[Other]", "URL=steam://rungameid/440", &icon_file]);
+        assert!(outside.is_err());
+    }
+
+    #[test]
+    fn ignores_case_of_keys_and_sections() {
+        let shortcut = parse(&[
+            "[internetshortcut]",
+            "url=steam://rungameid/440",
+            &format!("iconfile={ICON_DIR}/abc123.ico"),
+        ])
+        .unwrap();
+        assert_eq!(shortcut.game_id, "440");
+        assert_eq!(shortcut.icon_filename, "abc123.ico");
+
+        let shortcut = parse(&[
+            "[INTERNETSHORTCUT]",
+            "Url=steam://rungameid/570",
+            &format!("ICONFILE={ICON_DIR}/def456.ico"),
+        ])
+        .unwrap();
+        assert_eq!(shortcut.game_id, "570");
+        assert_eq!(shortcut.icon_filename, "def456.ico");
+    }
+
+    #[test]
+    fn rejects_duplicate_keys_of_any_case() {
+        let icon_file = format!("IconFile={ICON_DIR}/abc123.ico");
+        let duplicate_url = parse(&[
+            "[InternetShortcut]",
+            "URL=steam://rungameid/440",
+            "url=steam://rungameid/570",
+            &icon_file,
+        ]);
+        assert!(duplicate_url.is_err());
+    }
+
+    #[test]
+    fn replaces_icon_dir_of_any_case() {
+        let shortcut = text(&[
+            "[internetshortcut]",
+            "url=steam://rungameid/440",
+            "iconfile=/elsewhere/abc123.ico",
+        ]);
+        let fixed = super::replace_icon_dir(&shortcut, Path::new(ICON_DIR))
+            .unwrap()
+            .unwrap();
+        let icon_file = format!(
+            "iconfile={}",
+            Path::new(ICON_DIR).join("abc123.ico").display()
+        );
+        assert_eq!(
+            fixed,
+            text(&[
+                "[internetshortcut]",
+                "url=steam://rungameid/440",
+                &icon_file
+            ])
+        );
+    }
+
+    #[test]
+    fn rejects_binary_data() {
+        assert!(parse(&["[InternetShortcut]\0"]).is_err());
+        let odd_utf16 = parse_internet_shortcut(
+            [0xff, 0xfe, 0x41].as_slice(),
+            Path::new("/desktop"),
+            Path::new(ICON_DIR),
+        );
+        assert!(odd_utf16.is_err());
+    }
+
+    #[test]
+    fn replaces_icon_dir() {
+        let shortcut = format_internet_shortcut("440", Path::new("/elsewhere/abc123.ico"));
+        let fixed = super::replace_icon_dir(&shortcut, Path::new(ICON_DIR))
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            fixed,
+            format_internet_shortcut("440", &Path::new(ICON_DIR).join("abc123.ico"))
+        );
+
+        let without_icon = text(&["[InternetShortcut]", "URL=steam://rungameid/440"]);
+        let fixed = super::replace_icon_dir(&without_icon, Path::new(ICON_DIR)).unwrap();
+        assert!(fixed.is_none());
+    }
+
+    #[test]
+    fn parses_desktop_entry() {
+        let text = text(&[
+            "[Desktop Entry]",
+            "Name=Team Fortress 2",
+            "Exec=steam steam://rungameid/440",
+            "Icon=steam_icon_440",
+            "Type=Application",
+        ]);
+        let shortcut = parse_desktop_entry(text.as_bytes()).unwrap();
+        assert_eq!(shortcut.game_id, "440");
+        assert_eq!(shortcut.icon_filename, "steam_icon_440.png");
+        assert!(shortcut.cdn_filename.is_none());
+    }
+
+    #[test]
+    fn rejects_malformed_desktop_entries() {
+        let malformed = [
+            // Missing icon
+            &["[Desktop Entry]", "Exec=steam steam://rungameid/440"][..],
+            // Not launched through Steam
+            &["[Desktop Entry]", "Exec=game", "Icon=steam_icon_440"],
+            // Icon from somewhere else
+            &[
+                "[Desktop Entry]",
+                "Exec=steam steam://rungameid/440",
+                "Icon=game",
+            ],
+            // Duplicate keys
+            &[
+                "[Desktop Entry]",
+                "Exec=steam steam://rungameid/440",
+                "URL=steam://rungameid/570",
+                "Icon=steam_icon_440",
+            ],
+            // Outside of the section
+            &[
+                "Exec=steam steam://rungameid/440",
+                "Icon=steam_icon_440",
+                "[Desktop Entry]",
+            ],
+        ];
+        for lines in malformed {
+            assert!(
+                parse_desktop_entry(text(lines).as_bytes()).is_err(),
+                "{lines:?}"
+            );
+        }
+
+        let non_steam = text(&[
+            "[Desktop Entry]",
+            "Exec=steam steam://rungameid/12345678901234567890",
+            "Icon=steam_icon_1",
+        ]);
+        let error = parse_desktop_entry(non_steam.as_bytes()).err().unwrap();
+        assert!(error.is::<NonSteamGame>());
+    }
+
+    #[test]
+    fn parses_launch_script() {
+        let script = text(&["#!/bin/sh", "open steam://run/440"]);
+        assert_eq!(parse_launch_script(script.as_bytes()).unwrap(), "440");
+        let script = text(&["#!/bin/sh", "open 'steam://rungameid/570'"]);
+        assert_eq!(parse_launch_script(script.as_bytes()).unwrap(), "570");
+    }
+
+    #[test]
+    fn rejects_malformed_launch_scripts() {
+        let other_app = text(&["#!/bin/sh", "open /Applications/Game.app"]);
+        assert!(parse_launch_script(other_app.as_bytes()).is_err());
+        let two_games = text(&["open steam://run/440", "open steam://run/570"]);
+        assert!(parse_launch_script(two_games.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn finds_game_id() {
+        let shortcut = text(&["[InternetShortcut]", "URL=steam://rungameid/440"]);
+        let game_id = find_game_id(shortcut.as_bytes()).unwrap();
+        assert_eq!(game_id.as_deref(), Some("440"));
+        let other = text(&["URL=https://example.com/"]);
+        assert_eq!(find_game_id(other.as_bytes()).unwrap(), None);
+    }
+
+    #[test]
+    fn parses_bundle_icon_file() {
+        let plist = text(&["<key>CFBundleIconFile</key>", "<string>shortcut</string>"]);
+        let icon_file = parse_bundle_icon_file(plist.as_bytes()).unwrap();
+        assert_eq!(icon_file, "shortcut.icns");
+        let plist = text(&[
+            "<key>CFBundleIconFile</key>",
+            "<string>../shortcut.icns</string>",
+        ]);
+        assert!(parse_bundle_icon_file(plist.as_bytes()).is_err());
+    }
+}
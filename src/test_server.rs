@@ -0,0 +1,207 @@
+//! Local HTTP server standing in for Steam in tests.
+
+use std::future;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+
+use tokio::io::{AsyncBufReadExt as _, AsyncWriteExt as _, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+
+/// Request the server received.
+#[derive(Debug, Clone)]
+pub struct Request {
+    /// Method (e.g. `GET`)
+    pub method: String,
+
+    /// Path, including any query
+    pub path: String,
+
+    /// Headers, in the order they were sent
+    pub headers: Vec<(String, String)>,
+}
+
+impl Request {
+    /// Value of the header `name` (compared case-insensitively), if it was sent.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Response for the server to send.
+#[derive(Debug, Clone)]
+pub struct Response {
+    /// Status code
+    status: u16,
+
+    /// Headers, on top of `Content-Length` (unless it's one of them) and `Connection`
+    headers: Vec<(String, String)>,
+
+    /// Body, which isn't sent in answer to `HEAD` requests
+    body: Vec<u8>,
+
+    /// Whether to hold the connection open once the response is sent, rather than closing it
+    stall: bool,
+}
+
+impl Response {
+    /// Response with `status` and nothing else.
+    pub fn new(status: u16) -> Self {
+        Self {
+            status,
+            headers: Vec::new(),
+            body: Vec::new(),
+            stall: false,
+        }
+    }
+
+    /// Successful response with `body`, served as `content_type`.
+    pub fn ok(content_type: &str, body: impl Into<Vec<u8>>) -> Self {
+        Self::new(200)
+            .header("Content-Type", content_type)
+            .body(body)
+    }
+
+    /// The response with the header `name` set to `value`.
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_owned(), value.to_owned()));
+        self
+    }
+
+    /// The response with `body`.
+    pub fn body(mut self, body: impl Into<Vec<u8>>) -> Self {
+        self.body = body.into();
+        self
+    }
+
+    /// The response with the connection held open once it's sent,
+    /// so a client expecting more of the body (going by `Content-Length`) waits for it forever.
+    pub fn stall(mut self) -> Self {
+        self.stall = true;
+        self
+    }
+}
+
+/// Server answering every request with whatever its handler says,
+/// given the request and how many came before it.
+/// Stops once dropped.
+pub struct TestServer {
+    /// Address the server is listening on
+    addr: SocketAddr,
+
+    /// Every request received so far
+    requests: Arc<Mutex<Vec<Request>>>,
+
+    /// Task accepting connections
+    task: JoinHandle<()>,
+}
+
+impl TestServer {
+    /// Start a server on a free local port, answering requests with `handler`.
+    pub async fn start(
+        handler: impl Fn(&Request, usize) -> Response + Send + Sync + 'static,
+    ) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("failed to bind test server");
+        let addr = listener.local_addr().expect("test server has no address");
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let handler = Arc::new(handler);
+        let task = tokio::spawn({
+            let requests = requests.clone();
+            async move {
+                while let Ok((stream, _)) = listener.accept().await {
+                    let requests = requests.clone();
+                    let handler = handler.clone();
+                    tokio::spawn(async move {
+                        let _ = serve(stream, &requests, &*handler).await;
+                    });
+                }
+            }
+        });
+        Self {
+            addr,
+            requests,
+            task,
+        }
+    }
+
+    /// Base URL of the server (e.g. `http://127.0.0.1:1234`).
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// Every request received so far, in order.
+    pub fn requests(&self) -> Vec<Request> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Answer the request on `stream`, then close the connection.
+async fn serve(
+    stream: TcpStream,
+    requests: &Mutex<Vec<Request>>,
+    handler: &(impl Fn(&Request, usize) -> Response + ?Sized),
+) -> std::io::Result<()> {
+    let mut stream = BufReader::new(stream);
+
+    // Requests never have bodies, so they end with the first blank line
+    let mut line = String::new();
+    stream.read_line(&mut line).await?;
+    let mut parts = line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_owned();
+    let path = parts.next().unwrap_or_default().to_owned();
+    let mut headers = Vec::new();
+    loop {
+        line.clear();
+        stream.read_line(&mut line).await?;
+        let Some((name, value)) = line.trim_end().split_once(':') else {
+            break;
+        };
+        headers.push((name.to_owned(), value.trim().to_owned()));
+    }
+    let request = Request {
+        method,
+        path,
+        headers,
+    };
+
+    let response = {
+        let mut requests = requests.lock().unwrap();
+        let response = handler(&request, requests.len());
+        requests.push(request.clone());
+        response
+    };
+
+    let mut head = format!("HTTP/1.1 {} Test\r\nConnection: close\r\n", response.status);
+    let has_length = response
+        .headers
+        .iter()
+        .any(|(name, _)| name.eq_ignore_ascii_case("Content-Length"));
+    if !has_length {
+        head.push_str(&format!("Content-Length: {}\r\n", response.body.len()));
+    }
+    for (name, value) in &response.headers {
+        head.push_str(&format!("{name}: {value}\r\n"));
+    }
+    head.push_str("\r\n");
+    let stream = stream.get_mut();
+    stream.write_all(head.as_bytes()).await?;
+    if request.method != "HEAD" {
+        stream.write_all(&response.body).await?;
+    }
+    if response.stall {
+        stream.flush().await?;
+        future::pending::<()>().await;
+    }
+    stream.shutdown().await
+}
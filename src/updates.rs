@@ -0,0 +1,180 @@
+//! Checking whether icons already there have changed on the CDN (`--check-updates`),
+//! going by what it says about them rather than downloading them again.
+
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+use anyhow::{Context as _, Result};
+
+use crate::download::RemoteIcon;
+use crate::{ico, paths};
+
+/// How an icon already there compares to what the CDN says about it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Comparison {
+    /// Everything the CDN said matches the icon
+    Same,
+
+    /// The icon has changed on the CDN, for the given reason
+    Changed(String),
+
+    /// The CDN said nothing the icon could be compared with
+    Unknown,
+}
+
+/// Compare the icon at `icon_path` with what the CDN says about it in `remote`.
+/// It's changed if the CDN modified it after it was saved, or if it's a different size,
+/// though sizes are only compared for ICOs (saved either as the CDN sent them,
+/// or embedding the PNG it sent), since icons converted to other formats never match.
+pub fn compare(icon_path: &Path, remote: &RemoteIcon) -> Result<Comparison> {
+    let icon_path = paths::to_long_path(icon_path);
+    let mut compared = false;
+
+    if let Some(last_modified) = remote.last_modified {
+        let modified = fs::metadata(&icon_path)
+            .and_then(|metadata| metadata.modified())
+            .context("Failed to read when the icon was modified")?;
+        if SystemTime::UNIX_EPOCH + Duration::from_secs(last_modified) > modified {
+            return Ok(Comparison::Changed(
+                "modified on the CDN since it was saved".to_owned(),
+            ));
+        }
+        compared = true;
+    }
+
+    let is_ico = !matches!(
+        icon_path
+            .extension()
+            .and_then(|extension| extension.to_str()),
+        Some("png" | "icns")
+    );
+    if let Some(len) = remote.len
+        && is_ico
+    {
+        let bytes = fs::read(&icon_path).context("Failed to read icon")?;
+        let embedded_len = ico::embedded_png(&bytes).map(|png| png.len() as u64);
+        if bytes.len() as u64 != len && embedded_len != Some(len) {
+            return Ok(Comparison::Changed(format!(
+                "{len} bytes on the CDN, rather than {}",
+                bytes.len()
+            )));
+        }
+        compared = true;
+    }
+
+    Ok(if compared {
+        Comparison::Same
+    } else {
+        Comparison::Unknown
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::png;
+
+    /// ICO wrapping a 1x1 PNG, saved in `dir` with `extension`, last modified at `modified`
+    /// seconds since the Unix epoch.
+    fn save_icon(dir: &TempDir, extension: &str, modified: u64) -> (std::path::PathBuf, Vec<u8>) {
+        let png = png::encode_rgba(1, 1, &[0, 0, 0xff, 0xff]);
+        let contents = match extension {
+            "png" => png.clone(),
+            _ => ico::from_png(&png).unwrap(),
+        };
+        let path = dir.path().join(format!("icon.{extension}"));
+        fs::write(&path, &contents).unwrap();
+        File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(modified))
+            .unwrap();
+        (path, png)
+    }
+
+    #[test]
+    fn same_when_everything_matches() {
+        let dir = TempDir::new().unwrap();
+        let (path, _) = save_icon(&dir, "ico", 2_000);
+        let len = fs::metadata(&path).unwrap().len();
+        let remote = RemoteIcon {
+            len: Some(len),
+            last_modified: Some(1_000),
+        };
+        assert_eq!(compare(&path, &remote).unwrap(), Comparison::Same);
+
+        // Modified at the same moment it was saved is still the same
+        let remote = RemoteIcon {
+            len: None,
+            last_modified: Some(2_000),
+        };
+        assert_eq!(compare(&path, &remote).unwrap(), Comparison::Same);
+    }
+
+    #[test]
+    fn changed_when_modified_later() {
+        let dir = TempDir::new().unwrap();
+        let (path, _) = save_icon(&dir, "ico", 2_000);
+        let remote = RemoteIcon {
+            len: None,
+            last_modified: Some(2_001),
+        };
+        assert!(matches!(
+            compare(&path, &remote).unwrap(),
+            Comparison::Changed(_)
+        ));
+    }
+
+    #[test]
+    fn changed_when_size_differs() {
+        let dir = TempDir::new().unwrap();
+        let (path, _) = save_icon(&dir, "ico", 2_000);
+        let remote = RemoteIcon {
+            len: Some(1),
+            last_modified: Some(1_000),
+        };
+        assert!(matches!(
+            compare(&path, &remote).unwrap(),
+            Comparison::Changed(_)
+        ));
+    }
+
+    #[test]
+    fn same_size_as_embedded_png() {
+        // Icons served as PNGs are saved wrapped in an ICO
+        let dir = TempDir::new().unwrap();
+        let (path, png) = save_icon(&dir, "ico", 2_000);
+        let remote = RemoteIcon {
+            len: Some(png.len() as u64),
+            last_modified: None,
+        };
+        assert_eq!(compare(&path, &remote).unwrap(), Comparison::Same);
+    }
+
+    #[test]
+    fn ignores_size_of_converted_icons() {
+        let dir = TempDir::new().unwrap();
+        let (path, _) = save_icon(&dir, "png", 2_000);
+        let remote = RemoteIcon {
+            len: Some(1),
+            last_modified: None,
+        };
+        assert_eq!(compare(&path, &remote).unwrap(), Comparison::Unknown);
+    }
+
+    #[test]
+    fn unknown_without_validators() {
+        let dir = TempDir::new().unwrap();
+        let (path, _) = save_icon(&dir, "ico", 2_000);
+        assert_eq!(
+            compare(&path, &RemoteIcon::default()).unwrap(),
+            Comparison::Unknown
+        );
+    }
+}
@@ -0,0 +1,298 @@
+//! Reading Valve's text KeyValues (VDF) files, such as app manifests.
+//! Binary ones are read by [`crate::binary_vdf`] into the same [`Value`]s.
+
+use std::iter::Peekable;
+use std::str::CharIndices;
+
+use anyhow::{Context as _, Result, bail};
+
+/// Value within a KeyValues file.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    /// Text (numbers in binary files are converted to text)
+    String(String),
+
+    /// Nested keys and values, in the order they appeared
+    Object(Vec<(String, Value)>),
+}
+
+impl Value {
+    /// Value for `key` if this is an object containing it.
+    /// Like Steam, keys are case-insensitive.
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Self::Object(entries) => entries
+                .iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case(key))
+                .map(|(_, value)| value),
+            Self::String(_) => None,
+        }
+    }
+
+    /// Nested keys and values, if this is an object.
+    pub fn entries(&self) -> &[(String, Value)] {
+        match self {
+            Self::Object(entries) => entries,
+            Self::String(_) => &[],
+        }
+    }
+
+    /// Text, if this is a string.
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::String(text) => Some(text),
+            Self::Object(_) => None,
+        }
+    }
+}
+
+/// Parse a text KeyValues file (e.g. `appmanifest_620.acf`) into an object of its root keys.
+pub fn parse_text(text: &str) -> Result<Value> {
+    let mut tokens = Tokens {
+        text,
+        chars: text.char_indices().peekable(),
+    };
+    let entries = parse_entries(&mut tokens, false)?;
+    Ok(Value::Object(entries))
+}
+
+/// Token within a text KeyValues file.
+#[derive(Debug, PartialEq)]
+enum Token {
+    /// Start of an object
+    Open,
+
+    /// End of an object
+    Close,
+
+    /// Key or value, quoted or not
+    String(String),
+}
+
+/// Splits a text KeyValues file into tokens.
+struct Tokens<'a> {
+    /// Entire file
+    text: &'a str,
+
+    /// Remaining characters
+    chars: Peekable<CharIndices<'a>>,
+}
+
+impl Tokens<'_> {
+    /// Next token, skipping whitespace, comments, and platform conditionals (e.g. `[$WIN32]`).
+    fn next(&mut self) -> Result<Option<Token>> {
+        loop {
+            let Some((start, char)) = self.chars.next() else {
+                return Ok(None);
+            };
+            match char {
+                _ if char.is_whitespace() => {}
+                '{' => return Ok(Some(Token::Open)),
+                '}' => return Ok(Some(Token::Close)),
+                '"' => return self.quoted().map(|text| Some(Token::String(text))),
+                '/' if self.chars.next_if(|&(_, next)| next == '/').is_some() => {
+                    while self.chars.next_if(|&(_, next)| next != '\n').is_some() {}
+                }
+                '[' => {
+                    while self.chars.next_if(|&(_, next)| next != ']').is_some() {}
+                    if self.chars.next().is_none() {
+                        bail!("Unterminated conditional");
+                    }
+                }
+                _ => {
+                    // Unquoted text runs until whitespace or something meaningful
+                    let mut end = start + char.len_utf8();
+                    while let Some((index, next)) = self.chars.next_if(|&(_, next)| {
+                        !next.is_whitespace() && !matches!(next, '{' | '}' | '"')
+                    }) {
+                        end = index + next.len_utf8();
+                    }
+                    return Ok(Some(Token::String(self.text[start..end].to_owned())));
+                }
+            }
+        }
+    }
+
+    /// Rest of a quoted string, after its opening quote.
+    fn quoted(&mut self) -> Result<String> {
+        let mut text = String::new();
+        loop {
+            match self.chars.next() {
+                Some((_, '"')) => return Ok(text),
+                Some((_, '\\')) => match self.chars.next() {
+                    Some((_, 'n')) => text.push('\n'),
+                    Some((_, 't')) => text.push('\t'),
+                    Some((_, escaped)) => text.push(escaped),
+                    None => break,
+                },
+                Some((_, char)) => text.push(char),
+                None => break,
+            }
+        }
+        bail!("Unterminated string");
+    }
+}
+
+/// Parse keys and values until the end of the current object
+/// (or the end of the file, if not `nested`).
+fn parse_entries(tokens: &mut Tokens, nested: bool) -> Result<Vec<(String, Value)>> {
+    let mut entries = Vec::new();
+    loop {
+        let key = match tokens.next()? {
+            Some(Token::String(key)) => key,
+            Some(Token::Close) if nested => return Ok(entries),
+            None if !nested => return Ok(entries),
+            Some(Token::Close) => bail!("Unexpected `}}`"),
+            Some(Token::Open) => bail!("Expected a key, but found `{{`"),
+            None => bail!("Unexpected end of file within an object"),
+        };
+        let value = match tokens.next()? {
+            Some(Token::String(value)) => Value::String(value),
+            Some(Token::Open) => Value::Object(
+                parse_entries(tokens, true).with_context(|| format!("Failed to parse `{key}`"))?,
+            ),
+            Some(Token::Close) | None => bail!("Missing value for `{key}`"),
+        };
+        entries.push((key, value));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Object with `entries`.
+    fn object(entries: &[(&str, Value)]) -> Value {
+        Value::Object(
+            entries
+                .iter()
+                .map(|(key, value)| ((*key).to_owned(), value.clone()))
+                .collect(),
+        )
+    }
+
+    /// String `text`.
+    fn string(text: &str) -> Value {
+        Value::String(text.to_owned())
+    }
+
+    #[test]
+    fn parses_quoted_keys_and_values() {
+        let root = parse_text(r#""name" "Portal 2" "empty" """#).unwrap();
+        assert_eq!(
+            root,
+            object(&[("name", string("Portal 2")), ("empty", string(""))])
+        );
+    }
+
+    #[test]
+    fn parses_unquoted_keys_and_values() {
+        let root = parse_text("appid 620\nname\t\"Portal 2\"").unwrap();
+        assert_eq!(
+            root,
+            object(&[("appid", string("620")), ("name", string("Portal 2"))])
+        );
+    }
+
+    #[test]
+    fn parses_escapes() {
+        let root = parse_text(r#""path" "C:\\Games\\\"Quoted\"\n\tNext""#).unwrap();
+        assert_eq!(
+            root.get("path").unwrap().as_str(),
+            Some("C:\\Games\\\"Quoted\"\n\tNext")
+        );
+    }
+
+    #[test]
+    fn parses_nested_sections() {
+        let text = [
+            "\"AppState\"",
+            "{",
+            "\t\"appid\" \"620\"",
+            "\t\"UserConfig\"",
+            "\t{",
+            "\t\t\"language\" \"english\"",
+            "\t}",
+            "\t\"Empty\" {}",
+            "}",
+        ]
+        .join("\n");
+        let root = parse_text(&text).unwrap();
+        assert_eq!(
+            root,
+            object(&[(
+                "AppState",
+                object(&[
+                    ("appid", string("620")),
+                    ("UserConfig", object(&[("language", string("english"))])),
+                    ("Empty", object(&[])),
+                ])
+            )])
+        );
+    }
+
+    #[test]
+    fn looks_up_keys_case_insensitively() {
+        let root = parse_text(r#""AppState" { "AppID" "620" }"#).unwrap();
+        let state = root.get("appstate").unwrap();
+        assert_eq!(state.get("appid").and_then(Value::as_str), Some("620"));
+        assert_eq!(state.get("name"), None);
+        assert_eq!(state.as_str(), None);
+        assert_eq!(state.get("appid").unwrap().entries(), []);
+    }
+
+    #[test]
+    fn skips_comments_and_conditionals() {
+        let text = [
+            "// Written by Steam",
+            "\"a\" \"1\" // Trailing comment",
+            "\"b\" \"2\" [$WIN32]",
+            "// \"c\" \"3\"",
+            "\"d\" \"http://example.com/\"",
+        ]
+        .join("\r\n");
+        let root = parse_text(&text).unwrap();
+        assert_eq!(
+            root,
+            object(&[
+                ("a", string("1")),
+                ("b", string("2")),
+                ("d", string("http://example.com/")),
+            ])
+        );
+    }
+
+    #[test]
+    fn parses_nothing() {
+        assert_eq!(parse_text("").unwrap(), object(&[]));
+        assert_eq!(parse_text(" \n// Nothing here\n").unwrap(), object(&[]));
+    }
+
+    #[test]
+    fn rejects_malformed_files() {
+        for text in [
+            r#""unterminated"#,
+            r#""key" "unterminated"#,
+            r#""key" "escaped quote\""#,
+            r#""key""#,
+            r#""key" {"#,
+            r#""key" { "nested" "value""#,
+            r#""key" { "nested" }"#,
+            "}",
+            "{ \"key\" \"value\" }",
+            r#""key" "value" }"#,
+            r#""key" "value" [$WIN32"#,
+        ] {
+            assert!(parse_text(text).is_err(), "{text}");
+        }
+    }
+
+    #[test]
+    fn survives_truncation() {
+        let text = include_str!("../tests/fixtures/appmanifest_620.acf");
+        for (index, _) in text.char_indices() {
+            // Whether or not what's left parses, it mustn't panic
+            let _ = parse_text(&text[..index]);
+        }
+    }
+}
@@ -0,0 +1,153 @@
+//! Checking icons that were already saved, so damaged ones can be found (and downloaded again).
+
+use std::error::Error;
+use std::fmt::{self, Display};
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Result, anyhow};
+use log::*;
+
+use crate::platform::ICON_EXTENSION;
+use crate::report::VerifyReport;
+use crate::{icns, ico, jpeg, paths, png, resolver, sha1};
+
+/// What's wrong with a damaged icon, in the order icons are checked for them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Problem {
+    /// The icon couldn't be read at all
+    Unreadable,
+
+    /// The icon is empty
+    Empty,
+
+    /// The icon isn't valid for its format
+    Invalid,
+
+    /// The icon is named after a hash it doesn't have
+    HashMismatch,
+}
+
+impl Problem {
+    /// Every problem, in the order icons are checked for them.
+    pub const ALL: [Self; 4] = [
+        Self::Unreadable,
+        Self::Empty,
+        Self::Invalid,
+        Self::HashMismatch,
+    ];
+
+    /// Name used for the problem in machine-readable output.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Unreadable => "unreadable",
+            Self::Empty => "empty",
+            Self::Invalid => "invalid",
+            Self::HashMismatch => "hash-mismatch",
+        }
+    }
+}
+
+/// Error for icons that are damaged, saying how.
+#[derive(Debug)]
+pub struct DamagedIcon {
+    /// What's wrong with the icon
+    pub problem: Problem,
+
+    /// Details of what's wrong, for the logs
+    pub detail: String,
+}
+
+impl Display for DamagedIcon {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.detail)
+    }
+}
+
+impl Error for DamagedIcon {}
+
+/// Check that the icon saved at `icon_path` is intact, i.e. that it isn't empty,
+/// is structurally valid for its format,
+/// and has the hash it's named after (if it's named like the icons on the CDN).
+pub fn check_icon(icon_path: &Path) -> Result<(), DamagedIcon> {
+    let damaged = |problem, detail: String| DamagedIcon { problem, detail };
+
+    let contents = fs::read(paths::to_long_path(icon_path)).map_err(|error| {
+        damaged(
+            Problem::Unreadable,
+            format!("Failed to read icon file: {error}"),
+        )
+    })?;
+    if contents.is_empty() {
+        return Err(damaged(Problem::Empty, "Icon file is empty".to_owned()));
+    }
+
+    let extension = icon_path
+        .extension()
+        .and_then(|extension| extension.to_str());
+    let valid = match extension {
+        Some("png") => png::dimensions(&contents).map(|_| ()),
+        Some("icns") => icns::validate(&contents),
+        // Other artwork (see `--art`) is saved the same way as icons
+        Some("jpg") if contents.starts_with(&jpeg::SIGNATURE) => Ok(()),
+        Some("jpg") => Err(anyhow!("Missing JPEG header")),
+        // Icons the CDN serves as PNGs are saved as they are with `--no-convert`
+        _ if contents.starts_with(&png::SIGNATURE) => png::dimensions(&contents).map(|_| ()),
+        _ => ico::validate(&contents),
+    };
+    if let Err(error) = valid {
+        return Err(damaged(Problem::Invalid, format!("{error:#}")));
+    }
+
+    // Icons named like the ones on the CDN should still have the hash they're named after
+    let filename = icon_path
+        .file_name()
+        .map(|filename| filename.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_default();
+    if resolver::is_cdn_filename(&filename) {
+        let expected_hash = filename.strip_suffix(".ico").unwrap_or_default();
+        let actual_hash = sha1::hex_digest(&contents);
+        // Icons the CDN serves as PNGs are wrapped in an ICO, so it's the PNG that has the hash
+        let converted_hash = ico::embedded_png(&contents).map(sha1::hex_digest);
+        if actual_hash != expected_hash && converted_hash.as_deref() != Some(expected_hash) {
+            return Err(damaged(
+                Problem::HashMismatch,
+                format!("Icon file has hash {actual_hash} rather than the one it's named after"),
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Check every icon directly in `local_icon_dir`,
+/// recording each one that's damaged in `report`.
+pub fn verify_icons(
+    local_icon_dir: &Path,
+    report: &mut VerifyReport,
+    check_sigint: &impl Fn() -> Result<()>,
+) -> Result<()> {
+    let mut icon_paths = Vec::new();
+    for entry in local_icon_dir.read_dir()? {
+        let entry = entry?;
+        let is_icon = entry
+            .file_name()
+            .to_string_lossy()
+            .to_lowercase()
+            .ends_with(ICON_EXTENSION);
+        if is_icon && entry.file_type()?.is_file() {
+            icon_paths.push(entry.path());
+        }
+    }
+    icon_paths.sort_unstable();
+
+    for icon_path in icon_paths {
+        check_sigint()?;
+        report.checked += 1;
+        if let Err(damaged) = check_icon(&icon_path) {
+            warn!("`{}` is damaged: {damaged}", icon_path.display());
+            report.damaged.push((icon_path, damaged));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,113 @@
+//! Watching the shortcut directory for shortcuts being created or changed.
+
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{Context as _, Result, bail};
+use log::*;
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher as _};
+use tokio::sync::mpsc::{self, UnboundedReceiver};
+use tokio::time;
+
+use crate::platform::{self, SHORTCUTS_ARE_DIRS};
+
+/// How long to wait for things to settle down after a shortcut changes,
+/// since creating one (or an app bundle) takes several filesystem events
+const DEBOUNCE: Duration = Duration::from_secs(2);
+
+/// Watches a directory for shortcuts being created or changed.
+pub struct ShortcutWatcher {
+    /// Watcher sending events to `events` (which stops once dropped)
+    _watcher: RecommendedWatcher,
+
+    /// Filesystem events from the watcher
+    events: UnboundedReceiver<notify::Result<Event>>,
+}
+
+impl ShortcutWatcher {
+    /// Start watching `dirs` (and their subdirectories, if `recursive`).
+    pub fn new(dirs: &[PathBuf], recursive: bool) -> Result<Self> {
+        let (sender, events) = mpsc::unbounded_channel();
+        let mut watcher = notify::recommended_watcher(move |event| {
+            // Nothing is listening anymore if this fails
+            let _ = sender.send(event);
+        })
+        .context("Failed to create filesystem watcher")?;
+
+        // App bundles are directories, so changes within them need to be seen too
+        let mode = if recursive || SHORTCUTS_ARE_DIRS {
+            RecursiveMode::Recursive
+        } else {
+            RecursiveMode::NonRecursive
+        };
+        for dir in dirs {
+            watcher
+                .watch(dir, mode)
+                .with_context(|| format!("Failed to watch `{}`", dir.display()))?;
+        }
+
+        Ok(Self {
+            _watcher: watcher,
+            events,
+        })
+    }
+
+    /// Wait for shortcuts to be created or changed,
+    /// returning them once there haven't been any more changes for a moment.
+    pub async fn changed_shortcuts(&mut self) -> Result<Vec<PathBuf>> {
+        let mut shortcuts = BTreeSet::new();
+        while shortcuts.is_empty() {
+            let event = self.next_event().await?;
+            shortcuts.extend(changed_shortcuts(event));
+        }
+
+        // Collect everything else that changes before things settle down
+        while let Ok(event) = time::timeout(DEBOUNCE, self.next_event()).await {
+            shortcuts.extend(changed_shortcuts(event?));
+        }
+
+        Ok(shortcuts.into_iter().collect())
+    }
+
+    /// Wait for the next event from the watcher.
+    /// Errors with individual events are logged rather than returned.
+    async fn next_event(&mut self) -> Result<Event> {
+        loop {
+            match self.events.recv().await {
+                Some(Ok(event)) => return Ok(event),
+                Some(Err(error)) => warn!("Error while watching for new shortcuts: {error}"),
+                None => bail!("Stopped receiving changes to the shortcut directory"),
+            }
+        }
+    }
+}
+
+/// Shortcuts that were created or changed by `event`.
+fn changed_shortcuts(event: Event) -> Vec<PathBuf> {
+    if !matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+        return Vec::new();
+    }
+
+    event
+        .paths
+        .iter()
+        .filter_map(|path| shortcut_containing(path))
+        .collect()
+}
+
+/// The shortcut `path` is (or is within, for app bundles), if any.
+fn shortcut_containing(path: &Path) -> Option<PathBuf> {
+    let is_shortcut =
+        |path: &Path| path.file_name().is_some() && platform::has_shortcut_extension(path);
+
+    if SHORTCUTS_ARE_DIRS {
+        path.ancestors()
+            .find(|ancestor| is_shortcut(ancestor))
+            .map(Path::to_owned)
+    } else if is_shortcut(path) {
+        Some(path.to_owned())
+    } else {
+        None
+    }
+}
@@ -0,0 +1,122 @@
+#!/usr/bin/env python3
+"""Writes icon.exe: a minimal 64-bit Windows executable whose only section holds icon resources.
+
+It has two icon groups: #1 with a 1x1 image, and #2 with that image and a 2x2 one
+(both PNGs, as Windows has been able to load since Vista). It has no code, so it can't run.
+"""
+
+import struct
+import zlib
+from pathlib import Path
+
+FILE_ALIGNMENT = 0x200
+SECTION_ADDRESS = 0x1000
+RT_ICON = 3
+RT_GROUP_ICON = 14
+LANGUAGE = 0x409
+
+
+def png(size, rgba):
+    def chunk(kind, data):
+        body = kind + data
+        return struct.pack(">I", len(data)) + body + struct.pack(">I", zlib.crc32(body))
+
+    rows = b"".join(b"\0" + rgba * size for _ in range(size))
+    return (
+        b"\x89PNG\r\n\x1a\n"
+        + chunk(b"IHDR", struct.pack(">IIBBBBB", size, size, 8, 6, 0, 0, 0))
+        + chunk(b"IDAT", zlib.compress(rows))
+        + chunk(b"IEND", b"")
+    )
+
+
+def align(data, alignment):
+    return data + b"\0" * (-len(data) % alignment)
+
+
+images = {1: (1, png(1, b"\xff\0\0\xff")), 2: (2, png(2, b"\0\0\xff\xff"))}
+groups = {1: [1], 2: [1, 2]}
+
+group_data = {}
+for group_id, image_ids in groups.items():
+    data = struct.pack("<HHH", 0, 1, len(image_ids))
+    for image_id in image_ids:
+        size, image = images[image_id]
+        data += struct.pack("<BBBBHHIH", size, size, 0, 0, 1, 32, len(image), image_id)
+    group_data[group_id] = data
+
+resources = {
+    RT_ICON: {image_id: image for image_id, (_, image) in images.items()},
+    RT_GROUP_ICON: group_data,
+}
+
+
+def directory(ids):
+    return struct.pack("<IIHHHH", 0, 0, 0, 0, 0, len(ids))
+
+
+# Lay out the directories (root, each type, each resource's languages), then data entries, then data
+type_ids = sorted(resources)
+resource_ids = [(type_id, resource_id) for type_id in type_ids for resource_id in sorted(resources[type_id])]
+root_len = 16 + 8 * len(type_ids)
+type_offsets = {}
+offset = root_len
+for type_id in type_ids:
+    type_offsets[type_id] = offset
+    offset += 16 + 8 * len(resources[type_id])
+language_offsets = {}
+for key in resource_ids:
+    language_offsets[key] = offset
+    offset += 16 + 8
+entry_offsets = {}
+for key in resource_ids:
+    entry_offsets[key] = offset
+    offset += 16
+data_offsets = {}
+for type_id, resource_id in resource_ids:
+    offset += -offset % 8
+    data_offsets[(type_id, resource_id)] = offset
+    offset += len(resources[type_id][resource_id])
+
+section = directory(type_ids)
+for type_id in type_ids:
+    section += struct.pack("<II", type_id, 0x8000_0000 | type_offsets[type_id])
+for type_id in type_ids:
+    section += directory(resources[type_id])
+    for resource_id in sorted(resources[type_id]):
+        section += struct.pack("<II", resource_id, 0x8000_0000 | language_offsets[(type_id, resource_id)])
+for key in resource_ids:
+    section += directory([LANGUAGE]) + struct.pack("<II", LANGUAGE, entry_offsets[key])
+for type_id, resource_id in resource_ids:
+    data = resources[type_id][resource_id]
+    section += struct.pack("<IIII", SECTION_ADDRESS + data_offsets[(type_id, resource_id)], len(data), 0, 0)
+for type_id, resource_id in resource_ids:
+    section = align(section, 8) + resources[type_id][resource_id]
+section_len = len(section)
+section = align(section, FILE_ALIGNMENT)
+
+# DOS header pointing at the PE header right after it
+dos_header = b"MZ" + b"\0" * 58 + struct.pack("<I", 64)
+
+# COFF header: x86-64, one section, executable large-address-aware image
+optional_header_len = 112 + 16 * 8
+coff_header = b"PE\0\0" + struct.pack("<HHIIIHH", 0x8664, 1, 0, 0, 0, optional_header_len, 0x22)
+
+# PE32+ optional header, with only the resource table's data directory filled in
+directories = [(0, 0)] * 16
+directories[2] = (SECTION_ADDRESS, section_len)
+optional_header = struct.pack(
+    "<HBBIIIIIQIIHHHHHHIIIIHHQQQQII",
+    0x20B, 14, 0, 0, len(section), 0, 0, 0,
+    0x1_4000_0000, 0x1000, FILE_ALIGNMENT, 6, 0, 0, 0, 6, 0, 0,
+    SECTION_ADDRESS + 0x1000, FILE_ALIGNMENT, 0, 2, 0x8160,
+    0x10_0000, 0x1000, 0x10_0000, 0x1000, 0, len(directories),
+) + b"".join(struct.pack("<II", *directory) for directory in directories)
+assert len(optional_header) == optional_header_len
+
+section_header = b".rsrc\0\0\0" + struct.pack(
+    "<IIIIIIHHI", section_len, SECTION_ADDRESS, len(section), FILE_ALIGNMENT, 0, 0, 0, 0, 0x4000_0040
+)
+
+headers = align(dos_header + coff_header + optional_header + section_header, FILE_ALIGNMENT)
+Path(__file__).with_name("icon.exe").write_bytes(headers + section)